@@ -1,30 +1,381 @@
+use std::collections::HashMap;
+use std::fmt;
+
+/// Index of a declaration in the symbol table of the resolver.
+pub type DeclId = usize;
+
+/// A method declared by a trait, its name, parameters and return type.
+pub type MethodSig = (Expr, Vec<(Expr, Type)>, Type);
+
 #[derive(Debug, PartialEq, Clone)]
 pub enum Expr {
     Int(i32),
+    TypedInt(u64, Type),
+    Float(f64),
     Bool(bool),
-    Var(String),
+    Str(String),
+    /// A name, with the declaration the resolver linked it to. Names that are not values,
+    /// like the fields of a struct, are never linked
+    Var(String, Option<DeclId>),
+
+    Array(Vec<Expr>),
+    ArrayRepeat(Box<Expr>, usize),
+    Index(Box<Expr>, Box<Expr>),
+
+    StructLit(String, Vec<(String, Expr)>),
+    Field(Box<Expr>, String),
+    /// `Enum::Variant(args)`
+    Variant(String, String, Vec<Expr>),
+
+    /// `(a, b)`, also the names a tuple is destructured into by `let` and parameters
+    Tuple(Vec<Expr>),
+    /// `t.0`
+    TupleField(Box<Expr>, usize),
 
     BinExpr(Box<Expr>, Op, Box<Expr>),
     VarExpr(Box<Expr>, Op, Box<Expr>),
+    Cast(Box<Expr>, Type),
 
-    Let(Box<Expr>, Type, Box<Expr>),
+    /// `let x: T = value`, the type checker fills in the type when it is left out
+    Let(Box<Expr>, Option<Type>, Box<Expr>),
+    /// `let x: T;`, a variable that is assigned later, the type checker infers the type
+    /// from its uses when it is left out
+    Declare(Box<Expr>, Option<Type>),
 
     If(Box<Expr>, Vec<Expr>),
     IfElse(Box<Expr>, Vec<Expr>, Vec<Expr>),
     While(Box<Expr>, Vec<Expr>),
+    /// `for i in range { block }`, the loop variable is scoped to the block
+    For(Box<Expr>, Box<Expr>, Vec<Expr>),
+    /// `start..end`, or `start..=end` which includes the end
+    Range(Box<Expr>, Box<Expr>, bool),
+    /// `loop { block }`, its value is the value of the `break` that ends it
+    Loop(Vec<Expr>),
+    Match(Box<Expr>, Vec<(Pattern, Vec<Expr>)>),
+    /// `{ stmts; value }` used as an expression
+    Block(Vec<Expr>),
 
-    Fn(Box<Expr>, Vec<(Expr, Type)>, Type, Vec<Expr>),
+    /// `fn name<T: Trait>(params) -> type { block }`, the type parameters are empty for
+    /// functions that are not generic, each has the traits it is bound by
+    Fn(
+        Box<Expr>,
+        Vec<(String, Vec<String>)>,
+        Vec<(Expr, Type)>,
+        Type,
+        Vec<Expr>,
+    ),
     FnCall(Box<Expr>, Vec<Expr>),
+    /// `receiver.method(args)`
+    MethodCall(Box<Expr>, String, Vec<Expr>),
+    /// `|x: i32| x + n`, a function value capturing the variables it uses by value
+    Closure(Vec<(Expr, Type)>, Box<Expr>),
+    ExternFn(Box<Expr>, Vec<(Expr, Type)>, Type),
+    Struct(Box<Expr>, Vec<(Expr, Type)>),
+    Enum(Box<Expr>, Vec<(Expr, Vec<Type>)>),
+    /// A trait with the names, parameters and return types of its methods
+    Trait(Box<Expr>, Vec<MethodSig>),
+    /// `impl Trait for Type { methods }`
+    Impl(String, Type, Vec<Expr>),
+    /// `const NAME: T = value;`, the type checker replaces the value by its literal
+    Const(Box<Expr>, Type, Box<Expr>),
+    /// `static NAME: T = value;`, a global variable that is mutable for `static mut`
+    Static(Box<Expr>, Type, Box<Expr>, bool),
+    /// `mod name { items }`, or `mod name;` whose items are loaded from `name.rs`
+    Mod(String, Option<Vec<Expr>>),
+    /// `use math::add;`, the path of the imported item
+    Use(Vec<String>),
+    /// `pub item`, visible outside of the module it is declared in
+    Pub(Box<Expr>),
 
     Return(Box<Expr>),
+    /// `break`, with the value of the `loop` it ends
+    Break(Option<Box<Expr>>),
+    Continue,
+    /// The last expression of a block when it is not followed by `;`, the value of the block
+    Tail(Box<Expr>),
+
+    Spanned(Span, Box<Expr>),
 }
 
-#[derive(Debug, PartialEq, Copy, Clone)]
+impl Expr {
+    /// Strips the source span of a statement, if any.
+    pub fn unspanned(self) -> Expr {
+        match self {
+            Expr::Spanned(_, expr) => expr.unspanned(),
+            expr => expr,
+        }
+    }
+
+    /// Whether this is the tail expression giving a block its value.
+    pub fn is_tail(&self) -> bool {
+        match self {
+            Expr::Spanned(_, expr) => expr.is_tail(),
+            Expr::Tail(_) => true,
+            _ => false,
+        }
+    }
+
+    /// Names bound by a `let` or parameter, in the order they appear.
+    pub fn binding_names(&self, names: &mut Vec<String>) {
+        match self {
+            Expr::Tuple(bindings) => {
+                for binding in bindings {
+                    binding.binding_names(names);
+                }
+            }
+            Expr::Var(name, _) => names.push(name.to_string()),
+            _ => {}
+        }
+    }
+
+    /// Variables and functions a resolved expression refers to with their declarations,
+    /// in order of first use. Closures capture the ones that are variables.
+    pub fn referenced_vars(&self, vars: &mut Vec<(String, DeclId)>) {
+        let visit_block = |block: &[Expr], vars: &mut Vec<(String, DeclId)>| {
+            for expr in block {
+                expr.referenced_vars(vars);
+            }
+        };
+        match self {
+            Expr::Var(name, Some(id)) if !vars.iter().any(|(_, other)| other == id) => {
+                vars.push((name.to_string(), *id));
+            }
+            Expr::Array(elems) | Expr::Tuple(elems) | Expr::Variant(_, _, elems) => {
+                visit_block(elems, vars)
+            }
+            Expr::StructLit(_, fields) => {
+                for (_, expr) in fields {
+                    expr.referenced_vars(vars);
+                }
+            }
+            Expr::ArrayRepeat(expr, _)
+            | Expr::Field(expr, _)
+            | Expr::TupleField(expr, _)
+            | Expr::Cast(expr, _)
+            | Expr::Let(_, _, expr)
+            | Expr::Closure(_, expr)
+            | Expr::Return(expr)
+            | Expr::Tail(expr)
+            | Expr::Spanned(_, expr) => expr.referenced_vars(vars),
+            Expr::Index(l, r)
+            | Expr::BinExpr(l, _, r)
+            | Expr::VarExpr(l, _, r)
+            | Expr::Range(l, r, _) => {
+                l.referenced_vars(vars);
+                r.referenced_vars(vars);
+            }
+            Expr::If(cond, block) | Expr::While(cond, block) => {
+                cond.referenced_vars(vars);
+                visit_block(block, vars);
+            }
+            Expr::IfElse(cond, block1, block2) => {
+                cond.referenced_vars(vars);
+                visit_block(block1, vars);
+                visit_block(block2, vars);
+            }
+            Expr::Block(block) | Expr::Loop(block) => visit_block(block, vars),
+            Expr::Break(Some(expr)) => expr.referenced_vars(vars),
+            Expr::For(_, range, block) => {
+                range.referenced_vars(vars);
+                visit_block(block, vars);
+            }
+            Expr::Match(expr, arms) => {
+                expr.referenced_vars(vars);
+                for (_, block) in arms {
+                    visit_block(block, vars);
+                }
+            }
+            Expr::FnCall(fn_var, args) => {
+                fn_var.referenced_vars(vars);
+                visit_block(args, vars);
+            }
+            Expr::MethodCall(receiver, _, args) => {
+                receiver.referenced_vars(vars);
+                visit_block(args, vars);
+            }
+            _ => {}
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Pattern {
+    Wildcard,
+    /// A variable with the declaration the resolver linked it to
+    Binding(String, Option<DeclId>),
+    /// An integer or bool literal
+    Literal(Expr),
+    Variant(String, String, Vec<Pattern>),
+}
+
+impl fmt::Display for Pattern {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Pattern::Wildcard => write!(f, "_"),
+            Pattern::Binding(name, _) => write!(f, "{}", name),
+            Pattern::Literal(Expr::Int(i)) => write!(f, "{}", i),
+            Pattern::Literal(Expr::TypedInt(i, int_type)) => write!(f, "{}{}", i, int_type),
+            Pattern::Literal(Expr::Bool(b)) => write!(f, "{}", b),
+            Pattern::Literal(expr) => write!(f, "{:?}", expr),
+            Pattern::Variant(enum_name, variant, fields) => {
+                write!(f, "{}::{}", enum_name, variant)?;
+                if !fields.is_empty() {
+                    let fields: Vec<String> = fields.iter().map(|p| p.to_string()).collect();
+                    write!(f, "({})", fields.join(", "))?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Line and column (both 1-based) where a statement starts in the source.
+#[derive(Debug, PartialEq, Copy, Clone, Default)]
+pub struct Span {
+    pub line: u32,
+    pub column: u32,
+    /// Module file the statement is in, `None` for the file given to the compiler
+    pub file: Option<&'static str>,
+}
+
+impl fmt::Display for Span {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(file) = self.file {
+            write!(f, "{}:", file)?;
+        }
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
 pub enum Type {
     Int,
+    I8,
+    I16,
+    I64,
+    U8,
+    U32,
+    U64,
+    Usize,
+    Float,
     Bool,
-    // Str,
+    Str,
     Void,
+    /// `!`, the type of expressions that never produce a value such as a `loop`
+    /// without `break`
+    Never,
+    Array(Box<Type>, usize),
+    /// A struct or enum declared in the program
+    Named(String),
+    Tuple(Vec<Type>),
+    /// `fn(i32) -> bool`, the type of functions and closures used as values
+    Fn(Vec<Type>, Box<Type>),
+    /// Unknown type the type checker infers, never written in a program
+    Var(usize),
+}
+
+impl Type {
+    /// Replaces the type parameters named in `args`, written as `Named` types.
+    pub fn substitute(&self, args: &HashMap<String, Type>) -> Type {
+        match self {
+            Type::Named(name) => match args.get(name) {
+                Some(arg) => arg.clone(),
+                None => self.clone(),
+            },
+            Type::Array(elem_type, len) => Type::Array(Box::new(elem_type.substitute(args)), *len),
+            Type::Tuple(types) => Type::Tuple(types.iter().map(|ty| ty.substitute(args)).collect()),
+            Type::Fn(param_types, ret_type) => Type::Fn(
+                param_types.iter().map(|ty| ty.substitute(args)).collect(),
+                Box::new(ret_type.substitute(args)),
+            ),
+            _ => self.clone(),
+        }
+    }
+
+    /// Name of the function implementing `method` for values of this type.
+    pub fn method_path(&self, method: &str) -> String {
+        format!("{}::{}", self, method)
+    }
+
+    /// Bit width and signedness of an integer type, `Int` is `i32`.
+    pub fn int_info(&self) -> Option<(u32, bool)> {
+        match self {
+            Type::I8 => Some((8, true)),
+            Type::I16 => Some((16, true)),
+            Type::Int => Some((32, true)),
+            Type::I64 => Some((64, true)),
+            Type::U8 => Some((8, false)),
+            Type::U32 => Some((32, false)),
+            Type::U64 => Some((64, false)),
+            Type::Usize => Some((std::mem::size_of::<usize>() as u32 * 8, false)),
+            _ => None,
+        }
+    }
+
+    pub fn is_int(&self) -> bool {
+        self.int_info().is_some()
+    }
+
+    /// Types the arithmetic operators work on.
+    pub fn is_numeric(&self) -> bool {
+        self.is_int() || *self == Type::Float
+    }
+
+    /// Smallest and largest value of an integer type.
+    pub fn int_range(&self) -> (i128, i128) {
+        match self.int_info() {
+            Some((bits, true)) => (-(1 << (bits - 1)), (1 << (bits - 1)) - 1),
+            Some((bits, false)) => (0, (1 << bits) - 1),
+            None => panic!("{} is not an integer type", self),
+        }
+    }
+
+    /// Truncates `val` to the width of an integer type, like an `as` cast in Rust.
+    pub fn wrap_int(&self, val: i128) -> i128 {
+        let (bits, signed) = self.int_info().unwrap();
+        let val = val & ((1 << bits) - 1);
+        if signed && val >> (bits - 1) == 1 {
+            val - (1 << bits)
+        } else {
+            val
+        }
+    }
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Type::Int => write!(f, "i32"),
+            Type::I8 => write!(f, "i8"),
+            Type::I16 => write!(f, "i16"),
+            Type::I64 => write!(f, "i64"),
+            Type::U8 => write!(f, "u8"),
+            Type::U32 => write!(f, "u32"),
+            Type::U64 => write!(f, "u64"),
+            Type::Usize => write!(f, "usize"),
+            Type::Float => write!(f, "f64"),
+            Type::Bool => write!(f, "bool"),
+            Type::Str => write!(f, "str"),
+            Type::Void => write!(f, "()"),
+            Type::Never => write!(f, "!"),
+            Type::Array(elem_type, len) => write!(f, "[{}; {}]", elem_type, len),
+            Type::Named(name) => write!(f, "{}", name),
+            Type::Tuple(types) => {
+                let types: Vec<String> = types.iter().map(|ty| ty.to_string()).collect();
+                write!(f, "({})", types.join(", "))
+            }
+            Type::Fn(param_types, ret_type) => {
+                let param_types: Vec<String> =
+                    param_types.iter().map(|ty| ty.to_string()).collect();
+                write!(f, "fn({})", param_types.join(", "))?;
+                match **ret_type {
+                    Type::Void => Ok(()),
+                    _ => write!(f, " -> {}", ret_type),
+                }
+            }
+            Type::Var(_) => write!(f, "_"),
+        }
+    }
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -41,6 +392,7 @@ pub enum AriOp {
     Sub,
     Mul,
     Div,
+    Rem,
 }
 
 #[derive(Debug, PartialEq, Copy, Clone)]
@@ -50,6 +402,7 @@ pub enum AssOp {
     SubEq,
     DivEq,
     MulEq,
+    RemEq,
 }
 #[derive(Debug, PartialEq, Copy, Clone)]
 pub enum LogOp {
@@ -1,10 +1,13 @@
+use std::path::{Path, PathBuf};
 use structopt::StructOpt;
 
 use crate::ast::*;
 use crate::interpreter::*;
 use crate::llvm::*;
+use crate::modules::*;
 use crate::parser::*;
 use crate::program::*;
+use crate::resolver::*;
 use crate::type_checker::*;
 
 #[derive(Debug, StructOpt)]
@@ -15,6 +18,11 @@ execute examples:
 cargo run -- 
 cargo run -- -l
 cargo run -- -l -a
+cargo run -- -l -w
+cargo run -- -l -g
+cargo run -- --emit=typed-ast
+cargo run -- --emit=symbols
+cargo run -- main.rs
 cargo run -- --help
 "
 )]
@@ -24,37 +32,98 @@ struct Opt {
 
     #[structopt(short, long)]
     ast: bool,
+
+    /// Use wrapping instead of checked integer arithmetic
+    #[structopt(short, long)]
+    wrapping: bool,
+
+    /// Emit DWARF debug info for the compiled program
+    #[structopt(short = "g", long)]
+    debug: bool,
+
+    /// Print an intermediate form of the program, `typed-ast` is the ast with inferred types
+    /// and `symbols` the declarations its names are linked to
+    #[structopt(long, possible_values = &["typed-ast", "symbols"])]
+    emit: Option<String>,
+
+    /// Source file of the root module, `mod name;` loads `name.rs` next to it. Runs the
+    /// built-in example program when left out
+    #[structopt(parse(from_os_str))]
+    file: Option<PathBuf>,
 }
 
 pub fn cli() {
     let opt = Opt::from_args();
 
-    let p = program();
+    let p = match &opt.file {
+        Some(file) => std::fs::read_to_string(file).expect("ERROR: Could not read the file!"),
+        None => program(),
+    };
+    let dir = match &opt.file {
+        Some(file) => file.parent().unwrap_or_else(|| Path::new(".")),
+        None => Path::new("."),
+    };
 
-    let mut ast = match parser(&p) {
-        Ok(res) => res,
-        Err(e) => {
-            panic!("Error: {:#}", e)
-        }
+    let ast = match parse_file(&p, None) {
+        Ok(ast) => ast,
+        Err(span) => panic!("Error: could not parse the program at {}", span),
     };
 
     if opt.ast {
         println!("ast = {:#?}", ast);
     }
 
-    if type_checker(ast.clone().1) {
-        if opt.llvm {
-            let _res = llvm(ast.1);
-        } else {
-            ast.1.push(Expr::Return(Box::new(Expr::FnCall(
-                Box::new(Expr::Var("main".to_string())),
-                vec![],
-            ))));
-
-            let res = interpreter(ast.1);
-            println!("interp:  {:#?}", res);
+    let ast = match load_modules(ast, dir).and_then(resolve) {
+        Ok((ast, symbols)) => {
+            if opt.emit.as_deref() == Some("symbols") {
+                println!("symbols = {:#?}", symbols);
+            }
+            ast
+        }
+        Err(e) => {
+            eprintln!("{}", e);
+            panic!("ERROR: Name resolution failed!");
+        }
+    };
+
+    let mut typed_ast = match check(ast) {
+        Ok(typed_ast) if !typed_ast.is_empty() => typed_ast,
+        Ok(_) => panic!("ERROR: Typechecker failed!"),
+        Err(e) => {
+            eprintln!("{}", e);
+            panic!("ERROR: Typechecker failed!");
+        }
+    };
+
+    if opt.emit.as_deref() == Some("typed-ast") {
+        println!("typed ast = {:#?}", typed_ast);
+    }
+
+    if opt.llvm {
+        let options = CompileOptions {
+            wrapping: opt.wrapping,
+            debug: opt.debug,
+            source_path: opt.file.clone(),
+        };
+        if let Err(e) = llvm_with_options(typed_ast, &options) {
+            eprintln!("{}", e);
+            std::process::exit(101);
         }
     } else {
-        panic!("ERROR: Typechecker failed!");
+        typed_ast.push(Expr::Return(Box::new(Expr::FnCall(
+            Box::new(Expr::Var("main".to_string(), None)),
+            vec![],
+        ))));
+
+        let options = RunOptions {
+            wrapping: opt.wrapping,
+        };
+        match run_with_options(typed_ast, &options) {
+            Ok(res) => println!("interp:  {:#?}", res),
+            Err(e) => {
+                eprintln!("{}", e);
+                std::process::exit(101);
+            }
+        }
     }
 }
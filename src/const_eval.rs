@@ -0,0 +1,300 @@
+use crate::ast::*;
+
+use std::collections::HashMap;
+
+/// Evaluates the initializer of a `const` or `static` item to a literal, `consts` are the
+/// initializers of the constants it can refer to. The type checker has already checked
+/// `expr`, the error is a message for anything that can not run at compile time.
+pub fn const_eval(expr: &Expr, consts: &HashMap<String, Expr>) -> Result<Expr, String> {
+    let mut evaluator = ConstEval {
+        consts,
+        evaluating: Vec::new(),
+    };
+    evaluator.eval(expr)
+}
+
+struct ConstEval<'a> {
+    consts: &'a HashMap<String, Expr>,
+    /// Constants whose initializers are being evaluated, innermost last
+    evaluating: Vec<String>,
+}
+
+impl<'a> ConstEval<'a> {
+    fn eval(&mut self, expr: &Expr) -> Result<Expr, String> {
+        match expr {
+            Expr::Int(_) | Expr::TypedInt(..) | Expr::Float(_) | Expr::Bool(_) | Expr::Str(_) => {
+                Ok(expr.clone())
+            }
+            Expr::Var(name, _) => self.eval_const(name),
+            Expr::Array(elems) => Ok(Expr::Array(self.eval_all(elems)?)),
+            Expr::ArrayRepeat(elem, len) => Ok(Expr::Array(vec![self.eval(elem)?; *len])),
+            Expr::Tuple(elems) => Ok(Expr::Tuple(self.eval_all(elems)?)),
+            Expr::StructLit(name, fields) => {
+                let mut values = Vec::new();
+                for (field, expr) in fields {
+                    values.push((field.to_string(), self.eval(expr)?));
+                }
+                Ok(Expr::StructLit(name.to_string(), values))
+            }
+            Expr::Index(array, index) => match (self.eval(array)?, self.eval(index)?) {
+                (Expr::Array(mut elems), index) => {
+                    let len = elems.len();
+                    match int_value(&index) {
+                        Some((index, _)) if 0 <= index && index < len as i128 => {
+                            Ok(elems.swap_remove(index as usize))
+                        }
+                        Some((index, _)) => Err(format!(
+                            "evaluation of constant value failed: index out of bounds: the length is {} but the index is {}",
+                            len, index
+                        )),
+                        None => panic!("Invalid const index!"),
+                    }
+                }
+                _ => panic!("Invalid const index expr!"),
+            },
+            Expr::TupleField(tuple, index) => match self.eval(tuple)? {
+                Expr::Tuple(mut elems) => Ok(elems.swap_remove(*index)),
+                _ => panic!("Invalid const tuple field expr!"),
+            },
+            Expr::Field(value, field) => match self.eval(value)? {
+                Expr::StructLit(_, fields) => Ok(fields
+                    .into_iter()
+                    .find(|(other, _)| other == field)
+                    .unwrap()
+                    .1),
+                _ => panic!("Invalid const field expr!"),
+            },
+            Expr::BinExpr(l, op, r) | Expr::VarExpr(l, op, r) => {
+                let l = self.eval(l)?;
+                let r = self.eval(r)?;
+                eval_bin_expr(l, op.clone(), r)
+            }
+            Expr::Cast(expr, to_type) => Ok(eval_cast(self.eval(expr)?, to_type)),
+            Expr::Spanned(_, expr) => self.eval(expr),
+            Expr::FnCall(..) | Expr::MethodCall(..) => Err(
+                "calls in constants are limited to constant functions, tuple structs and tuple variants"
+                    .to_string(),
+            ),
+            Expr::Variant(..) => Err("enum values can not be used in constants".to_string()),
+            _ => Err("this expression can not be evaluated at compile time".to_string()),
+        }
+    }
+
+    fn eval_all(&mut self, exprs: &[Expr]) -> Result<Vec<Expr>, String> {
+        exprs.iter().map(|expr| self.eval(expr)).collect()
+    }
+
+    /// Value of a constant, its initializer is evaluated where it is used.
+    fn eval_const(&mut self, name: &str) -> Result<Expr, String> {
+        let init = match self.consts.get(name) {
+            Some(init) => init,
+            None => return Err("attempt to use a non-constant value in a constant".to_string()),
+        };
+        if self.evaluating.iter().any(|other| other == name) {
+            return Err(format!(
+                "cycle detected when evaluating the constant `{}`",
+                name
+            ));
+        }
+        self.evaluating.push(name.to_string());
+        let value = self.eval(init)?;
+        self.evaluating.pop();
+        Ok(value)
+    }
+}
+
+/// An integer literal as an `i128` with its type.
+fn int_value(expr: &Expr) -> Option<(i128, Type)> {
+    match expr {
+        Expr::Int(i) => Some((*i as i128, Type::Int)),
+        Expr::TypedInt(i, int_type) => Some((int_type.wrap_int(*i as i128), int_type.clone())),
+        _ => None,
+    }
+}
+
+/// The literal for `val` of the integer type `int_type`, negative values of the other
+/// integer types are stored in two's complement.
+fn int_literal(val: i128, int_type: Type) -> Expr {
+    match int_type {
+        Type::Int => Expr::Int(val as i32),
+        _ => Expr::TypedInt(val as u64, int_type),
+    }
+}
+
+fn eval_bin_expr(l: Expr, op: Op, r: Expr) -> Result<Expr, String> {
+    if let (Some((l, int_type)), Some((r, _))) = (int_value(&l), int_value(&r)) {
+        return match op {
+            Op::AriOp(op) => Ok(int_literal(eval_ari(l, op, r, &int_type)?, int_type)),
+            Op::RelOp(op) => Ok(Expr::Bool(compare(&l, op, &r))),
+            _ => panic!("Invalid const int expr!"),
+        };
+    }
+    match (l, op, r) {
+        (Expr::Float(l), Op::AriOp(op), Expr::Float(r)) => Ok(Expr::Float(match op {
+            AriOp::Add => l + r,
+            AriOp::Sub => l - r,
+            AriOp::Mul => l * r,
+            AriOp::Div => l / r,
+            AriOp::Rem => l % r,
+        })),
+        (Expr::Float(l), Op::RelOp(op), Expr::Float(r)) => Ok(Expr::Bool(compare(&l, op, &r))),
+        (Expr::Bool(l), Op::LogOp(LogOp::And), Expr::Bool(r)) => Ok(Expr::Bool(l && r)),
+        (Expr::Bool(l), Op::LogOp(LogOp::Or), Expr::Bool(r)) => Ok(Expr::Bool(l || r)),
+        (Expr::Bool(l), Op::RelOp(op), Expr::Bool(r)) => Ok(Expr::Bool(compare(&l, op, &r))),
+        (Expr::Str(l), Op::AriOp(AriOp::Add), Expr::Str(r)) => Ok(Expr::Str(l + &r)),
+        (Expr::Str(l), Op::RelOp(op), Expr::Str(r)) => Ok(Expr::Bool(compare(&l, op, &r))),
+        (_, Op::AssOp(_), _) => Err("assignments can not be evaluated at compile time".to_string()),
+        _ => panic!("Invalid const bin expr!"),
+    }
+}
+
+fn compare<T: PartialOrd>(l: &T, op: RelOp, r: &T) -> bool {
+    match op {
+        RelOp::Eq => l == r,
+        RelOp::Neq => l != r,
+        RelOp::Leq => l <= r,
+        RelOp::Geq => l >= r,
+        RelOp::Les => l < r,
+        RelOp::Gre => l > r,
+    }
+}
+
+/// Integer arithmetic checked against the range of `int_type`, overflow is an error at
+/// compile time even when the program uses wrapping arithmetic.
+fn eval_ari(l: i128, op: AriOp, r: i128, int_type: &Type) -> Result<i128, String> {
+    let failed = |msg: &str| Err(format!("evaluation of constant value failed: {}", msg));
+    match (op, r) {
+        (AriOp::Div, 0) => return failed("attempt to divide by zero"),
+        (AriOp::Rem, 0) => {
+            return failed("attempt to calculate the remainder with a divisor of zero")
+        }
+        _ => (),
+    }
+
+    let (res, msg) = match op {
+        AriOp::Add => (l + r, "attempt to add with overflow"),
+        AriOp::Sub => (l - r, "attempt to subtract with overflow"),
+        AriOp::Mul => (l * r, "attempt to multiply with overflow"),
+        AriOp::Div => (l / r, "attempt to divide with overflow"),
+        AriOp::Rem => (l % r, "attempt to calculate the remainder with overflow"),
+    };
+
+    // `MIN % -1` overflows just like `MIN / -1` does
+    let (min, max) = int_type.int_range();
+    let rem_overflow = op == AriOp::Rem && l == min && r == -1;
+    if res < min || max < res || rem_overflow {
+        return failed(msg);
+    }
+    Ok(res)
+}
+
+/// Converts a number or bool like `as` does at runtime.
+fn eval_cast(val: Expr, to_type: &Type) -> Expr {
+    let val = match (val, to_type) {
+        (Expr::Float(f), Type::Float) => return Expr::Float(f),
+        (Expr::Float(f), _) => {
+            let (min, max) = to_type.int_range();
+            (f as i128).max(min).min(max)
+        }
+        (Expr::Bool(b), _) => b as i128,
+        (val, _) => match int_value(&val) {
+            Some((i, _)) if *to_type == Type::Float => return Expr::Float(i as f64),
+            Some((i, _)) => i,
+            None => panic!("Invalid const cast of {:?} to {}!", val, to_type),
+        },
+    };
+    int_literal(to_type.wrap_int(val), to_type.clone())
+}
+
+#[cfg(test)]
+mod const_eval_tests {
+    use super::*;
+
+    fn eval(expr: Expr) -> Result<Expr, String> {
+        let mut consts = HashMap::new();
+        consts.insert("SIZE".to_string(), Expr::TypedInt(4, Type::Usize));
+        consts.insert(
+            "LOOP".to_string(),
+            Expr::BinExpr(
+                Box::new(Expr::Var("LOOP".to_string(), None)),
+                Op::AriOp(AriOp::Add),
+                Box::new(Expr::Int(1)),
+            ),
+        );
+        const_eval(&expr, &consts)
+    }
+
+    fn bin(l: Expr, op: Op, r: Expr) -> Expr {
+        Expr::BinExpr(Box::new(l), op, Box::new(r))
+    }
+
+    #[test]
+    fn test_const_eval() {
+        assert_eq!(
+            eval(bin(
+                Expr::Var("SIZE".to_string(), None),
+                Op::AriOp(AriOp::Mul),
+                Expr::TypedInt(2, Type::Usize)
+            )),
+            Ok(Expr::TypedInt(8, Type::Usize))
+        );
+        assert_eq!(
+            eval(bin(
+                Expr::TypedInt(1, Type::I8),
+                Op::AriOp(AriOp::Sub),
+                Expr::TypedInt(3, Type::I8)
+            )),
+            Ok(Expr::TypedInt(-2i64 as u64, Type::I8))
+        );
+        assert_eq!(
+            eval(Expr::Cast(Box::new(Expr::Int(300)), Type::U8)),
+            Ok(Expr::TypedInt(44, Type::U8))
+        );
+        assert_eq!(
+            eval(Expr::Index(
+                Box::new(Expr::ArrayRepeat(Box::new(Expr::Bool(true)), 2)),
+                Box::new(Expr::TypedInt(1, Type::Usize))
+            )),
+            Ok(Expr::Bool(true))
+        );
+        assert_eq!(
+            eval(bin(
+                Expr::Str("ab".to_string()),
+                Op::AriOp(AriOp::Add),
+                Expr::Str("c".to_string())
+            )),
+            Ok(Expr::Str("abc".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_const_eval_errors() {
+        assert_eq!(
+            eval(bin(
+                Expr::TypedInt(200, Type::U8),
+                Op::AriOp(AriOp::Add),
+                Expr::TypedInt(100, Type::U8)
+            )),
+            Err("evaluation of constant value failed: attempt to add with overflow".to_string())
+        );
+        assert_eq!(
+            eval(bin(Expr::Int(1), Op::AriOp(AriOp::Div), Expr::Int(0))),
+            Err("evaluation of constant value failed: attempt to divide by zero".to_string())
+        );
+        assert_eq!(
+            eval(Expr::Var("x".to_string(), None)),
+            Err("attempt to use a non-constant value in a constant".to_string())
+        );
+        assert_eq!(
+            eval(Expr::Var("LOOP".to_string(), None)),
+            Err("cycle detected when evaluating the constant `LOOP`".to_string())
+        );
+        assert_eq!(
+            eval(Expr::FnCall(Box::new(Expr::Var("f".to_string(), None)), vec![])),
+            Err(
+                "calls in constants are limited to constant functions, tuple structs and tuple variants"
+                    .to_string()
+            )
+        );
+    }
+}
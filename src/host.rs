@@ -0,0 +1,68 @@
+use crate::memory::ExprRep;
+use crate::runtime::write_output;
+
+use std::{collections::HashMap, sync::Mutex};
+
+/// Host function as seen by the interpreter, called with the evaluated arguments.
+pub type HostFn = fn(&[ExprRep]) -> ExprRep;
+
+lazy_static! {
+    static ref HOST_FNS: Mutex<HashMap<&'static str, HostFn>> = {
+        let mut m: HashMap<&'static str, HostFn> = HashMap::new();
+        m.insert("abs", |args| ExprRep::Int(abs(int_arg(args, 0))));
+        m.insert("putchar", |args| ExprRep::Int(putchar(int_arg(args, 0))));
+        Mutex::new(m)
+    };
+}
+
+/// Makes `func` callable from programs that declare `extern fn name`.
+#[cfg(test)]
+pub fn insert_host_fn(name: &'static str, func: HostFn) {
+    HOST_FNS.lock().unwrap().insert(name, func);
+}
+
+pub fn read_host_fn(name: &str) -> Option<HostFn> {
+    HOST_FNS.lock().unwrap().get(name).copied()
+}
+
+/// Addresses the JIT maps `extern fn` declarations to, a program declaring any other
+/// name is rejected before it runs.
+pub fn host_symbols() -> Vec<(&'static str, usize)> {
+    vec![
+        ("abs", abs as extern "C" fn(i32) -> i32 as usize),
+        ("putchar", putchar as extern "C" fn(i32) -> i32 as usize),
+    ]
+}
+
+fn int_arg(args: &[ExprRep], i: usize) -> i32 {
+    match args.get(i) {
+        Some(ExprRep::Int(val)) => *val,
+        arg => panic!("Expected i32 argument, found {:?}", arg),
+    }
+}
+
+pub extern "C" fn abs(x: i32) -> i32 {
+    x.wrapping_abs()
+}
+
+/// Writes the low byte of `c` to stdout, like the C function.
+pub extern "C" fn putchar(c: i32) -> i32 {
+    write_output(&(c as u8 as char).to_string());
+    c
+}
+
+#[cfg(test)]
+mod host_tests {
+    use super::*;
+
+    #[test]
+    fn test_host_fn() {
+        let abs = read_host_fn("abs").unwrap();
+        assert_eq!(abs(&[ExprRep::Int(-3)]), ExprRep::Int(3));
+        assert!(read_host_fn("missing").is_none());
+
+        insert_host_fn("double", |args| ExprRep::Int(int_arg(args, 0) * 2));
+        let double = read_host_fn("double").unwrap();
+        assert_eq!(double(&[ExprRep::Int(4)]), ExprRep::Int(8));
+    }
+}
@@ -1,44 +1,219 @@
 use crate::ast::*;
+use crate::host::*;
 use crate::memory::*;
+use crate::resolver::*;
+use crate::runtime::*;
 
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::{fmt, panic};
+
+thread_local! {
+    static WRAPPING: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Evaluation settings picked on the command line.
+#[derive(Debug, Default, Clone)]
+pub struct RunOptions {
+    pub wrapping: bool,
+}
+
+/// Error raised by the evaluated program, e.g. on integer overflow.
+#[derive(Debug, PartialEq, Clone)]
+pub struct RuntimeError {
+    pub span: Span,
+    pub msg: String,
+}
+
+impl fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "runtime error at {}: {}", self.span, self.msg)
+    }
+}
+
+/// Aborts evaluation of the program, the error is picked up again by `run`.
+fn runtime_error(msg: &str) -> ! {
+    panic::resume_unwind(Box::new(RuntimeError {
+        span: read_span(),
+        msg: msg.to_string(),
+    }))
+}
+
+/// Runs the program and reports runtime errors instead of unwinding.
+#[cfg(test)]
+pub fn run(ast: Vec<Expr>) -> Result<ExprRep, RuntimeError> {
+    run_with_options(ast, &RunOptions::default())
+}
+
+pub fn run_with_options(ast: Vec<Expr>, options: &RunOptions) -> Result<ExprRep, RuntimeError> {
+    set_span(Span::default());
+    // The mode is only visible to this thread for the duration of the run
+    let wrapping = WRAPPING.with(|w| w.replace(options.wrapping));
+    let res = panic::catch_unwind(move || interpreter(ast));
+    WRAPPING.with(|w| w.set(wrapping));
+    match res {
+        Ok(res) => Ok(res),
+        Err(payload) => match payload.downcast::<RuntimeError>() {
+            Ok(err) => Err(*err),
+            Err(payload) => panic::resume_unwind(payload),
+        },
+    }
+}
+
+/// Type checks the program in `src` and calls its `main` with `input` as stdin, returning
+/// the result and everything it printed.
+#[cfg(test)]
+pub fn run_main(src: &str, input: &str) -> (Result<ExprRep, RuntimeError>, String) {
+    let ast = crate::parser::parse_file(src, None).unwrap();
+    let mut ast = crate::type_checker::check(ast).unwrap();
+    ast.push(Expr::Return(Box::new(Expr::FnCall(
+        Box::new(Expr::Var("main".to_string(), None)),
+        vec![],
+    ))));
+    with_io(input, || run(ast))
+}
+
+/// Resolves the names of the program and runs it, a name that can't be resolved aborts
+/// like a runtime error.
 pub fn interpreter(ast: Vec<Expr>) -> ExprRep {
+    match resolve(ast) {
+        Ok((ast, _)) => eval_program(ast),
+        Err(e) => panic::resume_unwind(Box::new(RuntimeError {
+            span: e.span,
+            msg: e.msg,
+        })),
+    }
+}
+
+/// Runs a resolved program, variables are found by the declarations of their `Var`s.
+fn eval_program(ast: Vec<Expr>) -> ExprRep {
+    // The declarations are numbered per program, variables of an earlier run would alias
+    clear_vars();
+    // Functions are registered before any statement runs, so they can be called from
+    // anywhere regardless of the order they are defined in
+    for item in ast.iter().filter(|item| is_fn_item(item)) {
+        eval_expr(item.clone());
+    }
     let mut res = ExprRep::Null;
     for expr in ast.iter() {
-        res = eval_expr(expr.clone());
-        match res {
-            _ => continue,
+        res = match is_fn_item(expr) {
+            true => ExprRep::Null,
+            false => eval_expr(expr.clone()),
+        };
+        if let ExprRep::Return(value) = res {
+            return *value;
         }
     }
     return res;
 }
 
+/// Whether `expr` is a function, extern function or impl, which declare functions.
+fn is_fn_item(expr: &Expr) -> bool {
+    match expr {
+        Expr::Spanned(_, expr) => is_fn_item(expr),
+        Expr::Fn(..) | Expr::ExternFn(..) | Expr::Impl(..) => true,
+        _ => false,
+    }
+}
+
 fn eval_expr(expr: Expr) -> ExprRep {
     match expr {
         Expr::Int(i) => ExprRep::Int(i),
+        // Negative constants are stored in two's complement
+        Expr::TypedInt(i, int_type) => ExprRep::TypedInt(int_type.wrap_int(i as i128), int_type),
+        Expr::Float(f) => ExprRep::Float(f),
         Expr::Bool(b) => ExprRep::Bool(b),
-        Expr::Var(n) => read_var(&n),
+        Expr::Str(s) => ExprRep::Str(s),
+        Expr::Var(n, id) => eval_var(&n, id),
+
+        Expr::Array(elems) => ExprRep::Array(elems.into_iter().map(eval_expr).collect()),
+        Expr::ArrayRepeat(elem, len) => ExprRep::Array(vec![eval_expr(*elem); len]),
+        Expr::Index(array, index) => eval_index(*array, *index),
+
+        Expr::StructLit(name, fields) => ExprRep::Struct(
+            name,
+            fields
+                .into_iter()
+                .map(|(field, expr)| (field, eval_expr(expr)))
+                .collect(),
+        ),
+        Expr::Field(expr, field) => eval_field(eval_expr(*expr), &field),
+
+        Expr::Variant(name, variant, args) => {
+            ExprRep::Variant(name, variant, args.into_iter().map(eval_expr).collect())
+        }
+
+        Expr::Tuple(elems) => ExprRep::Tuple(elems.into_iter().map(eval_expr).collect()),
+        Expr::TupleField(expr, index) => match eval_expr(*expr) {
+            ExprRep::Tuple(mut elems) => elems.swap_remove(index),
+            _ => panic!("Invalid tuple field expr!"),
+        },
 
         Expr::BinExpr(l, op, r) => eval_bin_expr(*l, op, *r),
         Expr::VarExpr(var, op, expr) => eval_var_expr(*var, op, *expr),
+        Expr::Cast(expr, to_type) => eval_cast(eval_expr(*expr), to_type),
 
         Expr::Let(var, var_type, expr) => eval_let(*var, var_type, *expr),
+        // The variable has no value until it is assigned
+        Expr::Declare(var, _) => match *var {
+            Expr::Var(_, Some(id)) => bind_var(id, ExprRep::Null),
+            _ => panic!("Invalid declare var!"),
+        },
 
         Expr::If(cond, block) => eval_if(*cond, block),
         Expr::IfElse(cond, block1, block2) => eval_if_else(*cond, block1, block2),
         Expr::While(cond, block) => eval_while(*cond, block),
+        Expr::For(var, range, block) => eval_for(*var, *range, block),
+        Expr::Loop(block) => eval_loop(block),
+        Expr::Match(expr, arms) => eval_match(*expr, arms),
+        Expr::Block(block) => eval_block(block),
 
-        Expr::Fn(fn_var, params, ret_type, block) => eval_fn(*fn_var, params, ret_type, block),
+        Expr::Fn(fn_var, generics, params, ret_type, block) => {
+            let generics = generics.into_iter().map(|(generic, _)| generic).collect();
+            eval_fn(*fn_var, generics, params, ret_type, block)
+        }
         Expr::FnCall(fn_var, args) => eval_fn_call(*fn_var, args),
+        Expr::MethodCall(receiver, method, args) => eval_method_call(*receiver, method, args),
+        Expr::Closure(params, body) => eval_closure(params, *body),
+        Expr::ExternFn(fn_var, params, ret_type) => eval_extern_fn(*fn_var, params, ret_type),
+        Expr::Struct(..) => ExprRep::Null,
+        Expr::Enum(..) => ExprRep::Null,
+        Expr::Trait(..) => ExprRep::Null,
+        Expr::Impl(_, impl_type, methods) => eval_impl(impl_type, methods),
+        // Globals live in the same memory as every other variable
+        Expr::Const(var, _, value) | Expr::Static(var, _, value, _) => eval_let(*var, None, *value),
         Expr::Return(expr) => eval_return(*expr),
+        Expr::Break(value) => {
+            let value = value.map_or(ExprRep::Null, |value| eval_expr(*value));
+            ExprRep::Break(Box::new(value))
+        }
+        Expr::Continue => ExprRep::Continue,
+        Expr::Range(..) => panic!("Range outside of a for loop!"),
+        Expr::Mod(..) | Expr::Use(..) | Expr::Pub(..) => panic!("Modules are not resolved!"),
+        Expr::Tail(expr) => eval_expr(*expr),
+
+        Expr::Spanned(span, expr) => {
+            let prev = set_span(span);
+            let res = eval_expr(*expr);
+            set_span(prev);
+            res
+        }
     }
 }
 
-fn eval_fn(fn_var: Expr, params: Vec<(Expr, Type)>, ret_type: Type, block: Vec<Expr>) -> ExprRep {
+/// Generic functions are run directly, values of a type parameter can be of any type.
+fn eval_fn(
+    fn_var: Expr,
+    generics: Vec<String>,
+    params: Vec<(Expr, Type)>,
+    ret_type: Type,
+    block: Vec<Expr>,
+) -> ExprRep {
     match fn_var {
-        Expr::Var(a) => {
+        Expr::Var(a, _) => {
             insert_fn(
                 ExprRep::Var(a.to_string()),
-                ExprRep::Fn(params, ret_type, block),
+                ExprRep::Fn(generics, params, ret_type, block),
             );
         }
         _ => panic!("Fn stmt fail!"),
@@ -46,47 +221,232 @@ fn eval_fn(fn_var: Expr, params: Vec<(Expr, Type)>, ret_type: Type, block: Vec<E
     return ExprRep::Null;
 }
 
+fn eval_extern_fn(fn_var: Expr, params: Vec<(Expr, Type)>, ret_type: Type) -> ExprRep {
+    match fn_var {
+        Expr::Var(a, _) => insert_fn(
+            ExprRep::Var(a.to_string()),
+            ExprRep::ExternFn(a, params, ret_type),
+        ),
+        _ => panic!("Extern fn stmt fail!"),
+    }
+}
+
+/// Calls the host function registered under the name of the `extern fn`.
+fn eval_extern_fn_call(fn_var: &str, args: Vec<ExprRep>) -> ExprRep {
+    match read_host_fn(fn_var) {
+        Some(func) => func(&args),
+        None => runtime_error(&format!("unresolved extern fn `{}`", fn_var)),
+    }
+}
+
+/// Runs one of the functions every program can call, see `runtime::builtins`.
+fn eval_builtin(name: &str, args: Vec<Expr>) -> ExprRep {
+    let args: Vec<ExprRep> = args.into_iter().map(eval_expr).collect();
+    match (name, args.as_slice()) {
+        ("print_i32", [ExprRep::Int(x)]) => write_output(&x.to_string()),
+        ("print_f64", [ExprRep::Float(x)]) => write_output(&x.to_string()),
+        ("print_bool", [ExprRep::Bool(b)]) => write_output(&b.to_string()),
+        ("print_str", [ExprRep::Str(s)]) => write_output(s),
+        ("println", []) => write_output("\n"),
+        ("read_i32", []) => match read_input_i32() {
+            Some(x) => return ExprRep::Int(x),
+            None => runtime_error(READ_I32_ERROR),
+        },
+        ("len", [ExprRep::Str(s)]) => return ExprRep::Int(s.len() as i32),
+        ("len", [ExprRep::Array(elems)]) => return ExprRep::Int(elems.len() as i32),
+        _ => panic!("Invalid call of builtin {}!", name),
+    }
+    ExprRep::Null
+}
+
 fn eval_fn_call(fn_var: Expr, args: Vec<Expr>) -> ExprRep {
     match fn_var {
-        Expr::Var(fn_var) => match read_fn(&fn_var) {
-            ExprRep::Fn(params, ret_type, block) => {
-                if params.len() != args.clone().len() {
-                    panic!("params len != args len")
-                }
+        Expr::Var(fn_var, _) if is_builtin(&fn_var) => eval_builtin(&fn_var, args),
+        Expr::Var(fn_var, id) => {
+            // Variables holding functions or closures shadow the functions of the program
+            let callee = match id.map_or(ExprRep::Null, read_var) {
+                ExprRep::Null => read_fn(&fn_var),
+                callee => callee,
+            };
+            // Arguments are evaluated before any parameter is bound, they may read
+            // variables with the same names as the parameters
+            let eval_args: Vec<ExprRep> = args.into_iter().map(eval_expr).collect();
+            call_value(callee, eval_args)
+        }
+        _ => panic!("Invalid fn_var!"),
+    }
+}
 
-                for _ in 0..params.len() {
-                    let mut i = 0;
-                    for x in params.clone() {
-                        let eval_arg = eval_expr(args[i].clone());
-                        i += 1;
-                        match &x {
-                            (Expr::Var(v), t) => {
-                                match (t, eval_arg.clone()) {
-                                    (Type::Int, ExprRep::Int(_)) => {
-                                        insert_var(ExprRep::Var(v.to_string()), eval_arg)
-                                    }
-                                    (Type::Bool, ExprRep::Bool(_)) => {
-                                        insert_var(ExprRep::Var(v.to_string()), eval_arg)
-                                    }
-                                    _ => panic!("Return type does not match!"),
-                                };
-                            }
-                            _ => panic!("Invalid param var!"),
-                        }
-                    }
-                }
+/// Calls a function, extern function or closure with evaluated arguments.
+fn call_value(callee: ExprRep, eval_args: Vec<ExprRep>) -> ExprRep {
+    match callee {
+        ExprRep::Fn(generics, params, ret_type, block) => {
+            if params.len() != eval_args.len() {
+                panic!("params len != args len")
+            }
+            call_fn(generics, params, ret_type, block, eval_args)
+        }
+        ExprRep::ExternFn(name, ..) => eval_extern_fn_call(&name, eval_args),
+        ExprRep::Closure(params, body, env) => call_closure(params, *body, env, eval_args),
+        _ => panic!("Could not find fn_var in map!"),
+    }
+}
 
-                let res = eval_block(block);
-                match (ret_type, res.clone()) {
-                    (Type::Int, ExprRep::Int(_)) => res,
-                    (Type::Bool, ExprRep::Bool(_)) => res,
+/// A variable, or a function used as a value.
+fn eval_var(name: &str, id: Option<DeclId>) -> ExprRep {
+    match id.map_or(ExprRep::Null, read_var) {
+        ExprRep::Null => lookup_fn(name).unwrap_or(ExprRep::Null),
+        val => val,
+    }
+}
+
+/// Closures capture the values of the variables their body uses when they are created.
+fn eval_closure(params: Vec<(Expr, Type)>, body: Expr) -> ExprRep {
+    let mut vars = Vec::new();
+    body.referenced_vars(&mut vars);
+    // The parameters and the variables of the body are not bound yet
+    let env = vars
+        .into_iter()
+        .filter_map(|(_, id)| match read_var(id) {
+            ExprRep::Null => None,
+            val => Some((id, val)),
+        })
+        .collect();
+    ExprRep::Closure(params, Box::new(body), env)
+}
+
+/// Runs the body of a closure with its captured variables and parameters bound, in a
+/// call of its own.
+fn call_closure(
+    params: Vec<(Expr, Type)>,
+    body: Expr,
+    env: Vec<(DeclId, ExprRep)>,
+    eval_args: Vec<ExprRep>,
+) -> ExprRep {
+    push_frame();
+    for (id, val) in env {
+        bind_var(id, val);
+    }
+    for ((param, _), arg) in params.iter().zip(eval_args) {
+        eval_destructure(param, arg);
+    }
+    let res = match eval_expr(body) {
+        ExprRep::Return(value) => *value,
+        res => res,
+    };
+    pop_frame();
+    res
+}
+
+/// Binds the evaluated arguments to the parameters and runs the body, the variables of
+/// the call are its own and arguments are passed by value.
+fn call_fn(
+    generics: Vec<String>,
+    params: Vec<(Expr, Type)>,
+    ret_type: Type,
+    block: Vec<Expr>,
+    eval_args: Vec<ExprRep>,
+) -> ExprRep {
+    push_frame();
+    for (x, eval_arg) in params.into_iter().zip(eval_args) {
+        match &x {
+            (Expr::Var(_, Some(v)), t) => {
+                match (t, eval_arg.clone()) {
+                    (Type::Named(name), _) if generics.contains(name) => bind_var(*v, eval_arg),
+                    (Type::Int, ExprRep::Int(_)) => bind_var(*v, eval_arg),
+                    (Type::Bool, ExprRep::Bool(_)) => bind_var(*v, eval_arg),
+                    (Type::Str, ExprRep::Str(_)) => bind_var(*v, eval_arg),
+                    (Type::Float, ExprRep::Float(_)) => bind_var(*v, eval_arg),
+                    (t, ExprRep::TypedInt(_, int_type)) if *t == int_type => bind_var(*v, eval_arg),
+                    (Type::Array(..), ExprRep::Array(_)) => bind_var(*v, eval_arg),
+                    (Type::Named(_), ExprRep::Struct(..)) => bind_var(*v, eval_arg),
+                    (Type::Named(_), ExprRep::Variant(..)) => bind_var(*v, eval_arg),
+                    (Type::Tuple(_), ExprRep::Tuple(_)) => bind_var(*v, eval_arg),
+                    (Type::Fn(..), ExprRep::Fn(..))
+                    | (Type::Fn(..), ExprRep::ExternFn(..))
+                    | (Type::Fn(..), ExprRep::Closure(..)) => bind_var(*v, eval_arg),
                     _ => panic!("Return type does not match!"),
-                }
+                };
             }
-            _ => panic!("Could not find fn_var in map!"),
-        },
-        _ => panic!("Invalid fn_var!"),
+            (binding @ Expr::Tuple(_), _) => {
+                eval_destructure(binding, eval_arg);
+            }
+            _ => panic!("Invalid param var!"),
+        }
     }
+
+    let res = match eval_block(block) {
+        ExprRep::Return(value) => *value,
+        res => res,
+    };
+    pop_frame();
+    match (ret_type, res.clone()) {
+        (Type::Named(name), _) if generics.contains(&name) => res,
+        (Type::Void, ExprRep::Null) => res,
+        (Type::Int, ExprRep::Int(_)) => res,
+        (Type::Bool, ExprRep::Bool(_)) => res,
+        (Type::Str, ExprRep::Str(_)) => res,
+        (Type::Float, ExprRep::Float(_)) => res,
+        (t, ExprRep::TypedInt(_, int_type)) if t == int_type => res,
+        (Type::Array(..), ExprRep::Array(_)) => res,
+        (Type::Named(_), ExprRep::Struct(..)) => res,
+        (Type::Named(_), ExprRep::Variant(..)) => res,
+        (Type::Tuple(_), ExprRep::Tuple(_)) => res,
+        (Type::Fn(..), ExprRep::Fn(..))
+        | (Type::Fn(..), ExprRep::ExternFn(..))
+        | (Type::Fn(..), ExprRep::Closure(..)) => res,
+        _ => panic!("Return type does not match!"),
+    }
+}
+
+/// Calls the impl of `method` for the type of the receiver, the type checker made sure
+/// there is exactly one.
+fn eval_method_call(receiver: Expr, method: String, args: Vec<Expr>) -> ExprRep {
+    let receiver = eval_expr(receiver);
+    let path = value_type(&receiver).method_path(&method);
+    let mut eval_args = vec![receiver];
+    eval_args.extend(args.into_iter().map(eval_expr));
+    call_value(read_fn(&path), eval_args)
+}
+
+/// Type of a value, which is the static type the type checker found for it.
+fn value_type(val: &ExprRep) -> Type {
+    match val {
+        ExprRep::Int(_) => Type::Int,
+        ExprRep::TypedInt(_, int_type) => int_type.clone(),
+        ExprRep::Float(_) => Type::Float,
+        ExprRep::Bool(_) => Type::Bool,
+        ExprRep::Str(_) => Type::Str,
+        ExprRep::Array(elems) => Type::Array(Box::new(value_type(&elems[0])), elems.len()),
+        ExprRep::Struct(name, _) | ExprRep::Variant(name, ..) => Type::Named(name.to_string()),
+        ExprRep::Tuple(elems) => Type::Tuple(elems.iter().map(value_type).collect()),
+        _ => panic!("Value {:?} has no type!", val),
+    }
+}
+
+/// Registers the methods of an impl as functions named like `Circle::area`.
+fn eval_impl(impl_type: Type, methods: Vec<Expr>) -> ExprRep {
+    let mut self_type = HashMap::new();
+    self_type.insert("Self".to_string(), impl_type.clone());
+    for method in methods {
+        match method.unspanned() {
+            Expr::Fn(method_var, _, params, ret_type, block) => match *method_var {
+                Expr::Var(method, _) => {
+                    let params = params
+                        .into_iter()
+                        .map(|(param, param_type)| (param, param_type.substitute(&self_type)))
+                        .collect();
+                    insert_fn(
+                        ExprRep::Var(impl_type.method_path(&method)),
+                        ExprRep::Fn(Vec::new(), params, ret_type.substitute(&self_type), block),
+                    );
+                }
+                _ => panic!("Invalid method var!"),
+            },
+            _ => panic!("Impl stmt fail!"),
+        }
+    }
+    ExprRep::Null
 }
 
 pub fn eval_if(cond: Expr, block: Vec<Expr>) -> ExprRep {
@@ -101,20 +461,17 @@ pub fn eval_if(cond: Expr, block: Vec<Expr>) -> ExprRep {
     }
 }
 
+/// Runs the statements of a block, its value is the value of its tail expression. A
+/// `return`, `break` or `continue` ends the block early and is passed on.
 fn eval_block(block: Vec<Expr>) -> ExprRep {
-    let mut res = ExprRep::Null;
     for e in block.iter() {
-        res = eval_expr(e.clone());
-
-        // Check if the resulting expr contains a return value
-        match res {
-            ExprRep::Int(_) => break,
-            ExprRep::Bool(_) => break,
-            _ => continue,
+        let res = eval_expr(e.clone());
+        if res.is_jump() || e.is_tail() {
+            return res;
         }
+        // The values of other statements are thrown away
     }
-
-    res
+    ExprRep::Null
 }
 
 fn eval_if_else(cond: Expr, block1: Vec<Expr>, block2: Vec<Expr>) -> ExprRep {
@@ -131,42 +488,272 @@ fn eval_if_else(cond: Expr, block1: Vec<Expr>, block2: Vec<Expr>) -> ExprRep {
 }
 
 fn eval_while(cond: Expr, block: Vec<Expr>) -> ExprRep {
-    match eval_expr(cond) {
-        ExprRep::Bool(c) => {
-            if c {
-                return eval_block(block);
+    loop {
+        match eval_expr(cond.clone()) {
+            ExprRep::Bool(true) => {}
+            ExprRep::Bool(false) => return ExprRep::Null,
+            _ => panic!("While stmt fail!"),
+        }
+        match eval_block(block.clone()) {
+            ExprRep::Break(_) => return ExprRep::Null,
+            res @ ExprRep::Return(_) => return res,
+            _ => continue,
+        }
+    }
+}
+
+/// Runs the block until a `break`, whose value is the value of the loop.
+fn eval_loop(block: Vec<Expr>) -> ExprRep {
+    loop {
+        match eval_block(block.clone()) {
+            ExprRep::Break(value) => return *value,
+            res @ ExprRep::Return(_) => return res,
+            _ => continue,
+        }
+    }
+}
+
+/// Runs the block once for each integer of the range, the bounds are evaluated once.
+fn eval_for(var: Expr, range: Expr, block: Vec<Expr>) -> ExprRep {
+    let id = match var {
+        Expr::Var(_, Some(id)) => id,
+        _ => panic!("For stmt fail!"),
+    };
+    let (start, end, inclusive) = match range {
+        Expr::Range(start, end, inclusive) => (eval_expr(*start), eval_expr(*end), inclusive),
+        _ => panic!("For stmt fail!"),
+    };
+    let (start, end, int_type) = match (start, end) {
+        (ExprRep::Int(start), ExprRep::Int(end)) => (start as i128, end as i128, None),
+        (ExprRep::TypedInt(start, int_type), ExprRep::TypedInt(end, _)) => {
+            (start, end, Some(int_type))
+        }
+        _ => panic!("For stmt fail!"),
+    };
+    let end = if inclusive { end + 1 } else { end };
+
+    let mut res = ExprRep::Null;
+    for i in start..end {
+        let val = match &int_type {
+            Some(int_type) => ExprRep::TypedInt(i, int_type.clone()),
+            None => ExprRep::Int(i as i32),
+        };
+        bind_var(id, val);
+        match eval_block(block.clone()) {
+            ExprRep::Break(_) => break,
+            val @ ExprRep::Return(_) => {
+                res = val;
+                break;
             }
-            return ExprRep::Null;
+            _ => continue,
+        }
+    }
+    res
+}
+
+/// Runs the block of the first arm whose pattern matches the value.
+fn eval_match(expr: Expr, arms: Vec<(Pattern, Vec<Expr>)>) -> ExprRep {
+    let val = eval_expr(expr);
+    for (pattern, block) in arms {
+        let mut bindings = Vec::new();
+        if match_pattern(&pattern, &val, &mut bindings) {
+            for (id, val) in bindings {
+                bind_var(id, val);
+            }
+            return eval_block(block);
+        }
+    }
+    panic!("Match stmt fail!")
+}
+
+/// Whether `val` matches `pattern`, pushing the values of its bindings.
+fn match_pattern(pattern: &Pattern, val: &ExprRep, bindings: &mut Vec<(DeclId, ExprRep)>) -> bool {
+    match (pattern, val) {
+        (Pattern::Wildcard, _) => true,
+        (Pattern::Binding(_, Some(id)), _) => {
+            bindings.push((*id, val.clone()));
+            true
         }
-        _ => panic!("While stmt fail!"),
+        (Pattern::Literal(expr), _) => eval_expr(expr.clone()) == *val,
+        (Pattern::Variant(_, variant, patterns), ExprRep::Variant(_, other, fields)) => {
+            variant == other
+                && patterns
+                    .iter()
+                    .zip(fields.iter())
+                    .all(|(pattern, field)| match_pattern(pattern, field, bindings))
+        }
+        _ => false,
     }
 }
 
-fn eval_let(var: Expr, _var_type: Type, expr: Expr) -> ExprRep {
+fn eval_let(var: Expr, _var_type: Option<Type>, expr: Expr) -> ExprRep {
     match (var, eval_expr(expr)) {
-        (Expr::Var(v), ExprRep::Int(val)) => insert_var(ExprRep::Var(v), ExprRep::Int(val)),
-        (Expr::Var(v), ExprRep::Bool(val)) => insert_var(ExprRep::Var(v), ExprRep::Bool(val)),
+        // A `return` or `break` in the value leaves before anything is bound
+        (_, res) if res.is_jump() => res,
+        (Expr::Var(_, Some(v)), ExprRep::Int(val)) => bind_var(v, ExprRep::Int(val)),
+        (Expr::Var(_, Some(v)), ExprRep::Bool(val)) => bind_var(v, ExprRep::Bool(val)),
+        (Expr::Var(_, Some(v)), ExprRep::Str(val)) => bind_var(v, ExprRep::Str(val)),
+        (Expr::Var(_, Some(v)), val @ ExprRep::TypedInt(..)) => bind_var(v, val),
+        (Expr::Var(_, Some(v)), ExprRep::Float(val)) => bind_var(v, ExprRep::Float(val)),
+        (Expr::Var(_, Some(v)), val @ ExprRep::Array(_)) => bind_var(v, val),
+        (Expr::Var(_, Some(v)), val @ ExprRep::Struct(..)) => bind_var(v, val),
+        (Expr::Var(_, Some(v)), val @ ExprRep::Variant(..)) => bind_var(v, val),
+        (Expr::Var(_, Some(v)), val @ ExprRep::Tuple(_)) => bind_var(v, val),
+        (Expr::Var(_, Some(v)), val @ ExprRep::Fn(..))
+        | (Expr::Var(_, Some(v)), val @ ExprRep::ExternFn(..))
+        | (Expr::Var(_, Some(v)), val @ ExprRep::Closure(..)) => bind_var(v, val),
+        (binding @ Expr::Tuple(_), val @ ExprRep::Tuple(_)) => eval_destructure(&binding, val),
         _ => panic!("Invalid let expr!"),
     }
 }
 
+/// Binds the names of a destructuring `let` or parameter to the parts of a tuple.
+fn eval_destructure(binding: &Expr, val: ExprRep) -> ExprRep {
+    match (binding, val) {
+        (Expr::Tuple(bindings), ExprRep::Tuple(elems)) => {
+            for (binding, elem) in bindings.iter().zip(elems) {
+                eval_destructure(binding, elem);
+            }
+            ExprRep::Null
+        }
+        (Expr::Var(_, Some(v)), val) => bind_var(*v, val),
+        _ => panic!("Invalid destructuring!"),
+    }
+}
+
 fn eval_return(expr: Expr) -> ExprRep {
-    return eval_expr(expr);
+    match eval_expr(expr) {
+        res if res.is_jump() => res,
+        res => ExprRep::Return(Box::new(res)),
+    }
+}
+
+fn eval_index(array: Expr, index: Expr) -> ExprRep {
+    match eval_expr(array) {
+        ExprRep::Array(elems) => {
+            let i = array_index(elems.len(), eval_expr(index));
+            elems[i].clone()
+        }
+        _ => panic!("Invalid index expr!"),
+    }
+}
+
+/// Checks an evaluated index against the length of the array it indexes.
+fn array_index(len: usize, index: ExprRep) -> usize {
+    let i = match index {
+        ExprRep::Int(i) => i as i128,
+        ExprRep::TypedInt(i, _) => i,
+        _ => panic!("Invalid array index!"),
+    };
+    if i < 0 || i >= len as i128 {
+        runtime_error(&format!(
+            "index out of bounds: the len is {} but the index is {}",
+            len, i
+        ))
+    }
+    i as usize
+}
+
+fn eval_field(val: ExprRep, field: &str) -> ExprRep {
+    match val {
+        ExprRep::Struct(_, fields) => match fields.into_iter().find(|(name, _)| name == field) {
+            Some((_, val)) => val,
+            None => panic!("Could not find field {}!", field),
+        },
+        _ => panic!("Invalid field expr!"),
+    }
+}
+
+/// One step from a value to a part of it in an assignment target.
+enum Step {
+    Index(ExprRep),
+    TupleField(usize),
+    Field(String),
+}
+
+/// Evaluates an assignment target like `a[i].x` to the variable it is rooted
+/// at and the steps leading to the assigned part.
+fn eval_place(place: Expr) -> (DeclId, Vec<Step>) {
+    match place {
+        Expr::Var(_, Some(v)) => (v, Vec::new()),
+        Expr::Index(array, index) => {
+            let (v, mut path) = eval_place(*array);
+            path.push(Step::Index(eval_expr(*index)));
+            (v, path)
+        }
+        Expr::TupleField(expr, index) => {
+            let (v, mut path) = eval_place(*expr);
+            path.push(Step::TupleField(index));
+            (v, path)
+        }
+        Expr::Field(expr, field) => {
+            let (v, mut path) = eval_place(*expr);
+            path.push(Step::Field(field));
+            (v, path)
+        }
+        _ => panic!("Invalid assignment target!"),
+    }
+}
+
+/// Replaces the part at `path` inside `val` with `update` applied to it.
+fn update_elem(val: ExprRep, path: &[Step], update: impl FnOnce(ExprRep) -> ExprRep) -> ExprRep {
+    match (val, path.split_first()) {
+        (val, None) => update(val),
+        (ExprRep::Array(mut elems), Some((Step::Index(index), path))) => {
+            let i = array_index(elems.len(), index.clone());
+            elems[i] = update_elem(elems[i].clone(), path, update);
+            ExprRep::Array(elems)
+        }
+        (ExprRep::Tuple(mut elems), Some((Step::TupleField(index), path))) => {
+            elems[*index] = update_elem(elems[*index].clone(), path, update);
+            ExprRep::Tuple(elems)
+        }
+        (ExprRep::Struct(name, mut fields), Some((Step::Field(field), path))) => {
+            let i = match fields.iter().position(|(other, _)| other == field) {
+                Some(i) => i,
+                None => panic!("Could not find field {}!", field),
+            };
+            fields[i].1 = update_elem(fields[i].1.clone(), path, update);
+            ExprRep::Struct(name, fields)
+        }
+        _ => panic!("Invalid assignment target!"),
+    }
+}
+
+/// Assigns to an array element or struct field, `a[i].x op= val`.
+fn place_ass_op(place: Expr, op: AssOp, val: ExprRep) -> ExprRep {
+    let (v, path) = eval_place(place);
+    let array = update_elem(read_var(v), &path, |old| match op {
+        AssOp::Eq => val,
+        op => eval_compound_ass(old, ass_ari_op(op), val),
+    });
+    insert_var(v, array)
+}
+
+/// The new value of a compound assignment like `+=`.
+fn eval_compound_ass(old: ExprRep, op: AriOp, val: ExprRep) -> ExprRep {
+    match (old, val) {
+        (ExprRep::Int(l), ExprRep::Int(r)) => ExprRep::Int(eval_ari(l, op, r)),
+        (ExprRep::TypedInt(l, int_type), ExprRep::TypedInt(r, _)) => {
+            ExprRep::TypedInt(eval_typed_ari(l, op, r, &int_type), int_type)
+        }
+        (ExprRep::Float(l), ExprRep::Float(r)) => eval_float_expr(l, Op::AriOp(op), r),
+        (ExprRep::Str(l), ExprRep::Str(r)) => eval_str_expr(l, Op::AriOp(op), r),
+        _ => panic!("Invalid compound assignment!"),
+    }
 }
 
 fn eval_bin_expr(l: Expr, op: Op, r: Expr) -> ExprRep {
-    match (eval_expr(l), eval_expr(r.clone())) {
+    match (eval_expr(l), eval_expr(r)) {
         (ExprRep::Int(left), ExprRep::Int(right)) => eval_int_expr(left, op, right),
-        (ExprRep::Var(v), ExprRep::Int(right)) => match read_var(&v) {
-            ExprRep::Int(val) => eval_int_expr(val, op, right),
-            _ => ExprRep::Int(right),
-        },
+        (ExprRep::TypedInt(left, int_type), ExprRep::TypedInt(right, _)) => {
+            eval_typed_int_expr(left, op, right, int_type)
+        }
+        (ExprRep::Float(left), ExprRep::Float(right)) => eval_float_expr(left, op, right),
         (ExprRep::Bool(left), ExprRep::Bool(right)) => eval_bool_expr(left, op, right),
-        (ExprRep::Var(v), ExprRep::Bool(right)) => match read_var(&v) {
-            ExprRep::Bool(val) => eval_bool_expr(val, op, right),
-            _ => ExprRep::Bool(right),
-        },
-        (ExprRep::Null, _) => eval_expr(r),
+        (ExprRep::Str(left), ExprRep::Str(right)) => eval_str_expr(left, op, right),
+        // `let` values are `= value` with an empty left side, evaluated only once
+        (ExprRep::Null, right) => right,
         _ => panic!("Invalid bin expr!"),
     }
 }
@@ -181,12 +768,142 @@ fn eval_var_expr(var: Expr, op: Op, expr: Expr) -> ExprRep {
     }
 }
 
+/// Integer arithmetic with `i32` semantics, overflow is a runtime error unless
+/// wrapping arithmetic is enabled. Division by zero is always an error.
+fn eval_ari(l: i32, op: AriOp, r: i32) -> i32 {
+    match (op, r) {
+        (AriOp::Div, 0) => runtime_error("attempt to divide by zero"),
+        (AriOp::Rem, 0) => {
+            runtime_error("attempt to calculate the remainder with a divisor of zero")
+        }
+        _ => (),
+    }
+
+    if WRAPPING.with(Cell::get) {
+        return match op {
+            AriOp::Add => l.wrapping_add(r),
+            AriOp::Sub => l.wrapping_sub(r),
+            AriOp::Mul => l.wrapping_mul(r),
+            AriOp::Div => l.wrapping_div(r),
+            AriOp::Rem => l.wrapping_rem(r),
+        };
+    }
+
+    let (res, msg) = match op {
+        AriOp::Add => (l.checked_add(r), "attempt to add with overflow"),
+        AriOp::Sub => (l.checked_sub(r), "attempt to subtract with overflow"),
+        AriOp::Mul => (l.checked_mul(r), "attempt to multiply with overflow"),
+        AriOp::Div => (l.checked_div(r), "attempt to divide with overflow"),
+        AriOp::Rem => (
+            l.checked_rem(r),
+            "attempt to calculate the remainder with overflow",
+        ),
+    };
+
+    match res {
+        Some(val) => val,
+        None => runtime_error(msg),
+    }
+}
+
+/// Arithmetic on any integer type other than `i32`, computed exactly and then
+/// checked against (or, with wrapping arithmetic, truncated to) the type's range.
+fn eval_typed_ari(l: i128, op: AriOp, r: i128, int_type: &Type) -> i128 {
+    match (op, r) {
+        (AriOp::Div, 0) => runtime_error("attempt to divide by zero"),
+        (AriOp::Rem, 0) => {
+            runtime_error("attempt to calculate the remainder with a divisor of zero")
+        }
+        _ => (),
+    }
+
+    if WRAPPING.with(Cell::get) {
+        // Only 64-bit products can overflow an i128, the truncated bits are the same
+        return int_type.wrap_int(match op {
+            AriOp::Add => l + r,
+            AriOp::Sub => l - r,
+            AriOp::Mul => l.wrapping_mul(r),
+            AriOp::Div => l / r,
+            AriOp::Rem => l % r,
+        });
+    }
+
+    let (res, msg) = match op {
+        AriOp::Add => (l.checked_add(r), "attempt to add with overflow"),
+        AriOp::Sub => (l.checked_sub(r), "attempt to subtract with overflow"),
+        AriOp::Mul => (l.checked_mul(r), "attempt to multiply with overflow"),
+        AriOp::Div => (l.checked_div(r), "attempt to divide with overflow"),
+        AriOp::Rem => (
+            l.checked_rem(r),
+            "attempt to calculate the remainder with overflow",
+        ),
+    };
+
+    // `MIN % -1` overflows just like `MIN / -1` does
+    let (min, max) = int_type.int_range();
+    let rem_overflow = op == AriOp::Rem && l == min && r == -1;
+    match res {
+        Some(val) if min <= val && val <= max && !rem_overflow => val,
+        _ => runtime_error(msg),
+    }
+}
+
+fn eval_typed_int_expr(l: i128, op: Op, r: i128, int_type: Type) -> ExprRep {
+    match op {
+        Op::AriOp(op) => ExprRep::TypedInt(eval_typed_ari(l, op, r, &int_type), int_type),
+        Op::RelOp(RelOp::Eq) => ExprRep::Bool(l == r),
+        Op::RelOp(RelOp::Neq) => ExprRep::Bool(l != r),
+        Op::RelOp(RelOp::Leq) => ExprRep::Bool(l <= r),
+        Op::RelOp(RelOp::Geq) => ExprRep::Bool(l >= r),
+        Op::RelOp(RelOp::Les) => ExprRep::Bool(l < r),
+        Op::RelOp(RelOp::Gre) => ExprRep::Bool(l > r),
+        _ => panic!("Invalid {} expr!", int_type),
+    }
+}
+
+/// IEEE 754 arithmetic, division by zero gives an infinity or NaN instead of an error.
+fn eval_float_expr(l: f64, op: Op, r: f64) -> ExprRep {
+    match op {
+        Op::AriOp(AriOp::Add) => ExprRep::Float(l + r),
+        Op::AriOp(AriOp::Sub) => ExprRep::Float(l - r),
+        Op::AriOp(AriOp::Mul) => ExprRep::Float(l * r),
+        Op::AriOp(AriOp::Div) => ExprRep::Float(l / r),
+        Op::AriOp(AriOp::Rem) => ExprRep::Float(l % r),
+        Op::RelOp(RelOp::Eq) => ExprRep::Bool(l == r),
+        Op::RelOp(RelOp::Neq) => ExprRep::Bool(l != r),
+        Op::RelOp(RelOp::Leq) => ExprRep::Bool(l <= r),
+        Op::RelOp(RelOp::Geq) => ExprRep::Bool(l >= r),
+        Op::RelOp(RelOp::Les) => ExprRep::Bool(l < r),
+        Op::RelOp(RelOp::Gre) => ExprRep::Bool(l > r),
+        _ => panic!("Invalid Float expr!"),
+    }
+}
+
+/// Converts a number or bool to `to_type` like Rust, integers are truncated or
+/// extended and floats saturate at the bounds of the integer type.
+fn eval_cast(val: ExprRep, to_type: Type) -> ExprRep {
+    let val = match (val, &to_type) {
+        (ExprRep::Int(i), Type::Float) => return ExprRep::Float(i as f64),
+        (ExprRep::TypedInt(i, _), Type::Float) => return ExprRep::Float(i as f64),
+        (ExprRep::Float(f), Type::Float) => return ExprRep::Float(f),
+        (ExprRep::Float(f), _) => {
+            let (min, max) = to_type.int_range();
+            (f as i128).max(min).min(max)
+        }
+        (ExprRep::Int(i), _) => i as i128,
+        (ExprRep::TypedInt(i, _), _) => i,
+        (ExprRep::Bool(b), _) => b as i128,
+        (val, _) => panic!("Invalid cast of {:?} to {}!", val, to_type),
+    };
+    match to_type {
+        Type::Int => ExprRep::Int(to_type.wrap_int(val) as i32),
+        _ => ExprRep::TypedInt(to_type.wrap_int(val), to_type),
+    }
+}
+
 fn eval_int_expr(l: i32, op: Op, r: i32) -> ExprRep {
     match op {
-        Op::AriOp(AriOp::Add) => ExprRep::Int(l + r),
-        Op::AriOp(AriOp::Sub) => ExprRep::Int(l - r),
-        Op::AriOp(AriOp::Div) => ExprRep::Int(l / r),
-        Op::AriOp(AriOp::Mul) => ExprRep::Int(l * r),
+        Op::AriOp(op) => ExprRep::Int(eval_ari(l, op, r)),
         Op::RelOp(RelOp::Eq) => ExprRep::Bool(l == r),
         Op::RelOp(RelOp::Neq) => ExprRep::Bool(l != r),
         Op::RelOp(RelOp::Leq) => ExprRep::Bool(l <= r),
@@ -211,48 +928,158 @@ fn eval_bool_expr(l: bool, op: Op, r: bool) -> ExprRep {
     }
 }
 
+fn eval_str_expr(l: String, op: Op, r: String) -> ExprRep {
+    match op {
+        Op::AriOp(AriOp::Add) => ExprRep::Str(l + &r),
+        Op::RelOp(RelOp::Eq) => ExprRep::Bool(l == r),
+        Op::RelOp(RelOp::Neq) => ExprRep::Bool(l != r),
+        _ => panic!("Invalid Str expr!"),
+    }
+}
+
 fn var_ari_op(var: Expr, op: Op, expr: Expr) -> ExprRep {
     match (var, op, expr) {
-        (Expr::Var(v), op, Expr::Var(expr)) => match (read_var(&v), read_var(&expr)) {
-            (ExprRep::Int(v1), ExprRep::Int(v2)) => eval_int_expr(v1, op, v2),
-            _ => panic!("Var(Int) Var(Int) op fail!"),
+        (Expr::Var(_, Some(v)), op, Expr::Var(_, Some(expr))) => {
+            match (read_var(v), read_var(expr)) {
+                (ExprRep::Int(v1), ExprRep::Int(v2)) => eval_int_expr(v1, op, v2),
+                (ExprRep::TypedInt(v1, int_type), ExprRep::TypedInt(v2, _)) => {
+                    eval_typed_int_expr(v1, op, v2, int_type)
+                }
+                (ExprRep::Float(v1), ExprRep::Float(v2)) => eval_float_expr(v1, op, v2),
+                (ExprRep::Str(v1), ExprRep::Str(v2)) => eval_str_expr(v1, op, v2),
+                _ => panic!("Var(Int) Var(Int) op fail!"),
+            }
+        }
+        (Expr::Var(_, Some(v)), op, Expr::Str(expr)) => match read_var(v) {
+            ExprRep::Str(v1) => eval_str_expr(v1, op, expr),
+            _ => panic!("Var(Str) Str op fail!"),
+        },
+        (Expr::Str(s), op, expr) => match eval_expr(expr) {
+            ExprRep::Str(v2) => eval_str_expr(s, op, v2),
+            _ => panic!("Str op fail!"),
         },
-        (Expr::Var(v), op, Expr::Int(expr)) => match read_var(&v) {
+        (Expr::Var(_, Some(v)), op, Expr::Int(expr)) => match read_var(v) {
             ExprRep::Int(v1) => eval_int_expr(v1, op, expr),
             _ => panic!("Var(Int) Int op fail!"),
         },
-        _ => panic!("Invalid Var Log op!"),
+        (var, op, expr) => eval_bin_expr(var, op, expr),
     }
 }
 
 fn var_ass_op(var: Expr, op: Op, expr: Expr) -> ExprRep {
     match (var.clone(), op, eval_expr(expr)) {
-        (Expr::Var(v), Op::AssOp(AssOp::Eq), ExprRep::Int(val)) => {
-            insert_var(ExprRep::Var(v), ExprRep::Int(val))
+        (_, _, res) if res.is_jump() => res,
+        (Expr::Index(..), Op::AssOp(op), val)
+        | (Expr::Field(..), Op::AssOp(op), val)
+        | (Expr::TupleField(..), Op::AssOp(op), val) => place_ass_op(var, op, val),
+        (Expr::Var(_, Some(v)), Op::AssOp(AssOp::Eq), ExprRep::Int(val)) => {
+            insert_var(v, ExprRep::Int(val))
         }
-        (Expr::Var(v), Op::AssOp(AssOp::Eq), ExprRep::Bool(val)) => {
-            insert_var(ExprRep::Var(v), ExprRep::Bool(val))
+        (Expr::Var(_, Some(v)), Op::AssOp(AssOp::Eq), ExprRep::Bool(val)) => {
+            insert_var(v, ExprRep::Bool(val))
         }
-        (Expr::Var(v), Op::AssOp(AssOp::AddEq), ExprRep::Int(new_val)) => match eval_expr(var) {
-            ExprRep::Int(old_val) => insert_var(ExprRep::Var(v), ExprRep::Int(old_val + new_val)),
-            _ => panic!("Var Add update fail!"),
-        },
-        (Expr::Var(v), Op::AssOp(AssOp::SubEq), ExprRep::Int(new_val)) => match eval_expr(var) {
-            ExprRep::Int(old_val) => insert_var(ExprRep::Var(v), ExprRep::Int(old_val - new_val)),
-            _ => panic!("Var Sub update fail!"),
-        },
-        (Expr::Var(v), Op::AssOp(AssOp::DivEq), ExprRep::Int(new_val)) => match eval_expr(var) {
-            ExprRep::Int(old_val) => insert_var(ExprRep::Var(v), ExprRep::Int(old_val / new_val)),
-            _ => panic!("Var Div update fail!"),
-        },
-        (Expr::Var(v), Op::AssOp(AssOp::MulEq), ExprRep::Int(new_val)) => match eval_expr(var) {
-            ExprRep::Int(old_val) => insert_var(ExprRep::Var(v), ExprRep::Int(old_val * new_val)),
-            _ => panic!("Var Mul update fail!"),
+        (Expr::Var(_, Some(v)), Op::AssOp(AssOp::Eq), ExprRep::Str(val)) => {
+            insert_var(v, ExprRep::Str(val))
+        }
+        (Expr::Var(_, Some(v)), Op::AssOp(AssOp::Eq), val @ ExprRep::TypedInt(..)) => {
+            insert_var(v, val)
+        }
+        (Expr::Var(_, Some(v)), Op::AssOp(AssOp::Eq), ExprRep::Float(val)) => {
+            insert_var(v, ExprRep::Float(val))
+        }
+        (Expr::Var(_, Some(v)), Op::AssOp(AssOp::Eq), val @ ExprRep::Array(_)) => {
+            insert_var(v, val)
+        }
+        (Expr::Var(_, Some(v)), Op::AssOp(AssOp::Eq), val @ ExprRep::Struct(..)) => {
+            insert_var(v, val)
+        }
+        (Expr::Var(_, Some(v)), Op::AssOp(AssOp::Eq), val @ ExprRep::Variant(..)) => {
+            insert_var(v, val)
+        }
+        (Expr::Var(_, Some(v)), Op::AssOp(AssOp::Eq), val @ ExprRep::Tuple(_)) => {
+            insert_var(v, val)
+        }
+        (Expr::Var(_, Some(v)), Op::AssOp(op), ExprRep::Float(new_val)) => match eval_expr(var) {
+            ExprRep::Float(old_val) => insert_var(
+                v,
+                eval_float_expr(old_val, Op::AriOp(ass_ari_op(op)), new_val),
+            ),
+            _ => panic!("Var Float update fail!"),
         },
+        (Expr::Var(_, Some(v)), Op::AssOp(op), ExprRep::TypedInt(new_val, int_type)) => {
+            match eval_expr(var) {
+                ExprRep::TypedInt(old_val, _) => insert_var(
+                    v,
+                    ExprRep::TypedInt(
+                        eval_typed_ari(old_val, ass_ari_op(op), new_val, &int_type),
+                        int_type,
+                    ),
+                ),
+                _ => panic!("Var {} update fail!", int_type),
+            }
+        }
+        (Expr::Var(_, Some(v)), Op::AssOp(AssOp::AddEq), ExprRep::Str(new_val)) => {
+            match eval_expr(var) {
+                ExprRep::Str(old_val) => insert_var(v, ExprRep::Str(old_val + &new_val)),
+                _ => panic!("Var Add update fail!"),
+            }
+        }
+        (Expr::Var(_, Some(v)), Op::AssOp(AssOp::AddEq), ExprRep::Int(new_val)) => {
+            match eval_expr(var) {
+                ExprRep::Int(old_val) => {
+                    insert_var(v, ExprRep::Int(eval_ari(old_val, AriOp::Add, new_val)))
+                }
+                _ => panic!("Var Add update fail!"),
+            }
+        }
+        (Expr::Var(_, Some(v)), Op::AssOp(AssOp::SubEq), ExprRep::Int(new_val)) => {
+            match eval_expr(var) {
+                ExprRep::Int(old_val) => {
+                    insert_var(v, ExprRep::Int(eval_ari(old_val, AriOp::Sub, new_val)))
+                }
+                _ => panic!("Var Sub update fail!"),
+            }
+        }
+        (Expr::Var(_, Some(v)), Op::AssOp(AssOp::DivEq), ExprRep::Int(new_val)) => {
+            match eval_expr(var) {
+                ExprRep::Int(old_val) => {
+                    insert_var(v, ExprRep::Int(eval_ari(old_val, AriOp::Div, new_val)))
+                }
+                _ => panic!("Var Div update fail!"),
+            }
+        }
+        (Expr::Var(_, Some(v)), Op::AssOp(AssOp::MulEq), ExprRep::Int(new_val)) => {
+            match eval_expr(var) {
+                ExprRep::Int(old_val) => {
+                    insert_var(v, ExprRep::Int(eval_ari(old_val, AriOp::Mul, new_val)))
+                }
+                _ => panic!("Var Mul update fail!"),
+            }
+        }
+        (Expr::Var(_, Some(v)), Op::AssOp(AssOp::RemEq), ExprRep::Int(new_val)) => {
+            match eval_expr(var) {
+                ExprRep::Int(old_val) => {
+                    insert_var(v, ExprRep::Int(eval_ari(old_val, AriOp::Rem, new_val)))
+                }
+                _ => panic!("Var Rem update fail!"),
+            }
+        }
         _ => panic!("Var update fail!"),
     }
 }
 
+/// The arithmetic behind a compound assignment such as `+=`.
+fn ass_ari_op(op: AssOp) -> AriOp {
+    match op {
+        AssOp::AddEq => AriOp::Add,
+        AssOp::SubEq => AriOp::Sub,
+        AssOp::DivEq => AriOp::Div,
+        AssOp::MulEq => AriOp::Mul,
+        AssOp::RemEq => AriOp::Rem,
+        AssOp::Eq => panic!("`=` is not an arithmetic assignment!"),
+    }
+}
+
 fn var_log_op(var: Expr, op: Op, expr: Expr) -> ExprRep {
     match (eval_expr(var), op, eval_expr(expr)) {
         (ExprRep::Bool(b1), op, ExprRep::Bool(b2)) => eval_bool_expr(b1, op, b2),
@@ -262,8 +1089,13 @@ fn var_log_op(var: Expr, op: Op, expr: Expr) -> ExprRep {
 
 fn var_rel_op(var: Expr, op: Op, expr: Expr) -> ExprRep {
     match (eval_expr(var), op, eval_expr(expr)) {
+        (ExprRep::Str(s1), op, ExprRep::Str(s2)) => eval_str_expr(s1, op, s2),
         (ExprRep::Bool(b1), op, ExprRep::Bool(b2)) => eval_bool_expr(b1, op, b2),
         (ExprRep::Int(b1), op, ExprRep::Int(b2)) => eval_int_expr(b1, op, b2),
+        (ExprRep::TypedInt(b1, int_type), op, ExprRep::TypedInt(b2, _)) => {
+            eval_typed_int_expr(b1, op, b2, int_type)
+        }
+        (ExprRep::Float(f1), op, ExprRep::Float(f2)) => eval_float_expr(f1, op, f2),
         _ => panic!("Invalid Var Log op!"),
     }
 }
@@ -272,6 +1104,22 @@ fn var_rel_op(var: Expr, op: Op, expr: Expr) -> ExprRep {
 mod interpreter_tests {
     use super::*;
 
+    /// Runs `ast` like `interpreter` and returns the declarations its names are linked to.
+    fn run_resolved(ast: Vec<Expr>) -> SymbolTable {
+        let (ast, symbols) = resolve(ast).unwrap();
+        eval_program(ast);
+        symbols
+    }
+
+    /// The declaration of the last local variable called `name`.
+    fn local_id(symbols: &SymbolTable, name: &str) -> DeclId {
+        symbols
+            .decls
+            .iter()
+            .rposition(|decl| decl.name == name && decl.kind == DeclKind::Local)
+            .unwrap()
+    }
+
     #[test]
     fn test_eval_int() {
         assert_eq!(interpreter(vec![Expr::Int(1)]), ExprRep::Int(1));
@@ -286,22 +1134,20 @@ mod interpreter_tests {
 
     #[test]
     fn test_eval_var() {
-        insert_var(ExprRep::Var("a1".to_string()), ExprRep::Int(1));
+        // Variables are stored by their declarations, not by their names
+        insert_var(1, ExprRep::Int(1));
         assert_eq!(
-            interpreter(vec![Expr::Var("a1".to_string())]),
+            eval_expr(Expr::Var("a1".to_string(), Some(1))),
             ExprRep::Int(1)
         );
-        insert_var(ExprRep::Var("a2".to_string()), ExprRep::Bool(true));
+        insert_var(2, ExprRep::Bool(true));
         assert_eq!(
-            interpreter(vec![Expr::Var("a2".to_string())]),
+            eval_expr(Expr::Var("a1".to_string(), Some(2))),
             ExprRep::Bool(true)
         );
-        insert_var(
-            ExprRep::Var("a3".to_string()),
-            ExprRep::Var("a4".to_string()),
-        );
+        insert_var(3, ExprRep::Var("a4".to_string()));
         assert_eq!(
-            interpreter(vec![Expr::Var("a3".to_string())]),
+            eval_expr(Expr::Var("a3".to_string(), Some(3))),
             ExprRep::Var("a4".to_string())
         );
     }
@@ -312,6 +1158,19 @@ mod interpreter_tests {
         assert_eq!(eval_int_expr(3, Op::AriOp(AriOp::Sub), 2), ExprRep::Int(1));
         assert_eq!(eval_int_expr(10, Op::AriOp(AriOp::Div), 2), ExprRep::Int(5));
         assert_eq!(eval_int_expr(2, Op::AriOp(AriOp::Mul), 5), ExprRep::Int(10));
+        assert_eq!(eval_int_expr(7, Op::AriOp(AriOp::Rem), 3), ExprRep::Int(1));
+        assert_eq!(
+            eval_int_expr(-7, Op::AriOp(AriOp::Div), 2),
+            ExprRep::Int(-3)
+        );
+        assert_eq!(
+            eval_int_expr(-7, Op::AriOp(AriOp::Rem), 2),
+            ExprRep::Int(-1)
+        );
+        assert_eq!(
+            eval_int_expr(-1, Op::RelOp(RelOp::Les), 1),
+            ExprRep::Bool(true)
+        );
         assert_eq!(
             eval_int_expr(1, Op::RelOp(RelOp::Eq), 1),
             ExprRep::Bool(true)
@@ -338,6 +1197,371 @@ mod interpreter_tests {
         );
     }
 
+    #[test]
+    fn test_eval_overflow() {
+        let span = Span {
+            line: 3,
+            column: 9,
+            file: None,
+        };
+        let add = |l: i32, r: i32| {
+            vec![Expr::Spanned(
+                span,
+                Box::new(Expr::Return(Box::new(Expr::BinExpr(
+                    Box::new(Expr::Int(l)),
+                    Op::AriOp(AriOp::Add),
+                    Box::new(Expr::Int(r)),
+                )))),
+            )]
+        };
+
+        assert_eq!(run(add(1, 2)), Ok(ExprRep::Int(3)));
+        assert_eq!(
+            run(add(i32::MAX, 1)),
+            Err(RuntimeError {
+                span,
+                msg: "attempt to add with overflow".to_string()
+            })
+        );
+
+        assert_eq!(
+            run_with_options(add(i32::MAX, 1), &RunOptions { wrapping: true }),
+            Ok(ExprRep::Int(i32::MIN))
+        );
+        assert_eq!(
+            run(add(i32::MAX, 1)).map_err(|e| e.msg),
+            Err("attempt to add with overflow".to_string())
+        );
+    }
+
+    #[test]
+    fn test_eval_div_by_zero() {
+        let res = run(vec![Expr::Spanned(
+            Span {
+                line: 1,
+                column: 1,
+                file: None,
+            },
+            Box::new(Expr::Return(Box::new(Expr::BinExpr(
+                Box::new(Expr::Int(1)),
+                Op::AriOp(AriOp::Div),
+                Box::new(Expr::Int(0)),
+            )))),
+        )]);
+
+        assert_eq!(
+            res.unwrap_err().to_string(),
+            "runtime error at 1:1: attempt to divide by zero"
+        );
+    }
+
+    #[test]
+    fn test_eval_builtins() {
+        let call = |name: &str, args: Vec<Expr>| {
+            Expr::FnCall(Box::new(Expr::Var(name.to_string(), None)), args)
+        };
+        let p = vec![
+            call("print_i32", vec![call("read_i32", vec![])]),
+            call("println", vec![]),
+            call("print_bool", vec![Expr::Bool(false)]),
+            Expr::Return(Box::new(call("read_i32", vec![]))),
+        ];
+
+        let (res, output) = with_io("7\n-2\n", || run(p.clone()));
+        assert_eq!(res, Ok(ExprRep::Int(-2)));
+        assert_eq!(output, "7\nfalse");
+
+        let (res, _) = with_io("7\n", || run(p.clone()));
+        assert_eq!(res.unwrap_err().msg, READ_I32_ERROR);
+    }
+
+    #[test]
+    fn test_eval_str() {
+        let (res, output) = run_main(
+            r#"
+            fn greet(name: str) -> str {
+                let s: str = "hello, " + name;
+                s += "!\n";
+                return s
+            }
+
+            fn main() -> i32 {
+                let s: str = greet("world");
+                print_str(s);
+                if s == "hello, world!\n" {
+                    print_str("equal");
+                };
+                return len(s)
+            }
+            "#,
+            "",
+        );
+        assert_eq!(res, Ok(ExprRep::Int(14)));
+        assert_eq!(output, "hello, world!\nequal");
+    }
+
+    #[test]
+    fn test_eval_extern_fn() {
+        let call = |name: &str| {
+            Expr::Return(Box::new(Expr::FnCall(
+                Box::new(Expr::Var(name.to_string(), None)),
+                vec![Expr::Int(-5)],
+            )))
+        };
+        let decl = |name: &str| {
+            Expr::ExternFn(
+                Box::new(Expr::Var(name.to_string(), None)),
+                vec![(Expr::Var("x".to_string(), None), Type::Int)],
+                Type::Int,
+            )
+        };
+
+        assert_eq!(run(vec![decl("abs"), call("abs")]), Ok(ExprRep::Int(5)));
+        assert_eq!(
+            run(vec![decl("nothere"), call("nothere")]).unwrap_err().msg,
+            "unresolved extern fn `nothere`"
+        );
+    }
+
+    #[test]
+    fn test_eval_int_types() {
+        let eval = |expr: &str| {
+            let p = crate::parser::parser(&format!("fn main() -> i32 {{ return {} }}", expr))
+                .unwrap()
+                .1;
+            let body = match p[0].clone().unspanned() {
+                Expr::Fn(_, _, _, _, block) => block,
+                _ => unreachable!(),
+            };
+            run(body)
+        };
+
+        assert_eq!(eval("200u8 + 55u8"), Ok(ExprRep::TypedInt(255, Type::U8)));
+        assert_eq!(
+            eval("200u8 + 56u8").unwrap_err().msg,
+            "attempt to add with overflow"
+        );
+        assert_eq!(
+            eval("0u32 - 1u32").unwrap_err().msg,
+            "attempt to subtract with overflow"
+        );
+        assert_eq!(
+            eval("4294967295u32 / 2u32"),
+            Ok(ExprRep::TypedInt(2147483647, Type::U32))
+        );
+        assert_eq!(
+            eval("18446744073709551615u64 * 2u64").unwrap_err().msg,
+            "attempt to multiply with overflow"
+        );
+        assert_eq!(eval("300 as u8"), Ok(ExprRep::TypedInt(44, Type::U8)));
+        assert_eq!(eval("4294967295u32 as u64 as i32"), Ok(ExprRep::Int(-1)));
+        assert_eq!(eval("255u8 as i8"), Ok(ExprRep::TypedInt(-1, Type::I8)));
+        assert_eq!(eval("true as i16"), Ok(ExprRep::TypedInt(1, Type::I16)));
+        assert_eq!(eval("3000000000u32 > 1u32"), Ok(ExprRep::Bool(true)));
+    }
+
+    #[test]
+    fn test_eval_float() {
+        let eval = |expr: &str| {
+            let p = crate::parser::parser(&format!("fn main() -> i32 {{ return {} }}", expr))
+                .unwrap()
+                .1;
+            let body = match p[0].clone().unspanned() {
+                Expr::Fn(_, _, _, _, block) => block,
+                _ => unreachable!(),
+            };
+            run(body)
+        };
+
+        assert_eq!(eval("1.5 + 2e3"), Ok(ExprRep::Float(2001.5)));
+        assert_eq!(eval("7.5 % 2.0"), Ok(ExprRep::Float(1.5)));
+        assert_eq!(eval("1.0 / 0.0"), Ok(ExprRep::Float(f64::INFINITY)));
+        assert_eq!(eval("0.3 == 0.1 + 0.2"), Ok(ExprRep::Bool(false)));
+        assert_eq!(eval("7 as f64 / 2.0"), Ok(ExprRep::Float(3.5)));
+        assert_eq!(eval("2.9 as i32"), Ok(ExprRep::Int(2)));
+        assert_eq!(eval("1e10 as i32"), Ok(ExprRep::Int(i32::MAX)));
+        assert_eq!(eval("300.5 as u8"), Ok(ExprRep::TypedInt(255, Type::U8)));
+        assert_eq!(eval("(0.0 / 0.0) as i32"), Ok(ExprRep::Int(0)));
+    }
+
+    #[test]
+    fn test_eval_array() {
+        let p = crate::parser::parser(
+            "
+            fn main() -> i32 {
+                let a: [i32; 3] = [1, 2, 3];
+                let grid: [[u8; 2]; 2] = [[0u8; 2]; 2];
+                a[1] = 5;
+                a[2] *= 4;
+                grid[1][0] += 7u8;
+                return a[0] + a[1] + a[2] + grid[1][0] as i32 + len(grid)
+            }
+
+            fn out_of_bounds() -> i32 {
+                let a: [i32; 3] = [1, 2, 3];
+                let i: i32 = 3;
+                return a[i]
+            }
+        ",
+        )
+        .unwrap()
+        .1;
+        let call = |name: &str| {
+            let mut p = p.clone();
+            p.push(Expr::Return(Box::new(Expr::FnCall(
+                Box::new(Expr::Var(name.to_string(), None)),
+                vec![],
+            ))));
+            run(p)
+        };
+
+        assert_eq!(call("main"), Ok(ExprRep::Int(27)));
+        assert_eq!(
+            call("out_of_bounds").unwrap_err().msg,
+            "index out of bounds: the len is 3 but the index is 3"
+        );
+    }
+
+    #[test]
+    fn test_eval_struct() {
+        let (res, _) = run_main(
+            "
+            struct Point { x: i32, y: i32 }
+
+            struct Line { from: Point, to: Point }
+
+            fn shift(p: Point, by: i32) -> Point {
+                return Point { y: p.y + by, x: p.x + by }
+            }
+
+            fn main() -> Point {
+                let l: Line = Line { from: Point { x: 1, y: 2 }, to: Point { x: 0, y: 0 } };
+                l.from.x += 10;
+                l.to = shift(l.from, 1);
+                return l.to
+            }
+        ",
+            "",
+        );
+        assert_eq!(
+            res,
+            Ok(ExprRep::Struct(
+                "Point".to_string(),
+                vec![
+                    ("y".to_string(), ExprRep::Int(3)),
+                    ("x".to_string(), ExprRep::Int(12))
+                ]
+            ))
+        );
+    }
+
+    #[test]
+    fn test_eval_call_by_value() {
+        let (res, _) = run_main(
+            "
+            struct Point { x: i32, y: i32 }
+
+            fn move_right(p: Point) -> i32 {
+                p.x += 10;
+                return p.x
+            }
+
+            fn inc(a: i32) -> i32 {
+                a += 1;
+                return a
+            }
+
+            fn main() -> i32 {
+                let p: Point = Point { x: 1, y: 2 };
+                let moved: i32 = move_right(p);
+                let a: i32 = 1;
+                return p.x + p.y + moved + a + inc(a)
+            }
+        ",
+            "",
+        );
+        // 1 + 2 + 11 + 1 + 2, the callees only change their own copies
+        assert_eq!(res, Ok(ExprRep::Int(17)));
+    }
+
+    #[test]
+    fn test_eval_generic_fn() {
+        let (res, _) = run_main(
+            "
+            fn max<T>(a: T, b: T) -> T {
+                if a > b {
+                    return a
+                };
+                return b
+            }
+
+            fn main() -> i32 {
+                let a = max(3, 9);
+                let b = max(true, false);
+                return a + (b as i32)
+            }
+        ",
+            "",
+        );
+        assert_eq!(res, Ok(ExprRep::Int(10)));
+    }
+
+    #[test]
+    fn test_eval_tuple() {
+        let (res, _) = run_main(
+            "
+            fn div_mod(a: i32, b: i32) -> (i32, i32) {
+                return (a / b, a % b)
+            }
+
+            fn swap((x, y): (i32, bool)) -> (bool, i32) {
+                return (y, x)
+            }
+
+            fn main() -> (bool, i32) {
+                let (q, r): (i32, i32) = div_mod(17, 5);
+                let t: (i32, (bool, i32)) = (q, swap((r, true)));
+                t.1.1 *= 10;
+                return t.1
+            }
+        ",
+            "",
+        );
+        assert_eq!(
+            res,
+            Ok(ExprRep::Tuple(vec![ExprRep::Bool(true), ExprRep::Int(20)]))
+        );
+    }
+
+    #[test]
+    fn test_eval_match() {
+        let (res, output) = run_main(
+            "
+            enum Shape { Circle(i32), Rect(i32, i32), Empty }
+
+            fn area(s: Shape) -> i32 {
+                match s {
+                    Shape::Circle(r) => { return r * r * 3 },
+                    Shape::Rect(w, 0) => { return 0 - w },
+                    Shape::Rect(w, h) => { return w * h },
+                    Shape::Empty => { return 0 },
+                };
+                return 0
+            }
+
+            fn main() -> i32 {
+                let rect: Shape = Shape::Rect(4, 0);
+                print_i32(area(rect));
+                rect = Shape::Rect(2, 5);
+                return area(Shape::Circle(2)) + area(rect) + area(Shape::Empty)
+            }
+        ",
+            "",
+        );
+        assert_eq!(res, Ok(ExprRep::Int(22)));
+        assert_eq!(output, "-4");
+    }
+
     #[test]
     fn test_eval_bool_expr() {
         assert_eq!(
@@ -388,35 +1612,35 @@ mod interpreter_tests {
 
     #[test]
     fn test_eval_var_expr() {
-        interpreter(vec![
+        let symbols = run_resolved(vec![
             Expr::Let(
-                Box::new(Expr::Var("b1".to_string())),
-                Type::Int,
+                Box::new(Expr::Var("b1".to_string(), None)),
+                Some(Type::Int),
                 Box::new(Expr::BinExpr(
-                    Box::new(Expr::Var("".to_string())),
+                    Box::new(Expr::Var("".to_string(), None)),
                     Op::AssOp(AssOp::Eq),
                     Box::new(Expr::Int(2)),
                 )),
             ),
             Expr::VarExpr(
-                Box::new(Expr::Var("b1".to_string())),
+                Box::new(Expr::Var("b1".to_string(), None)),
                 Op::AssOp(AssOp::AddEq),
                 Box::new(Expr::Int(2)),
             ),
         ]);
-        assert_eq!(read_var("b1"), ExprRep::Int(4));
+        assert_eq!(read_var(local_id(&symbols, "b1")), ExprRep::Int(4));
         let res = interpreter(vec![
             Expr::Let(
-                Box::new(Expr::Var("b2".to_string())),
-                Type::Bool,
+                Box::new(Expr::Var("b2".to_string(), None)),
+                Some(Type::Bool),
                 Box::new(Expr::BinExpr(
-                    Box::new(Expr::Var("".to_string())),
+                    Box::new(Expr::Var("".to_string(), None)),
                     Op::AssOp(AssOp::Eq),
                     Box::new(Expr::Bool(false)),
                 )),
             ),
             Expr::VarExpr(
-                Box::new(Expr::Var("b2".to_string())),
+                Box::new(Expr::Var("b2".to_string(), None)),
                 Op::RelOp(RelOp::Eq),
                 Box::new(Expr::Bool(false)),
             ),
@@ -424,16 +1648,16 @@ mod interpreter_tests {
         assert_eq!(res, ExprRep::Bool(true));
         let res = interpreter(vec![
             Expr::Let(
-                Box::new(Expr::Var("b3".to_string())),
-                Type::Bool,
+                Box::new(Expr::Var("b3".to_string(), None)),
+                Some(Type::Bool),
                 Box::new(Expr::BinExpr(
-                    Box::new(Expr::Var("".to_string())),
+                    Box::new(Expr::Var("".to_string(), None)),
                     Op::AssOp(AssOp::Eq),
                     Box::new(Expr::Bool(false)),
                 )),
             ),
             Expr::VarExpr(
-                Box::new(Expr::Var("b3".to_string())),
+                Box::new(Expr::Var("b3".to_string(), None)),
                 Op::RelOp(RelOp::Neq),
                 Box::new(Expr::Bool(true)),
             ),
@@ -441,16 +1665,16 @@ mod interpreter_tests {
         assert_eq!(res, ExprRep::Bool(true));
         let res = interpreter(vec![
             Expr::Let(
-                Box::new(Expr::Var("b4".to_string())),
-                Type::Int,
+                Box::new(Expr::Var("b4".to_string(), None)),
+                Some(Type::Int),
                 Box::new(Expr::BinExpr(
-                    Box::new(Expr::Var("".to_string())),
+                    Box::new(Expr::Var("".to_string(), None)),
                     Op::AssOp(AssOp::Eq),
                     Box::new(Expr::Int(1)),
                 )),
             ),
             Expr::VarExpr(
-                Box::new(Expr::Var("b4".to_string())),
+                Box::new(Expr::Var("b4".to_string(), None)),
                 Op::RelOp(RelOp::Leq),
                 Box::new(Expr::Int(5)),
             ),
@@ -458,16 +1682,16 @@ mod interpreter_tests {
         assert_eq!(res, ExprRep::Bool(true));
         let res = interpreter(vec![
             Expr::Let(
-                Box::new(Expr::Var("b5".to_string())),
-                Type::Int,
+                Box::new(Expr::Var("b5".to_string(), None)),
+                Some(Type::Int),
                 Box::new(Expr::BinExpr(
-                    Box::new(Expr::Var("".to_string())),
+                    Box::new(Expr::Var("".to_string(), None)),
                     Op::AssOp(AssOp::Eq),
                     Box::new(Expr::Int(7)),
                 )),
             ),
             Expr::VarExpr(
-                Box::new(Expr::Var("b5".to_string())),
+                Box::new(Expr::Var("b5".to_string(), None)),
                 Op::RelOp(RelOp::Geq),
                 Box::new(Expr::Int(5)),
             ),
@@ -475,16 +1699,16 @@ mod interpreter_tests {
         assert_eq!(res, ExprRep::Bool(true));
         let res = interpreter(vec![
             Expr::Let(
-                Box::new(Expr::Var("b6".to_string())),
-                Type::Int,
+                Box::new(Expr::Var("b6".to_string(), None)),
+                Some(Type::Int),
                 Box::new(Expr::BinExpr(
-                    Box::new(Expr::Var("".to_string())),
+                    Box::new(Expr::Var("".to_string(), None)),
                     Op::AssOp(AssOp::Eq),
                     Box::new(Expr::Int(1)),
                 )),
             ),
             Expr::VarExpr(
-                Box::new(Expr::Var("b6".to_string())),
+                Box::new(Expr::Var("b6".to_string(), None)),
                 Op::RelOp(RelOp::Les),
                 Box::new(Expr::Int(5)),
             ),
@@ -492,16 +1716,16 @@ mod interpreter_tests {
         assert_eq!(res, ExprRep::Bool(true));
         let res = interpreter(vec![
             Expr::Let(
-                Box::new(Expr::Var("b7".to_string())),
-                Type::Int,
+                Box::new(Expr::Var("b7".to_string(), None)),
+                Some(Type::Int),
                 Box::new(Expr::BinExpr(
-                    Box::new(Expr::Var("".to_string())),
+                    Box::new(Expr::Var("".to_string(), None)),
                     Op::AssOp(AssOp::Eq),
                     Box::new(Expr::Int(6)),
                 )),
             ),
             Expr::VarExpr(
-                Box::new(Expr::Var("b7".to_string())),
+                Box::new(Expr::Var("b7".to_string(), None)),
                 Op::RelOp(RelOp::Gre),
                 Box::new(Expr::Int(5)),
             ),
@@ -511,108 +1735,113 @@ mod interpreter_tests {
 
     #[test]
     fn test_eval_let() {
-        interpreter(vec![Expr::Let(
-            Box::new(Expr::Var("c1".to_string())),
-            Type::Int,
+        let symbols = run_resolved(vec![Expr::Let(
+            Box::new(Expr::Var("c1".to_string(), None)),
+            Some(Type::Int),
             Box::new(Expr::BinExpr(
-                Box::new(Expr::Var("".to_string())),
+                Box::new(Expr::Var("".to_string(), None)),
                 Op::AssOp(AssOp::Eq),
                 Box::new(Expr::Int(1)),
             )),
         )]);
-        assert_eq!(read_var("c1"), ExprRep::Int(1));
-        interpreter(vec![Expr::Let(
-            Box::new(Expr::Var("c2".to_string())),
-            Type::Bool,
+        assert_eq!(read_var(local_id(&symbols, "c1")), ExprRep::Int(1));
+        let symbols = run_resolved(vec![Expr::Let(
+            Box::new(Expr::Var("c2".to_string(), None)),
+            Some(Type::Bool),
             Box::new(Expr::BinExpr(
-                Box::new(Expr::Var("".to_string())),
+                Box::new(Expr::Var("".to_string(), None)),
                 Op::AssOp(AssOp::Eq),
                 Box::new(Expr::Bool(true)),
             )),
         )]);
-        assert_eq!(read_var("c2"), ExprRep::Bool(true));
-        interpreter(vec![Expr::Let(
-            Box::new(Expr::Var("c3".to_string())),
-            Type::Bool,
+        assert_eq!(read_var(local_id(&symbols, "c2")), ExprRep::Bool(true));
+        let symbols = run_resolved(vec![Expr::Let(
+            Box::new(Expr::Var("c3".to_string(), None)),
+            Some(Type::Bool),
             Box::new(Expr::BinExpr(
                 Box::new(Expr::Bool(false)),
                 Op::LogOp(LogOp::And),
                 Box::new(Expr::Bool(true)),
             )),
         )]);
-        assert_eq!(read_var("c3"), ExprRep::Bool(false));
-        interpreter(vec![
+        assert_eq!(read_var(local_id(&symbols, "c3")), ExprRep::Bool(false));
+        let symbols = run_resolved(vec![
             Expr::Fn(
-                Box::new(Expr::Var("fnc1".to_string())),
-                vec![(Expr::Var("c4".to_string()), Type::Int)],
+                Box::new(Expr::Var("fnc1".to_string(), None)),
+                vec![],
+                vec![(Expr::Var("c4".to_string(), None), Type::Int)],
                 Type::Int,
-                vec![Expr::Return(Box::new(Expr::Var("c4".to_string())))],
+                vec![Expr::Return(Box::new(Expr::Var("c4".to_string(), None)))],
             ),
             Expr::Let(
-                Box::new(Expr::Var("c5".to_string())),
-                Type::Int,
+                Box::new(Expr::Var("c5".to_string(), None)),
+                Some(Type::Int),
                 Box::new(Expr::BinExpr(
-                    Box::new(Expr::Var("".to_string())),
+                    Box::new(Expr::Var("".to_string(), None)),
                     Op::AssOp(AssOp::Eq),
                     Box::new(Expr::FnCall(
-                        Box::new(Expr::Var("fnc1".to_string())),
+                        Box::new(Expr::Var("fnc1".to_string(), None)),
                         vec![Expr::Int(5)],
                     )),
                 )),
             ),
         ]);
-        assert_eq!(read_var("c5"), ExprRep::Int(5));
-        interpreter(vec![
+        assert_eq!(read_var(local_id(&symbols, "c5")), ExprRep::Int(5));
+        let symbols = run_resolved(vec![
             Expr::Fn(
-                Box::new(Expr::Var("fnc2".to_string())),
-                vec![(Expr::Var("c6".to_string()), Type::Int)],
+                Box::new(Expr::Var("fnc2".to_string(), None)),
+                vec![],
+                vec![(Expr::Var("c6".to_string(), None), Type::Int)],
                 Type::Int,
-                vec![Expr::Return(Box::new(Expr::Var("c6".to_string())))],
+                vec![Expr::Return(Box::new(Expr::Var("c6".to_string(), None)))],
             ),
             Expr::Fn(
-                Box::new(Expr::Var("fnc3".to_string())),
-                vec![(Expr::Var("c7".to_string()), Type::Int)],
+                Box::new(Expr::Var("fnc3".to_string(), None)),
+                vec![],
+                vec![(Expr::Var("c7".to_string(), None), Type::Int)],
                 Type::Int,
-                vec![Expr::Return(Box::new(Expr::Var("c7".to_string())))],
+                vec![Expr::Return(Box::new(Expr::Var("c7".to_string(), None)))],
             ),
             Expr::Fn(
-                Box::new(Expr::Var("fnc4".to_string())),
-                vec![(Expr::Var("c8".to_string()), Type::Int)],
+                Box::new(Expr::Var("fnc4".to_string(), None)),
+                vec![],
+                vec![(Expr::Var("c8".to_string(), None), Type::Int)],
                 Type::Int,
-                vec![Expr::Return(Box::new(Expr::Var("c8".to_string())))],
+                vec![Expr::Return(Box::new(Expr::Var("c8".to_string(), None)))],
             ),
             Expr::Fn(
-                Box::new(Expr::Var("fnc5".to_string())),
-                vec![(Expr::Var("c9".to_string()), Type::Int)],
+                Box::new(Expr::Var("fnc5".to_string(), None)),
+                vec![],
+                vec![(Expr::Var("c9".to_string(), None), Type::Int)],
                 Type::Int,
-                vec![Expr::Return(Box::new(Expr::Var("c9".to_string())))],
+                vec![Expr::Return(Box::new(Expr::Var("c9".to_string(), None)))],
             ),
             Expr::Let(
-                Box::new(Expr::Var("c10".to_string())),
-                Type::Int,
+                Box::new(Expr::Var("c10".to_string(), None)),
+                Some(Type::Int),
                 Box::new(Expr::BinExpr(
-                    Box::new(Expr::Var("".to_string())),
+                    Box::new(Expr::Var("".to_string(), None)),
                     Op::AssOp(AssOp::Eq),
                     Box::new(Expr::BinExpr(
                         Box::new(Expr::FnCall(
-                            Box::new(Expr::Var("fnc2".to_string())),
+                            Box::new(Expr::Var("fnc2".to_string(), None)),
                             vec![Expr::Int(5)],
                         )),
                         Op::AriOp(AriOp::Add),
                         Box::new(Expr::BinExpr(
                             Box::new(Expr::FnCall(
-                                Box::new(Expr::Var("fnc3".to_string())),
+                                Box::new(Expr::Var("fnc3".to_string(), None)),
                                 vec![Expr::Int(2)],
                             )),
                             Op::AriOp(AriOp::Add),
                             Box::new(Expr::BinExpr(
                                 Box::new(Expr::FnCall(
-                                    Box::new(Expr::Var("fnc4".to_string())),
+                                    Box::new(Expr::Var("fnc4".to_string(), None)),
                                     vec![Expr::Int(3)],
                                 )),
                                 Op::AriOp(AriOp::Add),
                                 Box::new(Expr::FnCall(
-                                    Box::new(Expr::Var("fnc5".to_string())),
+                                    Box::new(Expr::Var("fnc5".to_string(), None)),
                                     vec![Expr::Int(5)],
                                 )),
                             )),
@@ -621,7 +1850,7 @@ mod interpreter_tests {
                 )),
             ),
         ]);
-        assert_eq!(read_var("c10"), ExprRep::Int(15));
+        assert_eq!(read_var(local_id(&symbols, "c10")), ExprRep::Int(15));
     }
 
     #[test]
@@ -637,15 +1866,15 @@ mod interpreter_tests {
         assert_eq!(
             interpreter(vec![
                 Expr::Let(
-                    Box::new(Expr::Var("d1".to_string())),
-                    Type::Int,
+                    Box::new(Expr::Var("d1".to_string(), None)),
+                    Some(Type::Int),
                     Box::new(Expr::BinExpr(
-                        Box::new(Expr::Var("".to_string())),
+                        Box::new(Expr::Var("".to_string(), None)),
                         Op::AssOp(AssOp::Eq),
                         Box::new(Expr::Int(2)),
                     )),
                 ),
-                Expr::Return(Box::new(Expr::Var("d1".to_string())))
+                Expr::Return(Box::new(Expr::Var("d1".to_string(), None)))
             ]),
             ExprRep::Int(2)
         );
@@ -660,27 +1889,27 @@ mod interpreter_tests {
         assert_eq!(
             interpreter(vec![
                 Expr::Let(
-                    Box::new(Expr::Var("d2".to_string())),
-                    Type::Int,
+                    Box::new(Expr::Var("d2".to_string(), None)),
+                    Some(Type::Int),
                     Box::new(Expr::BinExpr(
-                        Box::new(Expr::Var("".to_string())),
+                        Box::new(Expr::Var("".to_string(), None)),
                         Op::AssOp(AssOp::Eq),
                         Box::new(Expr::Int(2)),
                     )),
                 ),
                 Expr::Let(
-                    Box::new(Expr::Var("d3".to_string())),
-                    Type::Int,
+                    Box::new(Expr::Var("d3".to_string(), None)),
+                    Some(Type::Int),
                     Box::new(Expr::BinExpr(
-                        Box::new(Expr::Var("".to_string())),
+                        Box::new(Expr::Var("".to_string(), None)),
                         Op::AssOp(AssOp::Eq),
                         Box::new(Expr::Int(2)),
                     )),
                 ),
                 Expr::Return(Box::new(Expr::VarExpr(
-                    Box::new(Expr::Var("d2".to_string())),
+                    Box::new(Expr::Var("d2".to_string(), None)),
                     Op::AriOp(AriOp::Add),
-                    Box::new(Expr::Var("d3".to_string())),
+                    Box::new(Expr::Var("d3".to_string(), None)),
                 )))
             ]),
             ExprRep::Int(4)
@@ -688,21 +1917,21 @@ mod interpreter_tests {
         assert_eq!(
             interpreter(vec![
                 Expr::Let(
-                    Box::new(Expr::Var("d4".to_string())),
-                    Type::Int,
+                    Box::new(Expr::Var("d4".to_string(), None)),
+                    Some(Type::Int),
                     Box::new(Expr::BinExpr(
-                        Box::new(Expr::Var("".to_string())),
+                        Box::new(Expr::Var("".to_string(), None)),
                         Op::AssOp(AssOp::Eq),
                         Box::new(Expr::Int(2)),
                     )),
                 ),
                 Expr::VarExpr(
-                    Box::new(Expr::Var("d4".to_string())),
+                    Box::new(Expr::Var("d4".to_string(), None)),
                     Op::AssOp(AssOp::AddEq),
                     Box::new(Expr::Int(1))
                 ),
                 Expr::Return(Box::new(Expr::VarExpr(
-                    Box::new(Expr::Var("d4".to_string())),
+                    Box::new(Expr::Var("d4".to_string(), None)),
                     Op::AriOp(AriOp::Add),
                     Box::new(Expr::Int(1)),
                 )))
@@ -712,16 +1941,16 @@ mod interpreter_tests {
         assert_eq!(
             interpreter(vec![
                 Expr::Let(
-                    Box::new(Expr::Var("d5".to_string())),
-                    Type::Int,
+                    Box::new(Expr::Var("d5".to_string(), None)),
+                    Some(Type::Int),
                     Box::new(Expr::BinExpr(
-                        Box::new(Expr::Var("".to_string())),
+                        Box::new(Expr::Var("".to_string(), None)),
                         Op::AssOp(AssOp::Eq),
                         Box::new(Expr::Int(2)),
                     )),
                 ),
                 Expr::Return(Box::new(Expr::VarExpr(
-                    Box::new(Expr::Var("d5".to_string())),
+                    Box::new(Expr::Var("d5".to_string(), None)),
                     Op::AriOp(AriOp::Sub),
                     Box::new(Expr::Int(1)),
                 )))
@@ -731,16 +1960,16 @@ mod interpreter_tests {
         assert_eq!(
             interpreter(vec![
                 Expr::Let(
-                    Box::new(Expr::Var("d6".to_string())),
-                    Type::Int,
+                    Box::new(Expr::Var("d6".to_string(), None)),
+                    Some(Type::Int),
                     Box::new(Expr::BinExpr(
-                        Box::new(Expr::Var("".to_string())),
+                        Box::new(Expr::Var("".to_string(), None)),
                         Op::AssOp(AssOp::Eq),
                         Box::new(Expr::Int(10)),
                     )),
                 ),
                 Expr::Return(Box::new(Expr::VarExpr(
-                    Box::new(Expr::Var("d6".to_string())),
+                    Box::new(Expr::Var("d6".to_string(), None)),
                     Op::AriOp(AriOp::Div),
                     Box::new(Expr::Int(5)),
                 )))
@@ -750,16 +1979,16 @@ mod interpreter_tests {
         assert_eq!(
             interpreter(vec![
                 Expr::Let(
-                    Box::new(Expr::Var("d7".to_string())),
-                    Type::Int,
+                    Box::new(Expr::Var("d7".to_string(), None)),
+                    Some(Type::Int),
                     Box::new(Expr::BinExpr(
-                        Box::new(Expr::Var("".to_string())),
+                        Box::new(Expr::Var("".to_string(), None)),
                         Op::AssOp(AssOp::Eq),
                         Box::new(Expr::Int(2)),
                     )),
                 ),
                 Expr::Return(Box::new(Expr::VarExpr(
-                    Box::new(Expr::Var("d7".to_string())),
+                    Box::new(Expr::Var("d7".to_string(), None)),
                     Op::AriOp(AriOp::Mul),
                     Box::new(Expr::Int(5)),
                 )))
@@ -780,28 +2009,28 @@ mod interpreter_tests {
         assert_eq!(
             interpreter(vec![
                 Expr::Let(
-                    Box::new(Expr::Var("f1".to_string())),
-                    Type::Bool,
+                    Box::new(Expr::Var("f1".to_string(), None)),
+                    Some(Type::Bool),
                     Box::new(Expr::BinExpr(
-                        Box::new(Expr::Var("".to_string())),
+                        Box::new(Expr::Var("".to_string(), None)),
                         Op::AssOp(AssOp::Eq),
                         Box::new(Expr::Bool(true)),
                     )),
                 ),
                 Expr::Let(
-                    Box::new(Expr::Var("f2".to_string())),
-                    Type::Bool,
+                    Box::new(Expr::Var("f2".to_string(), None)),
+                    Some(Type::Bool),
                     Box::new(Expr::BinExpr(
-                        Box::new(Expr::Var("".to_string())),
+                        Box::new(Expr::Var("".to_string(), None)),
                         Op::AssOp(AssOp::Eq),
                         Box::new(Expr::Bool(true)),
                     )),
                 ),
                 Expr::If(
                     Box::new(Expr::VarExpr(
-                        Box::new(Expr::Var("f1".to_string())),
+                        Box::new(Expr::Var("f1".to_string(), None)),
                         Op::RelOp(RelOp::Eq),
-                        Box::new(Expr::Var("f2".to_string()))
+                        Box::new(Expr::Var("f2".to_string(), None))
                     )),
                     vec![Expr::Return(Box::new(Expr::Int(1)))]
                 )
@@ -823,16 +2052,16 @@ mod interpreter_tests {
         assert_eq!(
             interpreter(vec![
                 Expr::Let(
-                    Box::new(Expr::Var("g1".to_string())),
-                    Type::Bool,
+                    Box::new(Expr::Var("g1".to_string(), None)),
+                    Some(Type::Bool),
                     Box::new(Expr::BinExpr(
-                        Box::new(Expr::Var("".to_string())),
+                        Box::new(Expr::Var("".to_string(), None)),
                         Op::AssOp(AssOp::Eq),
                         Box::new(Expr::Bool(true)),
                     )),
                 ),
                 Expr::IfElse(
-                    Box::new(Expr::Var("g1".to_string())),
+                    Box::new(Expr::Var("g1".to_string(), None)),
                     vec![Expr::Return(Box::new(Expr::Int(2)))],
                     vec![Expr::Return(Box::new(Expr::Int(1)))],
                 )
@@ -853,28 +2082,28 @@ mod interpreter_tests {
         assert_eq!(
             interpreter(vec![
                 Expr::Let(
-                    Box::new(Expr::Var("h1".to_string())),
-                    Type::Bool,
+                    Box::new(Expr::Var("h1".to_string(), None)),
+                    Some(Type::Bool),
                     Box::new(Expr::BinExpr(
-                        Box::new(Expr::Var("".to_string())),
+                        Box::new(Expr::Var("".to_string(), None)),
                         Op::AssOp(AssOp::Eq),
                         Box::new(Expr::Bool(true)),
                     )),
                 ),
                 Expr::Let(
-                    Box::new(Expr::Var("h2".to_string())),
-                    Type::Bool,
+                    Box::new(Expr::Var("h2".to_string(), None)),
+                    Some(Type::Bool),
                     Box::new(Expr::BinExpr(
-                        Box::new(Expr::Var("".to_string())),
+                        Box::new(Expr::Var("".to_string(), None)),
                         Op::AssOp(AssOp::Eq),
                         Box::new(Expr::Bool(false)),
                     )),
                 ),
                 Expr::While(
                     Box::new(Expr::VarExpr(
-                        Box::new(Expr::Var("h1".to_string())),
+                        Box::new(Expr::Var("h1".to_string(), None)),
                         Op::RelOp(RelOp::Neq),
-                        Box::new(Expr::Var("h2".to_string()))
+                        Box::new(Expr::Var("h2".to_string(), None))
                     )),
                     vec![Expr::Return(Box::new(Expr::Int(2)))]
                 )
@@ -887,13 +2116,14 @@ mod interpreter_tests {
         assert_eq!(
             interpreter(vec![
                 Expr::Fn(
-                    Box::new(Expr::Var("testfn1".to_string())),
-                    vec![(Expr::Var("i1".to_string()), Type::Int,),],
+                    Box::new(Expr::Var("testfn1".to_string(), None)),
+                    vec![],
+                    vec![(Expr::Var("i1".to_string(), None), Type::Int,),],
                     Type::Int,
-                    vec![Expr::Return(Box::new(Expr::Var("i1".to_string())))]
+                    vec![Expr::Return(Box::new(Expr::Var("i1".to_string(), None)))]
                 ),
                 Expr::Return(Box::new(Expr::FnCall(
-                    Box::new(Expr::Var("testfn1".to_string())),
+                    Box::new(Expr::Var("testfn1".to_string(), None)),
                     vec![Expr::Int(5)]
                 )))
             ]),
@@ -902,7 +2132,8 @@ mod interpreter_tests {
         assert_eq!(
             interpreter(vec![
                 Expr::Fn(
-                    Box::new(Expr::Var("testfn2".to_string())),
+                    Box::new(Expr::Var("testfn2".to_string(), None)),
+                    vec![],
                     vec![],
                     Type::Int,
                     vec![
@@ -914,7 +2145,7 @@ mod interpreter_tests {
                     ]
                 ),
                 Expr::Return(Box::new(Expr::FnCall(
-                    Box::new(Expr::Var("testfn2".to_string())),
+                    Box::new(Expr::Var("testfn2".to_string(), None)),
                     vec![]
                 )))
             ]),
@@ -923,7 +2154,8 @@ mod interpreter_tests {
         assert_eq!(
             interpreter(vec![
                 Expr::Fn(
-                    Box::new(Expr::Var("testfn3".to_string())),
+                    Box::new(Expr::Var("testfn3".to_string(), None)),
+                    vec![],
                     vec![],
                     Type::Int,
                     vec![
@@ -936,7 +2168,7 @@ mod interpreter_tests {
                     ]
                 ),
                 Expr::Return(Box::new(Expr::FnCall(
-                    Box::new(Expr::Var("testfn3".to_string())),
+                    Box::new(Expr::Var("testfn3".to_string(), None)),
                     vec![]
                 )))
             ]),
@@ -949,132 +2181,136 @@ mod interpreter_tests {
         assert_eq!(
             interpreter(vec![
                 Expr::Fn(
-                    Box::new(Expr::Var("testfn1".to_string())),
-                    vec![(Expr::Var("a".to_string()), Type::Bool)],
+                    Box::new(Expr::Var("testfn1".to_string(), None)),
+                    vec![],
+                    vec![(Expr::Var("a".to_string(), None), Type::Bool)],
                     Type::Int,
                     vec![
                         Expr::Let(
-                            Box::new(Expr::Var("c".to_string())),
-                            Type::Int,
+                            Box::new(Expr::Var("c".to_string(), None)),
+                            Some(Type::Int),
                             Box::new(Expr::BinExpr(
-                                Box::new(Expr::Var("".to_string())),
+                                Box::new(Expr::Var("".to_string(), None)),
                                 Op::AssOp(AssOp::Eq),
                                 Box::new(Expr::Int(2)),
                             )),
                         ),
                         Expr::IfElse(
-                            Box::new(Expr::Var("a".to_string())),
+                            Box::new(Expr::Var("a".to_string(), None)),
                             vec![
                                 Expr::Let(
-                                    Box::new(Expr::Var("b".to_string())),
-                                    Type::Int,
+                                    Box::new(Expr::Var("b".to_string(), None)),
+                                    Some(Type::Int),
                                     Box::new(Expr::BinExpr(
-                                        Box::new(Expr::Var("".to_string())),
+                                        Box::new(Expr::Var("".to_string(), None)),
                                         Op::AssOp(AssOp::Eq),
                                         Box::new(Expr::Int(1)),
                                     )),
                                 ),
-                                Expr::Return(Box::new(Expr::Var("b".to_string()))),
+                                Expr::Return(Box::new(Expr::Var("b".to_string(), None))),
                             ],
-                            vec![Expr::Return(Box::new(Expr::Var("c".to_string())))],
+                            vec![Expr::Return(Box::new(Expr::Var("c".to_string(), None)))],
                         ),
                     ],
                 ),
                 Expr::Fn(
-                    Box::new(Expr::Var("testfn2".to_string())),
+                    Box::new(Expr::Var("testfn2".to_string(), None)),
+                    vec![],
                     vec![],
                     Type::Int,
                     vec![Expr::Return(Box::new(Expr::FnCall(
-                        Box::new(Expr::Var("testfn1".to_string())),
+                        Box::new(Expr::Var("testfn1".to_string(), None)),
                         vec![Expr::Bool(true)],
                     )))],
                 ),
                 Expr::Fn(
-                    Box::new(Expr::Var("testfn3".to_string())),
+                    Box::new(Expr::Var("testfn3".to_string(), None)),
+                    vec![],
                     vec![
-                        (Expr::Var("b".to_string()), Type::Bool),
-                        (Expr::Var("c".to_string()), Type::Bool),
+                        (Expr::Var("b".to_string(), None), Type::Bool),
+                        (Expr::Var("c".to_string(), None), Type::Bool),
                     ],
                     Type::Int,
                     vec![
                         Expr::Let(
-                            Box::new(Expr::Var("d".to_string())),
-                            Type::Bool,
+                            Box::new(Expr::Var("d".to_string(), None)),
+                            Some(Type::Bool),
                             Box::new(Expr::VarExpr(
-                                Box::new(Expr::Var("b".to_string())),
+                                Box::new(Expr::Var("b".to_string(), None)),
                                 Op::LogOp(LogOp::And),
-                                Box::new(Expr::Var("c".to_string())),
+                                Box::new(Expr::Var("c".to_string(), None)),
                             )),
                         ),
                         Expr::Let(
-                            Box::new(Expr::Var("n".to_string())),
-                            Type::Int,
+                            Box::new(Expr::Var("n".to_string(), None)),
+                            Some(Type::Int),
                             Box::new(Expr::BinExpr(
-                                Box::new(Expr::Var("".to_string())),
+                                Box::new(Expr::Var("".to_string(), None)),
                                 Op::AssOp(AssOp::Eq),
                                 Box::new(Expr::Int(0)),
                             )),
                         ),
                         Expr::While(
                             Box::new(Expr::VarExpr(
-                                Box::new(Expr::Var("d".to_string())),
+                                Box::new(Expr::Var("d".to_string(), None)),
                                 Op::RelOp(RelOp::Eq),
                                 Box::new(Expr::Bool(true)),
                             )),
                             vec![
                                 Expr::VarExpr(
-                                    Box::new(Expr::Var("n".to_string())),
+                                    Box::new(Expr::Var("n".to_string(), None)),
                                     Op::AssOp(AssOp::AddEq),
                                     Box::new(Expr::Int(1)),
                                 ),
                                 Expr::VarExpr(
-                                    Box::new(Expr::Var("d".to_string())),
+                                    Box::new(Expr::Var("d".to_string(), None)),
                                     Op::AssOp(AssOp::Eq),
                                     Box::new(Expr::Bool(false)),
                                 ),
                             ],
                         ),
-                        Expr::Return(Box::new(Expr::Var("n".to_string()))),
+                        Expr::Return(Box::new(Expr::Var("n".to_string(), None))),
                     ],
                 ),
                 Expr::Fn(
-                    Box::new(Expr::Var("main".to_string())),
+                    Box::new(Expr::Var("main".to_string(), None)),
+                    vec![],
                     vec![],
                     Type::Int,
                     vec![
                         Expr::Let(
-                            Box::new(Expr::Var("a".to_string())),
-                            Type::Int,
+                            Box::new(Expr::Var("a".to_string(), None)),
+                            Some(Type::Int),
                             Box::new(Expr::BinExpr(
-                                Box::new(Expr::Var("".to_string())),
+                                Box::new(Expr::Var("".to_string(), None)),
                                 Op::AssOp(AssOp::Eq),
                                 Box::new(Expr::FnCall(
-                                    Box::new(Expr::Var("testfn2".to_string())),
+                                    Box::new(Expr::Var("testfn2".to_string(), None)),
                                     vec![],
                                 )),
                             )),
                         ),
                         Expr::Let(
-                            Box::new(Expr::Var("b".to_string())),
-                            Type::Int,
+                            Box::new(Expr::Var("b".to_string(), None)),
+                            Some(Type::Int),
                             Box::new(Expr::BinExpr(
-                                Box::new(Expr::Var("".to_string())),
+                                Box::new(Expr::Var("".to_string(), None)),
                                 Op::AssOp(AssOp::Eq),
                                 Box::new(Expr::FnCall(
-                                    Box::new(Expr::Var("testfn3".to_string())),
+                                    Box::new(Expr::Var("testfn3".to_string(), None)),
                                     vec![Expr::Bool(true), Expr::Bool(true)],
                                 )),
                             )),
                         ),
                         Expr::Return(Box::new(Expr::VarExpr(
-                            Box::new(Expr::Var("a".to_string())),
+                            Box::new(Expr::Var("a".to_string(), None)),
                             Op::AriOp(AriOp::Add),
-                            Box::new(Expr::Var("b".to_string())),
+                            Box::new(Expr::Var("b".to_string(), None)),
                         ))),
                     ],
                 ),
                 Expr::Return(Box::new(Expr::FnCall(
-                    Box::new(Expr::Var("main".to_string())),
+                    Box::new(Expr::Var("main".to_string(), None)),
                     vec![]
                 ))),
             ]),
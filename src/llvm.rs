@@ -1,21 +1,185 @@
 use crate::ast::*;
+use crate::host::host_symbols;
+use crate::memory::ExprRep;
+use crate::resolver::*;
+use crate::runtime::{builtins, runtime_symbols, LangStr, STR_CONCAT_FN, STR_EQ_FN};
 
 extern crate inkwell;
 
 use self::inkwell::{
+    basic_block::BasicBlock,
     builder::Builder,
     context::Context,
+    debug_info::{
+        AsDIScope, DICompileUnit, DIFlags, DIFlagsConstants, DISubprogram, DIType,
+        DWARFEmissionKind, DWARFSourceLanguage, DebugInfoBuilder,
+    },
     execution_engine::{ExecutionEngine, JitFunction},
-    module::Module,
-    types::BasicTypeEnum,
-    values::{BasicValueEnum, FunctionValue, InstructionValue, IntValue, PointerValue},
-    IntPredicate, OptimizationLevel,
+    module::{FlagBehavior, Linkage, Module},
+    types::{ArrayType, BasicType, BasicTypeEnum, FunctionType, StructType},
+    values::{
+        ArrayValue, BasicValueEnum, CallableValue, FloatValue, FunctionValue, InstructionValue,
+        IntValue, PointerValue, StructValue,
+    },
+    AddressSpace, FloatPredicate, IntPredicate, OptimizationLevel,
 };
 
 use core::panic;
-use std::{collections::HashMap, error::Error};
+use std::{
+    collections::HashMap,
+    convert::TryFrom,
+    error::Error,
+    ffi::CStr,
+    os::raw::c_char,
+    path::{Path, PathBuf},
+    process,
+};
 
 type ExprFunc = unsafe extern "C" fn() -> i32;
+type I64Func = unsafe extern "C" fn() -> i64;
+type FloatFunc = unsafe extern "C" fn() -> f64;
+type BoolFunc = unsafe extern "C" fn() -> bool;
+type StrFunc = unsafe extern "C" fn() -> LangStr;
+
+const PANIC_FN: &str = "lang_panic";
+const BOUNDS_PANIC_FN: &str = "lang_bounds_panic";
+
+/// File name recorded in the debug info of a program that was not read from a file.
+const SOURCE_FILE: &str = "program";
+
+/// DWARF base type encodings, see the DWARF 4 standard section 7.8.
+const DW_ATE_BOOLEAN: u32 = 0x02;
+const DW_ATE_FLOAT: u32 = 0x04;
+const DW_ATE_SIGNED: u32 = 0x05;
+const DW_ATE_UNSIGNED: u32 = 0x08;
+
+/// Called by compiled code on a runtime error, reports it and exits like the interpreter.
+extern "C" fn lang_panic(msg: *const c_char, line: i32, column: i32) {
+    let msg = unsafe { CStr::from_ptr(msg) }.to_string_lossy();
+    eprintln!("runtime error at {}:{}: {}", line, column, msg);
+    process::exit(101);
+}
+
+/// Called by compiled code when an array index is out of bounds.
+extern "C" fn lang_bounds_panic(len: i64, index: i64, line: i32, column: i32) {
+    eprintln!(
+        "runtime error at {}:{}: index out of bounds: the len is {} but the index is {}",
+        line, column, len, index
+    );
+    process::exit(101);
+}
+
+/// The arithmetic behind a compound assignment such as `+=`.
+fn ass_ari_op(op: Op) -> AriOp {
+    match op {
+        Op::AssOp(AssOp::AddEq) => AriOp::Add,
+        Op::AssOp(AssOp::SubEq) => AriOp::Sub,
+        Op::AssOp(AssOp::DivEq) => AriOp::Div,
+        Op::AssOp(AssOp::MulEq) => AriOp::Mul,
+        Op::AssOp(AssOp::RemEq) => AriOp::Rem,
+        _ => panic!("Invalid Var op!"),
+    }
+}
+
+/// Code generation settings picked on the command line.
+#[derive(Debug, Default, Clone)]
+pub struct CompileOptions {
+    pub wrapping: bool,
+    pub debug: bool,
+    /// File the program was read from, the debug info refers to it
+    pub source_path: Option<PathBuf>,
+}
+
+/// Debug metadata state, only present when compiling with `-g`.
+struct DebugInfo<'ctx> {
+    builder: DebugInfoBuilder<'ctx>,
+    compile_unit: DICompileUnit<'ctx>,
+    subprogram: Option<DISubprogram<'ctx>>,
+}
+
+/// Declaration span, type parameters, parameters, return type and body of a generic function.
+type GenericFn = (
+    Span,
+    Vec<(String, Vec<String>)>,
+    Vec<(Expr, Type)>,
+    Type,
+    Vec<Expr>,
+);
+
+/// Binds the type parameters in `param_type` to the matching parts of `arg_type`.
+fn infer_type_args(param_type: &Type, arg_type: &Type, type_args: &mut HashMap<String, Type>) {
+    match (param_type, arg_type) {
+        (Type::Named(name), _) => {
+            type_args.insert(name.to_string(), arg_type.clone());
+        }
+        (Type::Array(param_type, _), Type::Array(arg_type, _)) => {
+            infer_type_args(param_type, arg_type, type_args)
+        }
+        (Type::Tuple(param_types), Type::Tuple(arg_types)) => {
+            for (param_type, arg_type) in param_types.iter().zip(arg_types) {
+                infer_type_args(param_type, arg_type, type_args);
+            }
+        }
+        (Type::Fn(param_types, param_ret), Type::Fn(arg_types, arg_ret)) => {
+            for (param_type, arg_type) in param_types.iter().zip(arg_types) {
+                infer_type_args(param_type, arg_type, type_args);
+            }
+            infer_type_args(param_ret, arg_ret, type_args);
+        }
+        _ => {}
+    }
+}
+
+/// Name of a function from the `Var` of its declaration.
+fn fn_name(fn_var: Expr) -> String {
+    match fn_var {
+        Expr::Var(name, _) => name,
+        _ => panic!("Invalid fn var!"),
+    }
+}
+
+/// The value of a `let`, which is parsed as `Var("") = value`.
+fn let_value(expr: Expr) -> Expr {
+    match expr {
+        Expr::Spanned(span, expr) => Expr::Spanned(span, Box::new(let_value(*expr))),
+        Expr::BinExpr(_, _, value) | Expr::VarExpr(_, _, value) => *value,
+        expr => expr,
+    }
+}
+
+/// Records the types of the names a `let` or parameter binds, destructuring tuples.
+fn binding_types(binding: &Expr, ty: &Type, var_types: &mut HashMap<DeclId, Type>) {
+    match (binding, ty) {
+        (Expr::Tuple(bindings), Type::Tuple(types)) => {
+            for (binding, ty) in bindings.iter().zip(types) {
+                binding_types(binding, ty, var_types);
+            }
+        }
+        (Expr::Var(_, Some(id)), _) => {
+            var_types.insert(*id, ty.clone());
+        }
+        _ => panic!("Invalid destructuring!"),
+    }
+}
+
+/// Values flowing out of the branches of an `if` or `match`, `None` for branches without
+/// a value, with the basic blocks they come from.
+type Incoming<'ctx> = Vec<(Option<BasicValueEnum<'ctx>>, BasicBlock<'ctx>)>;
+
+/// The variants of an enum with the types of their fields.
+type Variants = Vec<(String, Vec<Type>)>;
+
+/// The function being compiled, saved while another one is compiled in the middle of it.
+struct FnState<'ctx> {
+    block: BasicBlock<'ctx>,
+    fn_value: Option<FunctionValue<'ctx>>,
+    variables: HashMap<DeclId, PointerValue<'ctx>>,
+    var_types: HashMap<DeclId, Type>,
+    type_args: HashMap<String, Type>,
+    ret_type: Type,
+    span: Span,
+    subprogram: Option<DISubprogram<'ctx>>,
+}
 
 // // ======================================================================================
 // // COMPILER =============================================================================
@@ -29,10 +193,28 @@ pub struct Compiler<'a, 'ctx> {
     pub execution_engine: &'a ExecutionEngine<'ctx>,
     pub fn_value_opt: Option<FunctionValue<'ctx>>,
 
-    variables: HashMap<String, PointerValue<'ctx>>,
-    fn_args: HashMap<String, Vec<Expr>>,
+    /// Variables of the function being compiled by their declarations
+    variables: HashMap<DeclId, PointerValue<'ctx>>,
+    var_types: HashMap<DeclId, Type>,
+    structs: HashMap<String, (StructType<'ctx>, Vec<(String, Type)>)>,
+    enums: HashMap<String, (StructType<'ctx>, Variants)>,
+    signatures: HashMap<String, (Vec<Type>, Type)>,
+    /// `const` and `static` items by their declarations, functions see them as variables
+    globals: HashMap<DeclId, (PointerValue<'ctx>, Type)>,
+    /// Generic functions by name, compiled once for each instantiation
+    generic_fns: HashMap<String, GenericFn>,
+    /// Types of the type parameters of the instantiation being compiled
+    type_args: HashMap<String, Type>,
+    ret_type: Type,
+    /// Where `continue` and `break` jump to in each loop around the statement, with the
+    /// values of the `break`s of a `loop` so far
+    loops: Vec<(BasicBlock<'ctx>, BasicBlock<'ctx>, Incoming<'ctx>)>,
 
     statement: (InstructionValue<'ctx>, bool),
+
+    wrapping: bool,
+    span: Span,
+    debug: Option<DebugInfo<'ctx>>,
 }
 
 impl<'a, 'ctx> Compiler<'a, 'ctx> {
@@ -42,1065 +224,4736 @@ impl<'a, 'ctx> Compiler<'a, 'ctx> {
     }
 
     #[inline]
-    fn get_variable(&self, name: &str) -> &PointerValue<'ctx> {
-        match self.variables.get(name) {
+    fn get_variable(&self, id: Option<DeclId>) -> &PointerValue<'ctx> {
+        match id.and_then(|id| self.variables.get(&id)) {
             Some(var) => var,
             None => panic!("ERROR: Can't find matching variable"),
         }
     }
 
-    fn create_entry_block_alloca(&mut self, name: &str, var_type: bool) -> PointerValue<'ctx> {
+    fn create_entry_block_alloca(
+        &mut self,
+        name: &str,
+        id: Option<DeclId>,
+        var_type: Type,
+    ) -> PointerValue<'ctx> {
+        let id = match id {
+            Some(id) => id,
+            None => panic!("Unresolved variable {}!", name),
+        };
+        let alloca = self.build_entry_alloca(self.llvm_type(&var_type), name);
+
+        self.variables.insert(id, alloca);
+        self.var_types.insert(id, var_type);
+        alloca
+    }
+
+    /// Allocas go in the entry block so that loops do not grow the stack.
+    fn build_entry_alloca(&self, ty: BasicTypeEnum<'ctx>, name: &str) -> PointerValue<'ctx> {
         let builder = self.context.create_builder();
         let entry = self.fn_value().get_first_basic_block().unwrap();
         match entry.get_first_instruction() {
             Some(first_instr) => builder.position_before(&first_instr),
             None => builder.position_at_end(entry),
         }
-        let alloca: PointerValue;
+        builder.build_alloca(ty, name)
+    }
 
-        if var_type {
-            alloca = builder.build_alloca(self.context.bool_type(), name);
-        } else {
-            alloca = builder.build_alloca(self.context.i32_type(), name);
+    /// Static type of an expression the type checker accepted, LLVM integers
+    /// do not tell signed and unsigned types apart.
+    fn type_of(&self, expr: &Expr) -> Type {
+        self.type_in(expr, &self.var_types)
+    }
+
+    /// Like `type_of` with the variables typed by `var_types`, the body of a closure also
+    /// sees its parameters.
+    fn type_in(&self, expr: &Expr, var_types: &HashMap<DeclId, Type>) -> Type {
+        match expr {
+            Expr::Int(_) => Type::Int,
+            Expr::TypedInt(_, int_type) => int_type.clone(),
+            Expr::Float(_) => Type::Float,
+            Expr::Bool(_) => Type::Bool,
+            Expr::Str(_) => Type::Str,
+            Expr::Var(var, id) => match (
+                id.and_then(|id| var_types.get(&id)),
+                self.signatures.get(var),
+            ) {
+                (Some(var_type), _) => var_type.clone(),
+                // A function used as a value
+                (None, Some((param_types, ret_type))) => {
+                    Type::Fn(param_types.clone(), Box::new(ret_type.clone()))
+                }
+                (None, None) => panic!("Could not find var {} in map!", var),
+            },
+            Expr::Cast(_, to_type) => to_type.clone(),
+            Expr::Array(elems) => {
+                Type::Array(Box::new(self.type_in(&elems[0], var_types)), elems.len())
+            }
+            Expr::ArrayRepeat(elem, len) => {
+                Type::Array(Box::new(self.type_in(elem, var_types)), *len)
+            }
+            Expr::Index(array, _) => match self.type_in(array, var_types) {
+                Type::Array(elem_type, _) => *elem_type,
+                _ => panic!("Invalid index expr!"),
+            },
+            Expr::StructLit(name, _) | Expr::Variant(name, ..) => Type::Named(name.to_string()),
+            Expr::Tuple(elems) => Type::Tuple(
+                elems
+                    .iter()
+                    .map(|elem| self.type_in(elem, var_types))
+                    .collect(),
+            ),
+            Expr::TupleField(expr, index) => match self.type_in(expr, var_types) {
+                Type::Tuple(mut types) => types.swap_remove(*index),
+                _ => panic!("Invalid tuple field expr!"),
+            },
+            Expr::Field(expr, field) => match self.type_in(expr, var_types) {
+                Type::Named(name) => {
+                    let (_, fields) = &self.structs[&name];
+                    let index = self.field_index(&name, field);
+                    fields[index as usize].1.clone()
+                }
+                _ => panic!("Invalid field expr!"),
+            },
+            Expr::BinExpr(l, op, r) | Expr::VarExpr(l, op, r) => match op {
+                Op::AriOp(_) => self.type_in(l, var_types),
+                Op::LogOp(_) | Op::RelOp(_) => Type::Bool,
+                Op::AssOp(_) => self.type_in(r, var_types),
+            },
+            Expr::FnCall(fn_var, args) => match &**fn_var {
+                Expr::Var(_, Some(id)) if var_types.contains_key(id) => match &var_types[id] {
+                    Type::Fn(_, ret_type) => *ret_type.clone(),
+                    _ => panic!("Invalid Fn Var!"),
+                },
+                Expr::Var(name, _) if self.generic_fns.contains_key(name) => {
+                    let (_, _, _, ret_type, _) = &self.generic_fns[name];
+                    ret_type.substitute(&self.infer_call_type_args(name, args, var_types))
+                }
+                Expr::Var(name, _) => self.signatures[name].1.clone(),
+                _ => panic!("Invalid Fn Var!"),
+            },
+            Expr::MethodCall(receiver, method, _) => {
+                let path = self.type_in(receiver, var_types).method_path(method);
+                self.signatures[&path].1.clone()
+            }
+            Expr::Closure(params, body) => {
+                let mut body_types = var_types.clone();
+                let param_types = params
+                    .iter()
+                    .map(|(param, param_type)| {
+                        let param_type = param_type.substitute(&self.type_args);
+                        binding_types(param, &param_type, &mut body_types);
+                        param_type
+                    })
+                    .collect();
+                Type::Fn(param_types, Box::new(self.type_in(body, &body_types)))
+            }
+            // A block that never completes does not decide the type
+            Expr::IfElse(_, block1, block2) => match self.block_type(block1, var_types) {
+                Type::Never => self.block_type(block2, var_types),
+                ty => ty,
+            },
+            Expr::Block(block) => self.block_type(block, var_types),
+            Expr::Match(expr, arms) => {
+                let ty = self.type_in(expr, var_types);
+                arms.iter()
+                    .map(|(pattern, block)| {
+                        let mut arm_types = var_types.clone();
+                        self.pattern_types(pattern, &ty, &mut arm_types);
+                        self.block_type(block, &arm_types)
+                    })
+                    .find(|arm_type| *arm_type != Type::Never)
+                    .unwrap_or(Type::Never)
+            }
+            Expr::Loop(block) => self.break_type(block, var_types),
+            Expr::Tail(expr) | Expr::Spanned(_, expr) => self.type_in(expr, var_types),
+            _ => Type::Void,
         }
+    }
 
-        self.variables.insert(name.to_string(), alloca);
-        alloca
+    /// Type of the tail expression of a block, which sees the `let`s before it.
+    fn block_type(&self, block: &[Expr], var_types: &HashMap<DeclId, Type>) -> Type {
+        let mut var_types = var_types.clone();
+        for stmt in block {
+            match stmt.clone().unspanned() {
+                Expr::Let(binding, var_type, expr) => {
+                    self.let_types(&binding, var_type, &expr, &mut var_types)
+                }
+                Expr::Declare(var, Some(var_type)) => {
+                    binding_types(&var, &var_type.substitute(&self.type_args), &mut var_types)
+                }
+                Expr::Tail(expr) => return self.type_in(&expr, &var_types),
+                Expr::Return(_) | Expr::Break(_) | Expr::Continue => return Type::Never,
+                _ => {}
+            }
+        }
+        Type::Void
+    }
+
+    /// Type of the values of the `break`s of a `loop` with this body, `!` without any.
+    fn break_type(&self, block: &[Expr], var_types: &HashMap<DeclId, Type>) -> Type {
+        let mut var_types = var_types.clone();
+        for stmt in block {
+            let ty = match stmt.clone().unspanned() {
+                Expr::Let(binding, var_type, expr) => {
+                    self.let_types(&binding, var_type, &expr, &mut var_types);
+                    continue;
+                }
+                Expr::Declare(var, Some(var_type)) => {
+                    binding_types(&var, &var_type.substitute(&self.type_args), &mut var_types);
+                    continue;
+                }
+                Expr::Break(Some(value)) => self.type_in(&value, &var_types),
+                Expr::Break(None) => Type::Void,
+                Expr::If(_, block) | Expr::Block(block) => self.break_type(&block, &var_types),
+                Expr::IfElse(_, block1, block2) => match self.break_type(&block1, &var_types) {
+                    Type::Never => self.break_type(&block2, &var_types),
+                    ty => ty,
+                },
+                Expr::Match(expr, arms) => {
+                    let ty = self.type_in(&expr, &var_types);
+                    arms.iter()
+                        .map(|(pattern, block)| {
+                            let mut arm_types = var_types.clone();
+                            self.pattern_types(pattern, &ty, &mut arm_types);
+                            self.break_type(block, &arm_types)
+                        })
+                        .find(|arm_type| *arm_type != Type::Never)
+                        .unwrap_or(Type::Never)
+                }
+                Expr::Tail(expr) => self.break_type(&[*expr], &var_types),
+                // The `break`s of nested loops end those
+                _ => Type::Never,
+            };
+            if ty != Type::Never {
+                return ty;
+            }
+        }
+        Type::Never
+    }
+
+    /// Records the types of the variables a `let` declares.
+    fn let_types(
+        &self,
+        binding: &Expr,
+        var_type: Option<Type>,
+        expr: &Expr,
+        var_types: &mut HashMap<DeclId, Type>,
+    ) {
+        let var_type = match var_type {
+            Some(var_type) => var_type.substitute(&self.type_args),
+            None => self.type_in(expr, var_types),
+        };
+        binding_types(binding, &var_type, var_types);
+    }
+
+    /// Records the types of the names a pattern binds when matching a value of `ty`.
+    fn pattern_types(&self, pattern: &Pattern, ty: &Type, var_types: &mut HashMap<DeclId, Type>) {
+        match pattern {
+            Pattern::Binding(_, Some(id)) => {
+                var_types.insert(*id, ty.clone());
+            }
+            Pattern::Variant(name, variant, patterns) => {
+                let (_, variants) = &self.enums[name];
+                let (_, field_types) = variants.iter().find(|(other, _)| other == variant).unwrap();
+                for (pattern, field_type) in patterns.iter().zip(field_types) {
+                    self.pattern_types(pattern, field_type, var_types);
+                }
+            }
+            Pattern::Binding(_, None) | Pattern::Wildcard | Pattern::Literal(_) => {}
+        }
     }
 
     fn compile_expr(&mut self, expr: &Expr) -> (InstructionValue<'ctx>, bool) {
         match expr.clone() {
-            Expr::Let(left, var_type, expr) => (self.compile_let(*left, var_type, *expr), false),
+            Expr::Let(left, var_type, expr) => self.compile_let(*left, var_type, *expr),
+            // Only reserves the variable, the statement before stays the last one
+            Expr::Declare(var, var_type) => {
+                self.compile_declare(*var, var_type);
+                self.statement
+            }
 
             Expr::VarExpr(var, op, expr) => (self.compile_var_expr(*var, op, *expr), false),
 
             Expr::If(cond, block) => (self.compile_if(*cond, block), false),
-            Expr::IfElse(cond, block1, block2) => {
-                (self.compile_if_else(*cond, block1, block2), false)
-            }
+            Expr::IfElse(cond, block1, block2) => self.compile_if_else(*cond, block1, block2),
             Expr::While(cond, block) => (self.compile_while(*cond, block), false),
+            Expr::For(var, range, block) => (self.compile_for(*var, *range, block), false),
+            Expr::Loop(block) => self.compile_loop(block),
+            Expr::Match(expr, arms) => self.compile_match(*expr, arms),
+            Expr::Block(block) => {
+                self.compile_block_value(block);
+                self.statement
+            }
 
-            Expr::Fn(fn_var, params, ret_type, block) => {
+            Expr::Fn(fn_var, _, params, ret_type, block) => {
                 (self.compile_fn(*fn_var, params, ret_type, block), false)
             }
             Expr::Return(expr) => {
-                let var = self.compile_stmt(*expr);
+                let var = self.compile_value(*expr);
+                let var = self.to_abi(var, &self.ret_type);
                 (self.builder.build_return(Some(&var)), true)
             }
+            Expr::Break(value) => {
+                let value = value.map(|value| self.compile_value(*value));
+                let block = self.builder.get_insert_block().unwrap();
+                let (_, break_block, incoming) = self.loops.last_mut().unwrap();
+                incoming.push((value, block));
+                (self.builder.build_unconditional_branch(*break_block), true)
+            }
+            Expr::Continue => {
+                let (continue_block, _, _) = self.loops.last().unwrap();
+                (
+                    self.builder.build_unconditional_branch(*continue_block),
+                    true,
+                )
+            }
+
+            Expr::FnCall(func_name, args) => {
+                self.build_fn_call(*func_name, args);
+                let block = self.builder.get_insert_block().unwrap();
+                (block.get_last_instruction().unwrap(), false)
+            }
+            Expr::MethodCall(receiver, method, args) => {
+                self.build_method_call(*receiver, &method, args);
+                let block = self.builder.get_insert_block().unwrap();
+                (block.get_last_instruction().unwrap(), false)
+            }
+
+            Expr::Spanned(span, expr) => {
+                let outer = std::mem::replace(&mut self.span, span);
+                self.set_debug_location();
+                let res = self.compile_expr(&expr);
+                // Code built after a nested statement belongs to the enclosing one again
+                self.span = outer;
+                self.set_debug_location();
+                res
+            }
 
             _ => panic!("Invalid compile expr"),
         }
     }
 
     fn compile_stmt(&mut self, expr: Expr) -> IntValue<'ctx> {
+        self.compile_value(expr).into_int_value()
+    }
+
+    fn compile_value(&mut self, expr: Expr) -> BasicValueEnum<'ctx> {
         match expr.clone() {
-            Expr::Int(i) => self.compile_int(i),
-            Expr::Bool(b) => self.compile_bool(b),
-            Expr::Var(var) => {
+            Expr::Int(i) => self.compile_int(i).into(),
+            Expr::TypedInt(i, int_type) => self
+                .llvm_type(&int_type)
+                .into_int_type()
+                .const_int(i, false)
+                .into(),
+            Expr::Float(f) => self.context.f64_type().const_float(f).into(),
+            Expr::Bool(b) => self.compile_bool(b).into(),
+            Expr::Str(s) => self.compile_str(&s).into(),
+            Expr::Var(var, id)
+                if !matches!(id, Some(id) if self.variables.contains_key(&id))
+                    && self.signatures.contains_key(&var) =>
+            {
+                self.compile_fn_value(&var)
+            }
+            Expr::Var(var, id) => {
                 if var != "" {
-                    let ptr_val = self.get_variable(&var);
-                    self.builder.build_load(*ptr_val, &var).into_int_value()
+                    let ptr_val = self.get_variable(id);
+                    self.builder.build_load(*ptr_val, &var)
                 } else {
-                    let alloca = self.create_entry_block_alloca("empty", false);
+                    let alloca = self.build_entry_alloca(self.llvm_type(&Type::Int), "empty");
                     let val = self.compile_stmt(Expr::Int(0));
                     self.builder.build_store(alloca, val);
+                    self.builder.build_load(alloca, &var)
+                }
+            }
 
-                    let ptr_val = self.get_variable("empty");
-                    self.builder.build_load(*ptr_val, &var).into_int_value()
+            Expr::Array(elems) => {
+                let array_type = self.llvm_type(&self.type_of(&expr)).into_array_type();
+                let vals: Vec<BasicValueEnum> =
+                    elems.into_iter().map(|e| self.compile_value(e)).collect();
+                self.compile_array(array_type, vals).into()
+            }
+            Expr::ArrayRepeat(elem, len) => {
+                let array_type = self.llvm_type(&self.type_of(&expr)).into_array_type();
+                let val = self.compile_value(*elem);
+                self.compile_array(array_type, vec![val; len]).into()
+            }
+            Expr::Index(..) | Expr::Field(..) | Expr::TupleField(..) => {
+                let ptr = self.compile_place(expr);
+                self.builder.build_load(ptr, "elem")
+            }
+            Expr::StructLit(name, fields) => {
+                let mut val = self.structs[&name].0.get_undef();
+                for (field, expr) in fields {
+                    let index = self.field_index(&name, &field);
+                    let field_val = self.compile_value(expr);
+                    val = self
+                        .builder
+                        .build_insert_value(val, field_val, index, &field)
+                        .unwrap()
+                        .into_struct_value();
+                }
+                val.into()
+            }
+            Expr::Tuple(elems) => {
+                let mut val = self
+                    .llvm_type(&self.type_of(&expr))
+                    .into_struct_type()
+                    .get_undef();
+                for (i, elem) in elems.into_iter().enumerate() {
+                    let elem_val = self.compile_value(elem);
+                    val = self
+                        .builder
+                        .build_insert_value(val, elem_val, i as u32, "tuple")
+                        .unwrap()
+                        .into_struct_value();
                 }
+                val.into()
+            }
+            Expr::Variant(name, variant, args) => {
+                let (tag, field_types) = self.variant_info(&name, &variant);
+                let vals: Vec<BasicValueEnum> =
+                    args.into_iter().map(|e| self.compile_value(e)).collect();
+
+                let ptr = self.build_entry_alloca(self.enums[&name].0.into(), &variant);
+                let tag_ptr = self.builder.build_struct_gep(ptr, 0, "tag").unwrap();
+                self.builder.build_store(tag_ptr, tag);
+                let payload = self.compile_payload_ptr(ptr, &field_types);
+                for (i, val) in vals.into_iter().enumerate() {
+                    let field_ptr = self
+                        .builder
+                        .build_struct_gep(payload, i as u32, "field")
+                        .unwrap();
+                    self.builder.build_store(field_ptr, val);
+                }
+                self.builder.build_load(ptr, &variant)
             }
 
             Expr::BinExpr(l, op, r) => self.compile_bin_expr(*l, op, *r),
+            Expr::VarExpr(l, op, r) => self.compile_bin_expr(*l, op, *r),
+            Expr::Cast(expr, to_type) => self.compile_cast(*expr, to_type),
 
             Expr::FnCall(func_name, args) => self.compile_fn_call(*func_name, args),
+            Expr::MethodCall(receiver, method, args) => {
+                match self.build_method_call(*receiver, &method, args) {
+                    Some(val) => val,
+                    None => panic!("Method call has no value!"),
+                }
+            }
+            Expr::Closure(params, body) => self.compile_closure(params, *body),
+            Expr::IfElse(cond, block1, block2) => {
+                let ty = self.type_of(&expr);
+                let (_, incoming) = self.build_if_else(*cond, block1, block2);
+                self.build_value_phi(&ty, incoming)
+            }
+            Expr::Match(scrutinee, arms) => {
+                let ty = self.type_of(&expr);
+                let (_, incoming) = self.build_match(*scrutinee, arms);
+                self.build_value_phi(&ty, incoming)
+            }
+            Expr::Block(block) => self.compile_block_value(block).unwrap(),
+            Expr::Loop(block) => {
+                let ty = self.type_of(&expr);
+                let (_, incoming) = self.build_loop(block);
+                self.build_value_phi(&ty, incoming)
+            }
             _ => panic!("Invalid compile stmt!"),
         }
     }
 
-    fn compile_fn_call(&mut self, func_name: Expr, args: Vec<Expr>) -> IntValue<'ctx> {
-        let name = match func_name {
-            Expr::Var(v) => v,
-            _ => panic!("Invalid Fn Var!"),
-        };
-
-        let function = self.module.get_function(&name).unwrap();
-
-        let argsv: Vec<BasicValueEnum> = args
-            .iter()
-            .map(|a| self.compile_stmt(a.clone()).into())
-            .collect();
-
-        let call = self
-            .builder
-            .build_call(function, &argsv, &name)
-            .try_as_basic_value()
-            .left()
-            .unwrap();
-
-        match call {
-            value => value.into_int_value(),
+    /// Arrays are first class aggregates, filled in one element at a time.
+    fn compile_array(
+        &self,
+        array_type: ArrayType<'ctx>,
+        vals: Vec<BasicValueEnum<'ctx>>,
+    ) -> ArrayValue<'ctx> {
+        let mut array = array_type.get_undef();
+        for (i, val) in vals.into_iter().enumerate() {
+            array = self
+                .builder
+                .build_insert_value(array, val, i as u32, "array")
+                .unwrap()
+                .into_array_value();
         }
+        array
     }
 
-    fn compile_let(&mut self, var: Expr, var_type: Type, expr: Expr) -> InstructionValue<'ctx> {
-        match (var, expr.clone()) {
-            (Expr::Var(left), Expr::VarExpr(v, _, _)) => {
-                let ptr_val = match var_type {
-                    Type::Int => self.create_entry_block_alloca(&left, false),
-                    Type::Bool => self.create_entry_block_alloca(&left, true),
-                    _ => panic!("Invalid Let expr type!"),
-                };
-
-                self.compile_expr(&expr);
-
-                let right_expr_val = match *v {
-                    Expr::Var(_) => self.compile_stmt(*v),
-
-                    _ => panic!("Invalid right expr val!"),
+    /// Address of a variable, array element, tuple or struct field, indices are bounds
+    /// checked.
+    fn compile_place(&mut self, expr: Expr) -> PointerValue<'ctx> {
+        match expr {
+            Expr::Var(_, id) => *self.get_variable(id),
+            Expr::Index(array, index) => {
+                let len = match self.type_of(&array) {
+                    Type::Array(_, len) => len,
+                    _ => panic!("Invalid index expr!"),
                 };
-
-                return self.builder.build_store(ptr_val, right_expr_val);
+                let ptr = self.compile_base_ptr(*array);
+
+                let index_type = self.type_of(&index);
+                let index = self.compile_value(*index).into_int_value();
+                let index = self.compile_bounds_check(index, &index_type, len);
+                let zero = self.context.i64_type().const_int(0, false);
+                unsafe {
+                    self.builder
+                        .build_in_bounds_gep(ptr, &[zero, index], "elem")
+                }
             }
-            (Expr::Var(left), _) => {
-                let ptr_val = match var_type {
-                    Type::Int => self.create_entry_block_alloca(&left, false),
-                    Type::Bool => self.create_entry_block_alloca(&left, true),
-                    _ => panic!("Invalid Let expr type!"),
+            Expr::Field(expr, field) => {
+                let index = match self.type_of(&expr) {
+                    Type::Named(name) => self.field_index(&name, &field),
+                    _ => panic!("Invalid field expr!"),
                 };
-                let val = self.compile_stmt(expr);
-                return self.builder.build_store(ptr_val, val);
+                let ptr = self.compile_base_ptr(*expr);
+                self.builder.build_struct_gep(ptr, index, &field).unwrap()
             }
-            _ => panic!("Invalid Expr!"),
+            Expr::TupleField(expr, index) => {
+                let ptr = self.compile_base_ptr(*expr);
+                self.builder
+                    .build_struct_gep(ptr, index as u32, "elem")
+                    .unwrap()
+            }
+            _ => panic!("Invalid place expr!"),
         }
     }
 
-    fn compile_var_expr(&mut self, var: Expr, op: Op, expr: Expr) -> InstructionValue<'ctx> {
-        let old_val = self.compile_stmt(var.clone());
-        let val = self.compile_stmt(expr.clone());
+    /// Address of an indexed array or accessed struct, values that are not stored in a
+    /// variable are accessed through a temporary.
+    fn compile_base_ptr(&mut self, expr: Expr) -> PointerValue<'ctx> {
+        match expr {
+            place @ Expr::Var(_, _)
+            | place @ Expr::Index(..)
+            | place @ Expr::Field(..)
+            | place @ Expr::TupleField(..) => self.compile_place(place),
+            expr => {
+                let val = self.compile_value(expr);
+                let ptr = self.build_entry_alloca(val.get_type(), "tmp");
+                self.builder.build_store(ptr, val);
+                ptr
+            }
+        }
+    }
 
-        match (var.clone(), op.clone(), expr.clone()) {
-            (Expr::Var(v), Op::AssOp(AssOp::Eq), Expr::Int(_)) => {
-                let var_ptr = self.get_variable(&v);
+    /// Position of a field in the declaration of its struct.
+    fn field_index(&self, name: &str, field: &str) -> u32 {
+        let (_, fields) = &self.structs[name];
+        fields.iter().position(|(other, _)| other == field).unwrap() as u32
+    }
 
-                self.builder.build_store(*var_ptr, val)
-            }
-            (Expr::Var(v), Op::AssOp(AssOp::Eq), Expr::Bool(_)) => {
-                let var_ptr = self.get_variable(&v);
+    /// Tag and field types of an enum variant.
+    fn variant_info(&self, name: &str, variant: &str) -> (IntValue<'ctx>, Vec<Type>) {
+        let (_, variants) = &self.enums[name];
+        let tag = variants
+            .iter()
+            .position(|(other, _)| other == variant)
+            .unwrap();
+        let tag_val = self.context.i32_type().const_int(tag as u64, false);
+        (tag_val, variants[tag].1.clone())
+    }
 
-                self.builder.build_store(*var_ptr, val)
-            }
-            (Expr::Var(v), Op::AssOp(AssOp::AddEq), Expr::Int(_)) => {
-                let var_ptr = self.get_variable(&v);
-                let new_val = self.compile_int_expr(old_val, Op::AriOp(AriOp::Add), val);
+    /// Address of the fields of a variant inside the payload of the enum at `ptr`.
+    fn compile_payload_ptr(
+        &self,
+        ptr: PointerValue<'ctx>,
+        field_types: &[Type],
+    ) -> PointerValue<'ctx> {
+        let field_types: Vec<BasicTypeEnum> = field_types
+            .iter()
+            .map(|field_type| self.llvm_type(field_type))
+            .collect();
+        let fields_type = self.context.struct_type(&field_types, false);
+        let payload = self.builder.build_struct_gep(ptr, 1, "payload").unwrap();
+        self.builder.build_pointer_cast(
+            payload,
+            fields_type.ptr_type(AddressSpace::Generic),
+            "fields",
+        )
+    }
 
-                self.builder.build_store(*var_ptr, new_val)
-            }
-            (Expr::Var(v), Op::AssOp(AssOp::SubEq), Expr::Int(_)) => {
-                let var_ptr = self.get_variable(&v);
-                let new_val = self.compile_int_expr(old_val, Op::AriOp(AriOp::Sub), val);
+    /// Upper bound of the size of values of `ty` in bytes, the payload of an enum is
+    /// sized from it as there is no target data to ask.
+    fn size_bound(&self, ty: &Type) -> u64 {
+        match ty {
+            Type::Float => 8,
+            Type::Bool => 1,
+            Type::Str => 16,
+            Type::Array(elem_type, len) => self.size_bound(elem_type) * *len as u64,
+            Type::Named(name) => match self.structs.get(name) {
+                Some((_, fields)) => fields
+                    .iter()
+                    .map(|(_, field_type)| 8 * self.word_bound(field_type))
+                    .sum(),
+                None => 8 + 8 * self.payload_words(name),
+            },
+            Type::Tuple(types) => types.iter().map(|ty| 8 * self.word_bound(ty)).sum(),
+            // A function pointer and the pointer to its environment
+            Type::Fn(..) => 16,
+            Type::Void | Type::Never => 0,
+            Type::Int
+            | Type::I8
+            | Type::I16
+            | Type::I64
+            | Type::U8
+            | Type::U32
+            | Type::U64
+            | Type::Usize => ty.int_info().unwrap().0 as u64 / 8,
+            Type::Var(_) => panic!("Invalid llvm type!"),
+        }
+    }
 
-                self.builder.build_store(*var_ptr, new_val)
-            }
-            (Expr::Var(v), Op::AssOp(AssOp::DivEq), Expr::Int(_)) => {
-                let var_ptr = self.get_variable(&v);
-                let new_val = self.compile_int_expr(old_val, Op::AriOp(AriOp::Div), val);
+    fn word_bound(&self, ty: &Type) -> u64 {
+        self.size_bound(ty).div_ceil(8)
+    }
 
-                self.builder.build_store(*var_ptr, new_val)
-            }
-            (Expr::Var(v), Op::AssOp(AssOp::MulEq), Expr::Int(_)) => {
-                let var_ptr = self.get_variable(&v);
-                let new_val = self.compile_int_expr(old_val, Op::AriOp(AriOp::Mul), val);
+    /// Number of `i64` words that hold the fields of the largest variant of an enum.
+    fn payload_words(&self, name: &str) -> u64 {
+        let (_, variants) = &self.enums[name];
+        variants
+            .iter()
+            .map(|(_, field_types)| field_types.iter().map(|ty| self.word_bound(ty)).sum())
+            .max()
+            .unwrap_or(0)
+    }
 
-                self.builder.build_store(*var_ptr, new_val)
+    /// Creates the named LLVM types of all structs and enums before any function uses
+    /// them, the bodies are set afterwards as the types may refer to each other.
+    fn declare_types(&mut self, ast: &[Expr]) {
+        let var_name = |var: Expr| match var {
+            Expr::Var(name, _) => name,
+            _ => panic!("Invalid type var!"),
+        };
+        for item in ast {
+            match item.clone().unspanned() {
+                Expr::Struct(struct_var, fields) => {
+                    let name = var_name(*struct_var);
+                    let fields = fields
+                        .into_iter()
+                        .map(|(field, field_type)| match field {
+                            Expr::Var(field, _) => (field, field_type),
+                            _ => panic!("Invalid field var!"),
+                        })
+                        .collect();
+                    let struct_type = self.context.opaque_struct_type(&name);
+                    self.structs.insert(name, (struct_type, fields));
+                }
+                Expr::Enum(enum_var, variants) => {
+                    let name = var_name(*enum_var);
+                    let variants = variants
+                        .into_iter()
+                        .map(|(variant, field_types)| match variant {
+                            Expr::Var(variant, _) => (variant, field_types),
+                            _ => panic!("Invalid variant var!"),
+                        })
+                        .collect();
+                    let enum_type = self.context.opaque_struct_type(&name);
+                    self.enums.insert(name, (enum_type, variants));
+                }
+                _ => continue,
             }
-            (Expr::Var(v1), _, Expr::Var(_)) => {
-                let var_ptr = self.get_variable(&v1);
-                let new_val = self.compile_int_expr(old_val, op, val);
+        }
 
-                self.builder.build_store(*var_ptr, new_val)
-            }
-            _ => panic!("Invalid Var op!"),
+        for (struct_type, fields) in self.structs.values() {
+            let field_types: Vec<BasicTypeEnum> = fields
+                .iter()
+                .map(|(_, field_type)| self.llvm_type(field_type))
+                .collect();
+            struct_type.set_body(&field_types, false);
+        }
+        // Enums are a tag followed by words big enough for the fields of any variant.
+        for (name, (enum_type, _)) in self.enums.iter() {
+            let payload_type = self
+                .context
+                .i64_type()
+                .array_type(self.payload_words(name) as u32);
+            enum_type.set_body(
+                &[self.context.i32_type().into(), payload_type.into()],
+                false,
+            );
         }
     }
 
-    fn compile_bin_expr(&mut self, l: Expr, op: Op, r: Expr) -> IntValue<'ctx> {
-        match (l.clone(), r.clone()) {
-            (Expr::Int(_), Expr::Int(_)) => {
-                let left = self.compile_stmt(l);
-                let right = self.compile_stmt(r);
-                self.compile_int_expr(left, op, right)
+    /// Defines a global variable for each `const` and `static`, initialized with the
+    /// literal the type checker evaluated it to. Only `static mut` ones can be written.
+    fn define_globals(&mut self, ast: &[Expr]) {
+        for item in ast {
+            let (var, ty, value, mutable) = match item.clone().unspanned() {
+                Expr::Const(var, ty, value) => (var, ty, value, false),
+                Expr::Static(var, ty, value, mutable) => (var, ty, value, mutable),
+                _ => continue,
+            };
+            let (name, id) = match *var {
+                Expr::Var(name, Some(id)) => (name, id),
+                _ => panic!("Invalid global var!"),
+            };
+            let global = self.module.add_global(self.llvm_type(&ty), None, &name);
+            global.set_initializer(&self.const_value(*value, &ty));
+            global.set_constant(!mutable);
+            self.globals.insert(id, (global.as_pointer_value(), ty));
+        }
+    }
+
+    /// The constant for a literal, the initializer of a global.
+    fn const_value(&mut self, expr: Expr, ty: &Type) -> BasicValueEnum<'ctx> {
+        match (expr, ty) {
+            // Without a function to build in, the bytes get a global of their own
+            (Expr::Str(s), _) => {
+                let i8_type = self.context.i8_type();
+                let bytes: Vec<IntValue> = s
+                    .bytes()
+                    .map(|byte| i8_type.const_int(byte as u64, false))
+                    .collect();
+                let global =
+                    self.module
+                        .add_global(i8_type.array_type(bytes.len() as u32), None, "str");
+                global.set_initializer(&i8_type.const_array(&bytes));
+                global.set_constant(true);
+                let ptr = global
+                    .as_pointer_value()
+                    .const_cast(i8_type.ptr_type(AddressSpace::Generic));
+                let len = self.compile_int(s.len() as i32);
+                self.str_type()
+                    .const_named_struct(&[ptr.into(), len.into()])
+                    .into()
             }
-            (Expr::Var(v), Expr::Int(_)) => {
-                if v != "" {
-                    let left = self.compile_stmt(l);
-                    let right = self.compile_stmt(r);
-                    self.compile_int_expr(left, op, right)
-                } else {
-                    return self.compile_stmt(r);
+            (Expr::Array(elems), Type::Array(elem_type, _)) => {
+                let vals: Vec<BasicValueEnum> = elems
+                    .into_iter()
+                    .map(|elem| self.const_value(elem, elem_type))
+                    .collect();
+                match self.llvm_type(elem_type) {
+                    BasicTypeEnum::IntType(int_type) => {
+                        let vals: Vec<IntValue> =
+                            vals.into_iter().map(|val| val.into_int_value()).collect();
+                        int_type.const_array(&vals).into()
+                    }
+                    BasicTypeEnum::FloatType(float_type) => {
+                        let vals: Vec<FloatValue> =
+                            vals.into_iter().map(|val| val.into_float_value()).collect();
+                        float_type.const_array(&vals).into()
+                    }
+                    BasicTypeEnum::StructType(struct_type) => {
+                        let vals: Vec<StructValue> = vals
+                            .into_iter()
+                            .map(|val| val.into_struct_value())
+                            .collect();
+                        struct_type.const_array(&vals).into()
+                    }
+                    BasicTypeEnum::ArrayType(array_type) => {
+                        let vals: Vec<ArrayValue> =
+                            vals.into_iter().map(|val| val.into_array_value()).collect();
+                        array_type.const_array(&vals).into()
+                    }
+                    _ => panic!("Invalid const array!"),
                 }
             }
-            (Expr::Bool(_), Expr::Bool(_)) => {
-                let left = self.compile_stmt(l);
-                let right = self.compile_stmt(r);
-                self.compile_bool_expr(left, op, right)
+            (Expr::Tuple(elems), Type::Tuple(types)) => {
+                let vals: Vec<BasicValueEnum> = elems
+                    .into_iter()
+                    .zip(types)
+                    .map(|(elem, ty)| self.const_value(elem, ty))
+                    .collect();
+                self.context.const_struct(&vals, false).into()
             }
-            (Expr::Var(v), Expr::Bool(_)) => {
-                if v != "" {
-                    let left = self.compile_stmt(l);
-                    let right = self.compile_stmt(r);
-                    self.compile_bool_expr(left, op, right)
-                } else {
-                    return self.compile_stmt(r);
-                }
+            // The fields in the order they are declared in
+            (Expr::StructLit(name, mut fields), _) => {
+                let (struct_type, field_types) = self.structs[&name].clone();
+                let vals: Vec<BasicValueEnum> = field_types
+                    .iter()
+                    .map(|(field, field_type)| {
+                        let index = fields.iter().position(|(other, _)| other == field);
+                        let (_, val) = fields.swap_remove(index.unwrap());
+                        self.const_value(val, field_type)
+                    })
+                    .collect();
+                struct_type.const_named_struct(&vals).into()
             }
-            (_, Expr::BinExpr(_, _, _)) => self.compile_stmt(r),
-            (Expr::FnCall(_, _), Expr::FnCall(_, _)) => {
-                let left_val = self.compile_stmt(l);
-                let right_val = self.compile_stmt(r);
-                self.compile_int_expr(left_val, op, right_val)
+            (expr, _) => self.compile_value(expr),
+        }
+    }
+
+    /// Generic functions are compiled when they are called, see `instantiate`.
+    fn collect_generic_fns(&mut self, ast: &[Expr]) {
+        let mut span = Span::default();
+        for item in ast {
+            if let Expr::Spanned(item_span, _) = item {
+                span = *item_span;
             }
-            (_, Expr::FnCall(_, _)) => {
-                return self.compile_stmt(r);
+            if let Expr::Fn(fn_var, generics, params, ret_type, block) = item.clone().unspanned() {
+                if let (Expr::Var(name, _), false) = (*fn_var, generics.is_empty()) {
+                    let generic_fn = (span, generics, params, ret_type, block);
+                    self.generic_fns.insert(name, generic_fn);
+                }
             }
-            (_, _) => {
-                let left_val = self.compile_stmt(l);
-                let right_val = self.compile_stmt(r);
-                self.compile_int_expr(left_val, op, right_val)
-            } // _ => panic!("Invalid Bin expr!"),
         }
     }
 
-    fn compile_bool_expr(
-        &mut self,
-        l: IntValue<'ctx>,
-        op: Op,
-        r: IntValue<'ctx>,
+    /// Extends `index` to 64 bits and panics unless it is below `len`, negative indices
+    /// become large unsigned values and fail the same check.
+    fn compile_bounds_check(
+        &self,
+        index: IntValue<'ctx>,
+        index_type: &Type,
+        len: usize,
     ) -> IntValue<'ctx> {
-        match op {
-            Op::LogOp(LogOp::And) => self.builder.build_and(l, r, "and"),
-            Op::LogOp(LogOp::Or) => self.builder.build_or(l, r, "or"),
-            Op::RelOp(RelOp::Eq) => self.builder.build_int_compare(IntPredicate::EQ, l, r, "Eq"),
-            Op::RelOp(RelOp::Neq) => self
-                .builder
-                .build_int_compare(IntPredicate::NE, l, r, "Neq"),
-            Op::RelOp(RelOp::Leq) => self
-                .builder
-                .build_int_compare(IntPredicate::SLE, l, r, "Leq"),
-            Op::RelOp(RelOp::Geq) => self
-                .builder
-                .build_int_compare(IntPredicate::SGE, l, r, "Geq"),
-            Op::RelOp(RelOp::Les) => self
-                .builder
-                .build_int_compare(IntPredicate::SGT, l, r, "Les"),
-            Op::RelOp(RelOp::Gre) => self
-                .builder
-                .build_int_compare(IntPredicate::SLT, l, r, "Gre"),
-            _ => panic!("Invalid Bool expr!"),
-        }
+        let i64_type = self.context.i64_type();
+        let index = match index_type.int_info().unwrap() {
+            (64, _) => index,
+            (_, true) => self.builder.build_int_s_extend(index, i64_type, "index"),
+            (_, false) => self.builder.build_int_z_extend(index, i64_type, "index"),
+        };
+        let len = i64_type.const_int(len as u64, false);
+        let out_of_bounds =
+            self.builder
+                .build_int_compare(IntPredicate::UGE, index, len, "out_of_bounds");
+
+        let panic_fn = match self.module.get_function(BOUNDS_PANIC_FN) {
+            Some(function) => function,
+            None => {
+                let i32_type = self.context.i32_type();
+                let fn_type = self.context.void_type().fn_type(
+                    &[
+                        i64_type.into(),
+                        i64_type.into(),
+                        i32_type.into(),
+                        i32_type.into(),
+                    ],
+                    false,
+                );
+                self.module.add_function(BOUNDS_PANIC_FN, fn_type, None)
+            }
+        };
+        self.build_panic_call(out_of_bounds, panic_fn, &[len.into(), index.into()]);
+        index
     }
 
-    fn compile_int_expr(&self, l: IntValue<'ctx>, op: Op, r: IntValue<'ctx>) -> IntValue<'ctx> {
-        match op {
-            Op::AriOp(AriOp::Add) => self.builder.build_int_add(l, r, "add"),
-            Op::AriOp(AriOp::Sub) => self.builder.build_int_sub(l, r, "sub"),
-            Op::AriOp(AriOp::Div) => self.builder.build_int_unsigned_div(l, r, "div"),
-            Op::AriOp(AriOp::Mul) => self.builder.build_int_mul(l, r, "mul"),
-            Op::RelOp(RelOp::Eq) => self.builder.build_int_compare(IntPredicate::EQ, l, r, "Eq"),
-            Op::RelOp(RelOp::Neq) => self
-                .builder
-                .build_int_compare(IntPredicate::NE, l, r, "Neq"),
-            Op::RelOp(RelOp::Leq) => self
-                .builder
-                .build_int_compare(IntPredicate::SLE, l, r, "Leq"),
-            Op::RelOp(RelOp::Geq) => self
-                .builder
-                .build_int_compare(IntPredicate::SGE, l, r, "Geq"),
-            Op::RelOp(RelOp::Les) => self
-                .builder
-                .build_int_compare(IntPredicate::SGT, l, r, "Les"),
-            Op::RelOp(RelOp::Gre) => self
-                .builder
-                .build_int_compare(IntPredicate::SLT, l, r, "Gre"),
-            _ => panic!("Invalid Int expr!"),
+    fn compile_fn_call(&mut self, func_name: Expr, args: Vec<Expr>) -> BasicValueEnum<'ctx> {
+        match self.build_fn_call(func_name, args) {
+            Some(val) => val,
+            None => panic!("Fn call has no value!"),
         }
     }
 
-    fn compile_cond(&mut self, cond: Expr) -> IntValue<'ctx> {
-        match cond.clone() {
-            Expr::Int(_) => self.compile_stmt(cond),
-            Expr::Bool(_) => self.compile_stmt(cond),
-            Expr::Var(_) => self.compile_stmt(cond),
-            Expr::VarExpr(v, op, r) => match *v {
-                Expr::Var(_) => {
-                    let left = self.compile_stmt(*v);
-                    let right = self.compile_stmt(*r);
+    /// Builds a call, functions returning `()` give no value.
+    fn build_fn_call(&mut self, func_name: Expr, args: Vec<Expr>) -> Option<BasicValueEnum<'ctx>> {
+        let (name, id) = match func_name {
+            Expr::Var(v, id) => (v, id),
+            _ => panic!("Invalid Fn Var!"),
+        };
 
-                    self.compile_bool_expr(left, op, right)
-                }
-                Expr::Int(_) => {
-                    let left = self.compile_stmt(*v);
-                    let right = self.compile_stmt(*r);
+        // The length of an array is known statically
+        if let ("len", [arg]) = (name.as_str(), args.as_slice()) {
+            if let Type::Array(_, len) = self.type_of(arg) {
+                self.compile_value(arg.clone());
+                return Some(self.compile_int(len as i32).into());
+            }
+        }
 
-                    self.compile_bool_expr(left, op, right)
-                }
-                Expr::Bool(_) => {
-                    let left = self.compile_stmt(*v);
-                    let right = self.compile_stmt(*r);
+        // Variables holding functions or closures shadow the functions of the program
+        if let Some(Type::Fn(param_types, ret_type)) =
+            id.and_then(|id| self.var_types.get(&id)).cloned()
+        {
+            return self.build_closure_call(&name, id, args, param_types, *ret_type);
+        }
 
-                    self.compile_bool_expr(left, op, right)
-                }
-                _ => panic!("Invalid Var expr comparsion!"),
-            },
+        let name = if self.generic_fns.contains_key(&name) {
+            self.instantiate(&name, &args)
+        } else {
+            name
+        };
+        let function = self.module.get_function(&name).unwrap();
+        let (param_types, ret_type) = self.signatures[&name].clone();
 
-            _ => panic!("Invalid cond stmt!"),
-        }
-    }
+        let argsv: Vec<BasicValueEnum> = args
+            .iter()
+            .zip(param_types)
+            .map(|(a, t)| {
+                let arg = self.compile_value(a.clone());
+                self.to_abi(arg, &t)
+            })
+            .collect();
 
-    fn compile_if(&mut self, cond: Expr, block: Vec<Expr>) -> InstructionValue<'ctx> {
-        let cond = self.compile_cond(cond);
+        // Calls without a value can not be named
+        let call_name = match ret_type {
+            Type::Void => "",
+            _ => &name,
+        };
+        let call = self
+            .builder
+            .build_call(function, &argsv, call_name)
+            .try_as_basic_value()
+            .left()?;
 
-        let then_block = self.context.append_basic_block(self.fn_value(), "then");
-        let cont_block = self.context.append_basic_block(self.fn_value(), "cont");
+        Some(self.to_native(call, &ret_type))
+    }
 
-        self.builder
-            .build_conditional_branch(cond, then_block, cont_block);
-        self.builder.position_at_end(then_block);
-        self.compile_block(block);
-
-        self.builder.build_unconditional_branch(cont_block);
-        self.builder.position_at_end(cont_block);
-
-        let phi = self.builder.build_phi(self.context.i32_type(), "if");
-        phi.add_incoming(&[
-            (&self.compile_int(1), then_block),
-            (&self.compile_int(0), cont_block),
-        ]);
-        phi.as_instruction()
-    }
-
-    fn compile_if_else(
+    /// Methods are static calls of the impl for the type of the receiver, which is
+    /// passed as the first argument.
+    fn build_method_call(
         &mut self,
-        cond: Expr,
-        block1: Vec<Expr>,
-        block2: Vec<Expr>,
-    ) -> InstructionValue<'ctx> {
-        let cond = self.compile_cond(cond);
-
-        let basic_block1 = self.context.append_basic_block(self.fn_value(), "block1");
-        let basic_block2 = self.context.append_basic_block(self.fn_value(), "block2");
-        let cont_block = self.context.append_basic_block(self.fn_value(), "cont");
-
-        self.builder
-            .build_conditional_branch(cond, basic_block1, basic_block2);
-
-        self.builder.position_at_end(basic_block1);
-        self.compile_block(block1);
-        self.builder.build_unconditional_branch(cont_block);
-
-        self.builder.position_at_end(basic_block2);
-        self.compile_block(block2);
-
-        self.builder.build_unconditional_branch(cont_block);
-
-        self.builder.position_at_end(cont_block);
-        let phi = self.builder.build_phi(self.context.i32_type(), "iftmp");
-
-        phi.add_incoming(&[
-            (&self.compile_int(1), basic_block1),
-            (&self.compile_int(0), basic_block2),
-        ]);
+        receiver: Expr,
+        method: &str,
+        args: Vec<Expr>,
+    ) -> Option<BasicValueEnum<'ctx>> {
+        let path = self.type_of(&receiver).method_path(method);
+        let mut args = args;
+        args.insert(0, receiver);
+        self.build_fn_call(Expr::Var(path, None), args)
+    }
 
-        phi.as_instruction()
+    /// Type arguments of a call of a generic function, inferred from the arguments.
+    fn infer_call_type_args(
+        &self,
+        name: &str,
+        args: &[Expr],
+        var_types: &HashMap<DeclId, Type>,
+    ) -> HashMap<String, Type> {
+        let (_, _, params, _, _) = &self.generic_fns[name];
+        let mut type_args = HashMap::new();
+        for ((_, param_type), arg) in params.iter().zip(args) {
+            infer_type_args(param_type, &self.type_in(arg, var_types), &mut type_args);
+        }
+        type_args
     }
 
-    fn compile_while(&mut self, cond: Expr, block: Vec<Expr>) -> InstructionValue<'ctx> {
-        let do_block = self.context.append_basic_block(self.fn_value(), "do");
-        let cont_block = self.context.append_basic_block(self.fn_value(), "cont");
+    /// Compiles the instantiation of a generic function for the types of `args` unless
+    /// it exists already, returns its mangled name such as `max<i32>`.
+    fn instantiate(&mut self, name: &str, args: &[Expr]) -> String {
+        let type_args = self.infer_call_type_args(name, args, &self.var_types);
+        let (span, generics, params, ret_type, block) = self.generic_fns[name].clone();
+        let type_arg_names: Vec<String> = generics
+            .iter()
+            .map(|(generic, _)| type_args[generic].to_string())
+            .collect();
+        let mangled_name = format!("{}<{}>", name, type_arg_names.join(", "));
+        if self.module.get_function(&mangled_name).is_some() {
+            return mangled_name;
+        }
 
-        let cond = self.compile_cond(cond);
+        let params = params
+            .into_iter()
+            .map(|(param, param_type)| (param, param_type.substitute(&type_args)))
+            .collect();
+        let ret_type = ret_type.substitute(&type_args);
+
+        // The caller is compiled further once the instantiation is done
+        let caller = self.save_fn_state();
+        self.type_args = type_args;
+        self.span = span;
+        self.compile_fn(
+            Expr::Var(mangled_name.clone(), None),
+            params,
+            ret_type,
+            block,
+        );
+        self.restore_fn_state(caller);
+        mangled_name
+    }
 
-        self.builder
-            .build_conditional_branch(cond, do_block, cont_block);
-        self.builder.position_at_end(do_block);
-        self.compile_block(block);
+    fn save_fn_state(&mut self) -> FnState<'ctx> {
+        FnState {
+            block: self.builder.get_insert_block().unwrap(),
+            fn_value: self.fn_value_opt,
+            variables: std::mem::take(&mut self.variables),
+            var_types: std::mem::take(&mut self.var_types),
+            type_args: self.type_args.clone(),
+            ret_type: self.ret_type.clone(),
+            span: self.span,
+            subprogram: self.debug.as_ref().and_then(|debug| debug.subprogram),
+        }
+    }
 
-        self.builder
-            .build_conditional_branch(cond, do_block, cont_block);
-        self.builder.position_at_end(cont_block);
+    /// Starts a function with only the globals in scope.
+    fn reset_variables(&mut self) {
+        self.variables.clear();
+        self.var_types.clear();
+        for (id, (ptr, ty)) in self.globals.iter() {
+            self.variables.insert(*id, *ptr);
+            self.var_types.insert(*id, ty.clone());
+        }
+    }
 
-        let phi = self.builder.build_phi(self.context.i32_type(), "while");
-        phi.add_incoming(&[
-            (&self.compile_int(0), do_block),
-            (&self.compile_int(1), do_block),
-        ]);
-        phi.as_instruction()
+    fn restore_fn_state(&mut self, state: FnState<'ctx>) {
+        self.builder.position_at_end(state.block);
+        self.fn_value_opt = state.fn_value;
+        self.variables = state.variables;
+        self.var_types = state.var_types;
+        self.type_args = state.type_args;
+        self.ret_type = state.ret_type;
+        self.span = state.span;
+        if let Some(debug) = self.debug.as_mut() {
+            debug.subprogram = state.subprogram;
+        }
+        self.set_debug_location();
     }
 
-    fn compile_int(&self, int: i32) -> IntValue<'ctx> {
-        self.context.i32_type().const_int(int as u64, false)
+    /// Functions and closures used as values are `{ i8*, i8* }` pairs of a function
+    /// pointer and a pointer to the captured variables. The function takes the latter
+    /// as its first argument.
+    fn closure_type(&self) -> StructType<'ctx> {
+        let ptr_type = self.context.i8_type().ptr_type(AddressSpace::Generic);
+        self.context
+            .struct_type(&[ptr_type.into(), ptr_type.into()], false)
     }
 
-    fn compile_bool(&self, b: bool) -> IntValue<'ctx> {
-        match b {
-            true => self.context.bool_type().const_int(1, false),
-            false => self.context.bool_type().const_int(0, false),
+    /// Type of the function behind a function value.
+    fn closure_fn_type(&self, param_types: &[Type], ret_type: &Type) -> FunctionType<'ctx> {
+        let mut abi_types = vec![self
+            .context
+            .i8_type()
+            .ptr_type(AddressSpace::Generic)
+            .into()];
+        abi_types.extend(
+            param_types
+                .iter()
+                .map(|param_type| self.abi_type(param_type)),
+        );
+        match ret_type {
+            Type::Void => self.context.void_type().fn_type(&abi_types, false),
+            _ => self.abi_type(ret_type).fn_type(&abi_types, false),
         }
     }
 
-    fn compile_block(&mut self, block: Vec<Expr>) -> InstructionValue<'ctx> {
-        let mut last_cmd: Option<InstructionValue> = None;
-
-        for expr in block.iter() {
-            self.statement = self.compile_expr(expr);
+    fn build_closure_value(
+        &self,
+        function: FunctionValue<'ctx>,
+        env: PointerValue<'ctx>,
+    ) -> BasicValueEnum<'ctx> {
+        let ptr_type = self.context.i8_type().ptr_type(AddressSpace::Generic);
+        let fn_ptr = self.builder.build_pointer_cast(
+            function.as_global_value().as_pointer_value(),
+            ptr_type,
+            "fn_ptr",
+        );
+        let closure = self.closure_type().get_undef();
+        let closure = self
+            .builder
+            .build_insert_value(closure, fn_ptr, 0, "closure")
+            .unwrap();
+        self.builder
+            .build_insert_value(closure, env, 1, "closure")
+            .unwrap()
+            .into_struct_value()
+            .into()
+    }
 
-            if self.statement.1 {
-                return self.statement.0;
+    /// A named function used as a value calls it through a wrapper that ignores the
+    /// environment.
+    fn compile_fn_value(&mut self, name: &str) -> BasicValueEnum<'ctx> {
+        let wrapper_name = format!("{}.value", name);
+        let wrapper = match self.module.get_function(&wrapper_name) {
+            Some(wrapper) => wrapper,
+            None => {
+                let function = self.module.get_function(name).unwrap();
+                let (param_types, ret_type) = self.signatures[name].clone();
+                let fn_type = self.closure_fn_type(&param_types, &ret_type);
+                let wrapper = self.module.add_function(&wrapper_name, fn_type, None);
+
+                // A builder of its own does not attach the caller's debug location
+                let builder = self.context.create_builder();
+                builder.position_at_end(self.context.append_basic_block(wrapper, "entry"));
+                let args: Vec<BasicValueEnum> = wrapper.get_params().into_iter().skip(1).collect();
+                let call = builder.build_call(function, &args, "");
+                match call.try_as_basic_value().left() {
+                    Some(val) => builder.build_return(Some(&val)),
+                    None => builder.build_return(None),
+                };
+                wrapper
             }
-            last_cmd = Some(self.statement.0);
-        }
-
-        match last_cmd {
-            Some(instruction) => instruction,
-            None => panic!(),
-        }
+        };
+        let env = self
+            .context
+            .i8_type()
+            .ptr_type(AddressSpace::Generic)
+            .const_null();
+        self.build_closure_value(wrapper, env)
     }
 
-    fn compile_fn(
-        &mut self,
-        fn_var: Expr,
-        params: Vec<(Expr, Type)>,
-        _ret_type: Type,
-        block: Vec<Expr>,
-    ) -> InstructionValue<'ctx> {
-        let param_types: Vec<BasicTypeEnum> = params
-            .iter()
-            .map(|param| match param.1 {
-                Type::Int => self.context.i32_type().into(),
-                Type::Bool => self.context.bool_type().into(),
-                _ => unreachable!(),
+    /// Copies the captured variables to the heap and compiles the body as a function
+    /// reading them from there.
+    fn compile_closure(&mut self, params: Vec<(Expr, Type)>, body: Expr) -> BasicValueEnum<'ctx> {
+        let closure = Expr::Closure(params.clone(), Box::new(body.clone()));
+        let (param_types, ret_type) = match self.type_of(&closure) {
+            Type::Fn(param_types, ret_type) => (param_types, *ret_type),
+            _ => panic!("Invalid closure!"),
+        };
+
+        // The parameters and the variables of the body are not variables of the caller
+        let mut vars = Vec::new();
+        body.referenced_vars(&mut vars);
+        let captures: Vec<(String, DeclId, Type)> = vars
+            .into_iter()
+            .filter(|(_, id)| self.variables.contains_key(id) && !self.globals.contains_key(id))
+            .map(|(name, id)| {
+                let ty = self.var_types[&id].clone();
+                (name, id, ty)
             })
             .collect();
 
-        let fn_type = self.context.i32_type().fn_type(&param_types, false);
-
-        let name = match fn_var {
-            Expr::Var(v) => v,
-            _ => panic!("Invalid fn var!"),
+        let ptr_type = self.context.i8_type().ptr_type(AddressSpace::Generic);
+        let capture_types: Vec<BasicTypeEnum> = captures
+            .iter()
+            .map(|(_, _, ty)| self.llvm_type(ty))
+            .collect();
+        let env_type = self.context.struct_type(&capture_types, false);
+        let env = match captures.is_empty() {
+            true => ptr_type.const_null(),
+            false => {
+                let env = self.builder.build_malloc(env_type, "env").unwrap();
+                for (i, (name, id, _)) in captures.iter().enumerate() {
+                    let val = self.builder.build_load(*self.get_variable(Some(*id)), name);
+                    let field = self.builder.build_struct_gep(env, i as u32, name).unwrap();
+                    self.builder.build_store(field, val);
+                }
+                self.builder.build_pointer_cast(env, ptr_type, "env")
+            }
         };
 
-        let function = self.module.add_function(&name, fn_type, None);
-        let basic_block = self.context.append_basic_block(function, &name);
-
+        let fn_type = self.closure_fn_type(&param_types, &ret_type);
+        let function = self.module.add_function("closure", fn_type, None);
+        let caller = self.save_fn_state();
+        self.reset_variables();
+        let basic_block = self.context.append_basic_block(function, "closure");
         self.fn_value_opt = Some(function);
         self.builder.position_at_end(basic_block);
-
-        match self.fn_args.get(&name) {
-            Some(arg_values) => {
-                let arg_values = arg_values;
-                if params.len() != arg_values.len() {
-                    panic!("params len != args len")
-                }
-                let arg_valuesv = arg_values.clone();
-
-                self.insert_fn_vars(params, arg_valuesv);
+        let params: Vec<(Expr, Type)> = params
+            .into_iter()
+            .zip(param_types)
+            .map(|((param, _), param_type)| (param, param_type))
+            .collect();
+        self.create_subprogram(function, "closure", &params, &ret_type);
+        self.ret_type = ret_type.clone();
+        self.set_debug_location();
+
+        let env_param = function.get_nth_param(0).unwrap().into_pointer_value();
+        let env_fields = self.builder.build_pointer_cast(
+            env_param,
+            env_type.ptr_type(AddressSpace::Generic),
+            "env",
+        );
+        for (i, (name, id, ty)) in captures.iter().enumerate() {
+            let field = self
+                .builder
+                .build_struct_gep(env_fields, i as u32, name)
+                .unwrap();
+            let val = self.builder.build_load(field, name);
+            self.compile_destructure(&Expr::Var(name.to_string(), Some(*id)), ty, val, None);
+        }
+        for (i, (param, param_type)) in params.iter().enumerate() {
+            let arg = function.get_nth_param(i as u32 + 1).unwrap();
+            let val = self.to_native(arg, param_type);
+            self.compile_destructure(param, param_type, val, Some(i as u32 + 1));
+        }
+        match ret_type {
+            Type::Void => {
+                self.compile_expr(&body);
+                self.builder.build_return(None);
+            }
+            _ => {
+                let val = self.compile_value(body);
+                let val = self.to_abi(val, &ret_type);
+                self.builder.build_return(Some(&val));
             }
-            None => (),
         }
+        self.restore_fn_state(caller);
 
-        self.compile_block(block)
+        self.build_closure_value(function, env)
     }
 
-    fn insert_fn_vars(&mut self, params: Vec<(Expr, Type)>, arg_values: Vec<Expr>) {
-        for i in 0..arg_values.len() {
-            match (&params[i], arg_values[i].clone()) {
-                ((Expr::Var(v), Type::Int), Expr::Int(i)) => {
-                    let alloca = self.create_entry_block_alloca(&v, false);
-                    let val = self.compile_int(i);
-                    self.builder.build_store(alloca, val);
+    /// Calls the function value in a variable, passing its environment first.
+    fn build_closure_call(
+        &mut self,
+        name: &str,
+        id: Option<DeclId>,
+        args: Vec<Expr>,
+        param_types: Vec<Type>,
+        ret_type: Type,
+    ) -> Option<BasicValueEnum<'ctx>> {
+        let closure = self
+            .builder
+            .build_load(*self.get_variable(id), name)
+            .into_struct_value();
+        let fn_ptr = self
+            .builder
+            .build_extract_value(closure, 0, "fn_ptr")
+            .unwrap()
+            .into_pointer_value();
+        let env = self.builder.build_extract_value(closure, 1, "env").unwrap();
+        let fn_type = self.closure_fn_type(&param_types, &ret_type);
+        let fn_ptr =
+            self.builder
+                .build_pointer_cast(fn_ptr, fn_type.ptr_type(AddressSpace::Generic), "fn");
+
+        let mut argsv = vec![env];
+        for (arg, param_type) in args.into_iter().zip(param_types) {
+            let arg = self.compile_value(arg);
+            argsv.push(self.to_abi(arg, &param_type));
+        }
+        let call_name = match ret_type {
+            Type::Void => "",
+            _ => name,
+        };
+        let callee = CallableValue::try_from(fn_ptr).unwrap();
+        let call = self
+            .builder
+            .build_call(callee, &argsv, call_name)
+            .try_as_basic_value()
+            .left()?;
 
-                    let ptr_val = self.get_variable(v);
-                    self.builder.build_load(*ptr_val, &v).into_int_value();
-                }
-                ((Expr::Var(v), Type::Bool), Expr::Bool(b)) => {
-                    let alloca = self.create_entry_block_alloca(&v, false);
-                    let val = self.compile_bool(b);
-                    self.builder.build_store(alloca, val);
+        Some(self.to_native(call, &ret_type))
+    }
 
-                    let ptr_val = self.get_variable(v);
-                    self.builder.build_load(*ptr_val, &v).into_int_value();
-                }
-                _ => panic!("Arg and params does not match!"),
+    /// Strings are `{ i8*, i32 }` fat pointers to their bytes and length.
+    fn str_type(&self) -> StructType<'ctx> {
+        let ptr_type = self.context.i8_type().ptr_type(AddressSpace::Generic);
+        self.context
+            .struct_type(&[ptr_type.into(), self.context.i32_type().into()], false)
+    }
+
+    /// Type of values of `ty` inside a function.
+    fn llvm_type(&self, ty: &Type) -> BasicTypeEnum<'ctx> {
+        match ty {
+            Type::Int => self.context.i32_type().into(),
+            Type::Float => self.context.f64_type().into(),
+            Type::Bool => self.context.bool_type().into(),
+            Type::Str => self.str_type().into(),
+            Type::Array(elem_type, len) => self.llvm_type(elem_type).array_type(*len as u32).into(),
+            Type::Named(name) => match self.structs.get(name) {
+                Some((struct_type, _)) => (*struct_type).into(),
+                None => self.enums[name].0.into(),
+            },
+            Type::Tuple(types) => {
+                let types: Vec<BasicTypeEnum> = types.iter().map(|ty| self.llvm_type(ty)).collect();
+                self.context.struct_type(&types, false).into()
+            }
+            Type::Fn(..) => self.closure_type().into(),
+            Type::Void | Type::Never => panic!("Invalid llvm type!"),
+            int_type => {
+                let (bits, _) = int_type.int_info().unwrap();
+                self.context.custom_width_int_type(bits).into()
             }
         }
     }
-}
 
-pub fn llvm(ast: Vec<Expr>) -> Result<(), Box<dyn Error>> {
-    let context = Context::create();
-    let module = context.create_module("llvm-program");
-    let builder = context.create_builder();
-    let execution_engine = module
-        .create_jit_execution_engine(OptimizationLevel::None)
-        .unwrap();
+    /// Type of `ty` in function signatures, bools are passed as `i8` like in C.
+    fn abi_type(&self, ty: &Type) -> BasicTypeEnum<'ctx> {
+        match ty {
+            Type::Bool => self.context.i8_type().into(),
+            _ => self.llvm_type(ty),
+        }
+    }
 
-    let mut compiler = Compiler {
-        context: &context,
-        builder: &builder,
-        module: &module,
-        execution_engine: &execution_engine,
-        fn_value_opt: None,
-        variables: HashMap::new(),
-        fn_args: HashMap::new(),
+    /// Widens `i1` bools to their ABI type when they leave a function.
+    fn to_abi(&self, val: BasicValueEnum<'ctx>, ty: &Type) -> BasicValueEnum<'ctx> {
+        match ty {
+            Type::Bool => self
+                .builder
+                .build_int_z_extend(val.into_int_value(), self.context.i8_type(), "abi")
+                .into(),
+            _ => val,
+        }
+    }
 
-        statement: (builder.build_return(None), false),
-    };
+    /// Truncates ABI bools back to `i1` when they enter a function.
+    fn to_native(&self, val: BasicValueEnum<'ctx>, ty: &Type) -> BasicValueEnum<'ctx> {
+        match ty {
+            Type::Bool => self
+                .builder
+                .build_int_truncate(val.into_int_value(), self.context.bool_type(), "bool")
+                .into(),
+            _ => val,
+        }
+    }
 
-    for expr in ast.clone() {
-        match expr {
-            Expr::Fn(_, _, _, block) => {
-                for i in block {
-                    match i {
-                        Expr::Return(r) => match *r {
-                            Expr::FnCall(v, a) => match *v {
-                                Expr::Var(s) => {
-                                    let fn_name = s;
-                                    let mut fn_args = Vec::new();
-                                    for j in a {
-                                        fn_args.push(j);
-                                    }
-                                    compiler.fn_args.insert(fn_name.to_string(), fn_args);
-                                }
-                                _ => continue,
-                            },
-                            _ => continue,
-                        },
-                        Expr::Let(_, _, e) => match *e {
-                            Expr::BinExpr(_, _, f) => match *f {
-                                Expr::FnCall(v, a) => match *v {
-                                    Expr::Var(s) => {
-                                        let fn_name = s;
-                                        let mut fn_args = Vec::new();
-                                        for j in a {
-                                            fn_args.push(j);
-                                        }
-                                        compiler.fn_args.insert(fn_name.to_string(), fn_args);
-                                    }
-                                    _ => continue,
-                                },
-                                _ => continue,
-                            },
-
-                            _ => continue,
-                        },
-                        _ => continue,
-                    }
-                }
+    fn compile_let(
+        &mut self,
+        var: Expr,
+        var_type: Option<Type>,
+        expr: Expr,
+    ) -> (InstructionValue<'ctx>, bool) {
+        // A value that never completes, like `{ return 1 }`, leaves nothing to bind
+        if self.type_of(&expr) == Type::Never {
+            return self.compile_expr(&let_value(expr));
+        }
+        // The type checker fills in the types of `let`s, only unchecked programs and the
+        // bodies of generic functions lack them
+        let var_type = match var_type {
+            Some(var_type) => var_type.substitute(&self.type_args),
+            None => self.type_of(&expr),
+        };
+        match var {
+            Expr::Var(left, id) => {
+                let ptr_val = self.create_entry_block_alloca(&left, id, var_type.clone());
+                self.declare_variable(&left, &var_type, ptr_val, None);
+                let val = self.compile_value(expr);
+                (self.builder.build_store(ptr_val, val), false)
+            }
+            binding @ Expr::Tuple(_) => {
+                let val = self.compile_value(expr);
+                (
+                    self.compile_destructure(&binding, &var_type, val, None),
+                    false,
+                )
             }
-            _ => continue,
+            _ => panic!("Invalid Expr!"),
         }
     }
 
-    for expr in ast {
-        match expr {
-            Expr::Fn(n, p, t, b) => {
-                compiler.compile_fn(*n, p, t, b);
+    fn compile_declare(&mut self, var: Expr, var_type: Option<Type>) {
+        let var_type = match var_type {
+            Some(var_type) => var_type.substitute(&self.type_args),
+            None => panic!("Declared var without a type!"),
+        };
+        match var {
+            Expr::Var(name, id) => {
+                let alloca = self.create_entry_block_alloca(&name, id, var_type.clone());
+                self.declare_variable(&name, &var_type, alloca, None);
             }
-            _ => continue,
+            _ => panic!("Invalid Expr!"),
         }
     }
 
-    compiler.module.print_to_stderr();
-    let compiled_program: JitFunction<ExprFunc> =
-        unsafe { compiler.execution_engine.get_function("main").ok().unwrap() };
-
-    unsafe {
-        println!("llvm-result: {} ", compiled_program.call());
+    /// Stores `val` in new variables, tuples are destructured into one variable per name.
+    fn compile_destructure(
+        &mut self,
+        binding: &Expr,
+        ty: &Type,
+        val: BasicValueEnum<'ctx>,
+        arg_no: Option<u32>,
+    ) -> InstructionValue<'ctx> {
+        match (binding, ty) {
+            (Expr::Var(name, id), _) => {
+                let alloca = self.create_entry_block_alloca(name, *id, ty.clone());
+                self.declare_variable(name, ty, alloca, arg_no);
+                self.builder.build_store(alloca, val)
+            }
+            (Expr::Tuple(bindings), Type::Tuple(types)) => {
+                let mut last_store = None;
+                for (i, (binding, ty)) in bindings.iter().zip(types).enumerate() {
+                    let elem = self
+                        .builder
+                        .build_extract_value(val.into_struct_value(), i as u32, "elem")
+                        .unwrap();
+                    last_store = Some(self.compile_destructure(binding, ty, elem, None));
+                }
+                last_store.unwrap()
+            }
+            _ => panic!("Invalid destructuring!"),
+        }
     }
 
-    Ok(())
-}
-
-#[cfg(test)]
-mod parse_tests {
-    use super::*;
-    use crate::parser::*;
-    use crate::type_checker::*;
+    fn compile_var_expr(&mut self, var: Expr, op: Op, expr: Expr) -> InstructionValue<'ctx> {
+        let var_type = self.type_of(&var);
+        let val = self.compile_value(expr);
+        let var_ptr = self.compile_place(var);
+        if op == Op::AssOp(AssOp::Eq) {
+            return self.builder.build_store(var_ptr, val);
+        }
+        let old_val = self.builder.build_load(var_ptr, "old");
+
+        let new_val: BasicValueEnum = match op {
+            Op::AssOp(AssOp::AddEq) if old_val.is_struct_value() => self
+                .compile_str_concat(old_val.into_struct_value(), val.into_struct_value())
+                .into(),
+            _ if old_val.is_float_value() => {
+                let (old_val, val) = (old_val.into_float_value(), val.into_float_value());
+                self.compile_float_expr(old_val, Op::AriOp(ass_ari_op(op)), val)
+            }
+            _ => {
+                let (old_val, val) = (old_val.into_int_value(), val.into_int_value());
+                self.compile_ari(old_val, ass_ari_op(op), val, &var_type)
+                    .into()
+            }
+        };
 
-    #[test]
-    fn test_llvm_return() {
-        let p = parser("fn main() -> i32 { return 1 }").unwrap().1;
-        let t = type_checker(p.clone());
+        self.builder.build_store(var_ptr, new_val)
+    }
 
-        if t {
-            assert!(llvm(p).is_ok());
+    fn compile_bin_expr(&mut self, l: Expr, op: Op, r: Expr) -> BasicValueEnum<'ctx> {
+        // `let a: i32 = 1` is parsed as `Var("") = 1`, only the right side carries a value
+        match &l {
+            Expr::Var(v, _) if v == "" => return self.compile_value(r),
+            _ => (),
         }
 
-        let p = parser("fn main() -> i32 { return 1 + 1 }").unwrap().1;
-        let t = type_checker(p.clone());
+        let ty = self.type_of(&l);
+        let left = self.compile_value(l);
+        let right = self.compile_value(r);
 
-        if t {
-            assert!(llvm(p).is_ok());
+        if left.is_struct_value() {
+            return self.compile_str_expr(left.into_struct_value(), op, right.into_struct_value());
+        }
+        if left.is_float_value() {
+            return self.compile_float_expr(left.into_float_value(), op, right.into_float_value());
         }
 
-        let p = parser("fn main() -> bool { return true }").unwrap().1;
-        let t = type_checker(p.clone());
-
-        if t {
-            assert!(llvm(p).is_ok());
+        let (left, right) = (left.into_int_value(), right.into_int_value());
+        if left.get_type().get_bit_width() == 1 {
+            self.compile_bool_expr(left, op, right).into()
+        } else {
+            self.compile_int_expr(left, op, right, &ty).into()
         }
     }
 
-    #[test]
-    fn test_llvm_let_bin_expr_int() {
-        let p = parser("fn main() -> i32 { let a: i32 = 1; return a }")
-            .unwrap()
-            .1;
-        let t = type_checker(p.clone());
-
-        if t {
-            assert!(llvm(p).is_ok());
+    /// Integer casts truncate or extend by the signedness of the source type, like Rust.
+    fn compile_cast(&mut self, expr: Expr, to_type: Type) -> BasicValueEnum<'ctx> {
+        let from_type = self.type_of(&expr);
+        let from_signed = match from_type.int_info() {
+            Some((_, signed)) => signed,
+            None => false,
+        };
+        let val = self.compile_value(expr);
+
+        match (&from_type, &to_type) {
+            (Type::Float, Type::Float) => return val,
+            (Type::Float, _) => {
+                return self
+                    .compile_float_to_int(val.into_float_value(), &to_type)
+                    .into()
+            }
+            (_, Type::Float) => {
+                let f64_type = self.context.f64_type();
+                let val = val.into_int_value();
+                return match from_signed {
+                    true => self
+                        .builder
+                        .build_signed_int_to_float(val, f64_type, "cast"),
+                    false => self
+                        .builder
+                        .build_unsigned_int_to_float(val, f64_type, "cast"),
+                }
+                .into();
+            }
+            _ => (),
         }
 
-        let p = parser("fn main() -> bool { let a: bool = true; return a }")
-            .unwrap()
-            .1;
-        let t = type_checker(p.clone());
-
-        if t {
-            assert!(llvm(p).is_ok());
-        }
+        let val = val.into_int_value();
+        let int_type = self.llvm_type(&to_type).into_int_type();
+        let from_bits = val.get_type().get_bit_width();
+        let to_bits = int_type.get_bit_width();
+        let res = if from_bits > to_bits {
+            self.builder.build_int_truncate(val, int_type, "cast")
+        } else if from_bits == to_bits {
+            val
+        } else if from_signed {
+            self.builder.build_int_s_extend(val, int_type, "cast")
+        } else {
+            self.builder.build_int_z_extend(val, int_type, "cast")
+        };
+        res.into()
+    }
 
-        let p = parser("fn main() -> i32 { let a: i32 = 1 + 1; return a }")
-            .unwrap()
-            .1;
-        let t = type_checker(p.clone());
+    /// Float to integer casts saturate at the bounds of the type and map NaN to 0 as in
+    /// Rust, `fptosi` alone gives poison for values out of range.
+    fn compile_float_to_int(&self, val: FloatValue<'ctx>, to_type: &Type) -> IntValue<'ctx> {
+        let (min, max) = to_type.int_range();
+        let (_, signed) = to_type.int_info().unwrap();
+        let int_type = self.llvm_type(to_type).into_int_type();
+        let f64_type = self.context.f64_type();
 
-        if t {
-            assert!(llvm(p).is_ok());
-        }
+        let conv = match signed {
+            true => self
+                .builder
+                .build_float_to_signed_int(val, int_type, "conv"),
+            false => self
+                .builder
+                .build_float_to_unsigned_int(val, int_type, "conv"),
+        };
+        let below = self.builder.build_float_compare(
+            FloatPredicate::OLE,
+            val,
+            f64_type.const_float((min - 1) as f64),
+            "below",
+        );
+        let above = self.builder.build_float_compare(
+            FloatPredicate::OGE,
+            val,
+            f64_type.const_float((max + 1) as f64),
+            "above",
+        );
+        let is_nan = self
+            .builder
+            .build_float_compare(FloatPredicate::UNO, val, val, "is_nan");
 
-        let p = parser("fn main() -> i32 { let a: i32 = 1 - 1; return a }")
-            .unwrap()
-            .1;
-        let t = type_checker(p.clone());
+        let min = int_type.const_int(min as u64, true);
+        let max = int_type.const_int(max as u64, false);
+        let res = self.builder.build_select(below, min, conv, "sat");
+        let res = self
+            .builder
+            .build_select(above, max, res.into_int_value(), "sat");
+        self.builder
+            .build_select(
+                is_nan,
+                int_type.const_int(0, false),
+                res.into_int_value(),
+                "sat",
+            )
+            .into_int_value()
+    }
 
-        if t {
-            assert!(llvm(p).is_ok());
+    /// IEEE 754 arithmetic, `!=` is the unordered compare so that NaN != NaN holds.
+    fn compile_float_expr(
+        &self,
+        l: FloatValue<'ctx>,
+        op: Op,
+        r: FloatValue<'ctx>,
+    ) -> BasicValueEnum<'ctx> {
+        let compare = |predicate, name| self.builder.build_float_compare(predicate, l, r, name);
+        match op {
+            Op::AriOp(AriOp::Add) => self.builder.build_float_add(l, r, "add").into(),
+            Op::AriOp(AriOp::Sub) => self.builder.build_float_sub(l, r, "sub").into(),
+            Op::AriOp(AriOp::Mul) => self.builder.build_float_mul(l, r, "mul").into(),
+            Op::AriOp(AriOp::Div) => self.builder.build_float_div(l, r, "div").into(),
+            Op::AriOp(AriOp::Rem) => self.builder.build_float_rem(l, r, "rem").into(),
+            Op::RelOp(RelOp::Eq) => compare(FloatPredicate::OEQ, "Eq").into(),
+            Op::RelOp(RelOp::Neq) => compare(FloatPredicate::UNE, "Neq").into(),
+            Op::RelOp(RelOp::Leq) => compare(FloatPredicate::OLE, "Leq").into(),
+            Op::RelOp(RelOp::Geq) => compare(FloatPredicate::OGE, "Geq").into(),
+            Op::RelOp(RelOp::Les) => compare(FloatPredicate::OLT, "Les").into(),
+            Op::RelOp(RelOp::Gre) => compare(FloatPredicate::OGT, "Gre").into(),
+            _ => panic!("Invalid Float expr!"),
         }
+    }
 
-        let p = parser("fn main() -> i32 { let a: i32 = 1 / 1; return a }")
-            .unwrap()
-            .1;
-        let t = type_checker(p.clone());
+    /// String literals are global constants, the fat pointer refers to their bytes.
+    fn compile_str(&self, s: &str) -> StructValue<'ctx> {
+        let global = self.builder.build_global_string_ptr(s, "str");
+        let len = self.compile_int(s.len() as i32);
+        self.str_type()
+            .const_named_struct(&[global.as_pointer_value().into(), len.into()])
+    }
 
-        if t {
-            assert!(llvm(p).is_ok());
+    fn compile_str_expr(
+        &mut self,
+        l: StructValue<'ctx>,
+        op: Op,
+        r: StructValue<'ctx>,
+    ) -> BasicValueEnum<'ctx> {
+        match op {
+            Op::AriOp(AriOp::Add) => self.compile_str_concat(l, r).into(),
+            Op::RelOp(RelOp::Eq) => self.compile_str_eq(l, r).into(),
+            Op::RelOp(RelOp::Neq) => {
+                let eq = self.compile_str_eq(l, r);
+                self.builder.build_not(eq, "Neq").into()
+            }
+            _ => panic!("Invalid Str expr!"),
         }
+    }
 
-        let p = parser("fn main() -> i32 { let a: i32 = 1 * 1; return a }")
-            .unwrap()
-            .1;
-        let t = type_checker(p.clone());
-
-        if t {
-            assert!(llvm(p).is_ok());
+    /// Declares a function of the runtime that compiled code calls on its own.
+    fn runtime_fn(
+        &self,
+        name: &str,
+        param_types: &[BasicTypeEnum<'ctx>],
+        ret_type: BasicTypeEnum<'ctx>,
+    ) -> FunctionValue<'ctx> {
+        match self.module.get_function(name) {
+            Some(function) => function,
+            None => self.module.add_function(
+                name,
+                ret_type.fn_type(param_types, false),
+                Some(Linkage::External),
+            ),
         }
+    }
 
-        let p = parser("fn main() -> bool { let a: bool = 1 == 1; return a }")
+    /// The new string is allocated by the runtime and never freed.
+    fn compile_str_concat(&self, l: StructValue<'ctx>, r: StructValue<'ctx>) -> StructValue<'ctx> {
+        let str_type = self.str_type().into();
+        let function = self.runtime_fn(STR_CONCAT_FN, &[str_type, str_type], str_type);
+        self.builder
+            .build_call(function, &[l.into(), r.into()], "concat")
+            .try_as_basic_value()
+            .left()
             .unwrap()
-            .1;
-        let t = type_checker(p.clone());
+            .into_struct_value()
+    }
 
-        if t {
-            assert!(llvm(p).is_ok());
+    fn compile_str_eq(&self, l: StructValue<'ctx>, r: StructValue<'ctx>) -> IntValue<'ctx> {
+        let str_type = self.str_type().into();
+        let function = self.runtime_fn(
+            STR_EQ_FN,
+            &[str_type, str_type],
+            self.context.i8_type().into(),
+        );
+        let eq = self
+            .builder
+            .build_call(function, &[l.into(), r.into()], "eq")
+            .try_as_basic_value()
+            .left()
+            .unwrap();
+        self.to_native(eq, &Type::Bool).into_int_value()
+    }
+
+    /// Bools are `i1` where `true` is all ones, so ordering must use unsigned predicates
+    /// to get `false < true` as in Rust.
+    fn compile_bool_expr(
+        &mut self,
+        l: IntValue<'ctx>,
+        op: Op,
+        r: IntValue<'ctx>,
+    ) -> IntValue<'ctx> {
+        match op {
+            Op::LogOp(LogOp::And) => self.builder.build_and(l, r, "and"),
+            Op::LogOp(LogOp::Or) => self.builder.build_or(l, r, "or"),
+            Op::RelOp(RelOp::Eq) => self.builder.build_int_compare(IntPredicate::EQ, l, r, "Eq"),
+            Op::RelOp(RelOp::Neq) => self
+                .builder
+                .build_int_compare(IntPredicate::NE, l, r, "Neq"),
+            Op::RelOp(RelOp::Leq) => self
+                .builder
+                .build_int_compare(IntPredicate::ULE, l, r, "Leq"),
+            Op::RelOp(RelOp::Geq) => self
+                .builder
+                .build_int_compare(IntPredicate::UGE, l, r, "Geq"),
+            Op::RelOp(RelOp::Les) => self
+                .builder
+                .build_int_compare(IntPredicate::ULT, l, r, "Les"),
+            Op::RelOp(RelOp::Gre) => self
+                .builder
+                .build_int_compare(IntPredicate::UGT, l, r, "Gre"),
+            _ => panic!("Invalid Bool expr!"),
         }
+    }
 
-        let p = parser("fn main() -> bool { let a: bool = 1 != 1; return a }")
-            .unwrap()
-            .1;
-        let t = type_checker(p.clone());
+    /// Follows Rust semantics for the integer type `ty`, division and remainder
+    /// truncate towards zero.
+    fn compile_int_expr(
+        &self,
+        l: IntValue<'ctx>,
+        op: Op,
+        r: IntValue<'ctx>,
+        ty: &Type,
+    ) -> IntValue<'ctx> {
+        let (_, signed) = ty.int_info().unwrap();
+        let predicate = |signed_pred, unsigned_pred| match signed {
+            true => signed_pred,
+            false => unsigned_pred,
+        };
 
-        if t {
-            assert!(llvm(p).is_ok());
+        match op {
+            Op::AriOp(op) => self.compile_ari(l, op, r, ty),
+            Op::RelOp(RelOp::Eq) => self.builder.build_int_compare(IntPredicate::EQ, l, r, "Eq"),
+            Op::RelOp(RelOp::Neq) => self
+                .builder
+                .build_int_compare(IntPredicate::NE, l, r, "Neq"),
+            Op::RelOp(RelOp::Leq) => self.builder.build_int_compare(
+                predicate(IntPredicate::SLE, IntPredicate::ULE),
+                l,
+                r,
+                "Leq",
+            ),
+            Op::RelOp(RelOp::Geq) => self.builder.build_int_compare(
+                predicate(IntPredicate::SGE, IntPredicate::UGE),
+                l,
+                r,
+                "Geq",
+            ),
+            Op::RelOp(RelOp::Les) => self.builder.build_int_compare(
+                predicate(IntPredicate::SLT, IntPredicate::ULT),
+                l,
+                r,
+                "Les",
+            ),
+            Op::RelOp(RelOp::Gre) => self.builder.build_int_compare(
+                predicate(IntPredicate::SGT, IntPredicate::UGT),
+                l,
+                r,
+                "Gre",
+            ),
+            _ => panic!("Invalid Int expr!"),
         }
+    }
 
-        let p = parser("fn main() -> bool { let a: bool = 1 <= 3; return a }")
-            .unwrap()
-            .1;
-        let t = type_checker(p.clone());
+    /// Integer arithmetic is checked and calls `lang_panic` on overflow, unless wrapping
+    /// arithmetic was asked for. Division by zero is always checked.
+    fn compile_ari(
+        &self,
+        l: IntValue<'ctx>,
+        op: AriOp,
+        r: IntValue<'ctx>,
+        ty: &Type,
+    ) -> IntValue<'ctx> {
+        let (bits, signed) = ty.int_info().unwrap();
+        let int_type = l.get_type();
+        let zero = int_type.const_int(0, false);
+        let neg_one = int_type.const_int(-1i64 as u64, true);
 
-        if t {
-            assert!(llvm(p).is_ok());
+        match op {
+            AriOp::Div | AriOp::Rem => {
+                let is_zero = self
+                    .builder
+                    .build_int_compare(IntPredicate::EQ, r, zero, "is_zero");
+                let msg = match op {
+                    AriOp::Div => "attempt to divide by zero",
+                    _ => "attempt to calculate the remainder with a divisor of zero",
+                };
+                self.build_check(is_zero, msg);
+            }
+            _ => (),
         }
 
-        let p = parser("fn main() -> bool { let a: bool = 4 >= 3; return a }")
-            .unwrap()
-            .1;
-        let t = type_checker(p.clone());
-
-        if t {
-            assert!(llvm(p).is_ok());
+        // Unsigned division can not overflow
+        if !signed {
+            match op {
+                AriOp::Div => return self.builder.build_int_unsigned_div(l, r, "div"),
+                AriOp::Rem => return self.builder.build_int_unsigned_rem(l, r, "rem"),
+                _ => (),
+            }
         }
 
-        let p = parser("fn main() -> bool { let a: bool = 1 < 3; return a }")
-            .unwrap()
-            .1;
-        let t = type_checker(p.clone());
+        if self.wrapping {
+            return match op {
+                AriOp::Add => self.builder.build_int_add(l, r, "add"),
+                AriOp::Sub => self.builder.build_int_sub(l, r, "sub"),
+                AriOp::Mul => self.builder.build_int_mul(l, r, "mul"),
+                AriOp::Div | AriOp::Rem => {
+                    let is_neg_one =
+                        self.builder
+                            .build_int_compare(IntPredicate::EQ, r, neg_one, "is_neg_one");
+                    // `MIN / -1` is undefined in LLVM, so -1 is special cased as in `wrapping_div`
+                    let one = int_type.const_int(1, false);
+                    let divisor = self
+                        .builder
+                        .build_select(is_neg_one, one, r, "divisor")
+                        .into_int_value();
+
+                    match op {
+                        AriOp::Div => {
+                            let quot = self.builder.build_int_signed_div(l, divisor, "div");
+                            let neg = self.builder.build_int_sub(zero, l, "neg");
+                            self.builder
+                                .build_select(is_neg_one, neg, quot, "div")
+                                .into_int_value()
+                        }
+                        _ => {
+                            let rem = self.builder.build_int_signed_rem(l, divisor, "rem");
+                            self.builder
+                                .build_select(is_neg_one, zero, rem, "rem")
+                                .into_int_value()
+                        }
+                    }
+                }
+            };
+        }
 
-        if t {
-            assert!(llvm(p).is_ok());
+        let prefix = if signed { "s" } else { "u" };
+        let intrinsic = |name: &str| format!("llvm.{}{}.with.overflow.i{}", prefix, name, bits);
+        match op {
+            AriOp::Add => {
+                self.compile_checked_ari(&intrinsic("add"), l, r, "attempt to add with overflow")
+            }
+            AriOp::Sub => self.compile_checked_ari(
+                &intrinsic("sub"),
+                l,
+                r,
+                "attempt to subtract with overflow",
+            ),
+            AriOp::Mul => self.compile_checked_ari(
+                &intrinsic("mul"),
+                l,
+                r,
+                "attempt to multiply with overflow",
+            ),
+            AriOp::Div | AriOp::Rem => {
+                let is_neg_one =
+                    self.builder
+                        .build_int_compare(IntPredicate::EQ, r, neg_one, "is_neg_one");
+                let min = int_type.const_int(ty.int_range().0 as u64, true);
+                let is_min = self
+                    .builder
+                    .build_int_compare(IntPredicate::EQ, l, min, "is_min");
+                let overflow = self.builder.build_and(is_min, is_neg_one, "overflow");
+
+                match op {
+                    AriOp::Div => {
+                        self.build_check(overflow, "attempt to divide with overflow");
+                        self.builder.build_int_signed_div(l, r, "div")
+                    }
+                    _ => {
+                        self.build_check(
+                            overflow,
+                            "attempt to calculate the remainder with overflow",
+                        );
+                        self.builder.build_int_signed_rem(l, r, "rem")
+                    }
+                }
+            }
         }
+    }
 
-        let p = parser("fn main() -> bool { let a: bool = 4 > 3; return a }")
+    /// Calls one of the `llvm.*.with.overflow.iN` intrinsics and checks its overflow bit.
+    fn compile_checked_ari(
+        &self,
+        intrinsic: &str,
+        l: IntValue<'ctx>,
+        r: IntValue<'ctx>,
+        msg: &str,
+    ) -> IntValue<'ctx> {
+        let function = match self.module.get_function(intrinsic) {
+            Some(function) => function,
+            None => {
+                let int_type = l.get_type();
+                let ret_type = self
+                    .context
+                    .struct_type(&[int_type.into(), self.context.bool_type().into()], false);
+                let fn_type = ret_type.fn_type(&[int_type.into(), int_type.into()], false);
+                self.module.add_function(intrinsic, fn_type, None)
+            }
+        };
+
+        let res = self
+            .builder
+            .build_call(function, &[l.into(), r.into()], "checked")
+            .try_as_basic_value()
+            .left()
             .unwrap()
-            .1;
-        let t = type_checker(p.clone());
+            .into_struct_value();
+        let val = self
+            .builder
+            .build_extract_value(res, 0, "val")
+            .unwrap()
+            .into_int_value();
+        let overflow = self
+            .builder
+            .build_extract_value(res, 1, "overflow")
+            .unwrap()
+            .into_int_value();
 
-        if t {
-            assert!(llvm(p).is_ok());
-        }
+        self.build_check(overflow, msg);
+        val
+    }
 
-        let p = parser("fn main() -> i32 { let a: i32 = 1; let b: i32 = a + 2; return b }")
-            .unwrap()
-            .1;
-        let t = type_checker(p.clone());
+    /// Branches to a call of `lang_panic` with the current span when `cond` holds.
+    fn build_check(&self, cond: IntValue<'ctx>, msg: &str) {
+        let panic_fn = match self.module.get_function(PANIC_FN) {
+            Some(function) => function,
+            None => {
+                let i32_type = self.context.i32_type();
+                let str_type = self.context.i8_type().ptr_type(AddressSpace::Generic);
+                let fn_type = self
+                    .context
+                    .void_type()
+                    .fn_type(&[str_type.into(), i32_type.into(), i32_type.into()], false);
+                self.module.add_function(PANIC_FN, fn_type, None)
+            }
+        };
+        let msg = self.builder.build_global_string_ptr(msg, "panic_msg");
+        self.build_panic_call(cond, panic_fn, &[msg.as_pointer_value().into()]);
+    }
 
-        if t {
-            assert!(llvm(p).is_ok());
+    /// Branches to a call of `panic_fn` with `args` and the current span when `cond` holds.
+    fn build_panic_call(
+        &self,
+        cond: IntValue<'ctx>,
+        panic_fn: FunctionValue<'ctx>,
+        args: &[BasicValueEnum<'ctx>],
+    ) {
+        let panic_block = self.context.append_basic_block(self.fn_value(), "panic");
+        let cont_block = self.context.append_basic_block(self.fn_value(), "cont");
+        self.builder
+            .build_conditional_branch(cond, panic_block, cont_block);
+
+        self.builder.position_at_end(panic_block);
+        let mut args = args.to_vec();
+        args.push(self.compile_int(self.span.line as i32).into());
+        args.push(self.compile_int(self.span.column as i32).into());
+        self.builder.build_call(panic_fn, &args, "");
+        self.builder.build_unreachable();
+
+        self.builder.position_at_end(cont_block);
+    }
+
+    /// Resolves the runtime functions and `extern fn`s used by the module to their host
+    /// implementations. Calling a function that has neither a body nor a host
+    /// implementation would crash, so such a program is rejected.
+    fn link_runtime(&self) -> Result<(), String> {
+        if let Some(function) = self.module.get_function(PANIC_FN) {
+            self.execution_engine.add_global_mapping(
+                &function,
+                lang_panic as extern "C" fn(*const c_char, i32, i32) as usize,
+            );
+        }
+        if let Some(function) = self.module.get_function(BOUNDS_PANIC_FN) {
+            self.execution_engine.add_global_mapping(
+                &function,
+                lang_bounds_panic as extern "C" fn(i64, i64, i32, i32) as usize,
+            );
+        }
+        let symbols: HashMap<&str, usize> = runtime_symbols()
+            .into_iter()
+            .chain(host_symbols())
+            .collect();
+        for (name, address) in symbols.iter() {
+            if let Some(function) = self.module.get_function(name) {
+                self.execution_engine
+                    .add_global_mapping(&function, *address);
+            }
+        }
+        let mut names: Vec<&String> = self.signatures.keys().collect();
+        names.sort();
+        for name in names {
+            let declared_only = match self.module.get_function(name) {
+                Some(function) => function.get_first_basic_block().is_none(),
+                None => false,
+            };
+            if declared_only && !symbols.contains_key(name.as_str()) {
+                return Err(format!("link error: unresolved extern fn `{}`", name));
+            }
         }
+        Ok(())
     }
 
-    #[test]
-    fn test_llvm_let_bin_expr_bool() {
-        let p = parser("fn main() -> bool { let a: bool = true && true; return a }")
-            .unwrap()
-            .1;
-        let t = type_checker(p.clone());
+    fn compile_cond(&mut self, cond: Expr) -> IntValue<'ctx> {
+        match cond.clone() {
+            Expr::Int(_) => self.compile_stmt(cond),
+            Expr::Bool(_) => self.compile_stmt(cond),
+            Expr::Var(_, _) => self.compile_stmt(cond),
+            Expr::VarExpr(v, op, r) => match *v {
+                Expr::Var(_, _)
+                | Expr::Int(_)
+                | Expr::TypedInt(..)
+                | Expr::Float(_)
+                | Expr::Bool(_)
+                | Expr::Str(_)
+                | Expr::Index(..)
+                | Expr::Field(..)
+                | Expr::TupleField(..)
+                | Expr::Cast(..) => self.compile_bin_expr(*v, op, *r).into_int_value(),
+                _ => panic!("Invalid Var expr comparsion!"),
+            },
 
-        if t {
-            assert!(llvm(p).is_ok());
+            _ => panic!("Invalid cond stmt!"),
         }
+    }
 
-        let p = parser("fn main() -> bool { let a: bool = true == true; return a }")
-            .unwrap()
-            .1;
-        let t = type_checker(p.clone());
+    fn compile_if(&mut self, cond: Expr, block: Vec<Expr>) -> InstructionValue<'ctx> {
+        let cond = self.compile_cond(cond);
 
-        if t {
-            assert!(llvm(p).is_ok());
-        }
+        let then_block = self.context.append_basic_block(self.fn_value(), "then");
+        let cont_block = self.context.append_basic_block(self.fn_value(), "cont");
 
-        let p = parser("fn main() -> bool { let a: bool = true != false; return a }")
-            .unwrap()
-            .1;
-        let t = type_checker(p.clone());
+        let branch = self
+            .builder
+            .build_conditional_branch(cond, then_block, cont_block);
+        self.builder.position_at_end(then_block);
+        self.compile_block(block);
 
-        if t {
-            assert!(llvm(p).is_ok());
+        self.branch_to(cont_block, None, &mut Vec::new());
+        self.builder.position_at_end(cont_block);
+        branch
+    }
+
+    fn compile_if_else(
+        &mut self,
+        cond: Expr,
+        block1: Vec<Expr>,
+        block2: Vec<Expr>,
+    ) -> (InstructionValue<'ctx>, bool) {
+        let (branch, incoming) = self.build_if_else(cond, block1, block2);
+        if incoming.is_empty() {
+            (self.builder.build_unreachable(), true)
+        } else {
+            (branch, false)
         }
+    }
 
-        let p = parser("fn main() -> bool { let a: bool = true <= false; return a }")
-            .unwrap()
-            .1;
-        let t = type_checker(p.clone());
+    /// Compiles both blocks of an `if` and positions the builder after them. Returns the
+    /// branch on the condition and the values of the blocks that fall through, with the
+    /// basic blocks they come from.
+    fn build_if_else(
+        &mut self,
+        cond: Expr,
+        block1: Vec<Expr>,
+        block2: Vec<Expr>,
+    ) -> (InstructionValue<'ctx>, Incoming<'ctx>) {
+        let cond = self.compile_cond(cond);
 
-        if t {
-            assert!(llvm(p).is_ok());
+        let basic_block1 = self.context.append_basic_block(self.fn_value(), "block1");
+        let basic_block2 = self.context.append_basic_block(self.fn_value(), "block2");
+        let cont_block = self.context.append_basic_block(self.fn_value(), "cont");
+
+        let branch = self
+            .builder
+            .build_conditional_branch(cond, basic_block1, basic_block2);
+
+        let mut incoming = Vec::new();
+        for (block, basic_block) in [(block1, basic_block1), (block2, basic_block2)] {
+            self.builder.position_at_end(basic_block);
+            let value = self.compile_block_value(block);
+            self.branch_to(cont_block, value, &mut incoming);
         }
 
-        let p = parser("fn main() -> bool { let a: bool = true >= false; return a }")
-            .unwrap()
-            .1;
-        let t = type_checker(p.clone());
+        self.builder.position_at_end(cont_block);
+        (branch, incoming)
+    }
 
-        if t {
-            assert!(llvm(p).is_ok());
+    /// Jumps to `cont_block` unless the current block already returned, recording
+    /// where the value of the block comes from.
+    fn branch_to(
+        &self,
+        cont_block: BasicBlock<'ctx>,
+        value: Option<BasicValueEnum<'ctx>>,
+        incoming: &mut Incoming<'ctx>,
+    ) {
+        let current_block = self.builder.get_insert_block().unwrap();
+        if current_block.get_terminator().is_none() {
+            self.builder.build_unconditional_branch(cont_block);
+            incoming.push((value, current_block));
         }
+    }
 
-        let p = parser("fn main() -> bool { let a: bool = true < false; return a }")
-            .unwrap()
-            .1;
-        let t = type_checker(p.clone());
+    /// Merges the values of the branches of an `if` or `match` of type `ty`.
+    fn build_value_phi(&self, ty: &Type, incoming: Incoming<'ctx>) -> BasicValueEnum<'ctx> {
+        let phi = self.builder.build_phi(self.llvm_type(ty), "value");
+        for (value, block) in incoming {
+            if let Some(value) = value {
+                phi.add_incoming(&[(&value, block)]);
+            }
+        }
+        phi.as_basic_value()
+    }
 
-        if t {
-            assert!(llvm(p).is_ok());
+    /// The condition is tested in a header block before each iteration, `continue` jumps
+    /// back to it.
+    fn compile_while(&mut self, cond: Expr, block: Vec<Expr>) -> InstructionValue<'ctx> {
+        let header_block = self.context.append_basic_block(self.fn_value(), "header");
+        let body_block = self.context.append_basic_block(self.fn_value(), "body");
+        let exit_block = self.context.append_basic_block(self.fn_value(), "exit");
+
+        self.builder.build_unconditional_branch(header_block);
+        self.builder.position_at_end(header_block);
+        let cond = self.compile_cond(cond);
+        let branch = self
+            .builder
+            .build_conditional_branch(cond, body_block, exit_block);
+
+        self.builder.position_at_end(body_block);
+        self.loops.push((header_block, exit_block, Vec::new()));
+        self.compile_block(block);
+        self.loops.pop();
+        self.branch_to(header_block, None, &mut Vec::new());
+
+        self.builder.position_at_end(exit_block);
+        branch
+    }
+
+    fn compile_loop(&mut self, block: Vec<Expr>) -> (InstructionValue<'ctx>, bool) {
+        let (branch, incoming) = self.build_loop(block);
+        if incoming.is_empty() {
+            (self.builder.build_unreachable(), true)
+        } else {
+            (branch, false)
         }
+    }
 
-        let p = parser("fn main() -> bool { let a: bool = true > false; return a }")
-            .unwrap()
-            .1;
-        let t = type_checker(p.clone());
+    /// Compiles the body of a `loop`, which jumps back to its start, and positions the
+    /// builder at the exit block the `break`s jump to. Returns the branch into the body and
+    /// the values of the `break`s with the basic blocks they come from.
+    fn build_loop(&mut self, block: Vec<Expr>) -> (InstructionValue<'ctx>, Incoming<'ctx>) {
+        let body_block = self.context.append_basic_block(self.fn_value(), "body");
+        let exit_block = self.context.append_basic_block(self.fn_value(), "exit");
 
-        if t {
-            assert!(llvm(p).is_ok());
+        let branch = self.builder.build_unconditional_branch(body_block);
+        self.builder.position_at_end(body_block);
+        self.loops.push((body_block, exit_block, Vec::new()));
+        self.compile_block(block);
+        let (_, _, incoming) = self.loops.pop().unwrap();
+        self.branch_to(body_block, None, &mut Vec::new());
+
+        self.builder.position_at_end(exit_block);
+        (branch, incoming)
+    }
+
+    /// Tests the loop variable against the end of the range in a header block and
+    /// increments it in a latch block after the body, `continue` jumps to the latch.
+    /// The bounds are computed once before the loop.
+    fn compile_for(&mut self, var: Expr, range: Expr, block: Vec<Expr>) -> InstructionValue<'ctx> {
+        let (name, id) = match var {
+            Expr::Var(name, id) => (name, id),
+            _ => panic!("Invalid for loop variable!"),
+        };
+        let (start, end, inclusive) = match range {
+            Expr::Range(start, end, inclusive) => (*start, *end, inclusive),
+            _ => panic!("Invalid for loop range!"),
+        };
+        let ty = self.type_of(&start);
+        let (_, signed) = ty.int_info().unwrap();
+        let start = self.compile_value(start).into_int_value();
+        let end = self.compile_value(end).into_int_value();
+
+        let ptr = self.create_entry_block_alloca(&name, id, ty.clone());
+        self.declare_variable(&name, &ty, ptr, None);
+        self.builder.build_store(ptr, start);
+
+        let header_block = self.context.append_basic_block(self.fn_value(), "header");
+        let body_block = self.context.append_basic_block(self.fn_value(), "body");
+        let latch_block = self.context.append_basic_block(self.fn_value(), "latch");
+        let exit_block = self.context.append_basic_block(self.fn_value(), "exit");
+
+        self.builder.build_unconditional_branch(header_block);
+        self.builder.position_at_end(header_block);
+        let i = self.builder.build_load(ptr, &name).into_int_value();
+        let predicate = match (signed, inclusive) {
+            (true, false) => IntPredicate::SLT,
+            (true, true) => IntPredicate::SLE,
+            (false, false) => IntPredicate::ULT,
+            (false, true) => IntPredicate::ULE,
+        };
+        let in_range = self
+            .builder
+            .build_int_compare(predicate, i, end, "in_range");
+        let branch = self
+            .builder
+            .build_conditional_branch(in_range, body_block, exit_block);
+
+        self.builder.position_at_end(body_block);
+        self.loops.push((latch_block, exit_block, Vec::new()));
+        self.compile_block(block);
+        self.loops.pop();
+        self.branch_to(latch_block, None, &mut Vec::new());
+
+        // An inclusive range stops at its end, the increment could overflow past it
+        self.builder.position_at_end(latch_block);
+        let i = self.builder.build_load(ptr, &name).into_int_value();
+        let next = self
+            .builder
+            .build_int_add(i, i.get_type().const_int(1, false), "next");
+        self.builder.build_store(ptr, next);
+        if inclusive {
+            let is_end = self
+                .builder
+                .build_int_compare(IntPredicate::EQ, i, end, "is_end");
+            self.builder
+                .build_conditional_branch(is_end, exit_block, header_block);
+        } else {
+            self.builder.build_unconditional_branch(header_block);
         }
 
-        let p =
-            parser("fn main() -> bool { let a: bool = true; let b: bool = a > false; return b }")
-                .unwrap()
-                .1;
-        let t = type_checker(p.clone());
+        self.builder.position_at_end(exit_block);
+        branch
+    }
 
-        if t {
-            assert!(llvm(p).is_ok());
+    /// Jumps on the tag of an enum, or tests the arms in order for other types, and
+    /// compiles the block of each arm once.
+    fn compile_match(
+        &mut self,
+        expr: Expr,
+        arms: Vec<(Pattern, Vec<Expr>)>,
+    ) -> (InstructionValue<'ctx>, bool) {
+        let (dispatch, incoming) = self.build_match(expr, arms);
+        if incoming.is_empty() {
+            (self.builder.build_unreachable(), true)
+        } else {
+            (dispatch, false)
         }
     }
 
-    #[test]
-    fn test_llvm_let_fn_call() {
-        let p = parser(
+    /// Like `build_if_else` for the arms of a match.
+    fn build_match(
+        &mut self,
+        expr: Expr,
+        arms: Vec<(Pattern, Vec<Expr>)>,
+    ) -> (InstructionValue<'ctx>, Incoming<'ctx>) {
+        let ty = self.type_of(&expr);
+        let ptr = self.compile_base_ptr(expr);
+
+        let arm_blocks: Vec<BasicBlock> = arms
+            .iter()
+            .map(|_| self.context.append_basic_block(self.fn_value(), "arm"))
+            .collect();
+        let cont_block = self.context.append_basic_block(self.fn_value(), "cont");
+
+        let variants = match &ty {
+            Type::Named(name) => self.enums.get(name).map(|(_, variants)| variants.clone()),
+            _ => None,
+        };
+        let dispatch = match variants {
+            Some(variants) => {
+                let tag_ptr = self.builder.build_struct_gep(ptr, 0, "tag").unwrap();
+                let tag = self.builder.build_load(tag_ptr, "tag").into_int_value();
+                let else_block = self
+                    .context
+                    .append_basic_block(self.fn_value(), "no_variant");
+                let cases: Vec<(IntValue, BasicBlock)> = (0..variants.len())
+                    .map(|tag| {
+                        let tag = self.context.i32_type().const_int(tag as u64, false);
+                        let block = self.context.append_basic_block(self.fn_value(), "variant");
+                        (tag, block)
+                    })
+                    .collect();
+                let switch = self.builder.build_switch(tag, else_block, &cases);
+                self.builder.position_at_end(else_block);
+                self.builder.build_unreachable();
+
+                for ((variant, _), (_, block)) in variants.iter().zip(cases.iter()) {
+                    self.builder.position_at_end(*block);
+                    let candidates: Vec<(&Pattern, BasicBlock)> = arms
+                        .iter()
+                        .zip(arm_blocks.iter())
+                        .filter(|((pattern, _), _)| match pattern {
+                            Pattern::Variant(_, other, _) => other == variant,
+                            _ => true,
+                        })
+                        .map(|((pattern, _), block)| (pattern, *block))
+                        .collect();
+                    self.compile_arm_tests(ptr, &candidates);
+                }
+                switch
+            }
+            None => {
+                let test_block = self.context.append_basic_block(self.fn_value(), "test");
+                let branch = self.builder.build_unconditional_branch(test_block);
+                self.builder.position_at_end(test_block);
+                let candidates: Vec<(&Pattern, BasicBlock)> = arms
+                    .iter()
+                    .zip(arm_blocks.iter())
+                    .map(|((pattern, _), block)| (pattern, *block))
+                    .collect();
+                self.compile_arm_tests(ptr, &candidates);
+                branch
+            }
+        };
+
+        let mut incoming = Vec::new();
+        for ((pattern, block), arm_block) in arms.into_iter().zip(arm_blocks) {
+            self.builder.position_at_end(arm_block);
+            self.compile_bindings(&pattern, ptr, &ty);
+            let value = self.compile_block_value(block);
+            self.branch_to(cont_block, value, &mut incoming);
+        }
+
+        self.builder.position_at_end(cont_block);
+        (dispatch, incoming)
+    }
+
+    /// Jumps to the block of the first arm whose pattern matches the value at `ptr`,
+    /// the type checker made sure that one does.
+    fn compile_arm_tests(
+        &mut self,
+        ptr: PointerValue<'ctx>,
+        arms: &[(&Pattern, BasicBlock<'ctx>)],
+    ) {
+        for (pattern, arm_block) in arms {
+            match self.compile_pattern_test(pattern, ptr) {
+                Some(matches) => {
+                    let next_block = self.context.append_basic_block(self.fn_value(), "next");
+                    self.builder
+                        .build_conditional_branch(matches, *arm_block, next_block);
+                    self.builder.position_at_end(next_block);
+                }
+                None => {
+                    self.builder.build_unconditional_branch(*arm_block);
+                    return;
+                }
+            }
+        }
+        self.builder.build_unreachable();
+    }
+
+    /// Whether the value at `ptr` matches `pattern`, `None` if it always does.
+    fn compile_pattern_test(
+        &mut self,
+        pattern: &Pattern,
+        ptr: PointerValue<'ctx>,
+    ) -> Option<IntValue<'ctx>> {
+        match pattern {
+            Pattern::Wildcard | Pattern::Binding(_, _) => None,
+            Pattern::Literal(expr) => {
+                let val = self.builder.build_load(ptr, "val").into_int_value();
+                let literal = self.compile_value(expr.clone()).into_int_value();
+                Some(
+                    self.builder
+                        .build_int_compare(IntPredicate::EQ, val, literal, "matches"),
+                )
+            }
+            Pattern::Variant(name, variant, patterns) => {
+                let (tag, field_types) = self.variant_info(name, variant);
+                let tag_ptr = self.builder.build_struct_gep(ptr, 0, "tag").unwrap();
+                let val = self.builder.build_load(tag_ptr, "tag").into_int_value();
+                let mut matches =
+                    self.builder
+                        .build_int_compare(IntPredicate::EQ, val, tag, "matches");
+                if patterns.is_empty() {
+                    return Some(matches);
+                }
+
+                let payload = self.compile_payload_ptr(ptr, &field_types);
+                for (i, pattern) in patterns.iter().enumerate() {
+                    let field_ptr = self
+                        .builder
+                        .build_struct_gep(payload, i as u32, "field")
+                        .unwrap();
+                    if let Some(field_matches) = self.compile_pattern_test(pattern, field_ptr) {
+                        matches = self.builder.build_and(matches, field_matches, "matches");
+                    }
+                }
+                Some(matches)
+            }
+        }
+    }
+
+    /// Copies the parts of the value at `ptr` that `pattern` binds into new variables.
+    fn compile_bindings(&mut self, pattern: &Pattern, ptr: PointerValue<'ctx>, ty: &Type) {
+        match pattern {
+            Pattern::Binding(name, id) => {
+                let alloca = self.create_entry_block_alloca(name, *id, ty.clone());
+                self.declare_variable(name, ty, alloca, None);
+                let val = self.builder.build_load(ptr, name);
+                self.builder.build_store(alloca, val);
+            }
+            Pattern::Variant(name, variant, patterns) => {
+                let (_, field_types) = self.variant_info(name, variant);
+                if patterns.is_empty() {
+                    return;
+                }
+                let payload = self.compile_payload_ptr(ptr, &field_types);
+                for (i, (pattern, field_type)) in
+                    patterns.iter().zip(field_types.iter()).enumerate()
+                {
+                    let field_ptr = self
+                        .builder
+                        .build_struct_gep(payload, i as u32, "field")
+                        .unwrap();
+                    self.compile_bindings(pattern, field_ptr, field_type);
+                }
+            }
+            Pattern::Wildcard | Pattern::Literal(_) => {}
+        }
+    }
+
+    fn compile_int(&self, int: i32) -> IntValue<'ctx> {
+        self.context.i32_type().const_int(int as u64, false)
+    }
+
+    fn compile_bool(&self, b: bool) -> IntValue<'ctx> {
+        match b {
+            true => self.context.bool_type().const_int(1, false),
+            false => self.context.bool_type().const_int(0, false),
+        }
+    }
+
+    fn compile_block(&mut self, block: Vec<Expr>) -> InstructionValue<'ctx> {
+        self.compile_block_value(block);
+        self.statement.0
+    }
+
+    /// Compiles the statements of a block and returns the value of its tail expression,
+    /// `None` without one, for a tail of type `()` or when the block returns before it.
+    fn compile_block_value(&mut self, mut block: Vec<Expr>) -> Option<BasicValueEnum<'ctx>> {
+        let tail = match block.last() {
+            Some(stmt) if stmt.is_tail() => block.pop(),
+            _ => None,
+        };
+        for expr in block.iter() {
+            self.statement = self.compile_expr(expr);
+
+            if self.statement.1 {
+                return None;
+            }
+        }
+        tail.and_then(|tail| self.compile_tail(tail))
+    }
+
+    fn compile_tail(&mut self, tail: Expr) -> Option<BasicValueEnum<'ctx>> {
+        match tail {
+            Expr::Spanned(span, tail) => {
+                let outer = std::mem::replace(&mut self.span, span);
+                self.set_debug_location();
+                let res = self.compile_tail(*tail);
+                self.span = outer;
+                self.set_debug_location();
+                res
+            }
+            // Tails of type `()` or `!` have no value
+            Expr::Tail(expr) if matches!(self.type_of(&expr), Type::Void | Type::Never) => {
+                self.statement = self.compile_expr(&expr);
+                None
+            }
+            Expr::Tail(expr) => Some(self.compile_value(*expr)),
+            _ => panic!("Invalid tail expr!"),
+        }
+    }
+
+    /// Adds the function to the module and records its signature for calls.
+    fn declare_fn(
+        &mut self,
+        name: &str,
+        param_types: &[Type],
+        ret_type: Type,
+        linkage: Option<Linkage>,
+    ) -> FunctionValue<'ctx> {
+        let abi_types: Vec<BasicTypeEnum> = param_types
+            .iter()
+            .map(|param_type| self.abi_type(param_type))
+            .collect();
+
+        let fn_type = match ret_type {
+            Type::Void => self.context.void_type().fn_type(&abi_types, false),
+            _ => self.abi_type(&ret_type).fn_type(&abi_types, false),
+        };
+
+        self.signatures
+            .insert(name.to_string(), (param_types.to_vec(), ret_type));
+        self.module.add_function(name, fn_type, linkage)
+    }
+
+    /// Declares the builtins, the JIT maps them to the runtime in `link_runtime`.
+    fn declare_builtins(&mut self) {
+        for (name, param_types, ret_type) in builtins() {
+            self.declare_fn(name, &param_types, ret_type, Some(Linkage::External));
+        }
+    }
+
+    /// Declares every function, extern function and method before any body is compiled,
+    /// so a call can refer to a function defined later in the program. Generic functions
+    /// are declared when they are instantiated.
+    fn declare_fns(&mut self, ast: &[Expr]) {
+        for item in ast {
+            match item.clone().unspanned() {
+                Expr::Fn(fn_var, generics, params, ret_type, _) if generics.is_empty() => {
+                    let param_types: Vec<Type> = params.into_iter().map(|param| param.1).collect();
+                    self.declare_fn(&fn_name(*fn_var), &param_types, ret_type, None);
+                }
+                Expr::ExternFn(fn_var, params, ret_type) => {
+                    let param_types: Vec<Type> = params.into_iter().map(|param| param.1).collect();
+                    let linkage = Some(Linkage::External);
+                    self.declare_fn(&fn_name(*fn_var), &param_types, ret_type, linkage);
+                }
+                Expr::Impl(_, impl_type, methods) => {
+                    let mut self_type = HashMap::new();
+                    self_type.insert("Self".to_string(), impl_type.clone());
+                    for method in methods {
+                        if let Expr::Fn(method_var, _, params, ret_type, _) = method.unspanned() {
+                            let param_types: Vec<Type> = params
+                                .into_iter()
+                                .map(|(_, param_type)| param_type.substitute(&self_type))
+                                .collect();
+                            let path = impl_type.method_path(&fn_name(*method_var));
+                            let ret_type = ret_type.substitute(&self_type);
+                            self.declare_fn(&path, &param_types, ret_type, None);
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn compile_fn(
+        &mut self,
+        fn_var: Expr,
+        params: Vec<(Expr, Type)>,
+        ret_type: Type,
+        block: Vec<Expr>,
+    ) -> InstructionValue<'ctx> {
+        let name = fn_name(fn_var);
+
+        // Only instances of generic functions are not declared up front
+        let function = match self.module.get_function(&name) {
+            Some(function) => function,
+            None => {
+                let param_types: Vec<Type> = params.iter().map(|param| param.1.clone()).collect();
+                self.declare_fn(&name, &param_types, ret_type.clone(), None)
+            }
+        };
+        let basic_block = self.context.append_basic_block(function, &name);
+
+        self.fn_value_opt = Some(function);
+        self.builder.position_at_end(basic_block);
+        self.create_subprogram(function, &name, &params, &ret_type);
+        self.ret_type = ret_type;
+        self.set_debug_location();
+
+        self.reset_variables();
+        for (i, (param, param_type)) in params.iter().enumerate() {
+            let arg = function.get_nth_param(i as u32).unwrap();
+            let val = self.to_native(arg, param_type);
+            self.compile_destructure(param, param_type, val, Some(i as u32 + 1));
+        }
+
+        match self.compile_block_value(block) {
+            Some(val) => {
+                let val = self.to_abi(val, &self.ret_type);
+                self.builder.build_return(Some(&val))
+            }
+            // Functions returning `()` can end without a `return`
+            None if self.ret_type == Type::Void
+                && self
+                    .builder
+                    .get_insert_block()
+                    .unwrap()
+                    .get_terminator()
+                    .is_none() =>
+            {
+                self.builder.build_return(None)
+            }
+            None => self.statement.0,
+        }
+    }
+
+    /// Compiles the methods of an impl as functions named like `Circle::area`, with
+    /// `Self` standing for the implementing type.
+    fn compile_impl(&mut self, impl_type: Type, methods: Vec<Expr>) {
+        let mut self_type = HashMap::new();
+        self_type.insert("Self".to_string(), impl_type.clone());
+        for method in methods {
+            if let Expr::Spanned(span, _) = &method {
+                self.span = *span;
+            }
+            match method.unspanned() {
+                Expr::Fn(method_var, _, params, ret_type, block) => match *method_var {
+                    Expr::Var(method, _) => {
+                        let params = params
+                            .into_iter()
+                            .map(|(param, param_type)| (param, param_type.substitute(&self_type)))
+                            .collect();
+                        let ret_type = ret_type.substitute(&self_type);
+                        self.type_args = self_type.clone();
+                        let path = Expr::Var(impl_type.method_path(&method), None);
+                        self.compile_fn(path, params, ret_type, block);
+                        self.type_args.clear();
+                    }
+                    _ => panic!("Invalid method var!"),
+                },
+                _ => panic!("Invalid impl method!"),
+            }
+        }
+    }
+
+    fn debug_type(&self, ty: &Type) -> Option<DIType<'ctx>> {
+        let debug = self.debug.as_ref()?;
+        let (size, encoding) = match ty {
+            Type::Bool => (8, DW_ATE_BOOLEAN),
+            Type::Float => (64, DW_ATE_FLOAT),
+            Type::Str
+            | Type::Void
+            | Type::Array(..)
+            | Type::Named(_)
+            | Type::Tuple(_)
+            | Type::Fn(..) => return None,
+            int_type => match int_type.int_info().unwrap() {
+                (bits, true) => (bits as u64, DW_ATE_SIGNED),
+                (bits, false) => (bits as u64, DW_ATE_UNSIGNED),
+            },
+        };
+        let basic_type = debug
+            .builder
+            .create_basic_type(&ty.to_string(), size, encoding, DIFlags::PUBLIC)
+            .unwrap();
+        Some(basic_type.as_type())
+    }
+
+    /// Attaches a subprogram describing `function` to it, starting at the current span.
+    fn create_subprogram(
+        &mut self,
+        function: FunctionValue<'ctx>,
+        name: &str,
+        params: &[(Expr, Type)],
+        ret_type: &Type,
+    ) {
+        let param_types: Vec<DIType> = match self.debug {
+            Some(_) => params
+                .iter()
+                .filter_map(|param| self.debug_type(&param.1))
+                .collect(),
+            None => return,
+        };
+        let ret_type = self.debug_type(ret_type);
+        let line = self.span.line;
+
+        let debug = self.debug.as_mut().unwrap();
+        let file = debug.compile_unit.get_file();
+        let subroutine_type =
+            debug
+                .builder
+                .create_subroutine_type(file, ret_type, &param_types, DIFlags::PUBLIC);
+        let subprogram = debug.builder.create_function(
+            debug.compile_unit.as_debug_info_scope(),
+            name,
+            None,
+            file,
+            line,
+            subroutine_type,
+            false,
+            true,
+            line,
+            DIFlags::PUBLIC,
+            false,
+        );
+        function.set_subprogram(subprogram);
+        debug.subprogram = Some(subprogram);
+    }
+
+    /// Tags the instructions built from here on with the current span.
+    fn set_debug_location(&self) {
+        let debug = match &self.debug {
+            Some(debug) => debug,
+            None => return,
+        };
+        if let Some(subprogram) = debug.subprogram {
+            let location = debug.builder.create_debug_location(
+                self.context,
+                self.span.line,
+                self.span.column,
+                subprogram.as_debug_info_scope(),
+                None,
+            );
+            self.builder
+                .set_current_debug_location(self.context, location);
+        }
+    }
+
+    /// Describes the variable stored in `alloca`, `arg_no` is the 1-based index of a parameter.
+    fn declare_variable(
+        &self,
+        name: &str,
+        ty: &Type,
+        alloca: PointerValue<'ctx>,
+        arg_no: Option<u32>,
+    ) {
+        let debug_type = match self.debug_type(ty) {
+            Some(debug_type) => debug_type,
+            None => return,
+        };
+        let debug = self.debug.as_ref().unwrap();
+        let subprogram = match debug.subprogram {
+            Some(subprogram) => subprogram,
+            None => return,
+        };
+
+        let scope = subprogram.as_debug_info_scope();
+        let file = debug.compile_unit.get_file();
+        let var_info = match arg_no {
+            Some(arg_no) => debug.builder.create_parameter_variable(
+                scope,
+                name,
+                arg_no,
+                file,
+                self.span.line,
+                debug_type,
+                true,
+                DIFlags::ZERO,
+            ),
+            None => debug.builder.create_auto_variable(
+                scope,
+                name,
+                file,
+                self.span.line,
+                debug_type,
+                true,
+                DIFlags::ZERO,
+                0,
+            ),
+        };
+        let location = debug.builder.create_debug_location(
+            self.context,
+            self.span.line,
+            self.span.column,
+            scope,
+            None,
+        );
+        debug.builder.insert_declare_at_end(
+            alloca,
+            Some(var_info),
+            None,
+            location,
+            self.builder.get_insert_block().unwrap(),
+        );
+    }
+
+    /// Sets up the compile unit that all debug metadata hangs off.
+    /// Compiles every item of the program into the module.
+    fn compile_program(&mut self, ast: Vec<Expr>) {
+        self.declare_builtins();
+        self.declare_types(&ast);
+        self.define_globals(&ast);
+        self.collect_generic_fns(&ast);
+        self.declare_fns(&ast);
+
+        for expr in ast {
+            if let Expr::Spanned(span, _) = &expr {
+                self.span = *span;
+            }
+            match expr.unspanned() {
+                Expr::Fn(_, generics, ..) if !generics.is_empty() => continue,
+                Expr::Fn(n, _, p, t, b) => {
+                    self.compile_fn(*n, p, t, b);
+                }
+                Expr::Impl(_, ty, methods) => self.compile_impl(ty, methods),
+                _ => continue,
+            }
+        }
+
+        self.finalize_debug_info();
+    }
+
+    fn init_debug_info(&mut self, source_path: Option<&Path>) {
+        let file_name = source_path
+            .and_then(Path::file_name)
+            .map_or(SOURCE_FILE.into(), |name| name.to_string_lossy());
+        let directory = match source_path.and_then(Path::parent) {
+            Some(dir) if !dir.as_os_str().is_empty() => dir.to_string_lossy(),
+            _ => ".".into(),
+        };
+        let (builder, compile_unit) = self.module.create_debug_info_builder(
+            true,
+            DWARFSourceLanguage::C,
+            &file_name,
+            &directory,
+            "d7050e",
+            false,
+            "",
+            0,
+            "",
+            DWARFEmissionKind::Full,
+            0,
+            false,
+            false,
+        );
+        self.module.add_basic_value_flag(
+            "Debug Info Version",
+            FlagBehavior::Warning,
+            self.context.i32_type().const_int(3, false),
+        );
+        self.debug = Some(DebugInfo {
+            builder,
+            compile_unit,
+            subprogram: None,
+        });
+    }
+
+    fn finalize_debug_info(&self) {
+        if let Some(debug) = &self.debug {
+            debug.builder.finalize();
+        }
+    }
+}
+
+#[cfg(test)]
+pub fn llvm(ast: Vec<Expr>) -> Result<ExprRep, Box<dyn Error>> {
+    llvm_with_options(ast, &CompileOptions::default())
+}
+
+pub fn llvm_with_options(
+    ast: Vec<Expr>,
+    options: &CompileOptions,
+) -> Result<ExprRep, Box<dyn Error>> {
+    let (ast, _) = resolve(ast).map_err(|e| e.to_string())?;
+    let context = Context::create();
+    let module = context.create_module("llvm-program");
+    let builder = context.create_builder();
+    let execution_engine = module
+        .create_jit_execution_engine(OptimizationLevel::None)
+        .unwrap();
+
+    let mut compiler = Compiler {
+        context: &context,
+        builder: &builder,
+        module: &module,
+        execution_engine: &execution_engine,
+        fn_value_opt: None,
+        variables: HashMap::new(),
+        var_types: HashMap::new(),
+        structs: HashMap::new(),
+        enums: HashMap::new(),
+        signatures: HashMap::new(),
+        globals: HashMap::new(),
+        generic_fns: HashMap::new(),
+        type_args: HashMap::new(),
+        ret_type: Type::Int,
+        loops: Vec::new(),
+
+        statement: (builder.build_return(None), false),
+
+        wrapping: options.wrapping,
+        span: Span::default(),
+        debug: None,
+    };
+
+    if options.debug {
+        compiler.init_debug_info(options.source_path.as_deref());
+    }
+    compiler.compile_program(ast);
+    compiler.module.print_to_stderr();
+    compiler.link_runtime()?;
+
+    // The result is read back as the C return value of `main`, which only fits scalars
+    if let Some((
+        _,
+        ret_type @ (Type::Array(..) | Type::Tuple(_) | Type::Named(_) | Type::Fn(..)),
+    )) = compiler.signatures.get("main")
+    {
+        return Err(format!(
+            "compile error: `main` returns `{}`, only numbers, bools and strings can be returned",
+            ret_type
+        )
+        .into());
+    }
+
+    let res = match compiler.signatures.get("main") {
+        Some((_, Type::Bool)) => {
+            let compiled_program: JitFunction<BoolFunc> =
+                unsafe { compiler.execution_engine.get_function("main").ok().unwrap() };
+            ExprRep::Bool(unsafe { compiled_program.call() })
+        }
+        Some((_, Type::Float)) => {
+            let compiled_program: JitFunction<FloatFunc> =
+                unsafe { compiler.execution_engine.get_function("main").ok().unwrap() };
+            ExprRep::Float(unsafe { compiled_program.call() })
+        }
+        Some((_, Type::Str)) => {
+            let compiled_program: JitFunction<StrFunc> =
+                unsafe { compiler.execution_engine.get_function("main").ok().unwrap() };
+            let s = unsafe { compiled_program.call() };
+            ExprRep::Str(s.as_str().to_string())
+        }
+        // Narrow integers are read back through a wider C return type and truncated
+        Some((_, int_type)) if int_type.is_int() && *int_type != Type::Int => {
+            let int_type = int_type.clone();
+            let res = match int_type.int_info().unwrap() {
+                (64, _) => {
+                    let compiled_program: JitFunction<I64Func> =
+                        unsafe { compiler.execution_engine.get_function("main").ok().unwrap() };
+                    unsafe { compiled_program.call() as i128 }
+                }
+                _ => {
+                    let compiled_program: JitFunction<ExprFunc> =
+                        unsafe { compiler.execution_engine.get_function("main").ok().unwrap() };
+                    unsafe { compiled_program.call() as i128 }
+                }
+            };
+            ExprRep::TypedInt(int_type.wrap_int(res), int_type)
+        }
+        _ => {
+            let compiled_program: JitFunction<ExprFunc> =
+                unsafe { compiler.execution_engine.get_function("main").ok().unwrap() };
+            ExprRep::Int(unsafe { compiled_program.call() })
+        }
+    };
+    println!("llvm-result: {:?} ", res);
+
+    Ok(res)
+}
+
+#[cfg(test)]
+mod parse_tests {
+    use super::*;
+    use crate::interpreter::*;
+    use crate::memory::*;
+    use crate::parser::*;
+    use crate::runtime::with_io;
+    use crate::type_checker::*;
+
+    /// Runs the program in `src` with the interpreter and compiled, with `input` as stdin,
+    /// and returns the result and output of each so the backends can be compared.
+    fn run_both(src: &str, input: &str) -> ((ExprRep, String), (ExprRep, String)) {
+        let (interp, interp_output) = run_main(src, input);
+        let typed = check(parse_file(src, None).unwrap()).unwrap();
+        let compiled = with_io(input, || llvm(typed).unwrap());
+        ((interp.unwrap(), interp_output), compiled)
+    }
+
+    #[test]
+    fn test_llvm_return() {
+        let p = parser("fn main() -> i32 { return 1 }").unwrap().1;
+        let t = type_checker(p.clone());
+
+        if t {
+            assert!(llvm(p).is_ok());
+        }
+
+        let p = parser("fn main() -> i32 { return 1 + 1 }").unwrap().1;
+        let t = type_checker(p.clone());
+
+        if t {
+            assert!(llvm(p).is_ok());
+        }
+
+        let p = parser("fn main() -> bool { return true }").unwrap().1;
+        let t = type_checker(p.clone());
+
+        if t {
+            assert!(llvm(p).is_ok());
+        }
+    }
+
+    #[test]
+    fn test_llvm_let_bin_expr_int() {
+        let p = parser("fn main() -> i32 { let a: i32 = 1; return a }")
+            .unwrap()
+            .1;
+        let t = type_checker(p.clone());
+
+        if t {
+            assert!(llvm(p).is_ok());
+        }
+
+        let p = parser("fn main() -> bool { let a: bool = true; return a }")
+            .unwrap()
+            .1;
+        let t = type_checker(p.clone());
+
+        if t {
+            assert!(llvm(p).is_ok());
+        }
+
+        let p = parser("fn main() -> i32 { let a: i32 = 1 + 1; return a }")
+            .unwrap()
+            .1;
+        let t = type_checker(p.clone());
+
+        if t {
+            assert!(llvm(p).is_ok());
+        }
+
+        let p = parser("fn main() -> i32 { let a: i32 = 1 - 1; return a }")
+            .unwrap()
+            .1;
+        let t = type_checker(p.clone());
+
+        if t {
+            assert!(llvm(p).is_ok());
+        }
+
+        let p = parser("fn main() -> i32 { let a: i32 = 1 / 1; return a }")
+            .unwrap()
+            .1;
+        let t = type_checker(p.clone());
+
+        if t {
+            assert!(llvm(p).is_ok());
+        }
+
+        let p = parser("fn main() -> i32 { let a: i32 = 1 * 1; return a }")
+            .unwrap()
+            .1;
+        let t = type_checker(p.clone());
+
+        if t {
+            assert!(llvm(p).is_ok());
+        }
+
+        let p = parser("fn main() -> bool { let a: bool = 1 == 1; return a }")
+            .unwrap()
+            .1;
+        let t = type_checker(p.clone());
+
+        if t {
+            assert!(llvm(p).is_ok());
+        }
+
+        let p = parser("fn main() -> bool { let a: bool = 1 != 1; return a }")
+            .unwrap()
+            .1;
+        let t = type_checker(p.clone());
+
+        if t {
+            assert!(llvm(p).is_ok());
+        }
+
+        let p = parser("fn main() -> bool { let a: bool = 1 <= 3; return a }")
+            .unwrap()
+            .1;
+        let t = type_checker(p.clone());
+
+        if t {
+            assert!(llvm(p).is_ok());
+        }
+
+        let p = parser("fn main() -> bool { let a: bool = 4 >= 3; return a }")
+            .unwrap()
+            .1;
+        let t = type_checker(p.clone());
+
+        if t {
+            assert!(llvm(p).is_ok());
+        }
+
+        let p = parser("fn main() -> bool { let a: bool = 1 < 3; return a }")
+            .unwrap()
+            .1;
+        let t = type_checker(p.clone());
+
+        if t {
+            assert!(llvm(p).is_ok());
+        }
+
+        let p = parser("fn main() -> bool { let a: bool = 4 > 3; return a }")
+            .unwrap()
+            .1;
+        let t = type_checker(p.clone());
+
+        if t {
+            assert!(llvm(p).is_ok());
+        }
+
+        let p = parser("fn main() -> i32 { let a: i32 = 1; let b: i32 = a + 2; return b }")
+            .unwrap()
+            .1;
+        let t = type_checker(p.clone());
+
+        if t {
+            assert!(llvm(p).is_ok());
+        }
+    }
+
+    #[test]
+    fn test_llvm_let_bin_expr_bool() {
+        let p = parser("fn main() -> bool { let a: bool = true && true; return a }")
+            .unwrap()
+            .1;
+        let t = type_checker(p.clone());
+
+        if t {
+            assert!(llvm(p).is_ok());
+        }
+
+        let p = parser("fn main() -> bool { let a: bool = true == true; return a }")
+            .unwrap()
+            .1;
+        let t = type_checker(p.clone());
+
+        if t {
+            assert!(llvm(p).is_ok());
+        }
+
+        let p = parser("fn main() -> bool { let a: bool = true != false; return a }")
+            .unwrap()
+            .1;
+        let t = type_checker(p.clone());
+
+        if t {
+            assert!(llvm(p).is_ok());
+        }
+
+        let p = parser("fn main() -> bool { let a: bool = true <= false; return a }")
+            .unwrap()
+            .1;
+        let t = type_checker(p.clone());
+
+        if t {
+            assert!(llvm(p).is_ok());
+        }
+
+        let p = parser("fn main() -> bool { let a: bool = true >= false; return a }")
+            .unwrap()
+            .1;
+        let t = type_checker(p.clone());
+
+        if t {
+            assert!(llvm(p).is_ok());
+        }
+
+        let p = parser("fn main() -> bool { let a: bool = true < false; return a }")
+            .unwrap()
+            .1;
+        let t = type_checker(p.clone());
+
+        if t {
+            assert!(llvm(p).is_ok());
+        }
+
+        let p = parser("fn main() -> bool { let a: bool = true > false; return a }")
+            .unwrap()
+            .1;
+        let t = type_checker(p.clone());
+
+        if t {
+            assert!(llvm(p).is_ok());
+        }
+
+        let p =
+            parser("fn main() -> bool { let a: bool = true; let b: bool = a > false; return b }")
+                .unwrap()
+                .1;
+        let t = type_checker(p.clone());
+
+        if t {
+            assert!(llvm(p).is_ok());
+        }
+    }
+
+    #[test]
+    fn test_llvm_let_fn_call() {
+        let p = parser(
+            "
+                fn testfn() -> i32 {
+                let b: i32 = (((1 + 2 + 3)));
+                return b
+            }
+
+            fn main() -> i32 {
+                let g: i32 = testfn();
+                return g
+            }
+        ",
+        )
+        .unwrap()
+        .1;
+        let t = type_checker(p.clone());
+
+        if t {
+            assert!(llvm(p).is_ok());
+        }
+    }
+
+    #[test]
+    fn test_llvm_let_var_expr() {
+        let p = parser(" fn main() -> i32 {let a: i32 = 3; a += 2; return a} ")
+            .unwrap()
+            .1;
+        let t = type_checker(p.clone());
+
+        if t {
+            assert!(llvm(p).is_ok());
+        }
+
+        let p = parser(" fn main() -> i32 {let a: i32 = 3; a -= 2; return a} ")
+            .unwrap()
+            .1;
+        let t = type_checker(p.clone());
+
+        if t {
+            assert!(llvm(p).is_ok());
+        }
+
+        let p = parser(" fn main() -> i32 {let a: i32 = 3; a /= 2; return a} ")
+            .unwrap()
+            .1;
+        let t = type_checker(p.clone());
+
+        if t {
+            assert!(llvm(p).is_ok());
+        }
+
+        let p = parser(" fn main() -> i32 {let a: i32 = 3; a *= 2; return a} ")
+            .unwrap()
+            .1;
+        let t = type_checker(p.clone());
+
+        if t {
+            assert!(llvm(p).is_ok());
+        }
+    }
+
+    #[test]
+    fn test_llvm_if() {
+        let p = parser(" fn main() -> i32 { if true { return 1 }; return 2 } ")
+            .unwrap()
+            .1;
+        let t = type_checker(p.clone());
+
+        if t {
+            assert!(llvm(p).is_ok());
+        }
+
+        let p = parser(" fn main() -> bool { if true { return false }; return true } ")
+            .unwrap()
+            .1;
+        let t = type_checker(p.clone());
+
+        if t {
+            assert!(llvm(p).is_ok());
+        }
+
+        let p = parser(
+            "        
+            fn testfn3() -> i32 {
+                let f: bool = true && true;
+                let n: i32 = 1;
+                if f == true {
+                    n += 1;
+                    f = false;
+                };
+                return n;    
+            }
+
+            fn main() -> i32 {
+                {{{ return testfn3(); }}}
+            }",
+        )
+        .unwrap()
+        .1;
+        let t = type_checker(p.clone());
+
+        if t {
+            assert!(llvm(p).is_ok());
+        }
+
+        let p = parser(
+            "        
+            fn testfn3() -> i32 {
+                let f: bool = true && true;
+                let n: i32 = 1;
+                if 1 == 1 {
+                    n += 1;
+                    f = false;
+                };
+                return n;    
+            }
+
+            fn main() -> i32 {
+                {{{ return testfn3(); }}}
+            }",
+        )
+        .unwrap()
+        .1;
+        let t = type_checker(p.clone());
+
+        if t {
+            assert!(llvm(p).is_ok());
+        }
+
+        let p = parser(
+            "        
+            fn testfn3() -> i32 {
+                let f: bool = true && true;
+                let n: i32 = 1;
+                if true == true {
+                    n += 1;
+                    f = false;
+                };
+                return n;    
+            }
+
+            fn main() -> i32 {
+                {{{ return testfn3(); }}}
+            }",
+        )
+        .unwrap()
+        .1;
+        let t = type_checker(p.clone());
+
+        if t {
+            assert!(llvm(p).is_ok());
+        }
+    }
+
+    #[test]
+    fn test_llvm_if_else() {
+        let p = parser(" fn main() -> i32 { if false { return 1 } else { return 3 }; return 2 } ")
+            .unwrap()
+            .1;
+        let t = type_checker(p.clone());
+
+        if t {
+            assert!(llvm(p).is_ok());
+        }
+
+        let p = parser(
+            " fn main() -> bool { if false { return true } else { return false }; return true } ",
+        )
+        .unwrap()
+        .1;
+        let t = type_checker(p.clone());
+
+        if t {
+            assert!(llvm(p).is_ok());
+        }
+    }
+
+    #[test]
+    fn test_llvm_when() {
+        let p = parser(" fn main() -> i32 { while true { return 1 }; return 2 } ")
+            .unwrap()
+            .1;
+        let t = type_checker(p.clone());
+
+        if t {
+            assert!(llvm(p).is_ok());
+        }
+
+        let p = parser(" fn main() -> bool { while true { return false }; return true } ")
+            .unwrap()
+            .1;
+        let t = type_checker(p.clone());
+
+        if t {
+            assert!(llvm(p).is_ok());
+        }
+    }
+
+    #[test]
+    fn test_llvm_fn() {
+        let p = parser(" fn testfn() -> i32 {return 2} fn main() -> i32 {return testfn()} ")
+            .unwrap()
+            .1;
+        let t = type_checker(p.clone());
+
+        if t {
+            assert!(llvm(p).is_ok());
+        }
+
+        let p = parser(" fn testfn2() -> i32 {return 2} fn testfn() -> i32 {return 1} fn main() -> i32 {let a:i32 = testfn() + testfn2(); return a} ")
+            .unwrap()
+            .1;
+        let t = type_checker(p.clone());
+
+        if t {
+            assert!(llvm(p).is_ok());
+        }
+
+        let p = parser(" fn testfn() -> i32 { if false { return 1 } else { return 3 }; return 2 } fn main() -> i32 {return testfn()} ")
+            .unwrap()
+            .1;
+        let t = type_checker(p.clone());
+
+        if t {
+            assert!(llvm(p).is_ok());
+        }
+        let p = parser(
+            " fn main() -> i32 { let a:i32 = 2; let b:i32 = 3; let c:i32 = a + b; return c} ",
+        )
+        .unwrap()
+        .1;
+        let t = type_checker(p.clone());
+
+        if t {
+            assert!(llvm(p).is_ok());
+        }
+        let p = parser(" fn testfn(a:i32) -> i32 {return a} fn main() -> i32 {return testfn(1)} ")
+            .unwrap()
+            .1;
+        let t = type_checker(p.clone());
+
+        if t {
+            assert!(llvm(p).is_ok());
+        }
+        let p = parser(
+            " fn testfn(a:bool) -> bool {return a} fn main() -> bool {return testfn(true)} ",
+        )
+        .unwrap()
+        .1;
+        let t = type_checker(p.clone());
+
+        if t {
+            assert!(llvm(p).is_ok());
+        }
+    }
+
+    #[test]
+    fn test_llvm_bool_fn() {
+        let p = parser("fn main() -> bool { return 1 < 2 }").unwrap().1;
+        assert_eq!(llvm(p).unwrap(), ExprRep::Bool(true));
+
+        let p = parser(
+            " fn testfn(a:bool) -> bool {return a} fn main() -> bool {return testfn(false)} ",
+        )
+        .unwrap()
+        .1;
+        assert_eq!(llvm(p).unwrap(), ExprRep::Bool(false));
+    }
+
+    /// Compiles the program with debug info for `source_path` and returns the module IR.
+    fn debug_ir(ast: Vec<Expr>, source_path: &str) -> String {
+        let (ast, _) = resolve(ast).unwrap();
+        let context = Context::create();
+        let module = context.create_module("debug-info");
+        let builder = context.create_builder();
+        let execution_engine = module
+            .create_jit_execution_engine(OptimizationLevel::None)
+            .unwrap();
+
+        let mut compiler = Compiler {
+            context: &context,
+            builder: &builder,
+            module: &module,
+            execution_engine: &execution_engine,
+            fn_value_opt: None,
+            variables: HashMap::new(),
+            var_types: HashMap::new(),
+            structs: HashMap::new(),
+            enums: HashMap::new(),
+            signatures: HashMap::new(),
+            globals: HashMap::new(),
+            generic_fns: HashMap::new(),
+            type_args: HashMap::new(),
+            ret_type: Type::Int,
+            loops: Vec::new(),
+
+            statement: (builder.build_return(None), false),
+
+            wrapping: false,
+            span: Span::default(),
+            debug: None,
+        };
+        compiler.init_debug_info(Some(Path::new(source_path)));
+        compiler.compile_program(ast);
+        module.print_to_string().to_string()
+    }
+
+    /// The metadata line of the IR describing `what`, e.g. `DISubprogram(name: "add"`.
+    fn metadata<'a>(ir: &'a str, what: &str) -> &'a str {
+        match ir.lines().find(|line| line.contains(what)) {
+            Some(line) => line,
+            None => panic!("no {} in {}", what, ir),
+        }
+    }
+
+    #[test]
+    fn test_llvm_debug_info() {
+        let p = parser(
+            "
+            fn add(a: i32, b: i32) -> i32 {
+                let c: i32 = a + b;
+                return c
+            }
+
+            fn main() -> i32 {
+                let x: i32 = 2;
+                return add(x, 3)
+            }
+        ",
+        )
+        .unwrap()
+        .1;
+        let options = CompileOptions {
+            debug: true,
+            ..CompileOptions::default()
+        };
+        assert_eq!(
+            llvm_with_options(p.clone(), &options).unwrap(),
+            ExprRep::Int(5)
+        );
+
+        let ir = debug_ir(p, "examples/add.rs");
+        assert!(metadata(&ir, "DICompileUnit(").contains("d7050e"));
+        let file = metadata(&ir, "DIFile(");
+        assert!(file.contains(r#"filename: "add.rs""#), "{}", file);
+        assert!(file.contains(r#"directory: "examples""#), "{}", file);
+        assert!(metadata(&ir, r#"DISubprogram(name: "add""#).contains("line: 2,"));
+        assert!(metadata(&ir, r#"DISubprogram(name: "main""#).contains("line: 7,"));
+        assert!(metadata(&ir, r#"DILocalVariable(name: "a", arg: 1"#).contains("line: 2,"));
+        assert!(metadata(&ir, r#"DILocalVariable(name: "c""#).contains("line: 3,"));
+        assert!(metadata(&ir, r#"DILocalVariable(name: "x""#).contains("line: 8,"));
+    }
+
+    #[test]
+    fn test_llvm_builtins() {
+        let (interp, compiled) = run_both(
+            "
+            fn main() -> i32 {
+                let a: i32 = read_i32();
+                print_i32(a);
+                println();
+                print_bool(a > 1);
+                return read_i32()
+            }
+        ",
+            "5\n-1\n",
+        );
+        assert_eq!(compiled, (ExprRep::Int(-1), "5\ntrue".to_string()));
+        assert_eq!(interp, compiled);
+    }
+
+    #[test]
+    fn test_llvm_call_stmt() {
+        // The values of calls used as statements are thrown away
+        let (interp, compiled) = run_both(
+            "
+            extern fn putchar(c: i32) -> i32;
+
+            static mut C: i32 = 0;
+
+            fn bump() -> i32 {
+                C += 1;
+                C
+            }
+
+            fn double(x: i32) -> i32 {
+                x * 2
+            }
+
+            fn main() -> i32 {
+                double(3);
+                putchar(65);
+                putchar(10);
+                bump();
+                bump();
+                C * 10
+            }
+        ",
+            "",
+        );
+        assert_eq!(compiled, (ExprRep::Int(20), "A\n".to_string()));
+        assert_eq!(interp, compiled);
+    }
+
+    #[test]
+    fn test_llvm_str() {
+        let p = parser(
+            r#"
+            fn greet(name: str) -> str {
+                let s: str = "hello, " + name;
+                s += "!\n";
+                return s
+            }
+
+            fn main() -> i32 {
+                let s: str = greet("wörld");
+                print_str(s);
+                if s != "hello, world!\n" {
+                    print_str("not equal");
+                };
+                return len(s)
+            }
+        "#,
+        )
+        .unwrap()
+        .1;
+        assert!(type_checker(p.clone()));
+
+        let (res, output) = with_io("", || llvm(p).unwrap());
+        assert_eq!(res, ExprRep::Int(15));
+        assert_eq!(output, "hello, wörld!\nnot equal");
+
+        let p = parser(r#"fn main() -> str { let a: str = "\"a\""; return a + "b" }"#)
+            .unwrap()
+            .1;
+        assert_eq!(llvm(p).unwrap(), ExprRep::Str("\"a\"b".to_string()));
+    }
+
+    #[test]
+    fn test_llvm_extern_fn() {
+        let (interp, compiled) = run_both(
+            "
+            extern fn abs(x: i32) -> i32;
+
+            fn main() -> i32 {
+                let a: i32 = 0 - 7;
+                return abs(a)
+            }
+        ",
+            "",
+        );
+        assert_eq!(compiled, (ExprRep::Int(7), String::new()));
+        assert_eq!(interp, compiled);
+    }
+
+    #[test]
+    fn test_llvm_unresolved_extern_fn() {
+        let src = "extern fn nosuch(x: i32) -> i32; fn main() -> i32 { nosuch(1) }";
+        let typed = check(parse_file(src, None).unwrap()).unwrap();
+        let err = llvm(typed).unwrap_err();
+        assert_eq!(err.to_string(), "link error: unresolved extern fn `nosuch`");
+
+        let (res, _) = run_main(src, "");
+        assert_eq!(res.unwrap_err().msg, "unresolved extern fn `nosuch`");
+    }
+
+    #[test]
+    fn test_llvm_enum_fn_payload() {
+        let (interp, compiled) = run_both(
             "
-                fn testfn() -> i32 {
-                let b: i32 = (((1 + 2 + 3)));
+            enum E {
+                F(fn(i32) -> i32),
+                N,
+            }
+
+            fn apply(e: E, x: i32) -> i32 {
+                match e {
+                    E::F(f) => f(x),
+                    E::N => x,
+                }
+            }
+
+            fn main() -> i32 {
+                let k = 10;
+                let add = E::F(|x: i32| x + k);
+                let a = apply(add, 5);
+                let b = apply(E::N, 7);
+                let c = a * 100;
+                c + b
+            }
+        ",
+            "",
+        );
+        assert_eq!(compiled, (ExprRep::Int(1507), "".to_string()));
+        assert_eq!(interp, compiled);
+    }
+
+    #[test]
+    fn test_llvm_return_in_block_value() {
+        let (interp, compiled) = run_both(
+            "
+            fn f(v: i32) -> i32 {
+                let x = {
+                    if v == 0 {
+                        return 1;
+                    };
+                    2
+                };
+                x + 100
+            }
+
+            fn g(v: i32) -> i32 {
+                let y = 0;
+                y = {
+                    if v == 0 {
+                        return 3;
+                    };
+                    4
+                };
+                y + 200
+            }
+
+            fn main() -> i32 {
+                print_i32(f(0));
+                print_i32(f(5));
+                print_i32(g(0));
+                g(5)
+            }
+        ",
+            "",
+        );
+        assert_eq!(compiled, (ExprRep::Int(204), "11023".to_string()));
+        assert_eq!(interp, compiled);
+    }
+
+    #[test]
+    fn test_llvm_return_as_value() {
+        let (interp, compiled) = run_both(
+            "
+            fn f(c: bool) -> i32 {
+                let x = if c { return 5 } else { 3 };
+                x * 10
+            }
+
+            fn g(v: i32) -> i32 {
+                match v {
+                    0 => { return 1 },
+                    1 => return 2,
+                    _ => 3,
+                }
+            }
+
+            fn h(v: i32) -> i32 {
+                let x: i32 = match v {
+                    0 => { return 4 },
+                    _ => loop { break 6; },
+                };
+                x
+            }
+
+            fn main() -> i32 {
+                print_i32(f(true));
+                print_i32(f(false));
+                print_i32(g(0));
+                print_i32(g(1));
+                print_i32(g(2));
+                print_i32(h(0));
+                h(1)
+            }
+        ",
+            "",
+        );
+        assert_eq!(compiled, (ExprRep::Int(6), "5301234".to_string()));
+        assert_eq!(interp, compiled);
+    }
+
+    #[test]
+    fn test_llvm_return_in_loop_value() {
+        let (interp, compiled) = run_both(
+            "
+            fn f(c: bool) -> i32 {
+                let y = loop {
+                    if c {
+                        return 7;
+                    };
+                    break 1;
+                };
+                y + 100
+            }
+
+            fn g(n: i32) -> i32 {
+                let i = 0;
+                while true {
+                    for j in 0..n {
+                        if j == 3 {
+                            return j;
+                        };
+                    };
+                    i += 1;
+                    if i == 2 {
+                        break;
+                    };
+                };
+                i
+            }
+
+            fn main() -> i32 {
+                print_i32(f(true));
+                print_i32(f(false));
+                print_i32(g(5));
+                g(2)
+            }
+        ",
+            "",
+        );
+        assert_eq!(compiled, (ExprRep::Int(2), "71013".to_string()));
+        assert_eq!(interp, compiled);
+    }
+
+    #[test]
+    fn test_llvm_main_non_scalar() {
+        let p = parser("fn main() -> (bool, i32) { return (true, 1) }")
+            .unwrap()
+            .1;
+        let err = llvm(p).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "compile error: `main` returns `(bool, i32)`, only numbers, bools and strings can be returned"
+        );
+
+        let p = parser("struct P { x: i32 } fn main() -> P { return P { x: 1 } }")
+            .unwrap()
+            .1;
+        assert!(llvm(p).is_err());
+    }
+
+    #[test]
+    fn test_llvm_int_types() {
+        let (interp, compiled) = run_both(
+            "
+            fn mean(a: u8, b: u8) -> u8 {
+                return ((a as u32 + b as u32) / 2u32) as u8
+            }
+
+            fn main() -> i64 {
+                let m: u8 = mean(250u8, 200u8);
+                let big: u64 = 18446744073709551615u64 / 3u64;
+                let x: i8 = 128 as i8;
+                x /= 3i8;
+                print_bool(big > 1u64);
+                print_i32(m as i32);
+                return x as i64 + big as i64
+            }
+        ",
+            "",
+        );
+        let expected = ExprRep::TypedInt(-42 + (u64::MAX / 3) as i64 as i128, Type::I64);
+        assert_eq!(compiled, (expected, "true225".to_string()));
+        assert_eq!(interp, compiled);
+
+        let p = parser("fn main() -> u8 { let a: u8 = 255u8; return a as i8 as u8 }")
+            .unwrap()
+            .1;
+        assert_eq!(llvm(p).unwrap(), ExprRep::TypedInt(255, Type::U8));
+    }
+
+    #[test]
+    fn test_llvm_int_var_ops() {
+        let (interp, compiled) = run_both(
+            "
+            fn main() -> u8 {
+                let a: u8 = 20u8;
+                let b: u8 = 10u8;
+                let d: u8 = a - b;
+                print_bool(d > b);
+                a + b
+            }
+        ",
+            "",
+        );
+        assert_eq!(
+            compiled,
+            (ExprRep::TypedInt(30, Type::U8), "false".to_string())
+        );
+        assert_eq!(interp, compiled);
+
+        let (res, _) = run_main(
+            "fn main() -> u8 { let a: u8 = 200u8; let b: u8 = 100u8; a + b }",
+            "",
+        );
+        assert_eq!(res.unwrap_err().msg, "attempt to add with overflow");
+    }
+
+    #[test]
+    fn test_llvm_float() {
+        let (interp, compiled) = run_both(
+            "
+            fn fall(t: f64) -> f64 {
+                let g: f64 = 9.81;
+                return g * t * t / 2.0
+            }
+
+            fn main() -> i32 {
+                let d: f64 = fall(3 as f64);
+                d -= 2.5e-2;
+                print_f64(d);
+                print_bool(d > 44.1);
+                let nan: f64 = 0.0 / 0.0;
+                print_bool(nan != nan);
+                return d as i32 + 1e10 as i32 - 2147483647
+            }
+        ",
+            "",
+        );
+        assert_eq!(
+            compiled,
+            (ExprRep::Int(44), "44.120000000000005truetrue".to_string())
+        );
+        assert_eq!(interp, compiled);
+
+        let p = parser("fn main() -> f64 { return 255u8 as f64 / 2.0 }")
+            .unwrap()
+            .1;
+        assert_eq!(llvm(p).unwrap(), ExprRep::Float(127.5));
+    }
+
+    #[test]
+    fn test_llvm_float_var_ops() {
+        let (interp, compiled) = run_both(
+            "
+            fn main() -> f64 {
+                let a = 2.0;
+                let b = 0.5;
+                let c = a / b;
+                print_f64(c);
+                a * b
+            }
+        ",
+            "",
+        );
+        assert_eq!(compiled, (ExprRep::Float(1.0), "4".to_string()));
+        assert_eq!(interp, compiled);
+    }
+
+    #[test]
+    fn test_llvm_array() {
+        let (interp, compiled) = run_both(
+            "
+            fn sum(a: [i32; 3]) -> i32 {
+                return a[0] + a[1] + a[2]
+            }
+
+            fn main() -> i32 {
+                let a: [i32; 3] = [1, 2, 3];
+                let grid: [[u8; 2]; 2] = [[0u8; 2]; 2];
+                a[1] = 5;
+                a[2] *= 4;
+                grid[1][0] += 7u8;
+                print_i32(grid[1][0] as i32);
+                let i: usize = 2usize;
+                print_bool(a[i] == 12);
+                return sum(a) + len(grid) + [10, 20][1]
+            }
+        ",
+            "",
+        );
+        assert_eq!(compiled, (ExprRep::Int(40), "7true".to_string()));
+        assert_eq!(interp, compiled);
+    }
+
+    #[test]
+    fn test_llvm_struct() {
+        let (interp, compiled) = run_both(
+            "
+            struct Point {
+                x: i32,
+                y: i32,
+            }
+
+            struct Line { from: Point, to: Point }
+
+            fn shift(p: Point, by: i32) -> Point {
+                return Point { y: p.y + by, x: p.x + by }
+            }
+
+            fn main() -> i32 {
+                let l: Line = Line { from: Point { x: 1, y: 2 }, to: shift(Point { x: 0, y: 0 }, 5) };
+                l.from.x += 10;
+                l.to = shift(l.to, 1);
+                print_i32(l.from.x);
+                print_i32(shift(l.from, 2).y);
+                let ps: [Point; 2] = [l.from; 2];
+                ps[1].y = 7;
+                return l.to.x * 100 + ps[1].y + ps[0].y
+            }
+        ",
+            "",
+        );
+        assert_eq!(compiled, (ExprRep::Int(654), "114".to_string()));
+        assert_eq!(interp, compiled);
+    }
+
+    #[test]
+    fn test_llvm_tuple() {
+        let (interp, compiled) = run_both(
+            "
+            fn div_mod(a: i32, b: i32) -> (i32, i32) {
+                return (a / b, a % b)
+            }
+
+            fn swap((x, y): (i32, bool)) -> (bool, i32) {
+                return (y, x)
+            }
+
+            fn main() -> i32 {
+                let (q, r): (i32, i32) = div_mod(17, 5);
+                let t: (i32, (bool, i32)) = (q, swap((r, true)));
+                t.1.1 *= 10;
+                print_bool(t.1.0);
+                let (flag, (x, y)): (bool, (i32, i32)) = (false, div_mod(t.1.1, 3));
+                print_bool(flag);
+                return y + (x * 10) + (t.0 * 100)
+            }
+        ",
+            "",
+        );
+        assert_eq!(compiled, (ExprRep::Int(362), "truefalse".to_string()));
+        assert_eq!(interp, compiled);
+    }
+
+    #[test]
+    fn test_llvm_inferred_let() {
+        let (interp, compiled) = run_both(
+            "
+            fn main() -> i64 {
+                let big = 4000000000i64;
+                let small = 7u8;
+                let pair = (small, big);
+                let (byte, wide) = pair;
+                let flags = [byte > 6u8; 3];
+                print_bool(flags[2]);
+                return wide + (byte as i64)
+            }
+        ",
+            "",
+        );
+        assert_eq!(
+            compiled,
+            (ExprRep::TypedInt(4000000007, Type::I64), "true".to_string())
+        );
+        assert_eq!(interp, compiled);
+    }
+
+    #[test]
+    fn test_llvm_deferred_let() {
+        let (interp, compiled) = run_both(
+            "
+            fn main() -> i32 {
+                let big;
+                let flag: bool;
+                flag = 3 > 2;
+                if flag {
+                    big = 7u8;
+                } else {
+                    big = 1u8;
+                };
+                let copy = big;
+                return copy as i32
+            }
+        ",
+            "",
+        );
+        assert_eq!(compiled, (ExprRep::Int(7), String::new()));
+        assert_eq!(interp, compiled);
+    }
+
+    #[test]
+    fn test_llvm_generic_fn() {
+        let (interp, compiled) = run_both(
+            "
+            fn max<T>(a: T, b: T) -> T {
+                if a > b {
+                    return a
+                };
                 return b
             }
 
-            fn main() -> i32 {
-                let g: i32 = testfn();
-                return g
+            fn swap<A, B>(pair: (A, B)) -> (B, A) {
+                let (first, second): (A, B) = pair;
+                return (second, first)
+            }
+
+            fn count<T>(n: i32, x: T) -> i32 {
+                if n > 0 {
+                    return count(n - 1, x) + 1
+                };
+                return 0
+            }
+
+            fn main() -> i32 {
+                let big = max(3, 7);
+                let f = max(1.5, 0.5);
+                print_f64(f);
+                let (flag, num) = swap((4u8, true));
+                print_bool(flag);
+                let byte = max(num, 9u8);
+                return big + (byte as i32) + (count(3, f) * 100)
+            }
+        ",
+            "",
+        );
+        assert_eq!(compiled, (ExprRep::Int(316), "1.5true".to_string()));
+        assert_eq!(interp, compiled);
+    }
+
+    #[test]
+    fn test_llvm_trait() {
+        let (interp, compiled) = run_both(
+            "
+            struct Rect {
+                w: i32,
+                h: i32,
+            }
+
+            trait Shape {
+                fn area(self) -> i32;
+                fn scaled(self, by: i32) -> Self;
+            }
+
+            trait Describe {
+                fn describe(self) -> bool;
+            }
+
+            impl Shape for Rect {
+                fn area(self) -> i32 {
+                    return self.w * self.h
+                }
+
+                fn scaled(self, by: i32) -> Self {
+                    return Rect { w: self.w * by, h: self.h * by }
+                }
+            }
+
+            impl Shape for u8 {
+                fn area(self) -> i32 {
+                    let side = self as i32;
+                    return side * side
+                }
+
+                fn scaled(self, by: i32) -> u8 {
+                    return self * (by as u8)
+                }
+            }
+
+            impl Describe for Rect {
+                fn describe(self) -> bool {
+                    print_i32(self.w);
+                    return self.w == self.h
+                }
+            }
+
+            fn total<T: Shape>(shape: T, times: i32) -> i32 {
+                let one = shape.area();
+                return one * times
+            }
+
+            fn main() -> i32 {
+                let r = Rect { w: 2, h: 3 };
+                let square = r.describe();
+                print_bool(square);
+                let bigger = r.scaled(2).area();
+                let side = 3u8;
+                return total(r, 10) + (total(side.scaled(2), 1) * 1000) + (bigger * 100000)
+            }
+        ",
+            "",
+        );
+        assert_eq!(compiled, (ExprRep::Int(2436060), "2false".to_string()));
+        assert_eq!(interp, compiled);
+    }
+
+    #[test]
+    fn test_llvm_block_value() {
+        let (interp, compiled) = run_both(
+            r#"
+            enum Shape {
+                Circle(i32),
+                Rect(i32, i32),
+            }
+
+            fn area(s: Shape) -> i32 {
+                match s {
+                    Shape::Circle(r) => r * r * 3,
+                    Shape::Rect(w, h) => w * h,
+                }
+            }
+
+            fn max(a: i32, b: i32) -> i32 {
+                if a > b { a } else { b }
+            }
+
+            fn sign(v: f64) -> str {
+                if v < 0.0 { "negative" } else { "positive" }
+            }
+
+            fn report(n: i32) -> () {
+                print_i32(n)
+            }
+
+            fn main() -> i32 {
+                let is_big = max(3, 4) > 3;
+                let byte: u8 = if is_big { 10u8 } else { 20u8 };
+                let scaled = {
+                    let five = 5;
+                    five * 2
+                };
+                let total = area(Shape::Rect(2, 3)) + { scaled };
+                let label = sign(0.0 - 1.5);
+                print_str(label);
+                report(total);
+                let circle_area = area(Shape::Circle(2));
+                let picked = match circle_area { 12 => byte as i32, _ => 0 };
+                let larger = max(if is_big { 7 } else { 8 }, 5);
+                picked + (scaled * 100) + (total * 1000) + (larger * 100000)
+            }
+        "#,
+            "",
+        );
+        assert_eq!(compiled, (ExprRep::Int(717010), "negative16".to_string()));
+        assert_eq!(interp, compiled);
+    }
+
+    #[test]
+    fn test_llvm_closure() {
+        let (interp, compiled) = run_both(
+            "
+            fn apply(f: fn(i32) -> i32, x: i32) -> i32 {
+                return f(x)
+            }
+
+            fn double(x: i32) -> i32 {
+                return x * 2
+            }
+
+            fn adder(k: i32) -> fn(i32) -> i32 {
+                return |x: i32| x + k
+            }
+
+            fn twice<T>(f: fn(T) -> T, x: T) -> T {
+                let once = f(x);
+                return f(once)
+            }
+
+            fn main() -> i32 {
+                let n = 10;
+                let add_n = |v: i32| v + n;
+                n = 20;
+                let v = 1;
+                let added = add_n(5);
+                let doubled = apply(double, v);
+                let add_three = adder(3);
+                let counted = add_three(4);
+                let half = twice(|h: f64| h / 2.0, 10.0);
+                print_f64(half);
+                let shout = |flag: bool| print_bool(flag);
+                shout(true);
+                return added + (doubled * 100) + (counted * 1000) + (v * 10000)
+            }
+        ",
+            "",
+        );
+        assert_eq!(compiled, (ExprRep::Int(17215), "2.5true".to_string()));
+        assert_eq!(interp, compiled);
+    }
+
+    #[test]
+    fn test_llvm_enum() {
+        let (interp, compiled) = run_both(
+            r#"
+            enum Shape { Circle(i32), Rect(i32, i32), Empty }
+
+            enum Tagged { Shape(bool, Shape), Label(str) }
+
+            fn area(s: Shape) -> i32 {
+                match s {
+                    Shape::Circle(r) => { return r * r * 3 },
+                    Shape::Rect(w, 0) => { return 0 - w },
+                    Shape::Rect(w, h) => { return w * h },
+                    Shape::Empty => { return 0 },
+                };
+                return 0
+            }
+
+            fn describe(t: Tagged) -> i32 {
+                match t {
+                    Tagged::Shape(true, shape) => { return area(shape) },
+                    Tagged::Label(l) => { print_str(l); },
+                    _ => { print_str("hidden"); },
+                };
+                return 0
+            }
+
+            fn main() -> i32 {
+                let rect: Shape = Shape::Rect(4, 0);
+                print_i32(area(rect));
+                rect = Shape::Rect(2, 5);
+                let hidden: i32 = 0;
+                hidden = describe(Tagged::Label("x"));
+                hidden = describe(Tagged::Shape(false, rect));
+                match 2 {
+                    1 => { print_i32(1); },
+                    n => { print_i32(n); },
+                };
+                return describe(Tagged::Shape(true, Shape::Circle(2))) + area(rect)
+            }
+        "#,
+            "",
+        );
+        assert_eq!(compiled, (ExprRep::Int(22), "-4xhidden2".to_string()));
+        assert_eq!(interp, compiled);
+    }
+
+    #[test]
+    fn test_llvm_for() {
+        let (interp, compiled) = run_both(
+            r#"
+            fn sum_odd(n: i32) -> i32 {
+                let sum = 0;
+                for k in 0..n {
+                    let parity = k % 2;
+                    if parity == 0 {
+                        continue
+                    };
+                    sum += k;
+                };
+                return sum
+            }
+
+            fn first_square_above(limit: i32) -> i32 {
+                let found = 0;
+                for r in 1..=limit {
+                    let square = r * r;
+                    if square > limit {
+                        found = r;
+                        break
+                    };
+                };
+                return found
+            }
+
+            fn main() -> i32 {
+                let count = 0;
+                for b in 250u8..=255u8 {
+                    count += 1;
+                };
+                let steps = 0;
+                while steps < 5 {
+                    steps += 1;
+                };
+                let x = 7;
+                for x in 0..3 {
+                    print_i32(x);
+                };
+                sum_odd(10) + (first_square_above(20) * 100) + (count * 1000) + (steps * 10000) + (x * 100000)
+            }
+        "#,
+            "",
+        );
+        assert_eq!(compiled, (ExprRep::Int(756525), "012".to_string()));
+        assert_eq!(interp, compiled);
+    }
+
+    #[test]
+    fn test_llvm_loop() {
+        let (interp, compiled) = run_both(
+            r#"
+            fn first_multiple(start: i32, divisor: i32) -> i32 {
+                let candidate = start;
+                loop {
+                    let remainder = candidate % divisor;
+                    if remainder == 0 {
+                        break candidate
+                    };
+                    candidate += 1;
+                }
+            }
+
+            fn collatz_steps(start: i32) -> i32 {
+                let current = start;
+                let steps = 0;
+                let reached_one = loop {
+                    if current == 1 {
+                        break true
+                    };
+                    let parity = current % 2;
+                    if parity == 0 {
+                        current = current / 2;
+                    } else {
+                        current = (current * 3) + 1;
+                    };
+                    steps += 1;
+                };
+                if reached_one { steps } else { 0 }
+            }
+
+            fn spin() -> i32 {
+                loop {}
             }
-        ",
-        )
-        .unwrap()
-        .1;
-        let t = type_checker(p.clone());
 
-        if t {
-            assert!(llvm(p).is_ok());
-        }
+            fn main() -> i32 {
+                let counted = 0;
+                loop {
+                    counted += 1;
+                    if counted == 4 {
+                        break
+                    };
+                    continue;
+                };
+                let found = first_multiple(10, 7);
+                print_i32(found);
+                let picked = if found > 100 { loop {} } else { found + 1 };
+                collatz_steps(6) + (counted * 100) + (picked * 1000)
+            }
+        "#,
+            "",
+        );
+        assert_eq!(compiled, (ExprRep::Int(15408), "14".to_string()));
+        assert_eq!(interp, compiled);
     }
 
     #[test]
-    fn test_llvm_let_var_expr() {
-        let p = parser(" fn main() -> i32 {let a: i32 = 3; a += 2; return a} ")
-            .unwrap()
-            .1;
-        let t = type_checker(p.clone());
+    fn test_llvm_globals() {
+        let (interp, compiled) = run_both(
+            r#"
+            const LIMIT: i32 = 4 * 5;
+            const MASK: u8 = (1u8 + 2u8) * 16u8;
+            const OFFSET: i8 = 1i8 - 3i8;
+            static PRIMES: [i32; 4] = [2, 3, 5, 7];
+            static NAME: str = "ab" + "c";
+            static mut COUNTER: i32 = LIMIT / 2;
+
+            struct Point {
+                x: i32,
+                y: i32
+            }
 
-        if t {
-            assert!(llvm(p).is_ok());
-        }
+            static ORIGIN: Point = Point { y: 2, x: 1 };
 
-        let p = parser(" fn main() -> i32 {let a: i32 = 3; a -= 2; return a} ")
-            .unwrap()
-            .1;
-        let t = type_checker(p.clone());
+            fn bump(n: i32) -> i32 {
+                COUNTER += n;
+                COUNTER
+            }
 
-        if t {
-            assert!(llvm(p).is_ok());
-        }
+            fn main() -> i32 {
+                let bumped = bump(PRIMES[3]);
+                let add = |x: i32| x + COUNTER;
+                print_str(NAME);
+                print_i32(OFFSET as i32);
+                let total = add(ORIGIN.x + ORIGIN.y);
+                total + LIMIT + (MASK as i32) + (bumped * 100)
+            }
+        "#,
+            "",
+        );
+        assert_eq!(compiled, (ExprRep::Int(1788), "abc-2".to_string()));
+        assert_eq!(interp, compiled);
+    }
 
-        let p = parser(" fn main() -> i32 {let a: i32 = 3; a /= 2; return a} ")
-            .unwrap()
-            .1;
-        let t = type_checker(p.clone());
+    #[test]
+    fn test_llvm_modules() {
+        let (interp, compiled) = run_both(
+            r#"
+            mod math {
+                pub const BASE: i32 = 10;
+
+                fn double(x: i32) -> i32 {
+                    x * 2
+                }
 
-        if t {
-            assert!(llvm(p).is_ok());
-        }
+                pub fn scale(x: i32) -> i32 {
+                    let doubled = double(x);
+                    doubled * BASE
+                }
 
-        let p = parser(" fn main() -> i32 {let a: i32 = 3; a *= 2; return a} ")
-            .unwrap()
-            .1;
-        let t = type_checker(p.clone());
+                pub mod shapes {
+                    pub fn area(w: i32, h: i32) -> i32 {
+                        super::scale(w * h)
+                    }
+                }
+            }
 
-        if t {
-            assert!(llvm(p).is_ok());
-        }
+            use math::shapes::area;
+
+            fn main() -> i32 {
+                let scaled = math::scale(3);
+                print_i32(math::BASE);
+                scaled + area(2, 4)
+            }
+        "#,
+            "",
+        );
+        assert_eq!(compiled, (ExprRep::Int(220), "10".to_string()));
+        assert_eq!(interp, compiled);
     }
 
     #[test]
-    fn test_llvm_if() {
-        let p = parser(" fn main() -> i32 { if true { return 1 }; return 2 } ")
-            .unwrap()
-            .1;
-        let t = type_checker(p.clone());
+    fn test_llvm_forward_refs() {
+        let (interp, compiled) = run_both(
+            r#"
+            fn main() -> i32 {
+                let even = is_even(7);
+                let parity = even as i32;
+                let square = Square { side: 3 };
+                let doubled = twice(square.area());
+                print_i32(larger(true, 4, doubled));
+                parity + doubled
+            }
 
-        if t {
-            assert!(llvm(p).is_ok());
-        }
+            fn is_even(n: i32) -> bool {
+                if n == 0 {
+                    return true
+                };
+                is_odd(n - 1)
+            }
 
-        let p = parser(" fn main() -> bool { if true { return false }; return true } ")
-            .unwrap()
-            .1;
-        let t = type_checker(p.clone());
+            fn is_odd(n: i32) -> bool {
+                if n == 0 {
+                    return false
+                };
+                is_even(n - 1)
+            }
 
-        if t {
-            assert!(llvm(p).is_ok());
-        }
+            fn larger<T>(tag: T, a: i32, b: i32) -> i32 {
+                let m = pick(a, b);
+                m
+            }
 
-        let p = parser(
-            "        
-            fn testfn3() -> i32 {
-                let f: bool = true && true;
-                let n: i32 = 1;
-                if f == true {
-                    n += 1;
-                    f = false;
+            fn pick(a: i32, b: i32) -> i32 {
+                if a > b {
+                    return a
                 };
-                return n;    
+                b
             }
 
-            fn main() -> i32 {
-                {{{ return testfn3(); }}}
-            }",
-        )
-        .unwrap()
-        .1;
-        let t = type_checker(p.clone());
+            fn twice(x: i32) -> i32 {
+                x * 2
+            }
 
-        if t {
-            assert!(llvm(p).is_ok());
-        }
+            trait Area {
+                fn area(self) -> i32;
+            }
+
+            impl Area for Square {
+                fn area(self) -> i32 {
+                    let side = self.side;
+                    side * side
+                }
+            }
+
+            struct Square {
+                side: i32
+            }
+        "#,
+            "",
+        );
+        assert_eq!(compiled, (ExprRep::Int(18), "18".to_string()));
+        assert_eq!(interp, compiled);
+    }
 
+    #[test]
+    fn test_llvm_fn_runtime_args() {
         let p = parser(
-            "        
-            fn testfn3() -> i32 {
-                let f: bool = true && true;
-                let n: i32 = 1;
-                if 1 == 1 {
-                    n += 1;
-                    f = false;
-                };
-                return n;    
+            "
+            fn add(a: i32, b: i32) -> i32 {
+                return a + b
             }
 
             fn main() -> i32 {
-                {{{ return testfn3(); }}}
-            }",
+                let x: i32 = 2;
+                return add(x, add(3, 4))
+            }
+        ",
         )
         .unwrap()
         .1;
-        let t = type_checker(p.clone());
-
-        if t {
-            assert!(llvm(p).is_ok());
-        }
+        assert_eq!(llvm(p).unwrap(), ExprRep::Int(9));
 
         let p = parser(
-            "        
-            fn testfn3() -> i32 {
-                let f: bool = true && true;
-                let n: i32 = 1;
-                if true == true {
-                    n += 1;
-                    f = false;
-                };
-                return n;    
+            "
+            fn and(a: bool, b: bool) -> bool {
+                let c: bool = a && b;
+                return c
             }
 
-            fn main() -> i32 {
-                {{{ return testfn3(); }}}
-            }",
+            fn main() -> bool {
+                let x: bool = true;
+                return and(x, and(x, false))
+            }
+        ",
         )
         .unwrap()
         .1;
-        let t = type_checker(p.clone());
-
-        if t {
-            assert!(llvm(p).is_ok());
-        }
+        assert_eq!(llvm(p).unwrap(), ExprRep::Bool(false));
     }
 
-    #[test]
-    fn test_llvm_if_else() {
-        let p = parser(" fn main() -> i32 { if false { return 1 } else { return 3 }; return 2 } ")
-            .unwrap()
-            .1;
-        let t = type_checker(p.clone());
+    /// Small deterministic generator so the differential tests are reproducible.
+    struct Lcg(u64);
 
-        if t {
-            assert!(llvm(p).is_ok());
+    impl Lcg {
+        fn next(&mut self) -> u64 {
+            self.0 = self
+                .0
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            self.0 >> 33
         }
 
-        let p = parser(
-            " fn main() -> bool { if false { return true } else { return false }; return true } ",
-        )
-        .unwrap()
-        .1;
-        let t = type_checker(p.clone());
+        fn below(&mut self, n: u64) -> u64 {
+            self.next() % n
+        }
+    }
 
-        if t {
-            assert!(llvm(p).is_ok());
+    fn gen_int_expr(rng: &mut Lcg, depth: u32) -> Expr {
+        if depth == 0 || rng.below(4) == 0 {
+            return Expr::Int(rng.below(2000) as i32);
         }
+
+        let op = match rng.below(5) {
+            0 => AriOp::Add,
+            1 => AriOp::Sub,
+            2 => AriOp::Mul,
+            3 => AriOp::Div,
+            _ => AriOp::Rem,
+        };
+
+        Expr::BinExpr(
+            Box::new(gen_int_expr(rng, depth - 1)),
+            Op::AriOp(op),
+            Box::new(gen_int_expr(rng, depth - 1)),
+        )
     }
 
-    #[test]
-    fn test_llvm_when() {
-        let p = parser(" fn main() -> i32 { while true { return 1 }; return 2 } ")
-            .unwrap()
-            .1;
-        let t = type_checker(p.clone());
+    fn gen_rel_expr(rng: &mut Lcg, depth: u32) -> Expr {
+        let op = match rng.below(6) {
+            0 => RelOp::Eq,
+            1 => RelOp::Neq,
+            2 => RelOp::Leq,
+            3 => RelOp::Geq,
+            4 => RelOp::Les,
+            _ => RelOp::Gre,
+        };
 
-        if t {
-            assert!(llvm(p).is_ok());
+        Expr::BinExpr(
+            Box::new(gen_int_expr(rng, depth)),
+            Op::RelOp(op),
+            Box::new(gen_int_expr(rng, depth)),
+        )
+    }
+
+    /// Reference `i32` semantics, `None` if the expression overflows or divides by zero.
+    fn checked_eval(expr: &Expr) -> Option<i32> {
+        match expr {
+            Expr::Int(i) => Some(*i),
+            Expr::BinExpr(l, Op::AriOp(op), r) => {
+                let (l, r) = (checked_eval(l)?, checked_eval(r)?);
+                match op {
+                    AriOp::Add => l.checked_add(r),
+                    AriOp::Sub => l.checked_sub(r),
+                    AriOp::Mul => l.checked_mul(r),
+                    AriOp::Div => l.checked_div(r),
+                    AriOp::Rem => l.checked_rem(r),
+                }
+            }
+            Expr::BinExpr(l, Op::RelOp(_), r) => {
+                checked_eval(l)?;
+                checked_eval(r)?;
+                Some(0)
+            }
+            _ => None,
         }
+    }
 
-        let p = parser(" fn main() -> bool { while true { return false }; return true } ")
-            .unwrap()
-            .1;
-        let t = type_checker(p.clone());
+    /// Replaces every literal with a load from its own alloca, so that the
+    /// builder cannot constant fold the expression away.
+    fn spill_literals(compiler: &mut Compiler<'_, '_>, expr: Expr, n: &mut usize) -> Expr {
+        match expr {
+            Expr::Int(i) => {
+                let name = format!("lit{}", n);
+                let id = Some(*n);
+                *n += 1;
 
-        if t {
-            assert!(llvm(p).is_ok());
+                let alloca = compiler.create_entry_block_alloca(&name, id, Type::Int);
+                let val = compiler.compile_int(i);
+                compiler.builder.build_store(alloca, val);
+
+                Expr::Var(name, id)
+            }
+            Expr::BinExpr(l, op, r) => {
+                let l = spill_literals(compiler, *l, n);
+                let r = spill_literals(compiler, *r, n);
+                Expr::BinExpr(Box::new(l), op, Box::new(r))
+            }
+            expr => expr,
         }
     }
 
-    #[test]
-    fn test_llvm_fn() {
-        let p = parser(" fn testfn() -> i32 {return 2} fn main() -> i32 {return testfn()} ")
-            .unwrap()
-            .1;
-        let t = type_checker(p.clone());
+    /// Compiles a single expression into a function and runs it in the JIT.
+    fn jit_eval(expr: Expr, wrapping: bool) -> ExprRep {
+        let context = Context::create();
+        let module = context.create_module("differential");
+        let builder = context.create_builder();
+        let execution_engine = module
+            .create_jit_execution_engine(OptimizationLevel::None)
+            .unwrap();
 
-        if t {
-            assert!(llvm(p).is_ok());
-        }
+        let mut compiler = Compiler {
+            context: &context,
+            builder: &builder,
+            module: &module,
+            execution_engine: &execution_engine,
+            fn_value_opt: None,
+            variables: HashMap::new(),
+            var_types: HashMap::new(),
+            structs: HashMap::new(),
+            enums: HashMap::new(),
+            signatures: HashMap::new(),
+            globals: HashMap::new(),
+            generic_fns: HashMap::new(),
+            type_args: HashMap::new(),
+            ret_type: Type::Int,
+            loops: Vec::new(),
+
+            statement: (builder.build_return(None), false),
+
+            wrapping,
+            span: Span::default(),
+            debug: None,
+        };
 
-        let p = parser(" fn testfn2() -> i32 {return 2} fn testfn() -> i32 {return 1} fn main() -> i32 {let a:i32 = testfn() + testfn2(); return a} ")
-            .unwrap()
-            .1;
-        let t = type_checker(p.clone());
+        let function = module.add_function("expr", context.i32_type().fn_type(&[], false), None);
+        let entry = context.append_basic_block(function, "entry");
+        compiler.fn_value_opt = Some(function);
+        builder.position_at_end(entry);
+
+        let expr = spill_literals(&mut compiler, expr, &mut 0);
+        let val = compiler.compile_stmt(expr);
+        let is_bool = val.get_type().get_bit_width() == 1;
+        let ret = match is_bool {
+            true => builder.build_int_z_extend(val, context.i32_type(), "ret"),
+            false => val,
+        };
+        builder.build_return(Some(&ret));
+        compiler.link_runtime().unwrap();
 
-        if t {
-            assert!(llvm(p).is_ok());
+        let compiled: JitFunction<ExprFunc> =
+            unsafe { execution_engine.get_function("expr").ok().unwrap() };
+        let res = unsafe { compiled.call() };
+
+        match is_bool {
+            true => ExprRep::Bool(res != 0),
+            false => ExprRep::Int(res),
         }
+    }
 
-        let p = parser(" fn testfn() -> i32 { if false { return 1 } else { return 3 }; return 2 } fn main() -> i32 {return testfn()} ")
-            .unwrap()
-            .1;
-        let t = type_checker(p.clone());
+    fn assert_backends_agree(expr: Expr) {
+        assert_eq!(
+            jit_eval(expr.clone(), false),
+            interpreter(vec![expr.clone()]),
+            "backends disagree on {:?}",
+            expr
+        );
+    }
 
-        if t {
-            assert!(llvm(p).is_ok());
+    #[test]
+    fn test_llvm_signed_semantics() {
+        let cases = vec![
+            (Expr::Int(7), AriOp::Div, Expr::Int(2)),
+            (Expr::Int(7), AriOp::Rem, Expr::Int(2)),
+            (
+                Expr::BinExpr(
+                    Box::new(Expr::Int(0)),
+                    Op::AriOp(AriOp::Sub),
+                    Box::new(Expr::Int(7)),
+                ),
+                AriOp::Div,
+                Expr::Int(2),
+            ),
+            (
+                Expr::BinExpr(
+                    Box::new(Expr::Int(0)),
+                    Op::AriOp(AriOp::Sub),
+                    Box::new(Expr::Int(7)),
+                ),
+                AriOp::Rem,
+                Expr::Int(2),
+            ),
+        ];
+
+        for (l, op, r) in cases {
+            assert_backends_agree(Expr::BinExpr(Box::new(l), Op::AriOp(op), Box::new(r)));
         }
-        let p = parser(
-            " fn main() -> i32 { let a:i32 = 2; let b:i32 = 3; let c:i32 = a + b; return c} ",
-        )
-        .unwrap()
-        .1;
-        let t = type_checker(p.clone());
 
-        if t {
-            assert!(llvm(p).is_ok());
+        for op in [RelOp::Les, RelOp::Gre, RelOp::Leq, RelOp::Geq] {
+            assert_backends_agree(Expr::BinExpr(
+                Box::new(Expr::BinExpr(
+                    Box::new(Expr::Int(0)),
+                    Op::AriOp(AriOp::Sub),
+                    Box::new(Expr::Int(1)),
+                )),
+                Op::RelOp(op),
+                Box::new(Expr::Int(1)),
+            ));
+            assert_backends_agree(Expr::BinExpr(
+                Box::new(Expr::Bool(false)),
+                Op::RelOp(op),
+                Box::new(Expr::Bool(true)),
+            ));
         }
-        let p = parser(" fn testfn(a:i32) -> i32 {return a} fn main() -> i32 {return testfn(1)} ")
-            .unwrap()
-            .1;
-        let t = type_checker(p.clone());
+    }
 
-        if t {
-            assert!(llvm(p).is_ok());
+    #[test]
+    fn test_llvm_wrapping() {
+        let bin_expr = |l: i32, op: AriOp, r: i32| {
+            Expr::BinExpr(
+                Box::new(Expr::Int(l)),
+                Op::AriOp(op),
+                Box::new(Expr::Int(r)),
+            )
+        };
+
+        assert_eq!(
+            jit_eval(bin_expr(i32::MAX, AriOp::Add, 1), true),
+            ExprRep::Int(i32::MIN)
+        );
+        assert_eq!(
+            jit_eval(bin_expr(i32::MIN, AriOp::Sub, 1), true),
+            ExprRep::Int(i32::MAX)
+        );
+        assert_eq!(
+            jit_eval(bin_expr(i32::MIN, AriOp::Div, -1), true),
+            ExprRep::Int(i32::MIN)
+        );
+        assert_eq!(
+            jit_eval(bin_expr(i32::MIN, AriOp::Rem, -1), true),
+            ExprRep::Int(0)
+        );
+        assert_eq!(
+            jit_eval(bin_expr(-7, AriOp::Div, 2), true),
+            ExprRep::Int(-3)
+        );
+    }
+
+    #[test]
+    fn test_llvm_differential_int_expr() {
+        let mut rng = Lcg(0x5eed);
+        let mut checked = 0;
+
+        while checked < 500 {
+            let expr = gen_int_expr(&mut rng, 4);
+            if checked_eval(&expr).is_none() {
+                continue;
+            }
+            assert_backends_agree(expr);
+            checked += 1;
         }
-        let p = parser(
-            " fn testfn(a:bool) -> bool {return a} fn main() -> bool {return testfn(true)} ",
-        )
-        .unwrap()
-        .1;
-        let t = type_checker(p.clone());
+    }
 
-        if t {
-            assert!(llvm(p).is_ok());
+    #[test]
+    fn test_llvm_differential_rel_expr() {
+        let mut rng = Lcg(0xc0ffee);
+        let mut checked = 0;
+
+        while checked < 500 {
+            let expr = gen_rel_expr(&mut rng, 3);
+            if checked_eval(&expr).is_none() {
+                continue;
+            }
+            assert_backends_agree(expr);
+            checked += 1;
         }
     }
 }
@@ -3,11 +3,17 @@ extern crate lazy_static;
 
 mod ast;
 mod cli;
+mod const_eval;
+mod host;
 mod interpreter;
 mod llvm;
 mod memory;
+mod modules;
 mod parser;
+mod patterns;
 mod program;
+mod resolver;
+mod runtime;
 mod type_checker;
 
 fn main() {
@@ -1,5 +1,6 @@
 use crate::ast::*;
 
+use std::cell::RefCell;
 use std::collections::HashMap;
 
 use lazy_static;
@@ -7,27 +8,69 @@ use std::sync::Mutex;
 #[derive(Debug, PartialEq, Clone)]
 pub enum ExprRep {
     Int(i32),
+    /// Value of any other integer type, always within the range of the type
+    TypedInt(i128, Type),
+    Float(f64),
     Var(String),
     Bool(bool),
+    Str(String),
+    Array(Vec<ExprRep>),
+    /// Struct name and field values
+    Struct(String, Vec<(String, ExprRep)>),
+    /// Enum name, variant name and field values
+    Variant(String, String, Vec<ExprRep>),
+    Tuple(Vec<ExprRep>),
 
-    Fn(Vec<(Expr, Type)>, Type, Vec<Expr>),
+    /// Type parameters, parameters, return type and body
+    Fn(Vec<String>, Vec<(Expr, Type)>, Type, Vec<Expr>),
+    /// Name, parameters and return type
+    ExternFn(String, Vec<(Expr, Type)>, Type),
+    /// Parameters, body and the values of the variables it captured
+    Closure(Vec<(Expr, Type)>, Box<Expr>, Vec<(DeclId, ExprRep)>),
 
+    /// Result of a `break` statement, ends the innermost loop with the value of the `break`
+    /// or `Null`
+    Break(Box<ExprRep>),
+    /// Result of a `continue` statement, ends the current iteration of the innermost loop
+    Continue,
+    /// Result of a `return` statement, ends the function call with the value
+    Return(Box<ExprRep>),
     Null,
 }
 
+impl ExprRep {
+    /// Whether this is the result of a `return`, `break` or `continue`, which leaves every
+    /// block up to the function or loop it belongs to.
+    pub fn is_jump(&self) -> bool {
+        matches!(
+            self,
+            ExprRep::Return(_) | ExprRep::Break(_) | ExprRep::Continue
+        )
+    }
+}
+
+thread_local! {
+    /// Variables of the running program by their declarations, the first frame holds the
+    /// globals and every function call pushes a frame for its own variables.
+    static FRAMES: RefCell<Vec<HashMap<DeclId, ExprRep>>> = RefCell::new(vec![HashMap::new()]);
+}
+
 lazy_static! {
-    static ref MEMORY: Mutex<HashMap<&'static str, ExprRep>> = {
-        let m = HashMap::new();
-        Mutex::new(m)
-    };
-    static ref SCOPE: Mutex<Vec<Mutex<HashMap<&'static str, ExprRep>>>> = {
-        let s = Vec::new();
-        Mutex::new(s)
-    };
     static ref FUNCTION_MAP: Mutex<HashMap<&'static str, ExprRep>> = {
         let f = HashMap::new();
         Mutex::new(f)
     };
+    static ref SPAN: Mutex<Span> = Mutex::new(Span::default());
+}
+
+/// Sets the span of the statement being evaluated, returns the previous one
+pub fn set_span(span: Span) -> Span {
+    let mut current = SPAN.lock().unwrap();
+    std::mem::replace(&mut *current, span)
+}
+
+pub fn read_span() -> Span {
+    *SPAN.lock().unwrap()
 }
 
 pub fn insert_fn(var: ExprRep, func: ExprRep) -> ExprRep {
@@ -41,31 +84,86 @@ pub fn insert_fn(var: ExprRep, func: ExprRep) -> ExprRep {
     return ExprRep::Null;
 }
 
-pub fn insert_var(key: ExprRep, val: ExprRep) -> ExprRep {
-    match key {
-        ExprRep::Var(v) => {
-            let mut map = MEMORY.lock().unwrap();
-            map.insert(Box::leak(v.into_boxed_str()), val);
-        }
-        _ => panic!("Could not insert key '{:?}' into map", key),
-    }
+/// Assigns a variable of the current call, or the global if the call has no such
+/// variable.
+pub fn insert_var(key: DeclId, val: ExprRep) -> ExprRep {
+    FRAMES.with(|frames| {
+        let mut frames = frames.borrow_mut();
+        let global = !frames.last().unwrap().contains_key(&key) && frames[0].contains_key(&key);
+        let frame = match global {
+            true => frames.first_mut(),
+            false => frames.last_mut(),
+        };
+        frame.unwrap().insert(key, val);
+    });
     return ExprRep::Null;
 }
 
-pub fn read_var(key: &str) -> ExprRep {
-    let map = MEMORY.lock().unwrap();
+/// Binds a new variable in the current call.
+pub fn bind_var(key: DeclId, val: ExprRep) -> ExprRep {
+    FRAMES.with(|frames| {
+        let mut frames = frames.borrow_mut();
+        frames.last_mut().unwrap().insert(key, val);
+    });
+    ExprRep::Null
+}
+
+/// Starts the variables of a function call, none of the caller's variables are visible
+/// until `pop_frame`.
+pub fn push_frame() {
+    FRAMES.with(|frames| frames.borrow_mut().push(HashMap::new()));
+}
 
-    match map.get(&key) {
+pub fn pop_frame() {
+    FRAMES.with(|frames| frames.borrow_mut().pop());
+}
+
+/// Forgets every variable, including the ones left by an earlier or failed run.
+pub fn clear_vars() {
+    FRAMES.with(|frames| *frames.borrow_mut() = vec![HashMap::new()]);
+}
+
+pub fn read_var(key: DeclId) -> ExprRep {
+    FRAMES.with(|frames| {
+        let frames = frames.borrow();
+        read_frames(&frames, key)
+    })
+}
+
+/// A variable of the current call, or else a global.
+fn read_frames(frames: &[HashMap<DeclId, ExprRep>], key: DeclId) -> ExprRep {
+    match frames
+        .last()
+        .unwrap()
+        .get(&key)
+        .or_else(|| frames[0].get(&key))
+    {
         Some(var) => match var {
             ExprRep::Int(num) => ExprRep::Int(*num),
+            ExprRep::TypedInt(num, int_type) => ExprRep::TypedInt(*num, int_type.clone()),
+            ExprRep::Float(num) => ExprRep::Float(*num),
             ExprRep::Bool(b) => ExprRep::Bool(*b),
+            ExprRep::Str(s) => ExprRep::Str(s.to_string()),
+            ExprRep::Array(elems) => ExprRep::Array(elems.clone()),
+            ExprRep::Struct(name, fields) => ExprRep::Struct(name.clone(), fields.clone()),
+            ExprRep::Variant(name, variant, fields) => {
+                ExprRep::Variant(name.clone(), variant.clone(), fields.clone())
+            }
+            ExprRep::Tuple(elems) => ExprRep::Tuple(elems.clone()),
             ExprRep::Var(v) => ExprRep::Var(v.to_string()),
+            ExprRep::Fn(..) | ExprRep::ExternFn(..) | ExprRep::Closure(..) => var.clone(),
             _ => panic!("Could not find var '{:#?}' in map", var),
         },
         None => ExprRep::Null,
     }
 }
 
+/// The function named `key`, if there is one.
+pub fn lookup_fn(key: &str) -> Option<ExprRep> {
+    let map = FUNCTION_MAP.lock().unwrap();
+    map.get(key).cloned()
+}
+
 pub fn read_fn(key: &str) -> ExprRep {
     let map = FUNCTION_MAP.lock().unwrap();
     match map.get(key) {
@@ -0,0 +1,625 @@
+use crate::ast::*;
+use crate::parser::*;
+use crate::runtime::*;
+
+use std::{collections::HashMap, fmt, fs, path::Path};
+
+/// Error found while loading the modules of a program or resolving the names and paths
+/// in it, reported before type checking.
+#[derive(Debug, PartialEq, Clone)]
+pub struct ResolveError {
+    pub span: Span,
+    pub msg: String,
+}
+
+impl fmt::Display for ResolveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "resolve error at {}: {}", self.span, self.msg)
+    }
+}
+
+/// Loads the items of each `mod name;` from `name.rs` or `name/mod.rs`, `dir` is the
+/// directory of the file the items are from. The modules of `name` are in `dir/name`.
+pub fn load_modules(ast: Vec<Expr>, dir: &Path) -> Result<Vec<Expr>, ResolveError> {
+    ast.into_iter().map(|item| load_item(item, dir)).collect()
+}
+
+fn load_item(item: Expr, dir: &Path) -> Result<Expr, ResolveError> {
+    match item {
+        Expr::Spanned(span, item) => match load_item(*item, dir) {
+            Ok(item) => Ok(Expr::Spanned(span, Box::new(item))),
+            Err(e) if e.span == Span::default() => Err(ResolveError { span, msg: e.msg }),
+            Err(e) => Err(e),
+        },
+        Expr::Pub(item) => Ok(Expr::Pub(Box::new(load_item(*item, dir)?))),
+        Expr::Mod(name, Some(items)) => {
+            let items = load_modules(items, &dir.join(&name))?;
+            Ok(Expr::Mod(name, Some(items)))
+        }
+        Expr::Mod(name, None) => {
+            let file = [
+                dir.join(format!("{}.rs", name)),
+                dir.join(&name).join("mod.rs"),
+            ]
+            .iter()
+            .find(|file| file.is_file())
+            .cloned();
+            let source = match file.as_ref().map(fs::read_to_string) {
+                Some(Ok(source)) => source,
+                _ => {
+                    return Err(ResolveError {
+                        span: Span::default(),
+                        msg: format!("file not found for module `{}`", name),
+                    })
+                }
+            };
+            // Spans keep the name of the file, it lives as long as the program
+            let path: &'static str =
+                Box::leak(file.unwrap().display().to_string().into_boxed_str());
+            let items = match parse_file(&source, Some(path)) {
+                Ok(items) => items,
+                Err(span) => {
+                    return Err(ResolveError {
+                        span,
+                        msg: format!("could not parse module `{}`", name),
+                    })
+                }
+            };
+            let items = load_modules(items, &dir.join(&name))?;
+            Ok(Expr::Mod(name, Some(items)))
+        }
+        item => Ok(item),
+    }
+}
+
+/// An item of a flattened program with the path of the module it is declared in.
+pub type ModuleItem = (Vec<String>, Expr);
+
+/// Checks the paths between the modules of a program and flattens them into the items
+/// of a single module, each with the path of the module it is declared in. Items in a
+/// module are renamed to their paths like `math::add`, the items of the root module keep
+/// their names. The names in the bodies of the items are left to the resolver.
+pub fn flatten_modules(ast: Vec<Expr>) -> Result<(Vec<ModuleItem>, ModuleResolver), ResolveError> {
+    let mut resolver = ModuleResolver {
+        modules: HashMap::new(),
+        module: Vec::new(),
+        span: Span::default(),
+    };
+
+    let mut uses = Vec::new();
+    resolver.collect(Vec::new(), &ast, &mut uses)?;
+    // A `use` can go through the `pub use` of another module, the imports are retried
+    // until no more of them resolve
+    while !uses.is_empty() {
+        let count = uses.len();
+        let mut error = None;
+        let mut failed = Vec::new();
+        for (module, path, public, span) in uses {
+            resolver.module = module.clone();
+            resolver.span = span;
+            if let Err(e) = resolver.import(path.clone(), public) {
+                error.get_or_insert(e);
+                failed.push((module, path, public, span));
+            }
+        }
+        if failed.len() == count {
+            return Err(error.unwrap());
+        }
+        uses = failed;
+    }
+
+    resolver.module = Vec::new();
+    let mut items = Vec::new();
+    for item in ast {
+        resolver.flatten(item, None, &mut items);
+    }
+    Ok((items, resolver))
+}
+
+/// What a path refers to.
+#[derive(Debug, Clone)]
+enum Target {
+    /// A function, constant or static by its full path
+    Value(String),
+    Module(Vec<String>),
+}
+
+#[derive(Default)]
+struct Module {
+    /// Functions, constants and statics by name, with their kind and whether they are
+    /// `pub`
+    values: HashMap<String, (&'static str, bool)>,
+    /// Child modules by name, with whether they are `pub`
+    modules: HashMap<String, bool>,
+    /// What the names imported by `use` refer to, with whether they are `pub use`d
+    imports: HashMap<String, (Target, bool)>,
+}
+
+/// The modules of a program, follows the paths used in them.
+pub struct ModuleResolver {
+    /// Every module by its path, the root module has the empty path
+    modules: HashMap<Vec<String>, Module>,
+    /// Path of the module being resolved
+    module: Vec<String>,
+    span: Span,
+}
+
+/// Full name of the item `name` in `module`.
+fn item_path(module: &[String], name: &str) -> String {
+    module
+        .iter()
+        .map(|segment| segment.as_str())
+        .chain(std::iter::once(name))
+        .collect::<Vec<&str>>()
+        .join("::")
+}
+
+fn module_name(module: &[String]) -> String {
+    match module.is_empty() {
+        true => "crate".to_string(),
+        false => module.join("::"),
+    }
+}
+
+impl ModuleResolver {
+    fn error<T>(&self, msg: String) -> Result<T, ResolveError> {
+        Err(ResolveError {
+            span: self.span,
+            msg,
+        })
+    }
+
+    /// Records the items declared in `module` and its child modules, the paths of their
+    /// `use`s are resolved once every module is known.
+    fn collect(
+        &mut self,
+        module: Vec<String>,
+        items: &[Expr],
+        uses: &mut Vec<(Vec<String>, Vec<String>, bool, Span)>,
+    ) -> Result<(), ResolveError> {
+        self.modules.insert(module.clone(), Module::default());
+        for item in items {
+            let (item, public) = match item {
+                Expr::Spanned(span, item) => {
+                    self.span = *span;
+                    match &**item {
+                        Expr::Pub(item) => (&**item, true),
+                        item => (item, false),
+                    }
+                }
+                Expr::Pub(item) => (&**item, true),
+                item => (item, false),
+            };
+            let (name, kind) = match item {
+                Expr::Fn(fn_var, ..) => (fn_var, "function"),
+                Expr::Const(var, ..) => (var, "constant"),
+                Expr::Static(var, ..) => (var, "static"),
+                Expr::Mod(name, Some(items)) => {
+                    let modules = &mut self.modules.get_mut(&module).unwrap().modules;
+                    if modules.insert(name.to_string(), public).is_some() {
+                        return self.error(format!("the name `{}` is defined multiple times", name));
+                    }
+                    let mut child = module.clone();
+                    child.push(name.to_string());
+                    self.collect(child, items, uses)?;
+                    continue;
+                }
+                Expr::Mod(..) => panic!("Module not loaded!"),
+                Expr::Use(path) => {
+                    uses.push((module.clone(), path.clone(), public, self.span));
+                    continue;
+                }
+                _ if module.is_empty() => continue,
+                _ => {
+                    return self.error(
+                        "structs, enums, traits, impls and extern functions can only be declared in the root module"
+                            .to_string(),
+                    )
+                }
+            };
+            let name = match name.as_ref() {
+                Expr::Var(name, _) => name.to_string(),
+                _ => panic!("Invalid item name!"),
+            };
+            let values = &mut self.modules.get_mut(&module).unwrap().values;
+            if values.insert(name.to_string(), (kind, public)).is_some() {
+                return self.error(format!("the name `{}` is defined multiple times", name));
+            }
+        }
+        Ok(())
+    }
+
+    /// Adds the item or module at the end of `path` to the names of the current module.
+    fn import(&mut self, path: Vec<String>, public: bool) -> Result<(), ResolveError> {
+        let target = self.resolve_path(&path, false)?;
+        let name = path.last().unwrap().to_string();
+        let module = self.modules.get_mut(&self.module).unwrap();
+        if module.values.contains_key(&name) || module.imports.contains_key(&name) {
+            return self.error(format!("the name `{}` is defined multiple times", name));
+        }
+        module.imports.insert(name, (target, public));
+        Ok(())
+    }
+
+    /// Follows a path from the current module. It starts at `crate`, `self`, `super`, a
+    /// child module or, if `imports` is set, a module imported by `use`.
+    fn resolve_path(&self, path: &[String], imports: bool) -> Result<Target, ResolveError> {
+        let current = &self.modules[&self.module];
+        let mut module = match path[0].as_str() {
+            "crate" => Vec::new(),
+            "self" => self.module.clone(),
+            "super" if self.module.is_empty() => {
+                return self.error("there are too many leading `super` keywords".to_string())
+            }
+            "super" => self.module[..self.module.len() - 1].to_vec(),
+            first if current.modules.contains_key(first) => {
+                let mut module = self.module.clone();
+                module.push(first.to_string());
+                module
+            }
+            first => match current.imports.get(first) {
+                Some((Target::Module(module), _)) if imports => module.clone(),
+                _ => {
+                    return self.error(format!(
+                        "failed to resolve: use of undeclared crate or module `{}`",
+                        first
+                    ))
+                }
+            },
+        };
+
+        let rest = &path[1..];
+        for (i, segment) in rest.iter().enumerate() {
+            // Private items are visible in the module they are declared in and its
+            // descendants
+            let visible = |public: bool| public || self.module.starts_with(&module);
+            let items = &self.modules[&module];
+            match (items.values.get(segment), items.modules.get(segment)) {
+                (Some((kind, public)), _) if i + 1 == rest.len() => {
+                    if !visible(*public) {
+                        return self.error(format!("{} `{}` is private", kind, segment));
+                    }
+                    return Ok(Target::Value(item_path(&module, segment)));
+                }
+                (_, Some(public)) => {
+                    if !visible(*public) {
+                        return self.error(format!("module `{}` is private", segment));
+                    }
+                    module.push(segment.to_string());
+                }
+                // Re-exported by a `pub use`
+                _ if matches!(items.imports.get(segment), Some((_, public)) if visible(*public)) => {
+                    match &items.imports[segment].0 {
+                        Target::Value(path) if i + 1 == rest.len() => {
+                            return Ok(Target::Value(path.clone()))
+                        }
+                        Target::Module(target) => module = target.clone(),
+                        Target::Value(_) => {
+                            return self
+                                .error(format!("failed to resolve: `{}` is not a module", segment))
+                        }
+                    }
+                }
+                _ if i + 1 == rest.len() => {
+                    return self.error(format!(
+                        "cannot find `{}` in module `{}`",
+                        segment,
+                        module_name(&module)
+                    ))
+                }
+                _ => {
+                    return self.error(format!(
+                        "failed to resolve: could not find `{}` in `{}`",
+                        segment,
+                        module_name(&module)
+                    ))
+                }
+            }
+        }
+        Ok(Target::Module(module))
+    }
+
+    /// Whether a path starting with `name` in `module` goes through a module rather than
+    /// an enum.
+    pub fn is_module(&self, module: &[String], name: &str) -> bool {
+        let current = &self.modules[module];
+        match name {
+            "crate" | "self" | "super" => true,
+            _ => {
+                current.modules.contains_key(name)
+                    || matches!(current.imports.get(name), Some((Target::Module(_), _)))
+            }
+        }
+    }
+
+    /// Full name of the function, constant or static a name or path used in `module`
+    /// refers to. Names in the root module that are not items or imports are left as
+    /// they are.
+    pub fn resolve_value(
+        &mut self,
+        module: &[String],
+        name: &str,
+        span: Span,
+    ) -> Result<String, ResolveError> {
+        self.module = module.to_vec();
+        self.span = span;
+        let path: Vec<String> = name.split("::").map(|s| s.to_string()).collect();
+        let target = match path.len() {
+            1 => {
+                let current = &self.modules[module];
+                if current.values.contains_key(name) {
+                    return Ok(item_path(module, name));
+                }
+                match current.imports.get(name) {
+                    Some((target, _)) => target.clone(),
+                    None if module.is_empty() || is_builtin(name) => return Ok(name.to_string()),
+                    None => {
+                        return self.error(format!("cannot find value `{}` in this scope", name))
+                    }
+                }
+            }
+            _ => self.resolve_path(&path, true)?,
+        };
+        match target {
+            Target::Value(path) => Ok(path),
+            Target::Module(_) => self.error(format!("expected value, found module `{}`", name)),
+        }
+    }
+
+    /// Appends the items of a module and of its child modules to `items`, keeping their
+    /// spans.
+    fn flatten(&mut self, item: Expr, span: Option<Span>, items: &mut Vec<ModuleItem>) {
+        match item {
+            Expr::Spanned(span, item) => self.flatten(*item, Some(span), items),
+            Expr::Pub(item) => self.flatten(*item, span, items),
+            Expr::Use(_) => {}
+            Expr::Mod(name, items_in) => {
+                self.module.push(name);
+                for item in items_in.unwrap() {
+                    self.flatten(item, None, items);
+                }
+                self.module.pop();
+            }
+            item => {
+                let item = self.rename_item(item);
+                items.push((
+                    self.module.clone(),
+                    match span {
+                        Some(span) => Expr::Spanned(span, Box::new(item)),
+                        None => item,
+                    },
+                ));
+            }
+        }
+    }
+
+    /// The name of an item declared in the current module is its path.
+    fn rename_item(&self, item: Expr) -> Expr {
+        let rename = |var: Box<Expr>| match *var {
+            Expr::Var(name, id) => Box::new(Expr::Var(item_path(&self.module, &name), id)),
+            _ => panic!("Invalid item name!"),
+        };
+        match item {
+            Expr::Fn(fn_var, generics, params, ret_type, block) => {
+                Expr::Fn(rename(fn_var), generics, params, ret_type, block)
+            }
+            Expr::Const(var, ty, value) => Expr::Const(rename(var), ty, value),
+            Expr::Static(var, ty, value, mutable) => Expr::Static(rename(var), ty, value, mutable),
+            item => item,
+        }
+    }
+}
+
+#[cfg(test)]
+mod modules_tests {
+    use super::*;
+    use crate::resolver::*;
+
+    fn resolve_str(input: &str) -> Result<Vec<Expr>, ResolveError> {
+        resolve(parser(input).unwrap().1).map(|(items, _)| items)
+    }
+
+    /// Full names of the items and variables a statement of a function uses.
+    fn used_names(item: &Expr, stmt: usize) -> Vec<String> {
+        match item.clone().unspanned() {
+            Expr::Fn(_, _, _, _, block) => {
+                let mut vars = Vec::new();
+                block[stmt].referenced_vars(&mut vars);
+                vars.into_iter().map(|(name, _)| name).collect()
+            }
+            item => panic!("Unexpected item {:?}", item),
+        }
+    }
+
+    fn item_names(items: &[Expr]) -> Vec<String> {
+        items
+            .iter()
+            .map(|item| match item.clone().unspanned() {
+                Expr::Fn(fn_var, ..) | Expr::Const(fn_var, ..) => match *fn_var {
+                    Expr::Var(name, _) => name,
+                    _ => panic!("Invalid item name!"),
+                },
+                item => panic!("Unexpected item {:?}", item),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_resolve_modules() {
+        let items = resolve_str(
+            "
+            mod math {
+                pub const ONE: i32 = 1;
+
+                pub fn add(a: i32, b: i32) -> i32 {
+                    return a + b
+                }
+
+                pub mod consts {
+                    pub fn two() -> i32 {
+                        return super::ONE + crate::math::ONE
+                    }
+                }
+
+                fn helper(add: i32) -> i32 {
+                    return add
+                }
+            }
+
+            use math::consts::two;
+
+            fn main() -> i32 {
+                let add = 5;
+                let sum = math::add(add, two());
+                let three = math::consts::two() + 1;
+                return sum + three
+            }
+            ",
+        )
+        .unwrap();
+        assert_eq!(
+            item_names(&items),
+            vec![
+                "math::ONE",
+                "math::add",
+                "math::consts::two",
+                "math::helper",
+                "main"
+            ]
+        );
+        // The local `add` shadows the function only in `main`
+        assert_eq!(
+            used_names(&items[4], 1),
+            vec!["math::add", "add", "math::consts::two"]
+        );
+        assert_eq!(used_names(&items[3], 0), vec!["add"]);
+    }
+
+    #[test]
+    fn test_resolve_pub_use() {
+        let items = resolve_str(
+            "
+            use shapes::area;
+
+            mod shapes {
+                pub use square::area;
+
+                pub mod square {
+                    pub fn area(side: i32) -> i32 {
+                        return side * side
+                    }
+                }
+            }
+
+            fn main() -> i32 {
+                return area(2) + shapes::area(3)
+            }
+            ",
+        )
+        .unwrap();
+        assert_eq!(used_names(&items[1], 0), vec!["shapes::square::area"]);
+
+        assert_eq!(
+            resolve_str(
+                "mod a { pub mod b { pub fn f() -> i32 { return 1 } } use b::f; } fn main() -> i32 { return a::f() }"
+            )
+            .unwrap_err()
+            .msg,
+            "cannot find `f` in module `a`"
+        );
+    }
+
+    #[test]
+    fn test_resolve_module_errors() {
+        let resolve_err = |p: &str| resolve_str(p).unwrap_err().msg;
+        assert_eq!(
+            resolve_err("mod m { fn f() -> i32 { return 1 } } fn main() -> i32 { return m::f() }"),
+            "function `f` is private"
+        );
+        assert_eq!(
+            resolve_err(
+                "mod a { mod b { pub fn f() -> i32 { return 1 } } } use a::b::f; fn main() -> i32 { return f() }"
+            ),
+            "module `b` is private"
+        );
+        assert_eq!(
+            resolve_err("mod m { } fn main() -> i32 { return m::g(1) }"),
+            "cannot find `g` in module `m`"
+        );
+        assert_eq!(
+            resolve_err("fn main() -> i32 { return nowhere::a::b() }"),
+            "failed to resolve: use of undeclared crate or module `nowhere`"
+        );
+        assert_eq!(
+            resolve_err("mod m { } use m::missing; fn main() -> i32 { return 0 }"),
+            "cannot find `missing` in module `m`"
+        );
+        assert_eq!(
+            resolve_err("fn f() -> i32 { return 1 } mod m { pub fn g() -> i32 { return f() } }"),
+            "cannot find value `f` in this scope"
+        );
+        assert_eq!(
+            resolve_err("mod m { struct S { x: i32 } }"),
+            "structs, enums, traits, impls and extern functions can only be declared in the root module"
+        );
+        assert_eq!(
+            resolve_err("mod m { fn f() -> i32 { return 1 } fn f() -> i32 { return 2 } }"),
+            "the name `f` is defined multiple times"
+        );
+    }
+
+    #[test]
+    fn test_load_modules() {
+        let dir = std::env::temp_dir().join(format!("modules_test_{}", std::process::id()));
+        fs::create_dir_all(dir.join("shapes")).unwrap();
+        fs::write(
+            dir.join("shapes.rs"),
+            "pub mod square;\npub fn sides() -> i32 { return 4 }",
+        )
+        .unwrap();
+        fs::write(
+            dir.join("shapes").join("square.rs"),
+            "pub fn area(side: i32) -> i32 { return side * side }",
+        )
+        .unwrap();
+
+        let ast = parser("mod shapes;\nfn main() -> i32 { return shapes::square::area(3) }")
+            .unwrap()
+            .1;
+        let items = resolve(load_modules(ast, &dir).unwrap()).unwrap().0;
+        assert_eq!(
+            item_names(&items),
+            vec!["shapes::square::area", "shapes::sides", "main"]
+        );
+
+        let ast = parser("mod missing;").unwrap().1;
+        assert_eq!(
+            load_modules(ast, &dir).unwrap_err().msg,
+            "file not found for module `missing`"
+        );
+
+        // Errors in a module file name the file
+        let bad = dir.join("bad.rs").display().to_string();
+        fs::write(&bad, "pub fn f() -> i32 {\n    return true\n}").unwrap();
+        let ast = parser("mod bad;\nfn main() -> i32 { return bad::f() }")
+            .unwrap()
+            .1;
+        assert_eq!(
+            crate::type_checker::check(load_modules(ast, &dir).unwrap())
+                .unwrap_err()
+                .to_string(),
+            format!(
+                "type error at {}:2:5: mismatched types: expected `i32`, found `bool`",
+                bad
+            )
+        );
+
+        fs::write(&bad, "pub fn f() -> i32 { return 1 }\nfn (").unwrap();
+        let ast = parser("mod bad;").unwrap().1;
+        assert_eq!(
+            load_modules(ast, &dir).unwrap_err().to_string(),
+            format!("resolve error at {}:2:1: could not parse module `bad`", bad)
+        );
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -2,41 +2,159 @@ extern crate nom;
 use crate::ast::*;
 use nom::{
     branch::alt,
-    bytes::complete::tag,
-    character::complete::{alphanumeric0, digit1, multispace0},
-    combinator::map,
-    multi::many0,
+    bytes::complete::{tag, take_while, take_while1},
+    character::complete::{char, digit1, multispace0, multispace1, one_of},
+    combinator::{map, not, opt, peek, recognize, verify},
+    error::ErrorKind,
+    multi::{many0, many1},
     sequence::{delimited, preceded, terminated, tuple},
-    IResult,
+    Err, IResult, Slice,
 };
+use nom_locate::LocatedSpanEx;
+use std::cell::Cell;
 
-pub fn parser(input: &str) -> IResult<&str, Vec<Expr>> {
-    many0(parse_scope)(input)
+/// Source text being parsed, tracking the line and offset it starts at and the module
+/// file it is from.
+pub type Input<'a> = LocatedSpanEx<&'a str, Option<&'static str>>;
+
+#[cfg(test)]
+pub fn parser(input: &str) -> IResult<Input<'_>, Vec<Expr>> {
+    many0(parse_scope)(Input::new_extra(input, None))
 }
 
-fn parse_scope(input: &str) -> IResult<&str, Expr> {
-    delimited(
+thread_local! {
+    /// The offset and span of the furthest statement a block could not parse, an item
+    /// that does not parse fails there rather than at its start.
+    static BLOCK_ERROR: Cell<Option<(usize, Span)>> = const { Cell::new(None) };
+}
+
+/// Parses a whole source file, `file` is the module file it is, if any. Fails with the
+/// position of the first statement that could not be parsed.
+pub fn parse_file(input: &str, file: Option<&'static str>) -> Result<Vec<Expr>, Span> {
+    let input = Input::new_extra(input, file);
+    BLOCK_ERROR.with(|error| error.set(None));
+    let rest = match many0(parse_scope)(input) {
+        Ok((rest, items)) => {
+            let rest = multispace0::<_, (Input, ErrorKind)>(rest).map_or(rest, |(rest, _)| rest);
+            if rest.fragment.is_empty() {
+                return Ok(items);
+            }
+            rest
+        }
+        Err(Err::Error((rest, _))) | Err(Err::Failure((rest, _))) => rest,
+        Err(Err::Incomplete(_)) => input,
+    };
+    match BLOCK_ERROR.with(Cell::get) {
+        Some((offset, span)) if offset > rest.offset => Err(span),
+        _ => Err(span_at(rest)),
+    }
+}
+
+fn parse_scope(input: Input) -> IResult<Input, Expr> {
+    spanned(delimited(
         multispace0,
         alt((
             parse_return,
+            parse_break,
+            parse_continue,
             parse_let,
+            parse_declare,
             parse_if_else,
             parse_if,
             parse_while,
+            parse_for,
+            parse_loop,
+            parse_match,
             parse_var_expr,
-            parse_fn,
+            parse_fn_call,
+            parse_method_call,
+            parse_item,
         )),
         multispace0,
-    )(input)
+    ))(input)
+}
+
+/// Declarations, which the type checker only accepts at the top level of a module.
+fn parse_item(input: Input) -> IResult<Input, Expr> {
+    alt((
+        parse_extern_fn,
+        parse_fn,
+        parse_struct,
+        parse_enum,
+        parse_trait,
+        parse_impl,
+        parse_const,
+        parse_static,
+        parse_mod,
+        parse_use,
+        parse_pub,
+    ))(input)
+}
+
+/// Position of the start of `input` in the source given to `parser`.
+fn span_at(input: Input) -> Span {
+    Span {
+        line: input.line,
+        column: input.get_column() as u32,
+        file: input.extra,
+    }
+}
+
+/// Wraps the statement parsed by `f` with the span it starts at.
+fn spanned<'a, F>(f: F) -> impl Fn(Input<'a>) -> IResult<Input<'a>, Expr>
+where
+    F: Fn(Input<'a>) -> IResult<Input<'a>, Expr>,
+{
+    move |input: Input<'a>| {
+        let (substring, _) = multispace0(input)?;
+        let span = span_at(substring);
+        let (substring, expr) = f(substring)?;
+
+        Ok((substring, Expr::Spanned(span, Box::new(expr))))
+    }
+}
+
+fn parse_int(input: Input) -> IResult<Input, Expr> {
+    let (substring, (digit, suffix)) = delimited(
+        multispace0,
+        tuple((digit1, opt(parse_int_type))),
+        multispace0,
+    )(input)?;
+
+    match suffix {
+        None | Some(Type::Int) => match digit.fragment.parse::<i32>() {
+            Ok(val) => Ok((substring, Expr::Int(val))),
+            Err(_) => Err(Err::Failure((input, ErrorKind::Digit))),
+        },
+        Some(int_type) => match digit.fragment.parse::<u64>() {
+            Ok(val) => Ok((substring, Expr::TypedInt(val, int_type))),
+            Err(_) => Err(Err::Failure((input, ErrorKind::Digit))),
+        },
+    }
 }
 
-fn parse_int(input: &str) -> IResult<&str, Expr> {
-    let (substring, digit) = delimited(multispace0, digit1, multispace0)(input)?;
+/// A float literal needs a fraction or an exponent, `1.5`, `2e3` or `2.5e-1`.
+fn parse_float(input: Input) -> IResult<Input, Expr> {
+    let exponent = || recognize(tuple((one_of("eE"), opt(one_of("+-")), digit1)));
+    let (substring, float) = delimited(
+        multispace0,
+        recognize(tuple((
+            digit1,
+            alt((
+                recognize(tuple((char('.'), digit1, opt(exponent())))),
+                exponent(),
+            )),
+        ))),
+        multispace0,
+    )(input)?;
 
-    Ok((substring, Expr::Int(digit.parse::<i32>().unwrap())))
+    Ok((
+        substring,
+        Expr::Float(float.fragment.parse::<f64>().unwrap()),
+    ))
 }
 
-fn parse_bool(input: &str) -> IResult<&str, Expr> {
+fn parse_bool(input: Input) -> IResult<Input, Expr> {
     delimited(
         multispace0,
         alt((
@@ -47,7 +165,35 @@ fn parse_bool(input: &str) -> IResult<&str, Expr> {
     )(input)
 }
 
-fn parse_op(input: &str) -> IResult<&str, Op> {
+/// A string literal, the escapes are `\n`, `\t`, `\r`, `\\` and `\"`.
+fn parse_str(input: Input) -> IResult<Input, Expr> {
+    let (substring, _) = preceded(multispace0, tag("\""))(input)?;
+
+    let mut string = String::new();
+    let mut chars = substring.fragment.char_indices();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '"' => {
+                let (substring, _) = multispace0(substring.slice(i + 1..))?;
+                return Ok((substring, Expr::Str(string)));
+            }
+            '\\' => match chars.next() {
+                Some((_, 'n')) => string.push('\n'),
+                Some((_, 't')) => string.push('\t'),
+                Some((_, 'r')) => string.push('\r'),
+                Some((_, '\\')) => string.push('\\'),
+                Some((_, '"')) => string.push('"'),
+                _ => return Err(Err::Error((substring.slice(i..), ErrorKind::Escaped))),
+            },
+            c => string.push(c),
+        }
+    }
+
+    // Unterminated literal
+    Err(Err::Error((input, ErrorKind::Tag)))
+}
+
+fn parse_op(input: Input) -> IResult<Input, Op> {
     delimited(
         multispace0,
         alt((parse_rel_op, parse_log_op, parse_ass_op, parse_ari_op)),
@@ -55,7 +201,7 @@ fn parse_op(input: &str) -> IResult<&str, Op> {
     )(input)
 }
 
-fn parse_ari_op(input: &str) -> IResult<&str, Op> {
+fn parse_ari_op(input: Input) -> IResult<Input, Op> {
     delimited(
         multispace0,
         alt((
@@ -63,12 +209,13 @@ fn parse_ari_op(input: &str) -> IResult<&str, Op> {
             map(tag("-"), |_| Op::AriOp(AriOp::Sub)),
             map(tag("*"), |_| Op::AriOp(AriOp::Mul)),
             map(tag("/"), |_| Op::AriOp(AriOp::Div)),
+            map(tag("%"), |_| Op::AriOp(AriOp::Rem)),
         )),
         multispace0,
     )(input)
 }
 
-fn parse_ass_op(input: &str) -> IResult<&str, Op> {
+fn parse_ass_op(input: Input) -> IResult<Input, Op> {
     delimited(
         multispace0,
         alt((
@@ -77,12 +224,13 @@ fn parse_ass_op(input: &str) -> IResult<&str, Op> {
             map(tag("-="), |_| Op::AssOp(AssOp::SubEq)),
             map(tag("/="), |_| Op::AssOp(AssOp::DivEq)),
             map(tag("*="), |_| Op::AssOp(AssOp::MulEq)),
+            map(tag("%="), |_| Op::AssOp(AssOp::RemEq)),
         )),
         multispace0,
     )(input)
 }
 
-fn parse_log_op(input: &str) -> IResult<&str, Op> {
+fn parse_log_op(input: Input) -> IResult<Input, Op> {
     delimited(
         multispace0,
         alt((
@@ -94,7 +242,7 @@ fn parse_log_op(input: &str) -> IResult<&str, Op> {
     )(input)
 }
 
-fn parse_rel_op(input: &str) -> IResult<&str, Op> {
+fn parse_rel_op(input: Input) -> IResult<Input, Op> {
     delimited(
         multispace0,
         alt((
@@ -109,29 +257,179 @@ fn parse_rel_op(input: &str) -> IResult<&str, Op> {
     )(input)
 }
 
-fn parse_bin_expr(input: &str) -> IResult<&str, Expr> {
+fn parse_bin_expr(input: Input) -> IResult<Input, Expr> {
     alt((
         map(
             tuple((
-                alt((parse_bool, parse_int, parse_paren, parse_fn_call, parse_var)),
+                parse_cast(parse_access(alt((
+                    parse_bool,
+                    parse_float,
+                    parse_int,
+                    parse_str,
+                    parse_array,
+                    parse_tuple,
+                    parse_paren,
+                    parse_variant,
+                    parse_struct_lit,
+                    parse_fn_call,
+                    parse_closure,
+                    parse_var,
+                )))),
                 parse_op,
                 parse_bin_expr,
             )),
             |(left, op, right)| Expr::BinExpr(Box::new(left), op, Box::new(right)),
         ),
-        parse_bool,
-        parse_int,
-        parse_paren,
-        parse_fn_call,
+        alt((parse_if_else, parse_match, parse_loop, parse_block_expr)),
+        parse_cast(parse_access(alt((
+            parse_bool,
+            parse_float,
+            parse_int,
+            parse_str,
+            parse_array,
+            parse_tuple,
+            parse_paren,
+            parse_variant,
+            parse_struct_lit,
+            parse_fn_call,
+            parse_closure,
+        )))),
+        map(tuple((parse_name, many1(parse_as))), |(var, types)| {
+            cast(var, types)
+        }),
+        parse_cast(map(
+            tuple((parse_name, many1(parse_accessor))),
+            |(var, accessors)| access(var, accessors),
+        )),
+    ))(input)
+}
+
+/// An array literal, `[1, 2, 3]` or `[0; 10]` which repeats the value.
+fn parse_array(input: Input) -> IResult<Input, Expr> {
+    delimited(
+        multispace0,
+        alt((
+            map(
+                tuple((
+                    tag("["),
+                    parse_arg,
+                    tag(";"),
+                    delimited(multispace0, digit1, multispace0),
+                    tag("]"),
+                )),
+                |(_, val, _, len, _)| {
+                    Expr::ArrayRepeat(Box::new(val), len.fragment.parse().unwrap())
+                },
+            ),
+            map(
+                delimited(
+                    tag("["),
+                    many0(alt((parse_arg, preceded(tag(","), parse_arg)))),
+                    preceded(multispace0, tag("]")),
+                ),
+                Expr::Array,
+            ),
+        )),
+        multispace0,
+    )(input)
+}
+
+/// An indexing `[i]`, a field access `.x` or a method call `.f(args)` following an operand.
+enum Accessor {
+    Index(Expr),
+    TupleField(usize),
+    Field(String),
+    Method(String, Vec<Expr>),
+}
+
+fn parse_accessor(input: Input) -> IResult<Input, Accessor> {
+    alt((
+        map(
+            delimited(
+                tag("["),
+                parse_arg,
+                delimited(multispace0, tag("]"), multispace0),
+            ),
+            Accessor::Index,
+        ),
+        map(
+            delimited(
+                tag("."),
+                terminated(digit1, not(take_while1(is_name_char))),
+                multispace0,
+            ),
+            |index: Input| Accessor::TupleField(index.fragment.parse().unwrap()),
+        ),
+        map(
+            preceded(tag("."), tuple((parse_field_name, parse_args))),
+            |(method, args)| Accessor::Method(method, args),
+        ),
+        map(preceded(tag("."), parse_field_name), Accessor::Field),
     ))(input)
 }
 
-fn parse_return(input: &str) -> IResult<&str, Expr> {
+fn access(expr: Expr, accessors: Vec<Accessor>) -> Expr {
+    accessors
+        .into_iter()
+        .fold(expr, |expr, accessor| match accessor {
+            Accessor::Index(i) => Expr::Index(Box::new(expr), Box::new(i)),
+            Accessor::TupleField(index) => Expr::TupleField(Box::new(expr), index),
+            Accessor::Field(field) => Expr::Field(Box::new(expr), field),
+            Accessor::Method(method, args) => Expr::MethodCall(Box::new(expr), method, args),
+        })
+}
+
+/// Applies the indexing and field accesses following the operand parsed by `f`, if any.
+fn parse_access<'a, F>(f: F) -> impl Fn(Input<'a>) -> IResult<Input<'a>, Expr>
+where
+    F: Fn(Input<'a>) -> IResult<Input<'a>, Expr>,
+{
+    map(tuple((f, many0(parse_accessor))), |(expr, accessors)| {
+        access(expr, accessors)
+    })
+}
+
+fn parse_as(input: Input) -> IResult<Input, Type> {
+    // `as` must not be the start of a longer name, e.g. in `x + assets`
+    preceded(
+        terminated(tag("as"), not(take_while1(is_name_char))),
+        parse_type,
+    )(input)
+}
+
+fn cast(expr: Expr, types: Vec<Type>) -> Expr {
+    types
+        .into_iter()
+        .fold(expr, |expr, ty| Expr::Cast(Box::new(expr), ty))
+}
+
+/// Applies the `as` casts following the operand parsed by `f`, if any.
+fn parse_cast<'a, F>(f: F) -> impl Fn(Input<'a>) -> IResult<Input<'a>, Expr>
+where
+    F: Fn(Input<'a>) -> IResult<Input<'a>, Expr>,
+{
+    map(tuple((f, many0(parse_as))), |(expr, types)| {
+        cast(expr, types)
+    })
+}
+
+/// `parse_bin_expr` can stop in front of an operator, e.g. at `+ b` in `1 + b` as
+/// a lone variable is not an operand there. Rejecting that lets `parse_var_expr` try.
+fn parse_full_bin_expr(input: Input) -> IResult<Input, Expr> {
+    terminated(parse_bin_expr, not(parse_op))(input)
+}
+
+fn parse_return(input: Input) -> IResult<Input, Expr> {
     let (substring, val) = delimited(
         multispace0,
         preceded(
             tag("return"),
-            alt((parse_paren, parse_bin_expr, parse_var_expr, parse_var)),
+            alt((
+                parse_cast(parse_paren),
+                parse_full_bin_expr,
+                parse_var_expr,
+                parse_var,
+            )),
         ),
         multispace0,
     )(input)?;
@@ -139,7 +437,32 @@ fn parse_return(input: &str) -> IResult<&str, Expr> {
     Ok((substring, Expr::Return(Box::new(val))))
 }
 
-fn parse_paren(input: &str) -> IResult<&str, Expr> {
+/// `break`, or `break value` in a `loop`.
+fn parse_break(input: Input) -> IResult<Input, Expr> {
+    let (substring, value) = delimited(
+        multispace0,
+        preceded(
+            terminated(tag("break"), not(take_while1(is_name_char))),
+            opt(preceded(multispace0, parse_arg)),
+        ),
+        multispace0,
+    )(input)?;
+
+    Ok((substring, Expr::Break(value.map(Box::new))))
+}
+
+fn parse_continue(input: Input) -> IResult<Input, Expr> {
+    map(
+        delimited(
+            multispace0,
+            terminated(tag("continue"), not(take_while1(is_name_char))),
+            multispace0,
+        ),
+        |_| Expr::Continue,
+    )(input)
+}
+
+fn parse_paren(input: Input) -> IResult<Input, Expr> {
     delimited(
         multispace0,
         delimited(
@@ -151,27 +474,123 @@ fn parse_paren(input: &str) -> IResult<&str, Expr> {
     )(input)
 }
 
-fn parse_fn_call(input: &str) -> IResult<&str, Expr> {
-    let (substring, (fn_name, args)) = tuple((parse_var, parse_args))(input)?;
+/// `(a, b)`, a tuple has at least two elements.
+fn parse_tuple(input: Input) -> IResult<Input, Expr> {
+    let (substring, (first, mut elems)) = delimited(
+        multispace0,
+        delimited(
+            tag("("),
+            terminated(
+                tuple((parse_arg, many1(preceded(tag(","), parse_arg)))),
+                opt(tag(",")),
+            ),
+            delimited(multispace0, tag(")"), multispace0),
+        ),
+        multispace0,
+    )(input)?;
+
+    elems.insert(0, first);
+    Ok((substring, Expr::Tuple(elems)))
+}
+
+/// The names a `let` or parameter binds, `(a, (b, c))` destructures a tuple.
+fn parse_binding(input: Input) -> IResult<Input, Expr> {
+    alt((
+        map(
+            delimited(
+                multispace0,
+                delimited(
+                    tag("("),
+                    terminated(
+                        tuple((parse_binding, many1(preceded(tag(","), parse_binding)))),
+                        opt(tag(",")),
+                    ),
+                    delimited(multispace0, tag(")"), multispace0),
+                ),
+                multispace0,
+            ),
+            |(first, mut names)| {
+                names.insert(0, first);
+                Expr::Tuple(names)
+            },
+        ),
+        parse_var,
+    ))(input)
+}
+
+/// `|x: i32, y: i32| x + y`, the parameters of a closure need types and its body is a
+/// single expression.
+fn parse_closure(input: Input) -> IResult<Input, Expr> {
+    let (substring, (params, body)) = tuple((
+        delimited(
+            preceded(multispace0, tag("|")),
+            many0(alt((parse_param, preceded(tag(","), parse_param)))),
+            tag("|"),
+        ),
+        parse_arg,
+    ))(input)?;
+
+    Ok((substring, Expr::Closure(params, Box::new(body))))
+}
+
+fn parse_fn_call(input: Input) -> IResult<Input, Expr> {
+    let (substring, (fn_name, args)) = tuple((alt((parse_path, parse_var)), parse_args))(input)?;
 
     Ok((substring, Expr::FnCall(Box::new(fn_name), args)))
 }
 
-fn parse_var(input: &str) -> IResult<&str, Expr> {
+/// A method call used as a statement, `shape.draw()`.
+fn parse_method_call(input: Input) -> IResult<Input, Expr> {
+    verify(parse_access(parse_name), |expr| {
+        matches!(expr, Expr::MethodCall(..))
+    })(input)
+}
+
+fn is_name_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+fn parse_var(input: Input) -> IResult<Input, Expr> {
+    delimited(
+        multispace0,
+        map(take_while(is_name_char), |var: Input| {
+            Expr::Var(var.fragment.to_string(), None)
+        }),
+        multispace0,
+    )(input)
+}
+
+/// Like `parse_var`, but the name must not be empty.
+fn parse_name(input: Input) -> IResult<Input, Expr> {
+    delimited(
+        multispace0,
+        map(take_while1(is_name_char), |var: Input| {
+            Expr::Var(var.fragment.to_string(), None)
+        }),
+        multispace0,
+    )(input)
+}
+
+fn parse_field_name(input: Input) -> IResult<Input, String> {
     delimited(
         multispace0,
-        map(alphanumeric0, |var: &str| Expr::Var(var.to_string())),
+        map(take_while1(is_name_char), |name: Input| {
+            name.fragment.to_string()
+        }),
         multispace0,
     )(input)
 }
 
-fn parse_arg(input: &str) -> IResult<&str, Expr> {
-    let (substring, val) = terminated(parse_bin_expr, multispace0)(input)?;
+fn parse_arg(input: Input) -> IResult<Input, Expr> {
+    let (substring, val) = terminated(
+        alt((parse_full_bin_expr, parse_var_expr, parse_name)),
+        multispace0,
+    )(input)?;
 
     Ok((substring, val))
 }
 
-fn parse_args(input: &str) -> IResult<&str, Vec<Expr>> {
+fn parse_args(input: Input) -> IResult<Input, Vec<Expr>> {
     let (substring, vec) = delimited(
         multispace0,
         delimited(
@@ -185,9 +604,15 @@ fn parse_args(input: &str) -> IResult<&str, Vec<Expr>> {
     Ok((substring, vec))
 }
 
-fn parse_var_expr(input: &str) -> IResult<&str, Expr> {
+fn parse_var_expr(input: Input) -> IResult<Input, Expr> {
     let (substring, (var, op, expr)) = tuple((
-        alt((parse_int, parse_bool, parse_var)),
+        parse_cast(parse_access(alt((
+            parse_float,
+            parse_int,
+            parse_bool,
+            parse_str,
+            parse_var,
+        )))),
         parse_op,
         alt((parse_bin_expr, parse_var)),
     ))(input)?;
@@ -195,30 +620,97 @@ fn parse_var_expr(input: &str) -> IResult<&str, Expr> {
     Ok((substring, Expr::VarExpr(Box::new(var), op, Box::new(expr))))
 }
 
-fn parse_block(input: &str) -> IResult<&str, Vec<Expr>> {
+/// Statements followed by `;`, except `return` and those ending in a block, and an
+/// optional tail expression.
+fn parse_block(input: Input) -> IResult<Input, Vec<Expr>> {
     alt((
-        delimited(
-            terminated(multispace0, tag("{")),
-            many0(alt((terminated(parse_scope, tag(";")), parse_return))),
-            terminated(multispace0, tag("}")),
-        ),
         delimited(
             terminated(multispace0, tag("{")),
             parse_block,
             terminated(multispace0, tag("}")),
         ),
+        map(
+            delimited(
+                terminated(multispace0, tag("{")),
+                tuple((
+                    many0(alt((
+                        terminated(parse_scope, tag(";")),
+                        spanned(alt((parse_return, parse_break, parse_continue))),
+                        // The last one before the `}` is the tail instead
+                        terminated(parse_block_stmt, not(peek(tag("}")))),
+                    ))),
+                    opt(alt((
+                        terminated(spanned(parse_tail), peek(tag("}"))),
+                        parse_block_stmt,
+                    ))),
+                )),
+                parse_block_end,
+            ),
+            |(mut block, tail)| {
+                block.extend(tail);
+                block
+            },
+        ),
+    ))(input)
+}
+
+/// The `}` of a block, anything else is a statement that could not be parsed.
+fn parse_block_end(input: Input) -> IResult<Input, Input> {
+    let (input, _) = multispace0(input)?;
+    tag("}")(input).inspect_err(|_| {
+        BLOCK_ERROR.with(|error| match error.get() {
+            Some((offset, _)) if offset >= input.offset => {}
+            _ => error.set(Some((input.offset, span_at(input)))),
+        })
+    })
+}
+
+/// Statements that end in a block, they need no `;` before the next statement.
+fn parse_block_stmt(input: Input) -> IResult<Input, Expr> {
+    spanned(delimited(
+        multispace0,
+        alt((
+            parse_if_else,
+            parse_if,
+            parse_while,
+            parse_for,
+            parse_loop,
+            parse_match,
+            parse_block_expr,
+        )),
+        multispace0,
     ))(input)
 }
 
-pub fn parse_let(input: &str) -> IResult<&str, Expr> {
+/// The last expression of a block when it is not followed by `;`, the value of the block.
+/// Unlike a `let` value, it can not start with an operator.
+fn parse_tail(input: Input) -> IResult<Input, Expr> {
+    map(
+        verify(parse_arg, |expr| match expr {
+            Expr::BinExpr(l, ..) | Expr::VarExpr(l, ..) => **l != Expr::Var(String::new(), None),
+            _ => true,
+        }),
+        |expr| Expr::Tail(Box::new(expr)),
+    )(input)
+}
+
+/// `{ let a = 1; a + 1 }` used as an expression.
+fn parse_block_expr(input: Input) -> IResult<Input, Expr> {
+    map(parse_block, Expr::Block)(input)
+}
+
+pub fn parse_let(input: Input) -> IResult<Input, Expr> {
     let (substring, (var, var_type, expr)) = tuple((
-        terminated(
-            preceded(delimited(multispace0, tag("let"), multispace0), parse_var),
-            tag(":"),
+        preceded(
+            delimited(multispace0, tag("let"), multispace0),
+            parse_binding,
         ),
-        parse_type,
+        opt(preceded(
+            delimited(multispace0, tag(":"), multispace0),
+            parse_type,
+        )),
         alt((
-            parse_bin_expr,
+            parse_full_bin_expr,
             preceded(
                 delimited(multispace0, tag("="), multispace0),
                 alt((parse_var_expr, parse_var)),
@@ -232,20 +724,100 @@ pub fn parse_let(input: &str) -> IResult<&str, Expr> {
     ))
 }
 
-fn parse_type(input: &str) -> IResult<&str, Type> {
+fn parse_declare(input: Input) -> IResult<Input, Expr> {
+    map(
+        tuple((
+            preceded(delimited(multispace0, tag("let"), multispace0), parse_name),
+            opt(preceded(
+                delimited(multispace0, tag(":"), multispace0),
+                parse_type,
+            )),
+            peek(tag(";")),
+        )),
+        |(var, var_type, _)| Expr::Declare(Box::new(var), var_type),
+    )(input)
+}
+
+fn parse_type(input: Input) -> IResult<Input, Type> {
     delimited(
         multispace0,
         alt((
-            map(tag("i32"), |_| Type::Int),
-            map(tag("bool"), |_| Type::Bool),
-            // map(tag("str"), |_| Type::Str),
+            // `bool` must not match the start of a struct name like `boolean`
+            terminated(
+                alt((
+                    parse_int_type,
+                    map(tag("f64"), |_| Type::Float),
+                    map(tag("bool"), |_| Type::Bool),
+                    map(tag("str"), |_| Type::Str),
+                )),
+                not(take_while1(is_name_char)),
+            ),
             map(tag("()"), |_| Type::Void),
+            map(
+                tuple((
+                    preceded(
+                        tag("fn"),
+                        delimited(
+                            preceded(multispace0, tag("(")),
+                            many0(alt((parse_type, preceded(tag(","), parse_type)))),
+                            tag(")"),
+                        ),
+                    ),
+                    opt(preceded(
+                        delimited(multispace0, tag("->"), multispace0),
+                        parse_type,
+                    )),
+                )),
+                |(param_types, ret_type)| {
+                    Type::Fn(param_types, Box::new(ret_type.unwrap_or(Type::Void)))
+                },
+            ),
+            map(
+                delimited(
+                    tag("("),
+                    terminated(
+                        tuple((parse_type, many1(preceded(tag(","), parse_type)))),
+                        opt(tag(",")),
+                    ),
+                    tag(")"),
+                ),
+                |(first, mut types)| {
+                    types.insert(0, first);
+                    Type::Tuple(types)
+                },
+            ),
+            map(
+                tuple((
+                    tag("["),
+                    parse_type,
+                    tag(";"),
+                    delimited(multispace0, digit1, multispace0),
+                    tag("]"),
+                )),
+                |(_, elem_type, _, len, _)| {
+                    Type::Array(Box::new(elem_type), len.fragment.parse().unwrap())
+                },
+            ),
+            map(parse_field_name, Type::Named),
         )),
         multispace0,
     )(input)
 }
 
-fn parse_if(input: &str) -> IResult<&str, Expr> {
+fn parse_int_type(input: Input) -> IResult<Input, Type> {
+    alt((
+        map(tag("i8"), |_| Type::I8),
+        map(tag("i16"), |_| Type::I16),
+        map(tag("i32"), |_| Type::Int),
+        map(tag("i64"), |_| Type::I64),
+        map(tag("u8"), |_| Type::U8),
+        map(tag("u32"), |_| Type::U32),
+        map(tag("u64"), |_| Type::U64),
+        map(tag("usize"), |_| Type::Usize),
+    ))(input)
+}
+
+fn parse_if(input: Input) -> IResult<Input, Expr> {
     let (substring, (cond, block)) = tuple((
         preceded(
             delimited(multispace0, tag("if"), multispace0),
@@ -257,7 +829,7 @@ fn parse_if(input: &str) -> IResult<&str, Expr> {
     Ok((substring, Expr::If(Box::new(cond), block)))
 }
 
-fn parse_if_else(input: &str) -> IResult<&str, Expr> {
+fn parse_if_else(input: Input) -> IResult<Input, Expr> {
     let (substring, (cond, block1, block2)) = tuple((
         preceded(
             delimited(multispace0, tag("if"), multispace0),
@@ -273,7 +845,7 @@ fn parse_if_else(input: &str) -> IResult<&str, Expr> {
     Ok((substring, Expr::IfElse(Box::new(cond), block1, block2)))
 }
 
-fn parse_while(input: &str) -> IResult<&str, Expr> {
+fn parse_while(input: Input) -> IResult<Input, Expr> {
     let (substring, (cond, block)) = tuple((
         preceded(
             delimited(multispace0, tag("while"), multispace0),
@@ -285,13 +857,71 @@ fn parse_while(input: &str) -> IResult<&str, Expr> {
     Ok((substring, Expr::While(Box::new(cond), block)))
 }
 
-fn parse_param(input: &str) -> IResult<&str, (Expr, Type)> {
-    let (substring, (var, var_type)) = tuple((terminated(parse_var, tag(":")), parse_type))(input)?;
+/// `for i in 0..n { block }`
+fn parse_for(input: Input) -> IResult<Input, Expr> {
+    let (substring, (var, range, block)) = tuple((
+        preceded(delimited(multispace0, tag("for"), multispace0), parse_name),
+        preceded(tag("in"), parse_range),
+        parse_block,
+    ))(input)?;
+
+    Ok((substring, Expr::For(Box::new(var), Box::new(range), block)))
+}
+
+fn parse_loop(input: Input) -> IResult<Input, Expr> {
+    let (substring, block) = preceded(
+        delimited(
+            multispace0,
+            terminated(tag("loop"), not(take_while1(is_name_char))),
+            multispace0,
+        ),
+        parse_block,
+    )(input)?;
+
+    Ok((substring, Expr::Loop(block)))
+}
+
+/// `start..end` or `start..=end`, ranges are only iterated over by `for`.
+fn parse_range(input: Input) -> IResult<Input, Expr> {
+    let (substring, (start, inclusive, end)) = tuple((
+        parse_arg,
+        alt((map(tag("..="), |_| true), map(tag(".."), |_| false))),
+        parse_arg,
+    ))(input)?;
+
+    Ok((
+        substring,
+        Expr::Range(Box::new(start), Box::new(end), inclusive),
+    ))
+}
+
+fn parse_param(input: Input) -> IResult<Input, (Expr, Type)> {
+    let (substring, (var, var_type)) = alt((
+        parse_self_param,
+        tuple((terminated(parse_binding, tag(":")), parse_type)),
+    ))(input)?;
 
     Ok((substring, (var, var_type)))
 }
 
-fn parse_params(input: &str) -> IResult<&str, Vec<(Expr, Type)>> {
+/// `self` without a type, the receiver of a method has type `Self`.
+fn parse_self_param(input: Input) -> IResult<Input, (Expr, Type)> {
+    map(
+        delimited(
+            multispace0,
+            terminated(tag("self"), not(take_while1(is_name_char))),
+            terminated(multispace0, not(tag(":"))),
+        ),
+        |_| {
+            (
+                Expr::Var("self".to_string(), None),
+                Type::Named("Self".to_string()),
+            )
+        },
+    )(input)
+}
+
+fn parse_params(input: Input) -> IResult<Input, Vec<(Expr, Type)>> {
     let (substring, val) = delimited(
         multispace0,
         delimited(
@@ -304,9 +934,51 @@ fn parse_params(input: &str) -> IResult<&str, Vec<(Expr, Type)>> {
 
     Ok((substring, val))
 }
-fn parse_fn(input: &str) -> IResult<&str, Expr> {
-    let (substring, (var, params, return_type, block)) = tuple((
+
+/// `<T: Shape + Debug, U>` after the name of a generic function, empty for other
+/// functions.
+fn parse_generics(input: Input) -> IResult<Input, Vec<(String, Vec<String>)>> {
+    let generic = || {
+        tuple((
+            parse_field_name,
+            map(
+                opt(preceded(
+                    tag(":"),
+                    tuple((
+                        parse_field_name,
+                        many0(preceded(tag("+"), parse_field_name)),
+                    )),
+                )),
+                |bounds| match bounds {
+                    Some((first, mut rest)) => {
+                        rest.insert(0, first);
+                        rest
+                    }
+                    None => Vec::new(),
+                },
+            ),
+        ))
+    };
+    map(
+        opt(delimited(
+            tag("<"),
+            tuple((generic(), many0(preceded(tag(","), generic())))),
+            delimited(multispace0, tag(">"), multispace0),
+        )),
+        |names| match names {
+            Some((first, mut rest)) => {
+                rest.insert(0, first);
+                rest
+            }
+            None => Vec::new(),
+        },
+    )(input)
+}
+
+fn parse_fn(input: Input) -> IResult<Input, Expr> {
+    let (substring, (var, generics, params, return_type, block)) = tuple((
         preceded(delimited(multispace0, tag("fn"), multispace0), parse_var),
+        parse_generics,
         parse_params,
         preceded(delimited(multispace0, tag("->"), multispace0), parse_type),
         parse_block,
@@ -314,36 +986,976 @@ fn parse_fn(input: &str) -> IResult<&str, Expr> {
 
     Ok((
         substring,
-        Expr::Fn(Box::new(var), params, return_type, block),
+        Expr::Fn(Box::new(var), generics, params, return_type, block),
     ))
 }
 
-#[cfg(test)]
-mod parse_tests {
-    use super::*;
+fn parse_extern_fn(input: Input) -> IResult<Input, Expr> {
+    let (substring, (var, params, return_type)) = terminated(
+        tuple((
+            preceded(
+                tuple((
+                    multispace0,
+                    tag("extern"),
+                    multispace0,
+                    tag("fn"),
+                    multispace0,
+                )),
+                parse_var,
+            ),
+            parse_params,
+            preceded(delimited(multispace0, tag("->"), multispace0), parse_type),
+        )),
+        tag(";"),
+    )(input)?;
 
-    #[test]
-    fn test_parse_int() {
-        assert_eq!(parse_int("1"), Ok(("", Expr::Int(1))));
-    }
+    Ok((
+        substring,
+        Expr::ExternFn(Box::new(var), params, return_type),
+    ))
+}
 
-    #[test]
-    fn test_parse_bool() {
-        assert_eq!(parse_bool("false"), Ok(("", Expr::Bool(false))));
-        assert_eq!(parse_bool("true"), Ok(("", Expr::Bool(true))));
-    }
+/// `const NAME: T = value;`, the value is evaluated at compile time.
+fn parse_const(input: Input) -> IResult<Input, Expr> {
+    let (substring, (var, const_type, value)) = tuple((
+        preceded(
+            delimited(
+                multispace0,
+                terminated(tag("const"), not(take_while1(is_name_char))),
+                multispace0,
+            ),
+            parse_name,
+        ),
+        preceded(tag(":"), parse_type),
+        delimited(
+            delimited(multispace0, tag("="), multispace0),
+            parse_arg,
+            tag(";"),
+        ),
+    ))(input)?;
 
-    #[test]
-    fn test_parse_type() {
-        assert_eq!(parse_type("i32"), Ok(("", Type::Int)));
-        assert_eq!(parse_type("bool"), Ok(("", Type::Bool)));
-        assert_eq!(parse_type("()"), Ok(("", Type::Void)));
-    }
+    Ok((
+        substring,
+        Expr::Const(Box::new(var), const_type, Box::new(value)),
+    ))
+}
 
-    #[test]
-    fn test_parse_op() {
-        assert_eq!(parse_op("+"), Ok(("", Op::AriOp(AriOp::Add))));
-        assert_eq!(parse_op("="), Ok(("", Op::AssOp(AssOp::Eq))));
+/// `static NAME: T = value;` or `static mut NAME: T = value;`, a global variable.
+fn parse_static(input: Input) -> IResult<Input, Expr> {
+    let (substring, (mutable, var, static_type, value)) = tuple((
+        preceded(
+            delimited(
+                multispace0,
+                terminated(tag("static"), not(take_while1(is_name_char))),
+                multispace0,
+            ),
+            map(opt(terminated(tag("mut"), multispace1)), |m| m.is_some()),
+        ),
+        parse_name,
+        preceded(tag(":"), parse_type),
+        delimited(
+            delimited(multispace0, tag("="), multispace0),
+            parse_arg,
+            tag(";"),
+        ),
+    ))(input)?;
+
+    Ok((
+        substring,
+        Expr::Static(Box::new(var), static_type, Box::new(value), mutable),
+    ))
+}
+
+/// `trait Shape { fn area(self) -> i32; }`, a trait declares the signatures of its methods.
+fn parse_trait(input: Input) -> IResult<Input, Expr> {
+    let method = tuple((
+        preceded(delimited(multispace0, tag("fn"), multispace0), parse_name),
+        parse_params,
+        terminated(
+            preceded(delimited(multispace0, tag("->"), multispace0), parse_type),
+            tag(";"),
+        ),
+    ));
+    let (substring, (var, methods)) = tuple((
+        preceded(
+            delimited(multispace0, tag("trait"), multispace0),
+            parse_name,
+        ),
+        delimited(
+            tag("{"),
+            many0(method),
+            delimited(multispace0, tag("}"), multispace0),
+        ),
+    ))(input)?;
+
+    Ok((substring, Expr::Trait(Box::new(var), methods)))
+}
+
+/// `impl Shape for Circle { fn area(self) -> i32 { ... } }`
+fn parse_impl(input: Input) -> IResult<Input, Expr> {
+    let (substring, (trait_name, impl_type, methods)) = tuple((
+        preceded(
+            delimited(multispace0, tag("impl"), multispace0),
+            parse_field_name,
+        ),
+        preceded(tag("for"), parse_type),
+        delimited(
+            tag("{"),
+            many0(spanned(parse_fn)),
+            delimited(multispace0, tag("}"), multispace0),
+        ),
+    ))(input)?;
+
+    Ok((substring, Expr::Impl(trait_name, impl_type, methods)))
+}
+
+/// `struct Point { x: i32, y: i32 }`, a struct has at least one field.
+fn parse_struct(input: Input) -> IResult<Input, Expr> {
+    let (substring, (var, fields)) = tuple((
+        preceded(
+            delimited(multispace0, tag("struct"), multispace0),
+            parse_name,
+        ),
+        delimited(
+            tag("{"),
+            terminated(
+                tuple((parse_param, many0(preceded(tag(","), parse_param)))),
+                opt(tag(",")),
+            ),
+            delimited(multispace0, tag("}"), multispace0),
+        ),
+    ))(input)?;
+
+    let (first, mut fields) = fields;
+    fields.insert(0, first);
+    Ok((substring, Expr::Struct(Box::new(var), fields)))
+}
+
+/// `Point { x: 1, y: 2 }`, requiring a field keeps `if a == b {}` a condition.
+fn parse_struct_lit(input: Input) -> IResult<Input, Expr> {
+    let field = || tuple((terminated(parse_field_name, tag(":")), parse_arg));
+    let (substring, (name, (first, mut fields))) = tuple((
+        parse_field_name,
+        delimited(
+            tag("{"),
+            terminated(
+                tuple((field(), many0(preceded(tag(","), field())))),
+                opt(tag(",")),
+            ),
+            delimited(multispace0, tag("}"), multispace0),
+        ),
+    ))(input)?;
+
+    fields.insert(0, first);
+    Ok((substring, Expr::StructLit(name, fields)))
+}
+
+/// `enum Shape { Circle(i32), Rect(i32, i32), Empty }`
+fn parse_enum(input: Input) -> IResult<Input, Expr> {
+    let variant = || {
+        tuple((
+            parse_name,
+            map(
+                opt(delimited(
+                    tag("("),
+                    tuple((parse_type, many0(preceded(tag(","), parse_type)))),
+                    tag(")"),
+                )),
+                |types| match types {
+                    Some((first, mut rest)) => {
+                        rest.insert(0, first);
+                        rest
+                    }
+                    None => Vec::new(),
+                },
+            ),
+            multispace0,
+        ))
+    };
+    let (substring, (var, (first, mut variants))) = tuple((
+        preceded(delimited(multispace0, tag("enum"), multispace0), parse_name),
+        delimited(
+            tag("{"),
+            terminated(
+                tuple((variant(), many0(preceded(tag(","), variant())))),
+                opt(tag(",")),
+            ),
+            delimited(multispace0, tag("}"), multispace0),
+        ),
+    ))(input)?;
+
+    variants.insert(0, first);
+    let variants = variants
+        .into_iter()
+        .map(|(name, types, _)| (name, types))
+        .collect();
+    Ok((substring, Expr::Enum(Box::new(var), variants)))
+}
+
+/// `Shape::Circle(1)`, or `Shape::Empty` for a variant without fields. Longer paths
+/// like `geometry::area(s)` and calls with empty parentheses like `math::zero()` are
+/// functions, the module pass tells the paths of two segments that name functions apart.
+fn parse_variant(input: Input) -> IResult<Input, Expr> {
+    let (substring, (segments, args)) = tuple((parse_segments, opt(parse_args)))(input)?;
+
+    let expr = match (segments.as_slice(), args) {
+        ([enum_name, variant], None) => {
+            Expr::Variant(enum_name.to_string(), variant.to_string(), vec![])
+        }
+        ([enum_name, variant], Some(args)) if !args.is_empty() => {
+            Expr::Variant(enum_name.to_string(), variant.to_string(), args)
+        }
+        (_, Some(args)) => Expr::FnCall(Box::new(Expr::Var(segments.join("::"), None)), args),
+        (_, None) => Expr::Var(segments.join("::"), None),
+    };
+    Ok((substring, expr))
+}
+
+/// The segments of a path with at least two of them, `a::b`.
+fn parse_segments(input: Input) -> IResult<Input, Vec<String>> {
+    let (substring, (first, mut segments)) = tuple((
+        parse_field_name,
+        many1(preceded(tag("::"), parse_field_name)),
+    ))(input)?;
+
+    segments.insert(0, first);
+    Ok((substring, segments))
+}
+
+/// A path as a single name, `math::add`.
+fn parse_path(input: Input) -> IResult<Input, Expr> {
+    map(parse_segments, |segments| {
+        Expr::Var(segments.join("::"), None)
+    })(input)
+}
+
+/// `mod name;`, or `mod name { items }` with the items of the module inline.
+fn parse_mod(input: Input) -> IResult<Input, Expr> {
+    let (substring, (name, items)) = tuple((
+        preceded(
+            delimited(
+                multispace0,
+                terminated(tag("mod"), not(take_while1(is_name_char))),
+                multispace0,
+            ),
+            parse_field_name,
+        ),
+        alt((
+            map(tag(";"), |_| None),
+            map(
+                delimited(
+                    tag("{"),
+                    many0(parse_scope),
+                    preceded(multispace0, tag("}")),
+                ),
+                Some,
+            ),
+        )),
+    ))(input)?;
+
+    Ok((substring, Expr::Mod(name, items)))
+}
+
+/// `use math::add;`
+fn parse_use(input: Input) -> IResult<Input, Expr> {
+    let (substring, (first, mut segments)) = delimited(
+        delimited(
+            multispace0,
+            terminated(tag("use"), not(take_while1(is_name_char))),
+            multispace0,
+        ),
+        tuple((
+            parse_field_name,
+            many0(preceded(tag("::"), parse_field_name)),
+        )),
+        tag(";"),
+    )(input)?;
+
+    segments.insert(0, first);
+    Ok((substring, Expr::Use(segments)))
+}
+
+/// An item visible outside of its module, like `pub fn` or `pub use`.
+fn parse_pub(input: Input) -> IResult<Input, Expr> {
+    let (substring, item) = preceded(
+        delimited(
+            multispace0,
+            terminated(tag("pub"), not(take_while1(is_name_char))),
+            multispace0,
+        ),
+        alt((
+            parse_extern_fn,
+            parse_fn,
+            parse_struct,
+            parse_enum,
+            parse_trait,
+            parse_const,
+            parse_static,
+            parse_mod,
+            parse_use,
+        )),
+    )(input)?;
+
+    Ok((substring, Expr::Pub(Box::new(item))))
+}
+
+fn parse_match(input: Input) -> IResult<Input, Expr> {
+    let arm = || {
+        terminated(
+            tuple((
+                terminated(
+                    parse_pattern,
+                    delimited(multispace0, tag("=>"), multispace0),
+                ),
+                alt((
+                    parse_block,
+                    map(
+                        spanned(alt((parse_return, parse_break, parse_continue))),
+                        |stmt| vec![stmt],
+                    ),
+                    // A single expression is the value of the arm
+                    map(
+                        spanned(terminated(parse_tail, peek(alt((tag(","), tag("}")))))),
+                        |tail| vec![tail],
+                    ),
+                    map(parse_scope, |stmt| vec![stmt]),
+                )),
+            )),
+            delimited(multispace0, opt(tag(",")), multispace0),
+        )
+    };
+    let (substring, (expr, arms)) = tuple((
+        preceded(delimited(multispace0, tag("match"), multispace0), parse_arg),
+        delimited(
+            tag("{"),
+            many1(arm()),
+            delimited(multispace0, tag("}"), multispace0),
+        ),
+    ))(input)?;
+
+    Ok((substring, Expr::Match(Box::new(expr), arms)))
+}
+
+fn parse_pattern(input: Input) -> IResult<Input, Pattern> {
+    delimited(
+        multispace0,
+        alt((
+            map(terminated(tag("_"), not(take_while1(is_name_char))), |_| {
+                Pattern::Wildcard
+            }),
+            map(
+                tuple((
+                    parse_field_name,
+                    tag("::"),
+                    parse_field_name,
+                    opt(delimited(
+                        tag("("),
+                        tuple((parse_pattern, many0(preceded(tag(","), parse_pattern)))),
+                        tag(")"),
+                    )),
+                )),
+                |(enum_name, _, variant, fields)| {
+                    let fields = match fields {
+                        Some((first, mut rest)) => {
+                            rest.insert(0, first);
+                            rest
+                        }
+                        None => Vec::new(),
+                    };
+                    Pattern::Variant(enum_name, variant, fields)
+                },
+            ),
+            map(alt((parse_bool, parse_int)), Pattern::Literal),
+            map(parse_field_name, |name| Pattern::Binding(name, None)),
+        )),
+        multispace0,
+    )(input)
+}
+
+#[cfg(test)]
+mod parse_tests {
+    use super::*;
+
+    /// Runs `f` on `input` as a whole source, giving back the rest as a `&str`.
+    fn run<'a, T>(
+        f: impl Fn(Input<'a>) -> IResult<Input<'a>, T>,
+        input: &'a str,
+    ) -> IResult<&'a str, T> {
+        f(Input::new_extra(input, None))
+            .map(|(rest, out)| (rest.fragment, out))
+            .map_err(|e| e.map(|(rest, kind)| (rest.fragment, kind)))
+    }
+
+    // The parsers under test, taking and returning plain `&str` sources
+    macro_rules! on_str {
+        ($($f:ident -> $out:ty,)*) => {$(
+            fn $f(input: &str) -> IResult<&str, $out> {
+                run(super::$f, input)
+            }
+        )*};
+    }
+
+    on_str! {
+        parse_arg -> Expr,
+        parse_args -> Vec<Expr>,
+        parse_ari_op -> Op,
+        parse_array -> Expr,
+        parse_as -> Type,
+        parse_ass_op -> Op,
+        parse_bin_expr -> Expr,
+        parse_block -> Vec<Expr>,
+        parse_bool -> Expr,
+        parse_break -> Expr,
+        parse_closure -> Expr,
+        parse_const -> Expr,
+        parse_declare -> Expr,
+        parse_enum -> Expr,
+        parse_extern_fn -> Expr,
+        parse_float -> Expr,
+        parse_fn -> Expr,
+        parse_fn_call -> Expr,
+        parse_for -> Expr,
+        parse_if -> Expr,
+        parse_if_else -> Expr,
+        parse_impl -> Expr,
+        parse_int -> Expr,
+        parse_let -> Expr,
+        parse_log_op -> Op,
+        parse_loop -> Expr,
+        parse_match -> Expr,
+        parse_method_call -> Expr,
+        parse_mod -> Expr,
+        parse_op -> Op,
+        parse_param -> (Expr, Type),
+        parse_params -> Vec<(Expr, Type)>,
+        parse_paren -> Expr,
+        parse_pub -> Expr,
+        parse_range -> Expr,
+        parse_rel_op -> Op,
+        parse_return -> Expr,
+        parse_scope -> Expr,
+        parse_static -> Expr,
+        parse_str -> Expr,
+        parse_struct -> Expr,
+        parse_tail -> Expr,
+        parse_trait -> Expr,
+        parse_type -> Type,
+        parse_var -> Expr,
+        parse_var_expr -> Expr,
+        parse_variant -> Expr,
+        parse_while -> Expr,
+    }
+
+    fn parser(input: &str) -> IResult<&str, Vec<Expr>> {
+        run(many0(super::parse_scope), input)
+    }
+
+    /// Spans depend on where a statement sits in the source, compare without them.
+    fn strip_spans(expr: Expr) -> Expr {
+        match expr.unspanned() {
+            Expr::If(cond, block) => Expr::If(cond, strip_block(block)),
+            Expr::IfElse(cond, block1, block2) => {
+                Expr::IfElse(cond, strip_block(block1), strip_block(block2))
+            }
+            Expr::While(cond, block) => Expr::While(cond, strip_block(block)),
+            Expr::For(var, range, block) => Expr::For(var, range, strip_block(block)),
+            Expr::Loop(block) => Expr::Loop(strip_block(block)),
+            Expr::Break(Some(expr)) => Expr::Break(Some(Box::new(strip_spans(*expr)))),
+            Expr::Block(block) => Expr::Block(strip_block(block)),
+            Expr::Tail(expr) => Expr::Tail(Box::new(strip_spans(*expr))),
+            Expr::Match(expr, arms) => Expr::Match(
+                expr,
+                arms.into_iter()
+                    .map(|(pattern, block)| (pattern, strip_block(block)))
+                    .collect(),
+            ),
+            Expr::Fn(fn_var, generics, params, ret_type, block) => {
+                Expr::Fn(fn_var, generics, params, ret_type, strip_block(block))
+            }
+            Expr::Impl(trait_name, impl_type, methods) => {
+                Expr::Impl(trait_name, impl_type, strip_block(methods))
+            }
+            expr => expr,
+        }
+    }
+
+    fn strip_block(block: Vec<Expr>) -> Vec<Expr> {
+        block.into_iter().map(strip_spans).collect()
+    }
+
+    #[test]
+    fn test_parse_int() {
+        assert_eq!(parse_int("1"), Ok(("", Expr::Int(1))));
+        assert_eq!(parse_int("1i32"), Ok(("", Expr::Int(1))));
+        assert_eq!(parse_int("10u8 "), Ok(("", Expr::TypedInt(10, Type::U8))));
+        assert_eq!(
+            parse_int("18446744073709551615u64"),
+            Ok(("", Expr::TypedInt(u64::MAX, Type::U64)))
+        );
+        assert!(parse_int("18446744073709551616u64").is_err());
+        assert!(parse_int("3000000000").is_err());
+        assert!(parse_int("3000000000i32").is_err());
+        assert_eq!(
+            parse_int("2usize"),
+            Ok(("", Expr::TypedInt(2, Type::Usize)))
+        );
+    }
+
+    #[test]
+    fn test_parse_float() {
+        assert_eq!(parse_float("1.5"), Ok(("", Expr::Float(1.5))));
+        assert_eq!(parse_float("2e3 "), Ok(("", Expr::Float(2000.0))));
+        assert_eq!(parse_float("2.5E-1"), Ok(("", Expr::Float(0.25))));
+        assert!(parse_float("1").is_err());
+        assert!(parse_float("1.").is_err());
+        assert_eq!(
+            parse_bin_expr("1.5 * x as f64"),
+            Ok((
+                "",
+                Expr::BinExpr(
+                    Box::new(Expr::Float(1.5)),
+                    Op::AriOp(AriOp::Mul),
+                    Box::new(Expr::Cast(
+                        Box::new(Expr::Var("x".to_string(), None)),
+                        Type::Float
+                    ))
+                )
+            ))
+        );
+    }
+
+    #[test]
+    fn test_parse_array() {
+        assert_eq!(
+            parse_array("[1, x]"),
+            Ok((
+                "",
+                Expr::Array(vec![Expr::Int(1), Expr::Var("x".to_string(), None)])
+            ))
+        );
+        assert_eq!(
+            parse_array("[0u8; 4]"),
+            Ok((
+                "",
+                Expr::ArrayRepeat(Box::new(Expr::TypedInt(0, Type::U8)), 4)
+            ))
+        );
+        assert!(parse_array("[1, 2; 3]").is_err());
+        assert_eq!(
+            parse_type("[[bool; 2]; 3]"),
+            Ok((
+                "",
+                Type::Array(Box::new(Type::Array(Box::new(Type::Bool), 2)), 3)
+            ))
+        );
+        assert_eq!(
+            parse_bin_expr("a[i][1] + 2"),
+            Ok((
+                "",
+                Expr::BinExpr(
+                    Box::new(Expr::Index(
+                        Box::new(Expr::Index(
+                            Box::new(Expr::Var("a".to_string(), None)),
+                            Box::new(Expr::Var("i".to_string(), None))
+                        )),
+                        Box::new(Expr::Int(1))
+                    )),
+                    Op::AriOp(AriOp::Add),
+                    Box::new(Expr::Int(2))
+                )
+            ))
+        );
+        assert_eq!(
+            parse_var_expr("a[0] += 7"),
+            Ok((
+                "",
+                Expr::VarExpr(
+                    Box::new(Expr::Index(
+                        Box::new(Expr::Var("a".to_string(), None)),
+                        Box::new(Expr::Int(0))
+                    )),
+                    Op::AssOp(AssOp::AddEq),
+                    Box::new(Expr::Int(7))
+                )
+            ))
+        );
+    }
+
+    #[test]
+    fn test_parse_struct() {
+        assert_eq!(
+            parse_struct("struct Point { x: i32, y: i32, }"),
+            Ok((
+                "",
+                Expr::Struct(
+                    Box::new(Expr::Var("Point".to_string(), None)),
+                    vec![
+                        (Expr::Var("x".to_string(), None), Type::Int),
+                        (Expr::Var("y".to_string(), None), Type::Int)
+                    ]
+                )
+            ))
+        );
+        assert_eq!(
+            parse_type("boolean"),
+            Ok(("", Type::Named("boolean".to_string())))
+        );
+        assert_eq!(
+            parse_bin_expr("Point { x: 1, y: a.x }"),
+            Ok((
+                "",
+                Expr::StructLit(
+                    "Point".to_string(),
+                    vec![
+                        ("x".to_string(), Expr::Int(1)),
+                        (
+                            "y".to_string(),
+                            Expr::Field(
+                                Box::new(Expr::Var("a".to_string(), None)),
+                                "x".to_string()
+                            )
+                        )
+                    ]
+                )
+            ))
+        );
+        assert_eq!(
+            parse_var_expr("l.from.x += 1"),
+            Ok((
+                "",
+                Expr::VarExpr(
+                    Box::new(Expr::Field(
+                        Box::new(Expr::Field(
+                            Box::new(Expr::Var("l".to_string(), None)),
+                            "from".to_string()
+                        )),
+                        "x".to_string()
+                    )),
+                    Op::AssOp(AssOp::AddEq),
+                    Box::new(Expr::Int(1))
+                )
+            ))
+        );
+    }
+
+    #[test]
+    fn test_parse_tuple() {
+        assert_eq!(
+            parse_type("(i32, (bool, str))"),
+            Ok((
+                "",
+                Type::Tuple(vec![Type::Int, Type::Tuple(vec![Type::Bool, Type::Str])])
+            ))
+        );
+        assert_eq!(
+            parse_bin_expr("(1, t.1.0)"),
+            Ok((
+                "",
+                Expr::Tuple(vec![
+                    Expr::Int(1),
+                    Expr::TupleField(
+                        Box::new(Expr::TupleField(
+                            Box::new(Expr::Var("t".to_string(), None)),
+                            1
+                        )),
+                        0
+                    )
+                ])
+            ))
+        );
+        assert_eq!(
+            parse_let("let (a, (b, c)): (i32, (bool, i32)) = f();"),
+            Ok((
+                ";",
+                Expr::Let(
+                    Box::new(Expr::Tuple(vec![
+                        Expr::Var("a".to_string(), None),
+                        Expr::Tuple(vec![
+                            Expr::Var("b".to_string(), None),
+                            Expr::Var("c".to_string(), None)
+                        ])
+                    ])),
+                    Some(Type::Tuple(vec![
+                        Type::Int,
+                        Type::Tuple(vec![Type::Bool, Type::Int])
+                    ])),
+                    Box::new(Expr::BinExpr(
+                        Box::new(Expr::Var("".to_string(), None)),
+                        Op::AssOp(AssOp::Eq),
+                        Box::new(Expr::FnCall(
+                            Box::new(Expr::Var("f".to_string(), None)),
+                            vec![]
+                        ))
+                    ))
+                )
+            ))
+        );
+        assert_eq!(
+            parse_params("((x, y): (i32, i32), s: i32)"),
+            Ok((
+                "",
+                vec![
+                    (
+                        Expr::Tuple(vec![
+                            Expr::Var("x".to_string(), None),
+                            Expr::Var("y".to_string(), None)
+                        ]),
+                        Type::Tuple(vec![Type::Int, Type::Int])
+                    ),
+                    (Expr::Var("s".to_string(), None), Type::Int)
+                ]
+            ))
+        );
+    }
+
+    #[test]
+    fn test_parse_enum() {
+        assert_eq!(
+            parse_enum("enum Shape { Circle(i32), Rect(i32, i32), Empty }"),
+            Ok((
+                "",
+                Expr::Enum(
+                    Box::new(Expr::Var("Shape".to_string(), None)),
+                    vec![
+                        (Expr::Var("Circle".to_string(), None), vec![Type::Int]),
+                        (
+                            Expr::Var("Rect".to_string(), None),
+                            vec![Type::Int, Type::Int]
+                        ),
+                        (Expr::Var("Empty".to_string(), None), vec![])
+                    ]
+                )
+            ))
+        );
+        assert_eq!(
+            parse_bin_expr("Shape::Rect(1, a)"),
+            Ok((
+                "",
+                Expr::Variant(
+                    "Shape".to_string(),
+                    "Rect".to_string(),
+                    vec![Expr::Int(1), Expr::Var("a".to_string(), None)]
+                )
+            ))
+        );
+        assert_eq!(
+            parse_match("match s { Shape::Rect(w, 0) => { return w }, _ => return 0 }")
+                .map(|(s, e)| (s, strip_spans(e))),
+            Ok((
+                "",
+                Expr::Match(
+                    Box::new(Expr::Var("s".to_string(), None)),
+                    vec![
+                        (
+                            Pattern::Variant(
+                                "Shape".to_string(),
+                                "Rect".to_string(),
+                                vec![
+                                    Pattern::Binding("w".to_string(), None),
+                                    Pattern::Literal(Expr::Int(0))
+                                ]
+                            ),
+                            vec![Expr::Return(Box::new(Expr::Var("w".to_string(), None)))]
+                        ),
+                        (
+                            Pattern::Wildcard,
+                            vec![Expr::Return(Box::new(Expr::Int(0)))]
+                        )
+                    ]
+                )
+            ))
+        );
+    }
+
+    #[test]
+    fn test_parse_cast() {
+        assert_eq!(parse_as("as u8"), Ok(("", Type::U8)));
+        assert!(parse_as("assets").is_err());
+        assert_eq!(
+            parse_bin_expr("a as u8"),
+            Ok((
+                "",
+                Expr::Cast(Box::new(Expr::Var("a".to_string(), None)), Type::U8)
+            ))
+        );
+        assert_eq!(
+            parse_bin_expr("1 as i64 as u32 + b as u32"),
+            Ok((
+                "",
+                Expr::BinExpr(
+                    Box::new(Expr::Cast(
+                        Box::new(Expr::Cast(Box::new(Expr::Int(1)), Type::I64)),
+                        Type::U32
+                    )),
+                    Op::AriOp(AriOp::Add),
+                    Box::new(Expr::Cast(
+                        Box::new(Expr::Var("b".to_string(), None)),
+                        Type::U32
+                    ))
+                )
+            ))
+        );
+        assert_eq!(
+            parse_var_expr("x as i32 < 3"),
+            Ok((
+                "",
+                Expr::VarExpr(
+                    Box::new(Expr::Cast(
+                        Box::new(Expr::Var("x".to_string(), None)),
+                        Type::Int
+                    )),
+                    Op::RelOp(RelOp::Les),
+                    Box::new(Expr::Int(3))
+                )
+            ))
+        );
+    }
+
+    #[test]
+    fn test_parse_bool() {
+        assert_eq!(parse_bool("false"), Ok(("", Expr::Bool(false))));
+        assert_eq!(parse_bool("true"), Ok(("", Expr::Bool(true))));
+    }
+
+    #[test]
+    fn test_parse_type() {
+        assert_eq!(parse_type("i32"), Ok(("", Type::Int)));
+        assert_eq!(parse_type("bool"), Ok(("", Type::Bool)));
+        assert_eq!(parse_type("()"), Ok(("", Type::Void)));
+        assert_eq!(parse_type("str"), Ok(("", Type::Str)));
+        assert_eq!(parse_type("u64"), Ok(("", Type::U64)));
+        assert_eq!(parse_type("usize"), Ok(("", Type::Usize)));
+        assert_eq!(parse_type("i8"), Ok(("", Type::I8)));
+        assert_eq!(parse_type("f64"), Ok(("", Type::Float)));
+        assert_eq!(
+            parse_type("fn(i32, bool) -> f64"),
+            Ok((
+                "",
+                Type::Fn(vec![Type::Int, Type::Bool], Box::new(Type::Float))
+            ))
+        );
+        assert_eq!(
+            parse_type("fn(fn() -> i32)"),
+            Ok((
+                "",
+                Type::Fn(
+                    vec![Type::Fn(vec![], Box::new(Type::Int))],
+                    Box::new(Type::Void)
+                )
+            ))
+        );
+        assert_eq!(
+            parse_type("fnord"),
+            Ok(("", Type::Named("fnord".to_string())))
+        );
+    }
+
+    #[test]
+    fn test_parse_closure() {
+        assert_eq!(
+            parse_let("let add = |x: i32, y: i32| x + y;"),
+            Ok((
+                ";",
+                Expr::Let(
+                    Box::new(Expr::Var("add".to_string(), None)),
+                    None,
+                    Box::new(Expr::BinExpr(
+                        Box::new(Expr::Var("".to_string(), None)),
+                        Op::AssOp(AssOp::Eq),
+                        Box::new(Expr::Closure(
+                            vec![
+                                (Expr::Var("x".to_string(), None), Type::Int),
+                                (Expr::Var("y".to_string(), None), Type::Int)
+                            ],
+                            Box::new(Expr::VarExpr(
+                                Box::new(Expr::Var("x".to_string(), None)),
+                                Op::AriOp(AriOp::Add),
+                                Box::new(Expr::Var("y".to_string(), None))
+                            ))
+                        ))
+                    ))
+                )
+            ))
+        );
+        assert_eq!(
+            parse_return("return || 42"),
+            Ok((
+                "",
+                Expr::Return(Box::new(Expr::Closure(vec![], Box::new(Expr::Int(42)))))
+            ))
+        );
+        assert_eq!(
+            parse_fn_call("apply(|n: i32| n * 2, 3)"),
+            Ok((
+                "",
+                Expr::FnCall(
+                    Box::new(Expr::Var("apply".to_string(), None)),
+                    vec![
+                        Expr::Closure(
+                            vec![(Expr::Var("n".to_string(), None), Type::Int)],
+                            Box::new(Expr::BinExpr(
+                                Box::new(Expr::Var("n".to_string(), None)),
+                                Op::AriOp(AriOp::Mul),
+                                Box::new(Expr::Int(2))
+                            ))
+                        ),
+                        Expr::Int(3)
+                    ]
+                )
+            ))
+        );
+        assert!(parse_closure("|x| x + 1").is_err());
+    }
+
+    #[test]
+    fn test_parse_str() {
+        assert_eq!(parse_str("\"\""), Ok(("", Expr::Str("".to_string()))));
+        assert_eq!(
+            parse_str(" \"hello world\" + a"),
+            Ok(("+ a", Expr::Str("hello world".to_string())))
+        );
+        assert_eq!(
+            parse_str(r#""a\tb\n\"c\"\\""#),
+            Ok(("", Expr::Str("a\tb\n\"c\"\\".to_string())))
+        );
+        assert!(parse_str(r#""a\qb""#).is_err());
+        assert_eq!(
+            parse_return("return \"a\" + b"),
+            Ok((
+                "",
+                Expr::Return(Box::new(Expr::VarExpr(
+                    Box::new(Expr::Str("a".to_string())),
+                    Op::AriOp(AriOp::Add),
+                    Box::new(Expr::Var("b".to_string(), None))
+                )))
+            ))
+        );
+        assert!(parse_str("\"abc").is_err());
+        assert_eq!(
+            parse_let("let s: str = a + \"b\";"),
+            Ok((
+                ";",
+                Expr::Let(
+                    Box::new(Expr::Var("s".to_string(), None)),
+                    Some(Type::Str),
+                    Box::new(Expr::BinExpr(
+                        Box::new(Expr::Var("".to_string(), None)),
+                        Op::AssOp(AssOp::Eq),
+                        Box::new(Expr::BinExpr(
+                            Box::new(Expr::Var("a".to_string(), None)),
+                            Op::AriOp(AriOp::Add),
+                            Box::new(Expr::Str("b".to_string()))
+                        ))
+                    ))
+                )
+            ))
+        );
+    }
+
+    #[test]
+    fn test_parse_op() {
+        assert_eq!(parse_op("+"), Ok(("", Op::AriOp(AriOp::Add))));
+        assert_eq!(parse_op("="), Ok(("", Op::AssOp(AssOp::Eq))));
         assert_eq!(parse_op("&&"), Ok(("", Op::LogOp(LogOp::And))));
         assert_eq!(parse_op(">"), Ok(("", Op::RelOp(RelOp::Gre))));
     }
@@ -354,6 +1966,7 @@ mod parse_tests {
         assert_eq!(parse_ari_op("-"), Ok(("", Op::AriOp(AriOp::Sub))));
         assert_eq!(parse_ari_op("*"), Ok(("", Op::AriOp(AriOp::Mul))));
         assert_eq!(parse_ari_op("/"), Ok(("", Op::AriOp(AriOp::Div))));
+        assert_eq!(parse_ari_op("%"), Ok(("", Op::AriOp(AriOp::Rem))));
     }
 
     #[test]
@@ -363,6 +1976,7 @@ mod parse_tests {
         assert_eq!(parse_ass_op("-="), Ok(("", Op::AssOp(AssOp::SubEq))));
         assert_eq!(parse_ass_op("/="), Ok(("", Op::AssOp(AssOp::DivEq))));
         assert_eq!(parse_ass_op("*="), Ok(("", Op::AssOp(AssOp::MulEq))));
+        assert_eq!(parse_ass_op("%="), Ok(("", Op::AssOp(AssOp::RemEq))));
     }
 
     #[test]
@@ -415,16 +2029,16 @@ mod parse_tests {
         );
         assert_eq!(
             parse_return("return a"),
-            Ok(("", Expr::Return(Box::new(Expr::Var("a".to_string())))))
+            Ok(("", Expr::Return(Box::new(Expr::Var("a".to_string(), None)))))
         );
         assert_eq!(
             parse_return("return a + b"),
             Ok((
                 "",
                 Expr::Return(Box::new(Expr::VarExpr(
-                    Box::new(Expr::Var("a".to_string())),
+                    Box::new(Expr::Var("a".to_string(), None)),
                     Op::AriOp(AriOp::Add),
-                    Box::new(Expr::Var("b".to_string())),
+                    Box::new(Expr::Var("b".to_string(), None)),
                 )))
             ))
         );
@@ -433,7 +2047,7 @@ mod parse_tests {
             Ok((
                 "",
                 Expr::Return(Box::new(Expr::BinExpr(
-                    Box::new(Expr::Var("a".to_string())),
+                    Box::new(Expr::Var("a".to_string(), None)),
                     Op::AriOp(AriOp::Add),
                     Box::new(Expr::Int(1)),
                 )))
@@ -444,7 +2058,7 @@ mod parse_tests {
             Ok((
                 "",
                 Expr::Return(Box::new(Expr::FnCall(
-                    Box::new(Expr::Var("testfn".to_string())),
+                    Box::new(Expr::Var("testfn".to_string(), None)),
                     vec![Expr::Int(1), Expr::Bool(false), Expr::Int(3)]
                 )))
             ))
@@ -459,12 +2073,28 @@ mod parse_tests {
 
     #[test]
     fn test_parse_var() {
-        assert_eq!(parse_var("a"), Ok(("", Expr::Var("a".to_string()))));
+        assert_eq!(parse_var("a"), Ok(("", Expr::Var("a".to_string(), None))));
+        assert_eq!(
+            parse_var("read_i32()"),
+            Ok(("()", Expr::Var("read_i32".to_string(), None)))
+        );
     }
 
     #[test]
     fn test_parse_arg() {
         assert_eq!(parse_arg("1"), Ok(("", Expr::Int(1))));
+        assert_eq!(parse_arg("a"), Ok(("", Expr::Var("a".to_string(), None))));
+        assert_eq!(
+            parse_arg("a + b"),
+            Ok((
+                "",
+                Expr::VarExpr(
+                    Box::new(Expr::Var("a".to_string(), None)),
+                    Op::AriOp(AriOp::Add),
+                    Box::new(Expr::Var("b".to_string(), None)),
+                )
+            ))
+        );
     }
 
     #[test]
@@ -477,17 +2107,31 @@ mod parse_tests {
             parse_args("(1, true, 3)"),
             Ok(("", vec![Expr::Int(1), Expr::Bool(true), Expr::Int(3)]))
         );
+        assert_eq!(parse_args("()"), Ok(("", vec![])));
+        assert_eq!(
+            parse_args("(a, f(b))"),
+            Ok((
+                "",
+                vec![
+                    Expr::Var("a".to_string(), None),
+                    Expr::FnCall(
+                        Box::new(Expr::Var("f".to_string(), None)),
+                        vec![Expr::Var("b".to_string(), None)]
+                    )
+                ]
+            ))
+        );
     }
 
     #[test]
     fn test_parse_param() {
         assert_eq!(
             parse_param("a:i32"),
-            Ok(("", (Expr::Var("a".to_string()), Type::Int)))
+            Ok(("", (Expr::Var("a".to_string(), None), Type::Int)))
         );
         assert_eq!(
             parse_param("a:bool"),
-            Ok(("", (Expr::Var("a".to_string()), Type::Bool)))
+            Ok(("", (Expr::Var("a".to_string(), None), Type::Bool)))
         );
     }
 
@@ -498,8 +2142,8 @@ mod parse_tests {
             Ok((
                 "",
                 vec![
-                    (Expr::Var("a".to_string()), Type::Int),
-                    (Expr::Var("b".to_string()), Type::Bool)
+                    (Expr::Var("a".to_string(), None), Type::Int),
+                    (Expr::Var("b".to_string(), None), Type::Bool)
                 ]
             ))
         );
@@ -512,7 +2156,7 @@ mod parse_tests {
             Ok((
                 "",
                 Expr::FnCall(
-                    Box::new(Expr::Var("testfn".to_string())),
+                    Box::new(Expr::Var("testfn".to_string(), None)),
                     vec![Expr::Int(1), Expr::Int(2), Expr::Int(3)]
                 )
             ))
@@ -522,7 +2166,7 @@ mod parse_tests {
             Ok((
                 "",
                 Expr::FnCall(
-                    Box::new(Expr::Var("testfn".to_string())),
+                    Box::new(Expr::Var("testfn".to_string(), None)),
                     vec![Expr::Int(1), Expr::Bool(false), Expr::Int(3)]
                 )
             ))
@@ -535,7 +2179,7 @@ mod parse_tests {
             Ok((
                 "",
                 Expr::VarExpr(
-                    Box::new(Expr::Var("a".to_string())),
+                    Box::new(Expr::Var("a".to_string(), None)),
                     Op::AssOp(AssOp::Eq),
                     Box::new(Expr::Int(1)),
                 )
@@ -546,9 +2190,9 @@ mod parse_tests {
             Ok((
                 "",
                 Expr::VarExpr(
-                    Box::new(Expr::Var("a".to_string())),
+                    Box::new(Expr::Var("a".to_string(), None)),
                     Op::LogOp(LogOp::And),
-                    Box::new(Expr::Var("b".to_string())),
+                    Box::new(Expr::Var("b".to_string(), None)),
                 )
             ))
         );
@@ -557,9 +2201,9 @@ mod parse_tests {
             Ok((
                 "",
                 Expr::VarExpr(
-                    Box::new(Expr::Var("a".to_string())),
+                    Box::new(Expr::Var("a".to_string(), None)),
                     Op::LogOp(LogOp::Or),
-                    Box::new(Expr::Var("b".to_string())),
+                    Box::new(Expr::Var("b".to_string(), None)),
                 )
             ))
         );
@@ -567,151 +2211,408 @@ mod parse_tests {
             parse_var_expr("a == 1"),
             Ok((
                 "",
-                Expr::VarExpr(
-                    Box::new(Expr::Var("a".to_string())),
-                    Op::RelOp(RelOp::Eq),
-                    Box::new(Expr::Int(1)),
-                )
+                Expr::VarExpr(
+                    Box::new(Expr::Var("a".to_string(), None)),
+                    Op::RelOp(RelOp::Eq),
+                    Box::new(Expr::Int(1)),
+                )
+            ))
+        );
+        assert_eq!(
+            parse_var_expr("a != a"),
+            Ok((
+                "",
+                Expr::VarExpr(
+                    Box::new(Expr::Var("a".to_string(), None)),
+                    Op::RelOp(RelOp::Neq),
+                    Box::new(Expr::Var("a".to_string(), None)),
+                )
+            ))
+        );
+    }
+
+    #[test]
+    fn test_parse_declare() {
+        assert_eq!(
+            parse_declare("let a;"),
+            Ok((
+                ";",
+                Expr::Declare(Box::new(Expr::Var("a".to_string(), None)), None)
+            ))
+        );
+        assert_eq!(
+            parse_declare("let a: u8 ;"),
+            Ok((
+                ";",
+                Expr::Declare(Box::new(Expr::Var("a".to_string(), None)), Some(Type::U8))
+            ))
+        );
+        assert!(parse_declare("let a = 1;").is_err());
+    }
+
+    #[test]
+    fn test_parse_let() {
+        assert_eq!(
+            parse_let("let a = 1 + 2"),
+            Ok((
+                "",
+                Expr::Let(
+                    Box::new(Expr::Var("a".to_string(), None)),
+                    None,
+                    Box::new(Expr::BinExpr(
+                        Box::new(Expr::Var("".to_string(), None)),
+                        Op::AssOp(AssOp::Eq),
+                        Box::new(Expr::BinExpr(
+                            Box::new(Expr::Int(1)),
+                            Op::AriOp(AriOp::Add),
+                            Box::new(Expr::Int(2))
+                        ))
+                    ))
+                ),
+            ))
+        );
+        assert_eq!(
+            parse_let("let a: i32 = 1"),
+            Ok((
+                "",
+                Expr::Let(
+                    Box::new(Expr::Var("a".to_string(), None)),
+                    Some(Type::Int),
+                    Box::new(Expr::BinExpr(
+                        Box::new(Expr::Var("".to_string(), None)),
+                        Op::AssOp(AssOp::Eq),
+                        Box::new(Expr::Int(1))
+                    ))
+                ),
+            ))
+        );
+        assert_eq!(
+            parse_let("let a: i32 = b"),
+            Ok((
+                "",
+                Expr::Let(
+                    Box::new(Expr::Var("a".to_string(), None)),
+                    Some(Type::Int),
+                    Box::new(Expr::Var("b".to_string(), None))
+                ),
+            ))
+        );
+        assert_eq!(
+            parse_let("let a: bool = b && c"),
+            Ok((
+                "",
+                Expr::Let(
+                    Box::new(Expr::Var("a".to_string(), None)),
+                    Some(Type::Bool),
+                    Box::new(Expr::VarExpr(
+                        Box::new(Expr::Var("b".to_string(), None)),
+                        Op::LogOp(LogOp::And),
+                        Box::new(Expr::Var("c".to_string(), None)),
+                    ))
+                ),
+            ))
+        );
+        assert_eq!(
+            parse_let("let a: i32 = b + c"),
+            Ok((
+                "",
+                Expr::Let(
+                    Box::new(Expr::Var("a".to_string(), None)),
+                    Some(Type::Int),
+                    Box::new(Expr::VarExpr(
+                        Box::new(Expr::Var("b".to_string(), None)),
+                        Op::AriOp(AriOp::Add),
+                        Box::new(Expr::Var("c".to_string(), None)),
+                    ))
+                ),
+            ))
+        );
+        assert_eq!(
+            parse_let("let a: bool = true"),
+            Ok((
+                "",
+                Expr::Let(
+                    Box::new(Expr::Var("a".to_string(), None)),
+                    Some(Type::Bool),
+                    Box::new(Expr::BinExpr(
+                        Box::new(Expr::Var("".to_string(), None)),
+                        Op::AssOp(AssOp::Eq),
+                        Box::new(Expr::Bool(true))
+                    ))
+                ),
+            ))
+        );
+    }
+    #[test]
+    fn test_parse_block() {
+        assert_eq!(
+            parse_block("{return 1}").map(|(s, b)| (s, strip_block(b))),
+            Ok(("", vec![Expr::Return(Box::new(Expr::Int(1)))]))
+        );
+        assert_eq!(
+            parse_block("{ print_i32(a); println(); }").map(|(s, b)| (s, strip_block(b))),
+            Ok((
+                "",
+                vec![
+                    Expr::FnCall(
+                        Box::new(Expr::Var("print_i32".to_string(), None)),
+                        vec![Expr::Var("a".to_string(), None)]
+                    ),
+                    Expr::FnCall(Box::new(Expr::Var("println".to_string(), None)), vec![]),
+                ]
+            ))
+        );
+        assert_eq!(
+            parse_block("{{return 1}}").map(|(s, b)| (s, strip_block(b))),
+            Ok(("", vec![Expr::Return(Box::new(Expr::Int(1)))]))
+        );
+        assert_eq!(
+            parse_block("{let a: i32 = 1; return 1}").map(|(s, b)| (s, strip_block(b))),
+            Ok((
+                "",
+                vec![
+                    Expr::Let(
+                        Box::new(Expr::Var("a".to_string(), None)),
+                        Some(Type::Int),
+                        Box::new(Expr::BinExpr(
+                            Box::new(Expr::Var("".to_string(), None)),
+                            Op::AssOp(AssOp::Eq),
+                            Box::new(Expr::Int(1))
+                        ))
+                    ),
+                    Expr::Return(Box::new(Expr::Int(1)))
+                ]
+            ))
+        );
+        assert_eq!(
+            parse_block("{let a: bool = true; return a}").map(|(s, b)| (s, strip_block(b))),
+            Ok((
+                "",
+                vec![
+                    Expr::Let(
+                        Box::new(Expr::Var("a".to_string(), None)),
+                        Some(Type::Bool),
+                        Box::new(Expr::BinExpr(
+                            Box::new(Expr::Var("".to_string(), None)),
+                            Op::AssOp(AssOp::Eq),
+                            Box::new(Expr::Bool(true))
+                        ))
+                    ),
+                    Expr::Return(Box::new(Expr::Var("a".to_string(), None)))
+                ]
+            ))
+        );
+    }
+    #[test]
+    fn test_parse_tail() {
+        let tail = |expr| Expr::Tail(Box::new(expr));
+        assert_eq!(
+            parse_fn("fn answer() -> i32 { 42 }").map(|(s, e)| (s, strip_spans(e))),
+            Ok((
+                "",
+                Expr::Fn(
+                    Box::new(Expr::Var("answer".to_string(), None)),
+                    vec![],
+                    vec![],
+                    Type::Int,
+                    vec![tail(Expr::Int(42))]
+                )
+            ))
+        );
+        assert_eq!(
+            parse_block("{ print_i32(a); a * 2 }").map(|(s, b)| (s, strip_block(b))),
+            Ok((
+                "",
+                vec![
+                    Expr::FnCall(
+                        Box::new(Expr::Var("print_i32".to_string(), None)),
+                        vec![Expr::Var("a".to_string(), None)]
+                    ),
+                    tail(Expr::BinExpr(
+                        Box::new(Expr::Var("a".to_string(), None)),
+                        Op::AriOp(AriOp::Mul),
+                        Box::new(Expr::Int(2))
+                    ))
+                ]
+            ))
+        );
+        assert_eq!(
+            parse_block("{ if c { 1 } else { 2 } }").map(|(s, b)| (s, strip_block(b))),
+            Ok((
+                "",
+                vec![tail(Expr::IfElse(
+                    Box::new(Expr::Var("c".to_string(), None)),
+                    vec![tail(Expr::Int(1))],
+                    vec![tail(Expr::Int(2))]
+                ))]
+            ))
+        );
+        assert_eq!(
+            parse_bin_expr("{ let t = 1; t }").map(|(s, e)| (s, strip_spans(e))),
+            Ok((
+                "",
+                Expr::Block(vec![
+                    Expr::Let(
+                        Box::new(Expr::Var("t".to_string(), None)),
+                        None,
+                        Box::new(Expr::BinExpr(
+                            Box::new(Expr::Var("".to_string(), None)),
+                            Op::AssOp(AssOp::Eq),
+                            Box::new(Expr::Int(1))
+                        ))
+                    ),
+                    tail(Expr::Var("t".to_string(), None))
+                ])
             ))
         );
         assert_eq!(
-            parse_var_expr("a != a"),
+            parse_match("match s { Shape::Circle(r) => r * 2, _ => 0 }")
+                .map(|(s, e)| (s, strip_spans(e))),
             Ok((
                 "",
-                Expr::VarExpr(
-                    Box::new(Expr::Var("a".to_string())),
-                    Op::RelOp(RelOp::Neq),
-                    Box::new(Expr::Var("a".to_string())),
+                Expr::Match(
+                    Box::new(Expr::Var("s".to_string(), None)),
+                    vec![
+                        (
+                            Pattern::Variant(
+                                "Shape".to_string(),
+                                "Circle".to_string(),
+                                vec![Pattern::Binding("r".to_string(), None)]
+                            ),
+                            vec![tail(Expr::BinExpr(
+                                Box::new(Expr::Var("r".to_string(), None)),
+                                Op::AriOp(AriOp::Mul),
+                                Box::new(Expr::Int(2))
+                            ))]
+                        ),
+                        (Pattern::Wildcard, vec![tail(Expr::Int(0))])
+                    ]
                 )
             ))
         );
-    }
+        assert!(parse_tail("+ 1").is_err());
 
-    #[test]
-    fn test_parse_let() {
+        // Statements ending in a block need no `;`, an `if` without `else` is never a tail
+        let var = |name: &str| Expr::Var(name.to_string(), None);
+        let bump = Expr::VarExpr(
+            Box::new(var("i")),
+            Op::AssOp(AssOp::AddEq),
+            Box::new(Expr::Int(1)),
+        );
         assert_eq!(
-            parse_let("let a: i32 = 1"),
+            parse_block("{ while i < n { i += 1; } loop { break } i }")
+                .map(|(s, b)| (s, strip_block(b))),
             Ok((
                 "",
-                Expr::Let(
-                    Box::new(Expr::Var("a".to_string())),
-                    Type::Int,
-                    Box::new(Expr::BinExpr(
-                        Box::new(Expr::Var("".to_string())),
-                        Op::AssOp(AssOp::Eq),
-                        Box::new(Expr::Int(1))
-                    ))
-                ),
+                vec![
+                    Expr::While(
+                        Box::new(Expr::VarExpr(
+                            Box::new(var("i")),
+                            Op::RelOp(RelOp::Les),
+                            Box::new(var("n"))
+                        )),
+                        vec![bump.clone()]
+                    ),
+                    Expr::Loop(vec![Expr::Break(None)]),
+                    tail(var("i"))
+                ]
             ))
         );
         assert_eq!(
-            parse_let("let a: i32 = b"),
+            parse_block("{ i += 1; if done { break; } }").map(|(s, b)| (s, strip_block(b))),
             Ok((
                 "",
-                Expr::Let(
-                    Box::new(Expr::Var("a".to_string())),
-                    Type::Int,
-                    Box::new(Expr::Var("b".to_string()))
-                ),
+                vec![
+                    bump,
+                    Expr::If(Box::new(var("done")), vec![Expr::Break(None)])
+                ]
             ))
         );
+    }
+    #[test]
+    fn test_parse_for() {
         assert_eq!(
-            parse_let("let a: bool = b && c"),
+            parse_for("for i in 0..n { if i == 3 { continue }; print_i32(i); }")
+                .map(|(s, e)| (s, strip_spans(e))),
             Ok((
                 "",
-                Expr::Let(
-                    Box::new(Expr::Var("a".to_string())),
-                    Type::Bool,
-                    Box::new(Expr::VarExpr(
-                        Box::new(Expr::Var("b".to_string())),
-                        Op::LogOp(LogOp::And),
-                        Box::new(Expr::Var("c".to_string())),
-                    ))
-                ),
+                Expr::For(
+                    Box::new(Expr::Var("i".to_string(), None)),
+                    Box::new(Expr::Range(
+                        Box::new(Expr::Int(0)),
+                        Box::new(Expr::Var("n".to_string(), None)),
+                        false
+                    )),
+                    vec![
+                        Expr::If(
+                            Box::new(Expr::VarExpr(
+                                Box::new(Expr::Var("i".to_string(), None)),
+                                Op::RelOp(RelOp::Eq),
+                                Box::new(Expr::Int(3))
+                            )),
+                            vec![Expr::Continue]
+                        ),
+                        Expr::FnCall(
+                            Box::new(Expr::Var("print_i32".to_string(), None)),
+                            vec![Expr::Var("i".to_string(), None)]
+                        )
+                    ]
+                )
             ))
         );
         assert_eq!(
-            parse_let("let a: i32 = b + c"),
+            parse_range("1..=10"),
             Ok((
                 "",
-                Expr::Let(
-                    Box::new(Expr::Var("a".to_string())),
-                    Type::Int,
-                    Box::new(Expr::VarExpr(
-                        Box::new(Expr::Var("b".to_string())),
-                        Op::AriOp(AriOp::Add),
-                        Box::new(Expr::Var("c".to_string())),
-                    ))
-                ),
+                Expr::Range(Box::new(Expr::Int(1)), Box::new(Expr::Int(10)), true)
             ))
         );
         assert_eq!(
-            parse_let("let a: bool = true"),
+            parse_block("{ while true { break }; }").map(|(s, b)| (s, strip_block(b))),
             Ok((
                 "",
-                Expr::Let(
-                    Box::new(Expr::Var("a".to_string())),
-                    Type::Bool,
-                    Box::new(Expr::BinExpr(
-                        Box::new(Expr::Var("".to_string())),
-                        Op::AssOp(AssOp::Eq),
-                        Box::new(Expr::Bool(true))
-                    ))
-                ),
+                vec![Expr::While(
+                    Box::new(Expr::Bool(true)),
+                    vec![Expr::Break(None)]
+                )]
             ))
         );
+        assert!(parse_break("breaking").is_err());
     }
     #[test]
-    fn test_parse_block() {
-        assert_eq!(
-            parse_block("{return 1}"),
-            Ok(("", vec![Expr::Return(Box::new(Expr::Int(1)))]))
-        );
+    fn test_parse_loop() {
         assert_eq!(
-            parse_block("{{return 1}}"),
-            Ok(("", vec![Expr::Return(Box::new(Expr::Int(1)))]))
-        );
-        assert_eq!(
-            parse_block("{let a: i32 = 1; return 1}"),
+            parse_bin_expr("loop { if done { break n * 2 }; n += 1; }")
+                .map(|(s, e)| (s, strip_spans(e))),
             Ok((
                 "",
-                vec![
-                    Expr::Let(
-                        Box::new(Expr::Var("a".to_string())),
-                        Type::Int,
-                        Box::new(Expr::BinExpr(
-                            Box::new(Expr::Var("".to_string())),
-                            Op::AssOp(AssOp::Eq),
-                            Box::new(Expr::Int(1))
-                        ))
+                Expr::Loop(vec![
+                    Expr::If(
+                        Box::new(Expr::Var("done".to_string(), None)),
+                        vec![Expr::Break(Some(Box::new(Expr::BinExpr(
+                            Box::new(Expr::Var("n".to_string(), None)),
+                            Op::AriOp(AriOp::Mul),
+                            Box::new(Expr::Int(2))
+                        ))))]
                     ),
-                    Expr::Return(Box::new(Expr::Int(1)))
-                ]
+                    Expr::VarExpr(
+                        Box::new(Expr::Var("n".to_string(), None)),
+                        Op::AssOp(AssOp::AddEq),
+                        Box::new(Expr::Int(1))
+                    )
+                ])
             ))
         );
         assert_eq!(
-            parse_block("{let a: bool = true; return a}"),
-            Ok((
-                "",
-                vec![
-                    Expr::Let(
-                        Box::new(Expr::Var("a".to_string())),
-                        Type::Bool,
-                        Box::new(Expr::BinExpr(
-                            Box::new(Expr::Var("".to_string())),
-                            Op::AssOp(AssOp::Eq),
-                            Box::new(Expr::Bool(true))
-                        ))
-                    ),
-                    Expr::Return(Box::new(Expr::Var("a".to_string())))
-                ]
-            ))
+            parse_block("{ loop {} }").map(|(s, b)| (s, strip_block(b))),
+            Ok(("", vec![Expr::Tail(Box::new(Expr::Loop(vec![])))]))
         );
+        assert!(parse_loop("looping {}").is_err());
     }
     #[test]
     fn test_parse_if() {
         assert_eq!(
-            parse_if("if true {return 1}"),
+            parse_if("if true {return 1}").map(|(s, e)| (s, strip_spans(e))),
             Ok((
                 "",
                 Expr::If(
@@ -721,24 +2622,24 @@ mod parse_tests {
             ))
         );
         assert_eq!(
-            parse_if("if a {return 1}"),
+            parse_if("if a {return 1}").map(|(s, e)| (s, strip_spans(e))),
             Ok((
                 "",
                 Expr::If(
-                    Box::new(Expr::Var("a".to_string())),
+                    Box::new(Expr::Var("a".to_string(), None)),
                     vec![Expr::Return(Box::new(Expr::Int(1)))]
                 )
             ))
         );
         assert_eq!(
-            parse_if("if a == b {return 1}"),
+            parse_if("if a == b {return 1}").map(|(s, e)| (s, strip_spans(e))),
             Ok((
                 "",
                 Expr::If(
                     Box::new(Expr::VarExpr(
-                        Box::new(Expr::Var("a".to_string())),
+                        Box::new(Expr::Var("a".to_string(), None)),
                         Op::RelOp(RelOp::Eq),
-                        Box::new(Expr::Var("b".to_string()))
+                        Box::new(Expr::Var("b".to_string(), None))
                     )),
                     vec![Expr::Return(Box::new(Expr::Int(1)))]
                 ),
@@ -748,7 +2649,7 @@ mod parse_tests {
     #[test]
     fn test_parse_if_else() {
         assert_eq!(
-            parse_if_else("if true {return 1} else {return 1}"),
+            parse_if_else("if true {return 1} else {return 1}").map(|(s, e)| (s, strip_spans(e))),
             Ok((
                 "",
                 Expr::IfElse(
@@ -759,11 +2660,11 @@ mod parse_tests {
             ))
         );
         assert_eq!(
-            parse_if_else("if a {return 1} else {return 1}"),
+            parse_if_else("if a {return 1} else {return 1}").map(|(s, e)| (s, strip_spans(e))),
             Ok((
                 "",
                 Expr::IfElse(
-                    Box::new(Expr::Var("a".to_string())),
+                    Box::new(Expr::Var("a".to_string(), None)),
                     vec![Expr::Return(Box::new(Expr::Int(1)))],
                     vec![Expr::Return(Box::new(Expr::Int(1)))],
                 )
@@ -771,14 +2672,14 @@ mod parse_tests {
         );
 
         assert_eq!(
-            parse_if_else("if a == b {return 1} else {return 1}"),
+            parse_if_else("if a == b {return 1} else {return 1}").map(|(s, e)| (s, strip_spans(e))),
             Ok((
                 "",
                 Expr::IfElse(
                     Box::new(Expr::VarExpr(
-                        Box::new(Expr::Var("a".to_string())),
+                        Box::new(Expr::Var("a".to_string(), None)),
                         Op::RelOp(RelOp::Eq),
-                        Box::new(Expr::Var("b".to_string()))
+                        Box::new(Expr::Var("b".to_string(), None))
                     )),
                     vec![Expr::Return(Box::new(Expr::Int(1)))],
                     vec![Expr::Return(Box::new(Expr::Int(1)))],
@@ -789,7 +2690,7 @@ mod parse_tests {
     #[test]
     fn test_parse_while() {
         assert_eq!(
-            parse_while("while false {return true}"),
+            parse_while("while false {return true}").map(|(s, e)| (s, strip_spans(e))),
             Ok((
                 "",
                 Expr::While(
@@ -799,14 +2700,14 @@ mod parse_tests {
             ))
         );
         assert_eq!(
-            parse_while("while a && b {return 1}"),
+            parse_while("while a && b {return 1}").map(|(s, e)| (s, strip_spans(e))),
             Ok((
                 "",
                 Expr::While(
                     Box::new(Expr::VarExpr(
-                        Box::new(Expr::Var("a".to_string())),
+                        Box::new(Expr::Var("a".to_string(), None)),
                         Op::LogOp(LogOp::And),
-                        Box::new(Expr::Var("b".to_string()))
+                        Box::new(Expr::Var("b".to_string(), None))
                     )),
                     vec![Expr::Return(Box::new(Expr::Int(1)))]
                 ),
@@ -816,43 +2717,367 @@ mod parse_tests {
     #[test]
     fn test_parse_fn() {
         assert_eq!(
-            parse_fn("fn testfn(a: i32) -> () { return 1 }"),
+            parse_fn("fn testfn(a: i32) -> () { return 1 }").map(|(s, e)| (s, strip_spans(e))),
             Ok((
                 "",
                 Expr::Fn(
-                    Box::new(Expr::Var("testfn".to_string())),
-                    vec![(Expr::Var("a".to_string()), Type::Int)],
+                    Box::new(Expr::Var("testfn".to_string(), None)),
+                    vec![],
+                    vec![(Expr::Var("a".to_string(), None), Type::Int)],
                     Type::Void,
                     vec![Expr::Return(Box::new(Expr::Int(1)))]
                 ),
             ))
         );
         assert_eq!(
-            parse_fn("fn testfn(a: bool) -> i32 { if a { let b: i32 = 1; return b};}"),
+            parse_fn("fn testfn(a: bool) -> i32 { if a { let b: i32 = 1; return b};}")
+                .map(|(s, e)| (s, strip_spans(e))),
             Ok((
                 "",
                 Expr::Fn(
-                    Box::new(Expr::Var("testfn".to_string())),
-                    vec![(Expr::Var("a".to_string()), Type::Bool)],
+                    Box::new(Expr::Var("testfn".to_string(), None)),
+                    vec![],
+                    vec![(Expr::Var("a".to_string(), None), Type::Bool)],
                     Type::Int,
                     vec![Expr::If(
-                        Box::new(Expr::Var("a".to_string())),
+                        Box::new(Expr::Var("a".to_string(), None)),
                         vec![
                             Expr::Let(
-                                Box::new(Expr::Var("b".to_string())),
-                                Type::Int,
+                                Box::new(Expr::Var("b".to_string(), None)),
+                                Some(Type::Int),
                                 Box::new(Expr::BinExpr(
-                                    Box::new(Expr::Var("".to_string())),
+                                    Box::new(Expr::Var("".to_string(), None)),
                                     Op::AssOp(AssOp::Eq),
                                     Box::new(Expr::Int(1))
                                 ))
                             ),
-                            Expr::Return(Box::new(Expr::Var("b".to_string())))
+                            Expr::Return(Box::new(Expr::Var("b".to_string(), None)))
                         ]
                     )]
                 ),
             ))
         );
+        assert_eq!(
+            parse_fn("fn pick<T, U>(a: T, b: [U; 2]) -> T { return a }")
+                .map(|(s, e)| (s, strip_spans(e))),
+            Ok((
+                "",
+                Expr::Fn(
+                    Box::new(Expr::Var("pick".to_string(), None)),
+                    vec![("T".to_string(), vec![]), ("U".to_string(), vec![])],
+                    vec![
+                        (
+                            Expr::Var("a".to_string(), None),
+                            Type::Named("T".to_string())
+                        ),
+                        (
+                            Expr::Var("b".to_string(), None),
+                            Type::Array(Box::new(Type::Named("U".to_string())), 2)
+                        )
+                    ],
+                    Type::Named("T".to_string()),
+                    vec![Expr::Return(Box::new(Expr::Var("a".to_string(), None)))]
+                ),
+            ))
+        );
+    }
+
+    #[test]
+    fn test_parse_extern_fn() {
+        assert_eq!(
+            parse_extern_fn("extern fn abs(x: i32) -> i32;"),
+            Ok((
+                "",
+                Expr::ExternFn(
+                    Box::new(Expr::Var("abs".to_string(), None)),
+                    vec![(Expr::Var("x".to_string(), None), Type::Int)],
+                    Type::Int,
+                ),
+            ))
+        );
+        assert_eq!(
+            parser("extern fn f() -> ();\nfn main() -> i32 { return 1 }")
+                .map(|(s, p)| (s, strip_block(p))),
+            Ok((
+                "",
+                vec![
+                    Expr::ExternFn(
+                        Box::new(Expr::Var("f".to_string(), None)),
+                        vec![],
+                        Type::Void
+                    ),
+                    Expr::Fn(
+                        Box::new(Expr::Var("main".to_string(), None)),
+                        vec![],
+                        vec![],
+                        Type::Int,
+                        vec![Expr::Return(Box::new(Expr::Int(1)))]
+                    ),
+                ]
+            ))
+        );
+        assert!(parse_extern_fn("extern fn abs(x: i32) -> i32").is_err());
+    }
+
+    #[test]
+    fn test_parse_const() {
+        assert_eq!(
+            parse_const("const LIMIT: i32 = SIZE * 2;"),
+            Ok((
+                "",
+                Expr::Const(
+                    Box::new(Expr::Var("LIMIT".to_string(), None)),
+                    Type::Int,
+                    Box::new(Expr::BinExpr(
+                        Box::new(Expr::Var("SIZE".to_string(), None)),
+                        Op::AriOp(AriOp::Mul),
+                        Box::new(Expr::Int(2))
+                    ))
+                ),
+            ))
+        );
+        assert_eq!(
+            parser("static mut COUNT: u8 = 0u8;\nstatic NAME: str = \"x\";")
+                .map(|(s, p)| (s, strip_block(p))),
+            Ok((
+                "",
+                vec![
+                    Expr::Static(
+                        Box::new(Expr::Var("COUNT".to_string(), None)),
+                        Type::U8,
+                        Box::new(Expr::TypedInt(0, Type::U8)),
+                        true
+                    ),
+                    Expr::Static(
+                        Box::new(Expr::Var("NAME".to_string(), None)),
+                        Type::Str,
+                        Box::new(Expr::Str("x".to_string())),
+                        false
+                    ),
+                ]
+            ))
+        );
+        assert!(parse_const("const LIMIT = 2;").is_err());
+        assert!(parse_const("constant: i32 = 2;").is_err());
+        assert!(parse_static("static mutable: i32 = 2").is_err());
+    }
+
+    #[test]
+    fn test_parse_mod() {
+        assert_eq!(
+            parser(
+                "mod shapes;\npub mod math { pub fn one() -> i32 { return 1 } }\nuse math::one;"
+            )
+            .map(|(s, p)| (s, strip_block(p))),
+            Ok((
+                "",
+                vec![
+                    Expr::Mod("shapes".to_string(), None),
+                    Expr::Pub(Box::new(Expr::Mod(
+                        "math".to_string(),
+                        Some(vec![Expr::Spanned(
+                            Span {
+                                line: 2,
+                                column: 16,
+                                file: None,
+                            },
+                            Box::new(Expr::Pub(Box::new(Expr::Fn(
+                                Box::new(Expr::Var("one".to_string(), None)),
+                                vec![],
+                                vec![],
+                                Type::Int,
+                                vec![Expr::Spanned(
+                                    Span {
+                                        line: 2,
+                                        column: 38,
+                                        file: None,
+                                    },
+                                    Box::new(Expr::Return(Box::new(Expr::Int(1))))
+                                )]
+                            ))))
+                        )])
+                    ))),
+                    Expr::Use(vec!["math".to_string(), "one".to_string()]),
+                ]
+            ))
+        );
+        assert_eq!(
+            parse_variant("geometry::area(s)"),
+            Ok((
+                "",
+                Expr::Variant(
+                    "geometry".to_string(),
+                    "area".to_string(),
+                    vec![Expr::Var("s".to_string(), None)]
+                )
+            ))
+        );
+        assert_eq!(
+            parse_variant("crate::math::zero()"),
+            Ok((
+                "",
+                Expr::FnCall(
+                    Box::new(Expr::Var("crate::math::zero".to_string(), None)),
+                    vec![]
+                )
+            ))
+        );
+        assert_eq!(
+            parse_scope("math::log(1)").map(|(s, e)| (s, strip_spans(e))),
+            Ok((
+                "",
+                Expr::FnCall(
+                    Box::new(Expr::Var("math::log".to_string(), None)),
+                    vec![Expr::Int(1)]
+                )
+            ))
+        );
+        assert!(parse_mod("module m;").is_err());
+        assert_eq!(
+            parse_pub("pub struct S { x: i32 }").map(|(s, e)| (s, strip_spans(e))),
+            Ok((
+                "",
+                Expr::Pub(Box::new(Expr::Struct(
+                    Box::new(Expr::Var("S".to_string(), None)),
+                    vec![(Expr::Var("x".to_string(), None), Type::Int)]
+                )))
+            ))
+        );
+        assert_eq!(
+            parse_pub("pub use math::one;"),
+            Ok((
+                "",
+                Expr::Pub(Box::new(Expr::Use(vec![
+                    "math".to_string(),
+                    "one".to_string()
+                ])))
+            ))
+        );
+        assert!(parse_pub("pub impl S { }").is_err());
+    }
+
+    #[test]
+    fn test_parse_file() {
+        assert_eq!(
+            parse_file("fn main() -> i32 { return 0 }\n", None).map(|items| items.len()),
+            Ok(1)
+        );
+        assert_eq!(
+            parse_file("fn main() -> i32 { return 0 }\n\nfn broken( {", None),
+            Err(Span {
+                line: 3,
+                column: 1,
+                file: None
+            })
+        );
+        assert_eq!(
+            parse_file("fn f(", Some("m.rs")).unwrap_err().to_string(),
+            "m.rs:1:1"
+        );
+        assert_eq!(
+            parse_file(
+                "fn main() -> i32 {\n    let i = 0;\n    if i == 0 { let j = 1 2; }\n    i\n}",
+                None
+            )
+            .unwrap_err()
+            .to_string(),
+            "3:17"
+        );
+    }
+
+    #[test]
+    fn test_parse_trait() {
+        assert_eq!(
+            parse_trait("trait Shape { fn area(self) -> i32; fn scaled(self, by: i32) -> Self; }"),
+            Ok((
+                "",
+                Expr::Trait(
+                    Box::new(Expr::Var("Shape".to_string(), None)),
+                    vec![
+                        (
+                            Expr::Var("area".to_string(), None),
+                            vec![(
+                                Expr::Var("self".to_string(), None),
+                                Type::Named("Self".to_string())
+                            )],
+                            Type::Int
+                        ),
+                        (
+                            Expr::Var("scaled".to_string(), None),
+                            vec![
+                                (
+                                    Expr::Var("self".to_string(), None),
+                                    Type::Named("Self".to_string())
+                                ),
+                                (Expr::Var("by".to_string(), None), Type::Int)
+                            ],
+                            Type::Named("Self".to_string())
+                        )
+                    ]
+                ),
+            ))
+        );
+        assert_eq!(
+            parse_impl("impl Shape for i32 { fn area(self) -> i32 { return self } }")
+                .map(|(s, e)| (s, strip_spans(e))),
+            Ok((
+                "",
+                Expr::Impl(
+                    "Shape".to_string(),
+                    Type::Int,
+                    vec![Expr::Fn(
+                        Box::new(Expr::Var("area".to_string(), None)),
+                        vec![],
+                        vec![(
+                            Expr::Var("self".to_string(), None),
+                            Type::Named("Self".to_string())
+                        )],
+                        Type::Int,
+                        vec![Expr::Return(Box::new(Expr::Var("self".to_string(), None)))]
+                    )]
+                ),
+            ))
+        );
+        assert_eq!(
+            parse_method_call("c.scaled(2).area()"),
+            Ok((
+                "",
+                Expr::MethodCall(
+                    Box::new(Expr::MethodCall(
+                        Box::new(Expr::Var("c".to_string(), None)),
+                        "scaled".to_string(),
+                        vec![Expr::Int(2)]
+                    )),
+                    "area".to_string(),
+                    vec![]
+                ),
+            ))
+        );
+        assert_eq!(
+            parse_fn("fn total<T: Shape + Sized>(s: T) -> i32 { return s.area() }")
+                .map(|(s, e)| (s, strip_spans(e))),
+            Ok((
+                "",
+                Expr::Fn(
+                    Box::new(Expr::Var("total".to_string(), None)),
+                    vec![(
+                        "T".to_string(),
+                        vec!["Shape".to_string(), "Sized".to_string()]
+                    )],
+                    vec![(
+                        Expr::Var("s".to_string(), None),
+                        Type::Named("T".to_string())
+                    )],
+                    Type::Int,
+                    vec![Expr::Return(Box::new(Expr::MethodCall(
+                        Box::new(Expr::Var("s".to_string(), None)),
+                        "area".to_string(),
+                        vec![]
+                    )))]
+                ),
+            ))
+        );
+        assert!(parse_method_call("c.r").is_err());
     }
 
     #[test]
@@ -890,132 +3115,137 @@ mod parse_tests {
                     return a + b
                 }
                 "
-            ),
+            )
+            .map(|(s, b)| (s, strip_block(b))),
             Ok((
                 "",
                 vec![
                     Expr::Fn(
-                        Box::new(Expr::Var("testfn1".to_string())),
-                        vec![(Expr::Var("a".to_string()), Type::Bool)],
+                        Box::new(Expr::Var("testfn1".to_string(), None)),
+                        vec![],
+                        vec![(Expr::Var("a".to_string(), None), Type::Bool)],
                         Type::Int,
                         vec![
                             Expr::Let(
-                                Box::new(Expr::Var("c".to_string())),
-                                Type::Int,
+                                Box::new(Expr::Var("c".to_string(), None)),
+                                Some(Type::Int),
                                 Box::new(Expr::BinExpr(
-                                    Box::new(Expr::Var("".to_string())),
+                                    Box::new(Expr::Var("".to_string(), None)),
                                     Op::AssOp(AssOp::Eq),
                                     Box::new(Expr::Int(2)),
                                 )),
                             ),
                             Expr::IfElse(
-                                Box::new(Expr::Var("a".to_string())),
+                                Box::new(Expr::Var("a".to_string(), None)),
                                 vec![
                                     Expr::Let(
-                                        Box::new(Expr::Var("b".to_string())),
-                                        Type::Int,
+                                        Box::new(Expr::Var("b".to_string(), None)),
+                                        Some(Type::Int),
                                         Box::new(Expr::BinExpr(
-                                            Box::new(Expr::Var("".to_string())),
+                                            Box::new(Expr::Var("".to_string(), None)),
                                             Op::AssOp(AssOp::Eq),
                                             Box::new(Expr::Int(1)),
                                         )),
                                     ),
-                                    Expr::Return(Box::new(Expr::Var("b".to_string()))),
+                                    Expr::Return(Box::new(Expr::Var("b".to_string(), None))),
                                 ],
-                                vec![Expr::Return(Box::new(Expr::Var("c".to_string())))],
+                                vec![Expr::Return(Box::new(Expr::Var("c".to_string(), None)))],
                             ),
                         ],
                     ),
                     Expr::Fn(
-                        Box::new(Expr::Var("testfn2".to_string())),
+                        Box::new(Expr::Var("testfn2".to_string(), None)),
+                        vec![],
                         vec![],
                         Type::Int,
                         vec![Expr::Return(Box::new(Expr::FnCall(
-                            Box::new(Expr::Var("testfn1".to_string())),
+                            Box::new(Expr::Var("testfn1".to_string(), None)),
                             vec![Expr::Bool(true)],
                         )))],
                     ),
                     Expr::Fn(
-                        Box::new(Expr::Var("testfn3".to_string())),
+                        Box::new(Expr::Var("testfn3".to_string(), None)),
+                        vec![],
                         vec![
-                            (Expr::Var("b".to_string()), Type::Bool),
-                            (Expr::Var("c".to_string()), Type::Bool),
+                            (Expr::Var("b".to_string(), None), Type::Bool),
+                            (Expr::Var("c".to_string(), None), Type::Bool),
                         ],
                         Type::Int,
                         vec![
                             Expr::Let(
-                                Box::new(Expr::Var("d".to_string())),
-                                Type::Bool,
+                                Box::new(Expr::Var("d".to_string(), None)),
+                                Some(Type::Bool),
                                 Box::new(Expr::VarExpr(
-                                    Box::new(Expr::Var("b".to_string())),
+                                    Box::new(Expr::Var("b".to_string(), None)),
                                     Op::LogOp(LogOp::And),
-                                    Box::new(Expr::Var("c".to_string())),
+                                    Box::new(Expr::Var("c".to_string(), None)),
                                 )),
                             ),
                             Expr::Let(
-                                Box::new(Expr::Var("n".to_string())),
-                                Type::Int,
+                                Box::new(Expr::Var("n".to_string(), None)),
+                                Some(Type::Int),
                                 Box::new(Expr::BinExpr(
-                                    Box::new(Expr::Var("".to_string())),
+                                    Box::new(Expr::Var("".to_string(), None)),
                                     Op::AssOp(AssOp::Eq),
                                     Box::new(Expr::Int(0)),
                                 )),
                             ),
                             Expr::While(
                                 Box::new(Expr::VarExpr(
-                                    Box::new(Expr::Var("d".to_string())),
+                                    Box::new(Expr::Var("d".to_string(), None)),
                                     Op::RelOp(RelOp::Eq),
                                     Box::new(Expr::Bool(true)),
                                 )),
                                 vec![
                                     Expr::VarExpr(
-                                        Box::new(Expr::Var("n".to_string())),
+                                        Box::new(Expr::Var("n".to_string(), None)),
                                         Op::AssOp(AssOp::AddEq),
                                         Box::new(Expr::Int(1)),
                                     ),
                                     Expr::VarExpr(
-                                        Box::new(Expr::Var("d".to_string())),
+                                        Box::new(Expr::Var("d".to_string(), None)),
                                         Op::AssOp(AssOp::Eq),
                                         Box::new(Expr::Bool(false)),
                                     ),
                                 ],
                             ),
-                            Expr::Return(Box::new(Expr::Var("n".to_string()))),
+                            Expr::Return(Box::new(Expr::Var("n".to_string(), None))),
                         ],
                     ),
                     Expr::Fn(
-                        Box::new(Expr::Var("main".to_string())),
+                        Box::new(Expr::Var("main".to_string(), None)),
+                        vec![],
                         vec![],
                         Type::Int,
                         vec![
                             Expr::Let(
-                                Box::new(Expr::Var("a".to_string())),
-                                Type::Int,
+                                Box::new(Expr::Var("a".to_string(), None)),
+                                Some(Type::Int),
                                 Box::new(Expr::BinExpr(
-                                    Box::new(Expr::Var("".to_string())),
+                                    Box::new(Expr::Var("".to_string(), None)),
                                     Op::AssOp(AssOp::Eq),
                                     Box::new(Expr::FnCall(
-                                        Box::new(Expr::Var("testfn2".to_string())),
+                                        Box::new(Expr::Var("testfn2".to_string(), None)),
                                         vec![],
                                     )),
                                 )),
                             ),
                             Expr::Let(
-                                Box::new(Expr::Var("b".to_string())),
-                                Type::Int,
+                                Box::new(Expr::Var("b".to_string(), None)),
+                                Some(Type::Int),
                                 Box::new(Expr::BinExpr(
-                                    Box::new(Expr::Var("".to_string())),
+                                    Box::new(Expr::Var("".to_string(), None)),
                                     Op::AssOp(AssOp::Eq),
                                     Box::new(Expr::FnCall(
-                                        Box::new(Expr::Var("testfn3".to_string())),
+                                        Box::new(Expr::Var("testfn3".to_string(), None)),
                                         vec![Expr::Bool(true), Expr::Bool(true)],
                                     )),
                                 )),
                             ),
                             Expr::Return(Box::new(Expr::VarExpr(
-                                Box::new(Expr::Var("a".to_string())),
+                                Box::new(Expr::Var("a".to_string(), None)),
                                 Op::AriOp(AriOp::Add),
-                                Box::new(Expr::Var("b".to_string())),
+                                Box::new(Expr::Var("b".to_string(), None)),
                             ))),
                         ],
                     ),
@@ -1023,4 +3253,53 @@ mod parse_tests {
             ))
         );
     }
+
+    #[test]
+    fn test_parse_span() {
+        assert_eq!(
+            parser("fn main() -> i32 {\n    let a: i32 = 1;\n    return a\n}"),
+            Ok((
+                "",
+                vec![Expr::Spanned(
+                    Span {
+                        line: 1,
+                        column: 1,
+                        file: None,
+                    },
+                    Box::new(Expr::Fn(
+                        Box::new(Expr::Var("main".to_string(), None)),
+                        vec![],
+                        vec![],
+                        Type::Int,
+                        vec![
+                            Expr::Spanned(
+                                Span {
+                                    line: 2,
+                                    column: 5,
+                                    file: None,
+                                },
+                                Box::new(Expr::Let(
+                                    Box::new(Expr::Var("a".to_string(), None)),
+                                    Some(Type::Int),
+                                    Box::new(Expr::BinExpr(
+                                        Box::new(Expr::Var("".to_string(), None)),
+                                        Op::AssOp(AssOp::Eq),
+                                        Box::new(Expr::Int(1))
+                                    ))
+                                ))
+                            ),
+                            Expr::Spanned(
+                                Span {
+                                    line: 3,
+                                    column: 5,
+                                    file: None,
+                                },
+                                Box::new(Expr::Return(Box::new(Expr::Var("a".to_string(), None))))
+                            ),
+                        ]
+                    ))
+                )]
+            ))
+        );
+    }
 }
@@ -0,0 +1,267 @@
+use crate::ast::*;
+
+use std::{collections::HashMap, iter, slice};
+
+/// Variants of every enum in the program with the types of their fields.
+pub type Enums = HashMap<String, Vec<(String, Vec<Type>)>>;
+
+/// A pattern reduced to what matters for exhaustiveness, bindings are wildcards.
+#[derive(Debug, PartialEq, Clone)]
+enum Pat {
+    Wild,
+    Ctor(Ctor, Vec<Pat>),
+}
+
+#[derive(Debug, PartialEq, Clone)]
+enum Ctor {
+    Bool(bool),
+    Int(i128),
+    /// Index of the variant in the declaration of its enum
+    Variant(usize),
+}
+
+/// Checks the arms of a `match` on a value of `ty`, every arm must be reachable
+/// and every value must be matched. The patterns must already be well typed.
+pub fn check_arms(patterns: &[Pattern], ty: &Type, enums: &Enums) -> Result<(), String> {
+    let mut rows: Vec<Vec<Pat>> = Vec::new();
+    for pattern in patterns {
+        let row = vec![lower(pattern, enums)];
+        if !is_useful(&rows, &row, slice::from_ref(ty), enums) {
+            return Err(format!("unreachable pattern `{}`", pattern));
+        }
+        rows.push(row);
+    }
+
+    match witness(&rows, slice::from_ref(ty), enums) {
+        Some(values) => Err(format!(
+            "non-exhaustive patterns: `{}` not covered",
+            raise(&values[0], ty, enums)
+        )),
+        None => Ok(()),
+    }
+}
+
+fn lower(pattern: &Pattern, enums: &Enums) -> Pat {
+    match pattern {
+        Pattern::Wildcard | Pattern::Binding(_, _) => Pat::Wild,
+        Pattern::Literal(Expr::Bool(b)) => Pat::Ctor(Ctor::Bool(*b), Vec::new()),
+        Pattern::Literal(Expr::Int(i)) => Pat::Ctor(Ctor::Int(*i as i128), Vec::new()),
+        Pattern::Literal(Expr::TypedInt(i, _)) => Pat::Ctor(Ctor::Int(*i as i128), Vec::new()),
+        Pattern::Literal(expr) => panic!("Invalid literal pattern {:?}!", expr),
+        Pattern::Variant(enum_name, variant, fields) => {
+            let index = enums[enum_name]
+                .iter()
+                .position(|(name, _)| name == variant)
+                .unwrap();
+            let fields = fields.iter().map(|field| lower(field, enums)).collect();
+            Pat::Ctor(Ctor::Variant(index), fields)
+        }
+    }
+}
+
+/// Turns a value found by `witness` back into a pattern that can be shown.
+fn raise(pat: &Pat, ty: &Type, enums: &Enums) -> Pattern {
+    match (pat, ty) {
+        (Pat::Wild, _) => Pattern::Wildcard,
+        (Pat::Ctor(Ctor::Bool(b), _), _) => Pattern::Literal(Expr::Bool(*b)),
+        (Pat::Ctor(Ctor::Int(i), _), _) => Pattern::Literal(Expr::Int(*i as i32)),
+        (Pat::Ctor(Ctor::Variant(index), fields), Type::Named(enum_name)) => {
+            let (variant, field_types) = &enums[enum_name][*index];
+            let fields = fields
+                .iter()
+                .zip(field_types)
+                .map(|(field, field_type)| raise(field, field_type, enums))
+                .collect();
+            Pattern::Variant(enum_name.to_string(), variant.to_string(), fields)
+        }
+        _ => panic!("Invalid pattern for type {}!", ty),
+    }
+}
+
+/// All constructors of `ty`, `None` when there are too many to list like for integers.
+fn all_ctors(ty: &Type, enums: &Enums) -> Option<Vec<Ctor>> {
+    match ty {
+        Type::Bool => Some(vec![Ctor::Bool(false), Ctor::Bool(true)]),
+        Type::Named(name) => enums
+            .get(name)
+            .map(|variants| (0..variants.len()).map(Ctor::Variant).collect()),
+        _ => None,
+    }
+}
+
+fn field_types(ctor: &Ctor, ty: &Type, enums: &Enums) -> Vec<Type> {
+    match (ctor, ty) {
+        (Ctor::Variant(index), Type::Named(name)) => enums[name][*index].1.clone(),
+        _ => Vec::new(),
+    }
+}
+
+/// Constructors at the start of the rows.
+fn used_ctors(rows: &[Vec<Pat>]) -> Vec<&Ctor> {
+    rows.iter()
+        .filter_map(|row| match &row[0] {
+            Pat::Ctor(ctor, _) => Some(ctor),
+            Pat::Wild => None,
+        })
+        .collect()
+}
+
+/// The rows that match a value built with `ctor`, with their first pattern
+/// replaced by the patterns for its fields.
+fn specialize(rows: &[Vec<Pat>], ctor: &Ctor, arity: usize) -> Vec<Vec<Pat>> {
+    rows.iter()
+        .filter_map(|row| {
+            let fields = match &row[0] {
+                Pat::Wild => vec![Pat::Wild; arity],
+                Pat::Ctor(other, fields) if other == ctor => fields.clone(),
+                Pat::Ctor(..) => return None,
+            };
+            Some(fields.into_iter().chain(row[1..].iter().cloned()).collect())
+        })
+        .collect()
+}
+
+/// The rows that match any value, without their first pattern.
+fn default_rows(rows: &[Vec<Pat>]) -> Vec<Vec<Pat>> {
+    rows.iter()
+        .filter(|row| row[0] == Pat::Wild)
+        .map(|row| row[1..].to_vec())
+        .collect()
+}
+
+/// Whether some values of `types` match `row` but none of `rows`, this is the
+/// usefulness check from "Warnings for pattern matching" by Luc Maranget.
+fn is_useful(rows: &[Vec<Pat>], row: &[Pat], types: &[Type], enums: &Enums) -> bool {
+    let (ty, rest) = match types.split_first() {
+        Some(split) => split,
+        None => return rows.is_empty(),
+    };
+
+    let specialized = |ctor: &Ctor, fields: Vec<Pat>| {
+        let field_types = field_types(ctor, ty, enums);
+        let types: Vec<Type> = field_types.into_iter().chain(rest.to_vec()).collect();
+        let arity = fields.len();
+        let row: Vec<Pat> = fields.into_iter().chain(row[1..].to_vec()).collect();
+        is_useful(&specialize(rows, ctor, arity), &row, &types, enums)
+    };
+
+    match &row[0] {
+        Pat::Ctor(ctor, fields) => specialized(ctor, fields.clone()),
+        Pat::Wild => {
+            let used = used_ctors(rows);
+            match all_ctors(ty, enums) {
+                Some(ctors) if ctors.iter().all(|ctor| used.contains(&ctor)) => {
+                    ctors.iter().any(|ctor| {
+                        let arity = field_types(ctor, ty, enums).len();
+                        specialized(ctor, vec![Pat::Wild; arity])
+                    })
+                }
+                _ => is_useful(&default_rows(rows), &row[1..], rest, enums),
+            }
+        }
+    }
+}
+
+/// Values of `types` that none of the rows match, if there are any.
+fn witness(rows: &[Vec<Pat>], types: &[Type], enums: &Enums) -> Option<Vec<Pat>> {
+    let (ty, rest) = match types.split_first() {
+        Some(split) => split,
+        None if rows.is_empty() => return Some(Vec::new()),
+        None => return None,
+    };
+
+    let used = used_ctors(rows);
+    match all_ctors(ty, enums) {
+        Some(ctors) if ctors.iter().all(|ctor| used.contains(&ctor)) => {
+            for ctor in ctors {
+                let field_types = field_types(&ctor, ty, enums);
+                let arity = field_types.len();
+                let types: Vec<Type> = field_types.into_iter().chain(rest.to_vec()).collect();
+                if let Some(mut fields) = witness(&specialize(rows, &ctor, arity), &types, enums) {
+                    let rest = fields.split_off(arity);
+                    return Some(iter::once(Pat::Ctor(ctor, fields)).chain(rest).collect());
+                }
+            }
+            None
+        }
+        ctors => {
+            let mut values = witness(&default_rows(rows), rest, enums)?;
+            // Name a missing constructor, unless no arm looks at this value at all
+            let missing = match ctors {
+                Some(ctors) if !used.is_empty() => {
+                    ctors.into_iter().find(|ctor| !used.contains(&ctor))
+                }
+                _ => None,
+            };
+            let value = match missing {
+                Some(ctor) => {
+                    let arity = field_types(&ctor, ty, enums).len();
+                    Pat::Ctor(ctor, vec![Pat::Wild; arity])
+                }
+                None => Pat::Wild,
+            };
+            values.insert(0, value);
+            Some(values)
+        }
+    }
+}
+
+#[cfg(test)]
+mod patterns_tests {
+    use super::*;
+
+    fn shape() -> Enums {
+        let mut enums = Enums::new();
+        enums.insert(
+            "Shape".to_string(),
+            vec![
+                ("Circle".to_string(), vec![Type::Bool]),
+                ("Rect".to_string(), vec![Type::Int, Type::Int]),
+            ],
+        );
+        enums
+    }
+
+    fn variant(name: &str, fields: Vec<Pattern>) -> Pattern {
+        Pattern::Variant("Shape".to_string(), name.to_string(), fields)
+    }
+
+    #[test]
+    fn test_check_arms() {
+        let enums = shape();
+        let ty = Type::Named("Shape".to_string());
+        let circle = |b| variant("Circle", vec![Pattern::Literal(Expr::Bool(b))]);
+        let rect = variant("Rect", vec![Pattern::Wildcard, Pattern::Wildcard]);
+
+        assert_eq!(
+            check_arms(&[circle(true), circle(false), rect.clone()], &ty, &enums),
+            Ok(())
+        );
+        assert_eq!(
+            check_arms(&[circle(true), rect.clone()], &ty, &enums),
+            Err("non-exhaustive patterns: `Shape::Circle(false)` not covered".to_string())
+        );
+        assert_eq!(
+            check_arms(&[circle(true)], &ty, &enums),
+            Err("non-exhaustive patterns: `Shape::Rect(_, _)` not covered".to_string())
+        );
+        assert_eq!(
+            check_arms(
+                &[Pattern::Binding("s".to_string(), None), rect.clone()],
+                &ty,
+                &enums
+            ),
+            Err("unreachable pattern `Shape::Rect(_, _)`".to_string())
+        );
+
+        let one = Pattern::Literal(Expr::Int(1));
+        assert_eq!(
+            check_arms(slice::from_ref(&one), &Type::Int, &enums),
+            Err("non-exhaustive patterns: `_` not covered".to_string())
+        );
+        assert_eq!(
+            check_arms(&[one.clone(), one, Pattern::Wildcard], &Type::Int, &enums),
+            Err("unreachable pattern `1`".to_string())
+        );
+    }
+}
@@ -30,6 +30,8 @@ pub fn program() -> String {
             let g: i32 = testfn2();
             let h: i32 = testfn3(true, 1);
             let i: i32 = g + h;
+            print_i32(i);
+            println();
             return i
         }
 
@@ -0,0 +1,590 @@
+use crate::ast::*;
+use crate::modules::*;
+use crate::runtime::*;
+
+use std::collections::HashMap;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum DeclKind {
+    Builtin,
+    Function,
+    ExternFn,
+    Const,
+    Static,
+    Param,
+    /// A variable of a `let`, `for` loop or match arm
+    Local,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Decl {
+    pub name: String,
+    pub kind: DeclKind,
+    /// Span of the item or statement declaring the name
+    pub span: Span,
+}
+
+/// Every declaration of a program, the `Var`s of the resolved program hold their index.
+#[derive(Debug, PartialEq, Default)]
+pub struct SymbolTable {
+    pub decls: Vec<Decl>,
+}
+
+/// Flattens the modules of a program and links every name used in it to its
+/// declaration. Items are declared before the function bodies are resolved, so they can
+/// be used anywhere in the program. The backends find variables by the declarations of
+/// their `Var`s, a program that is already resolved can be resolved again.
+pub fn resolve(ast: Vec<Expr>) -> Result<(Vec<Expr>, SymbolTable), ResolveError> {
+    let (items, modules) = flatten_modules(ast)?;
+    let mut resolver = Resolver {
+        table: SymbolTable::default(),
+        items: HashMap::new(),
+        scopes: vec![HashMap::new()],
+        modules,
+        module: Vec::new(),
+        span: Span::default(),
+    };
+    for (name, _, _) in builtins() {
+        let id = resolver.declare(name, DeclKind::Builtin);
+        resolver.items.insert(name.to_string(), id);
+    }
+    for (_, item) in items.iter() {
+        resolver.declare_item(item)?;
+    }
+    let items = items
+        .into_iter()
+        .map(|(module, item)| {
+            resolver.module = module;
+            resolver.resolve_item(item)
+        })
+        .collect::<Result<_, _>>()?;
+    Ok((items, resolver.table))
+}
+
+struct Resolver {
+    table: SymbolTable,
+    /// Builtins, functions, constants and statics by their full path
+    items: HashMap<String, DeclId>,
+    /// The local variables declared in each scope, the outermost one holds the variables
+    /// of statements outside of functions
+    scopes: Vec<HashMap<String, DeclId>>,
+    /// Paths between the modules of the program
+    modules: ModuleResolver,
+    /// Path of the module the item being resolved is declared in
+    module: Vec<String>,
+    span: Span,
+}
+
+impl Resolver {
+    fn error<T>(&self, msg: String) -> Result<T, ResolveError> {
+        Err(ResolveError {
+            span: self.span,
+            msg,
+        })
+    }
+
+    fn declare(&mut self, name: &str, kind: DeclKind) -> DeclId {
+        self.table.decls.push(Decl {
+            name: name.to_string(),
+            kind,
+            span: self.span,
+        });
+        self.table.decls.len() - 1
+    }
+
+    /// Adds a variable to the innermost scope, shadowing any outer one.
+    fn declare_local(&mut self, name: &str, kind: DeclKind) -> DeclId {
+        let id = self.declare(name, kind);
+        self.scopes.last_mut().unwrap().insert(name.to_string(), id);
+        id
+    }
+
+    fn lookup(&self, name: &str) -> Option<DeclId> {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(name).cloned())
+    }
+
+    fn declare_item(&mut self, item: &Expr) -> Result<(), ResolveError> {
+        let (var, kind) = match item {
+            Expr::Spanned(span, item) => {
+                self.span = *span;
+                return self.declare_item(item);
+            }
+            Expr::Fn(fn_var, ..) => (fn_var, DeclKind::Function),
+            Expr::ExternFn(fn_var, ..) => (fn_var, DeclKind::ExternFn),
+            Expr::Const(var, ..) => (var, DeclKind::Const),
+            Expr::Static(var, ..) => (var, DeclKind::Static),
+            _ => return Ok(()),
+        };
+        let name = match var.as_ref() {
+            Expr::Var(name, _) => name,
+            _ => panic!("Invalid item name!"),
+        };
+        // Items may shadow a builtin, but not each other
+        match self.items.get(name) {
+            Some(id) if self.table.decls[*id].kind != DeclKind::Builtin => {
+                self.error(format!("the name `{}` is defined multiple times", name))
+            }
+            _ => {
+                let id = self.declare(name, kind);
+                self.items.insert(name.to_string(), id);
+                Ok(())
+            }
+        }
+    }
+
+    /// The name of an item linked to its declaration.
+    fn item_var(&self, var: Expr) -> Expr {
+        match var {
+            Expr::Var(name, _) => {
+                let id = self.items[&name];
+                Expr::Var(name, Some(id))
+            }
+            _ => panic!("Invalid item name!"),
+        }
+    }
+
+    fn resolve_item(&mut self, item: Expr) -> Result<Expr, ResolveError> {
+        Ok(match item {
+            Expr::Spanned(span, item) => {
+                self.span = span;
+                Expr::Spanned(span, Box::new(self.resolve_item(*item)?))
+            }
+            Expr::Fn(fn_var, generics, params, ret_type, block) => {
+                let fn_var = self.item_var(*fn_var);
+                self.resolve_fn(fn_var, generics, params, ret_type, block)?
+            }
+            Expr::ExternFn(fn_var, params, ret_type) => {
+                self.scopes.push(HashMap::new());
+                let params = self.declare_params(params);
+                self.scopes.pop();
+                Expr::ExternFn(Box::new(self.item_var(*fn_var)), params?, ret_type)
+            }
+            Expr::Const(var, ty, value) => {
+                let value = self.resolve_box(*value)?;
+                Expr::Const(Box::new(self.item_var(*var)), ty, value)
+            }
+            Expr::Static(var, ty, value, mutable) => {
+                let value = self.resolve_box(*value)?;
+                Expr::Static(Box::new(self.item_var(*var)), ty, value, mutable)
+            }
+            Expr::Impl(trait_name, impl_type, methods) => {
+                let methods = methods
+                    .into_iter()
+                    .map(|method| self.resolve_method(method))
+                    .collect::<Result<_, _>>()?;
+                Expr::Impl(trait_name, impl_type, methods)
+            }
+            Expr::Trait(trait_var, methods) => {
+                let mut resolved = Vec::new();
+                for (method_var, params, ret_type) in methods {
+                    self.scopes.push(HashMap::new());
+                    let params = self.declare_params(params);
+                    self.scopes.pop();
+                    resolved.push((method_var, params?, ret_type));
+                }
+                Expr::Trait(trait_var, resolved)
+            }
+            item @ Expr::Struct(..) | item @ Expr::Enum(..) => item,
+            // Statements outside of functions are resolved in the outermost scope
+            expr => self.resolve_expr(expr)?,
+        })
+    }
+
+    fn resolve_fn(
+        &mut self,
+        fn_var: Expr,
+        generics: Vec<(String, Vec<String>)>,
+        params: Vec<(Expr, Type)>,
+        ret_type: Type,
+        block: Vec<Expr>,
+    ) -> Result<Expr, ResolveError> {
+        self.scopes.push(HashMap::new());
+        let body = self
+            .declare_params(params)
+            .and_then(|params| Ok((params, self.resolve_block(block)?)));
+        self.scopes.pop();
+        let (params, block) = body?;
+        Ok(Expr::Fn(
+            Box::new(fn_var),
+            generics,
+            params,
+            ret_type,
+            block,
+        ))
+    }
+
+    /// The methods of an impl are only called through their receiver, their names are
+    /// not values.
+    fn resolve_method(&mut self, method: Expr) -> Result<Expr, ResolveError> {
+        match method {
+            Expr::Spanned(span, method) => {
+                self.span = span;
+                Ok(Expr::Spanned(span, Box::new(self.resolve_method(*method)?)))
+            }
+            Expr::Fn(method_var, generics, params, ret_type, block) => {
+                self.resolve_fn(*method_var, generics, params, ret_type, block)
+            }
+            method => Ok(method),
+        }
+    }
+
+    fn declare_params(
+        &mut self,
+        params: Vec<(Expr, Type)>,
+    ) -> Result<Vec<(Expr, Type)>, ResolveError> {
+        let mut names = Vec::new();
+        for (param, _) in params.iter() {
+            param.binding_names(&mut names);
+        }
+        for (i, name) in names.iter().enumerate() {
+            if names[..i].contains(name) {
+                return self.error(format!(
+                    "identifier `{}` is bound more than once in this parameter list",
+                    name
+                ));
+            }
+        }
+        Ok(params
+            .into_iter()
+            .map(|(param, ty)| (self.declare_binding(param, DeclKind::Param), ty))
+            .collect())
+    }
+
+    /// Declares the names a `let`, parameter or `for` loop binds.
+    fn declare_binding(&mut self, binding: Expr, kind: DeclKind) -> Expr {
+        match binding {
+            Expr::Tuple(bindings) => Expr::Tuple(
+                bindings
+                    .into_iter()
+                    .map(|binding| self.declare_binding(binding, kind))
+                    .collect(),
+            ),
+            Expr::Var(name, _) => {
+                let id = self.declare_local(&name, kind);
+                Expr::Var(name, Some(id))
+            }
+            binding => binding,
+        }
+    }
+
+    fn declare_pattern(&mut self, pattern: Pattern) -> Pattern {
+        match pattern {
+            Pattern::Binding(name, _) => {
+                let id = self.declare_local(&name, DeclKind::Local);
+                Pattern::Binding(name, Some(id))
+            }
+            Pattern::Variant(enum_name, variant, fields) => Pattern::Variant(
+                enum_name,
+                variant,
+                fields
+                    .into_iter()
+                    .map(|field| self.declare_pattern(field))
+                    .collect(),
+            ),
+            pattern => pattern,
+        }
+    }
+
+    fn resolve_block(&mut self, block: Vec<Expr>) -> Result<Vec<Expr>, ResolveError> {
+        self.scopes.push(HashMap::new());
+        let block = self.resolve_exprs(block);
+        self.scopes.pop();
+        block
+    }
+
+    fn resolve_exprs(&mut self, exprs: Vec<Expr>) -> Result<Vec<Expr>, ResolveError> {
+        exprs
+            .into_iter()
+            .map(|expr| self.resolve_expr(expr))
+            .collect()
+    }
+
+    fn resolve_box(&mut self, expr: Expr) -> Result<Box<Expr>, ResolveError> {
+        Ok(Box::new(self.resolve_expr(expr)?))
+    }
+
+    /// Links a name or path to a local variable or an item.
+    fn resolve_var(&mut self, name: String, called: bool) -> Result<Expr, ResolveError> {
+        // The empty name stands in for the target of a `let` value
+        if name.is_empty() {
+            return Ok(Expr::Var(name, None));
+        }
+        if let Some(id) = self.lookup(&name) {
+            return Ok(Expr::Var(name, Some(id)));
+        }
+        // The items of a flattened program are named by their paths, which start at a
+        // module only before the program is flattened
+        let first = name.split("::").next().unwrap();
+        let path = match self.module.is_empty()
+            && self.items.contains_key(&name)
+            && !self.modules.is_module(&self.module, first)
+        {
+            true => name,
+            false => self.modules.resolve_value(&self.module, &name, self.span)?,
+        };
+        match self.items.get(&path) {
+            Some(id) => Ok(Expr::Var(path, Some(*id))),
+            None if called => self.error(format!("cannot find function `{}` in this scope", path)),
+            None => self.error(format!("cannot find value `{}` in this scope", path)),
+        }
+    }
+
+    fn resolve_expr(&mut self, expr: Expr) -> Result<Expr, ResolveError> {
+        Ok(match expr {
+            Expr::Var(name, _) => self.resolve_var(name, false)?,
+            Expr::Variant(first, second, args) if self.modules.is_module(&self.module, &first) => {
+                let args = self.resolve_exprs(args)?;
+                // Without parentheses the path is the function itself
+                match args.is_empty() {
+                    true => self.resolve_var(format!("{}::{}", first, second), false)?,
+                    false => Expr::FnCall(
+                        Box::new(self.resolve_var(format!("{}::{}", first, second), true)?),
+                        args,
+                    ),
+                }
+            }
+            Expr::Variant(enum_name, variant, args) => {
+                Expr::Variant(enum_name, variant, self.resolve_exprs(args)?)
+            }
+
+            Expr::Array(elems) => Expr::Array(self.resolve_exprs(elems)?),
+            Expr::ArrayRepeat(elem, len) => Expr::ArrayRepeat(self.resolve_box(*elem)?, len),
+            Expr::Index(array, index) => {
+                Expr::Index(self.resolve_box(*array)?, self.resolve_box(*index)?)
+            }
+            Expr::StructLit(name, fields) => {
+                let mut resolved = Vec::new();
+                for (field, expr) in fields {
+                    resolved.push((field, self.resolve_expr(expr)?));
+                }
+                Expr::StructLit(name, resolved)
+            }
+            Expr::Field(expr, field) => Expr::Field(self.resolve_box(*expr)?, field),
+            Expr::Tuple(elems) => Expr::Tuple(self.resolve_exprs(elems)?),
+            Expr::TupleField(expr, index) => Expr::TupleField(self.resolve_box(*expr)?, index),
+            Expr::BinExpr(l, op, r) => {
+                Expr::BinExpr(self.resolve_box(*l)?, op, self.resolve_box(*r)?)
+            }
+            Expr::VarExpr(l, op, r) => {
+                Expr::VarExpr(self.resolve_box(*l)?, op, self.resolve_box(*r)?)
+            }
+            Expr::Cast(expr, ty) => Expr::Cast(self.resolve_box(*expr)?, ty),
+
+            // The value is resolved before the names of the `let` are in scope
+            Expr::Let(binding, ty, value) => {
+                let value = self.resolve_box(*value)?;
+                let binding = self.declare_binding(*binding, DeclKind::Local);
+                Expr::Let(Box::new(binding), ty, value)
+            }
+            Expr::Declare(var, ty) => {
+                Expr::Declare(Box::new(self.declare_binding(*var, DeclKind::Local)), ty)
+            }
+            Expr::If(cond, block) => Expr::If(self.resolve_box(*cond)?, self.resolve_block(block)?),
+            Expr::IfElse(cond, block1, block2) => Expr::IfElse(
+                self.resolve_box(*cond)?,
+                self.resolve_block(block1)?,
+                self.resolve_block(block2)?,
+            ),
+            Expr::While(cond, block) => {
+                Expr::While(self.resolve_box(*cond)?, self.resolve_block(block)?)
+            }
+            Expr::For(var, range, block) => {
+                let range = self.resolve_box(*range)?;
+                self.scopes.push(HashMap::new());
+                let var = self.declare_binding(*var, DeclKind::Local);
+                let block = self.resolve_block(block);
+                self.scopes.pop();
+                Expr::For(Box::new(var), range, block?)
+            }
+            Expr::Range(start, end, inclusive) => Expr::Range(
+                self.resolve_box(*start)?,
+                self.resolve_box(*end)?,
+                inclusive,
+            ),
+            Expr::Loop(block) => Expr::Loop(self.resolve_block(block)?),
+            Expr::Match(expr, arms) => {
+                let expr = self.resolve_box(*expr)?;
+                let mut resolved = Vec::new();
+                for (pattern, block) in arms {
+                    self.scopes.push(HashMap::new());
+                    let pattern = self.declare_pattern(pattern);
+                    let block = self.resolve_block(block);
+                    self.scopes.pop();
+                    resolved.push((pattern, block?));
+                }
+                Expr::Match(expr, resolved)
+            }
+            Expr::Block(block) => Expr::Block(self.resolve_block(block)?),
+
+            Expr::FnCall(fn_var, args) => {
+                let fn_var = match *fn_var {
+                    Expr::Var(name, _) => self.resolve_var(name, true)?,
+                    fn_expr => self.resolve_expr(fn_expr)?,
+                };
+                Expr::FnCall(Box::new(fn_var), self.resolve_exprs(args)?)
+            }
+            Expr::MethodCall(receiver, method, args) => Expr::MethodCall(
+                self.resolve_box(*receiver)?,
+                method,
+                self.resolve_exprs(args)?,
+            ),
+            Expr::Closure(params, body) => {
+                self.scopes.push(HashMap::new());
+                let closure = self
+                    .declare_params(params)
+                    .and_then(|params| Ok(Expr::Closure(params, self.resolve_box(*body)?)));
+                self.scopes.pop();
+                closure?
+            }
+
+            Expr::Return(expr) => Expr::Return(self.resolve_box(*expr)?),
+            Expr::Break(Some(expr)) => Expr::Break(Some(self.resolve_box(*expr)?)),
+            Expr::Tail(expr) => Expr::Tail(self.resolve_box(*expr)?),
+            Expr::Spanned(span, expr) => {
+                self.span = span;
+                Expr::Spanned(span, self.resolve_box(*expr)?)
+            }
+
+            // Literals, and items in blocks which the type checker reports
+            expr => expr,
+        })
+    }
+}
+
+#[cfg(test)]
+mod resolver_tests {
+    use super::*;
+    use crate::parser::*;
+
+    fn resolve_str(input: &str) -> Result<(Vec<Expr>, SymbolTable), ResolveError> {
+        resolve(parser(input).unwrap().1)
+    }
+
+    fn resolve_err(input: &str) -> String {
+        resolve_str(input).unwrap_err().msg
+    }
+
+    /// Statements of the body of a resolved function.
+    fn fn_block(item: &Expr) -> Vec<Expr> {
+        match item.clone().unspanned() {
+            Expr::Fn(_, _, _, _, block) => block,
+            item => panic!("Unexpected item {:?}", item),
+        }
+    }
+
+    #[test]
+    fn test_resolve() {
+        let (items, table) = resolve_str(
+            "
+            fn main() -> i32 {
+                let x = 1;
+                let x = x + twice(x);
+                if true { let x = true; };
+                for i in 0..x { print_i32(i); };
+                x
+            }
+
+            fn twice(x: i32) -> i32 {
+                let f = |y: i32| y + LIMIT;
+                return f(x)
+            }
+
+            const LIMIT: i32 = 10;
+            ",
+        )
+        .unwrap();
+
+        // The kinds and lines of the declarations a statement uses
+        let uses = |stmt: &Expr| {
+            let mut vars = Vec::new();
+            stmt.referenced_vars(&mut vars);
+            vars.into_iter()
+                .map(|(name, id)| (name, table.decls[id].kind, table.decls[id].span.line))
+                .collect::<Vec<_>>()
+        };
+        let local = |name: &str, line| (name.to_string(), DeclKind::Local, line);
+
+        let main = fn_block(&items[0]);
+        // The shadowing `let` refers to the first `x` in its value
+        assert_eq!(
+            uses(&main[1]),
+            vec![local("x", 3), ("twice".to_string(), DeclKind::Function, 10)]
+        );
+        match main[1].clone().unspanned() {
+            Expr::Let(binding, ..) => match *binding {
+                Expr::Var(name, Some(id)) => {
+                    assert_eq!((name, table.decls[id].span.line), ("x".to_string(), 4))
+                }
+                binding => panic!("Unexpected binding {:?}", binding),
+            },
+            stmt => panic!("Unexpected statement {:?}", stmt),
+        }
+        assert_eq!(
+            uses(&main[3]),
+            vec![
+                local("x", 4),
+                ("print_i32".to_string(), DeclKind::Builtin, 0),
+                local("i", 6)
+            ]
+        );
+        // The `x` of the inner block is out of scope again
+        assert_eq!(uses(&main[4]), vec![local("x", 4)]);
+
+        let twice = fn_block(&items[1]);
+        assert_eq!(
+            uses(&twice[0]),
+            vec![
+                ("y".to_string(), DeclKind::Param, 11),
+                ("LIMIT".to_string(), DeclKind::Const, 15)
+            ]
+        );
+        assert_eq!(
+            uses(&twice[1]),
+            vec![local("f", 11), ("x".to_string(), DeclKind::Param, 10)]
+        );
+
+        // Resolving the resolved program links the names the same way
+        let (again, _) = resolve(items.clone()).unwrap();
+        assert_eq!(again, items);
+    }
+
+    #[test]
+    fn test_resolve_errors() {
+        assert_eq!(
+            resolve_err("fn main() -> i32 { let x = 1; return y }"),
+            "cannot find value `y` in this scope"
+        );
+        assert_eq!(
+            resolve_err("fn main() -> i32 { return nowhere(1) }"),
+            "cannot find function `nowhere` in this scope"
+        );
+        assert_eq!(
+            resolve_err("fn main() -> i32 { for i in 0..3 { let j = i; }; return i }"),
+            "cannot find value `i` in this scope"
+        );
+        assert_eq!(
+            resolve_err("fn f() -> i32 { return 1 } fn f() -> i32 { return 2 }"),
+            "the name `f` is defined multiple times"
+        );
+        assert_eq!(
+            resolve_err("fn f(a: i32, b: bool, a: i32) -> i32 { return a }"),
+            "identifier `a` is bound more than once in this parameter list"
+        );
+        assert_eq!(
+            resolve_err("fn f() -> i32 { let g = |n: i32, n: i32| n; return 0 }"),
+            "identifier `n` is bound more than once in this parameter list"
+        );
+        let err = resolve_str("fn main() -> i32 {\n    return missing\n}").unwrap_err();
+        assert_eq!(
+            err.span,
+            Span {
+                line: 2,
+                column: 5,
+                file: None,
+            }
+        );
+    }
+}
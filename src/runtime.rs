@@ -0,0 +1,203 @@
+use crate::ast::Type;
+
+use std::{
+    cell::RefCell,
+    collections::VecDeque,
+    io::{self, Write},
+    slice, str,
+};
+
+/// Functions every program can call without declaring them.
+pub fn builtins() -> Vec<(&'static str, Vec<Type>, Type)> {
+    vec![
+        ("print_i32", vec![Type::Int], Type::Void),
+        ("print_f64", vec![Type::Float], Type::Void),
+        ("print_bool", vec![Type::Bool], Type::Void),
+        ("print_str", vec![Type::Str], Type::Void),
+        ("println", vec![], Type::Void),
+        ("read_i32", vec![], Type::Int),
+        ("len", vec![Type::Str], Type::Int),
+    ]
+}
+
+pub fn is_builtin(name: &str) -> bool {
+    builtins().iter().any(|builtin| builtin.0 == name)
+}
+
+thread_local! {
+    static OUTPUT: RefCell<Option<String>> = const { RefCell::new(None) };
+    static INPUT: RefCell<Option<VecDeque<String>>> = const { RefCell::new(None) };
+}
+
+/// Runs `f` with `input` as stdin and returns everything the program printed.
+#[cfg(test)]
+pub fn with_io<R>(input: &str, f: impl FnOnce() -> R) -> (R, String) {
+    INPUT.with(|i| *i.borrow_mut() = Some(input.lines().map(String::from).collect()));
+    OUTPUT.with(|o| *o.borrow_mut() = Some(String::new()));
+
+    let res = f();
+
+    INPUT.with(|i| *i.borrow_mut() = None);
+    let output = OUTPUT.with(|o| o.borrow_mut().take()).unwrap_or_default();
+    (res, output)
+}
+
+pub fn write_output(s: &str) {
+    OUTPUT.with(|o| match &mut *o.borrow_mut() {
+        Some(buf) => buf.push_str(s),
+        None => {
+            let mut stdout = io::stdout();
+            stdout.write_all(s.as_bytes()).unwrap();
+            stdout.flush().unwrap();
+        }
+    })
+}
+
+/// Reads the next line of input as an `i32`, `None` at the end of input or if it
+/// is not a number.
+pub fn read_input_i32() -> Option<i32> {
+    let line = INPUT.with(|i| match &mut *i.borrow_mut() {
+        Some(lines) => lines.pop_front(),
+        None => {
+            let mut line = String::new();
+            match io::stdin().read_line(&mut line) {
+                Ok(n) if n > 0 => Some(line),
+                _ => None,
+            }
+        }
+    })?;
+    line.trim().parse().ok()
+}
+
+pub const READ_I32_ERROR: &str = "could not read an i32 from stdin";
+
+/// A string in compiled code, the `{ i8*, i32 }` fat pointer to its UTF-8 bytes.
+/// Passed by value it has the same C ABI as the LLVM struct on 64-bit targets.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct LangStr {
+    pub ptr: *const u8,
+    pub len: i32,
+}
+
+impl LangStr {
+    /// Moves `s` out of Rust's hands, strings in compiled programs are never freed.
+    pub fn leak(s: String) -> LangStr {
+        let s: &'static str = Box::leak(s.into_boxed_str());
+        LangStr {
+            ptr: s.as_ptr(),
+            len: s.len() as i32,
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        unsafe { str::from_utf8_unchecked(slice::from_raw_parts(self.ptr, self.len as usize)) }
+    }
+}
+
+// The runtime of programs compiled by the JIT, these are called with the C ABI
+// under the names in `builtins`. There is no ahead-of-time build, so the JIT is the
+// only place the runtime is linked into.
+
+pub extern "C" fn print_i32(x: i32) {
+    write_output(&x.to_string());
+}
+
+pub extern "C" fn print_f64(x: f64) {
+    write_output(&x.to_string());
+}
+
+pub extern "C" fn print_bool(b: bool) {
+    write_output(&b.to_string());
+}
+
+pub extern "C" fn print_str(s: LangStr) {
+    write_output(s.as_str());
+}
+
+pub extern "C" fn println() {
+    write_output("\n");
+}
+
+pub extern "C" fn read_i32() -> i32 {
+    match read_input_i32() {
+        Some(x) => x,
+        None => {
+            eprintln!("runtime error: {}", READ_I32_ERROR);
+            std::process::exit(101);
+        }
+    }
+}
+
+pub extern "C" fn len(s: LangStr) -> i32 {
+    s.len
+}
+
+pub const STR_CONCAT_FN: &str = "lang_str_concat";
+pub const STR_EQ_FN: &str = "lang_str_eq";
+
+pub extern "C" fn lang_str_concat(a: LangStr, b: LangStr) -> LangStr {
+    LangStr::leak(format!("{}{}", a.as_str(), b.as_str()))
+}
+
+pub extern "C" fn lang_str_eq(a: LangStr, b: LangStr) -> bool {
+    a.as_str() == b.as_str()
+}
+
+/// Addresses of the runtime functions, keyed by the name compiled code calls them by.
+pub fn runtime_symbols() -> Vec<(&'static str, usize)> {
+    vec![
+        ("print_i32", print_i32 as extern "C" fn(i32) as usize),
+        ("print_f64", print_f64 as extern "C" fn(f64) as usize),
+        ("print_bool", print_bool as extern "C" fn(bool) as usize),
+        ("print_str", print_str as extern "C" fn(LangStr) as usize),
+        ("println", println as extern "C" fn() as usize),
+        ("read_i32", read_i32 as extern "C" fn() -> i32 as usize),
+        ("len", len as extern "C" fn(LangStr) -> i32 as usize),
+        (
+            STR_CONCAT_FN,
+            lang_str_concat as extern "C" fn(LangStr, LangStr) -> LangStr as usize,
+        ),
+        (
+            STR_EQ_FN,
+            lang_str_eq as extern "C" fn(LangStr, LangStr) -> bool as usize,
+        ),
+    ]
+}
+
+#[cfg(test)]
+mod runtime_tests {
+    use super::*;
+
+    #[test]
+    fn test_with_io() {
+        let (x, output) = with_io("12\n-3\nfoo\n", || {
+            print_i32(read_input_i32().unwrap());
+            print_bool(true);
+            print_f64(0.5);
+            println();
+            read_input_i32()
+        });
+        assert_eq!(output, "12true0.5\n");
+        assert_eq!(x, Some(-3));
+
+        let (x, _) = with_io("foo", read_input_i32);
+        assert_eq!(x, None);
+        let (x, _) = with_io("", read_input_i32);
+        assert_eq!(x, None);
+    }
+
+    #[test]
+    fn test_lang_str() {
+        let a = LangStr::leak("foo".to_string());
+        let b = LangStr::leak("bär".to_string());
+        let c = lang_str_concat(a, b);
+        assert_eq!(c.as_str(), "foobär");
+        assert_eq!(len(c), 7);
+        assert!(lang_str_eq(c, LangStr::leak("foobär".to_string())));
+        assert!(!lang_str_eq(a, b));
+
+        let (_, output) = with_io("", || print_str(c));
+        assert_eq!(output, "foobär");
+    }
+}
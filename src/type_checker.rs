@@ -1,267 +1,2862 @@
 use crate::ast::*;
-use crate::memory::*;
+use crate::const_eval::*;
+use crate::patterns::*;
+use crate::resolver::*;
+use crate::runtime::*;
 
+use std::{collections::HashMap, fmt};
+
+/// How deeply a generic function may be instantiated inside its own instantiations,
+/// calling itself with ever larger types would never finish otherwise.
+const INSTANTIATION_LIMIT: usize = 8;
+
+/// Error found by the type checker, reported before the program runs.
+#[derive(Debug, PartialEq, Clone)]
+pub struct TypeError {
+    pub span: Span,
+    pub msg: String,
+}
+
+impl fmt::Display for TypeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "type error at {}: {}", self.span, self.msg)
+    }
+}
+
+/// Checks the program, the first type error found is reported on stderr.
+#[cfg(test)]
 pub fn type_checker(ast: Vec<Expr>) -> bool {
-    let mut res = Vec::new();
-    for expr in ast.iter() {
-        res.push(type_expr(expr.clone()));
+    if ast.is_empty() {
+        return false;
+    }
 
-        match res {
-            _ => continue,
+    match check(ast) {
+        Ok(_) => true,
+        Err(e) => {
+            eprintln!("{}", e);
+            false
         }
     }
+}
 
-    if res.is_empty() {
-        return false;
+/// Resolves the names of the program, then checks every function body against the
+/// declared signatures and returns the typed program, where every `let` carries the type
+/// of its variable.
+pub fn check(ast: Vec<Expr>) -> Result<Vec<Expr>, TypeError> {
+    let (ast, _) = resolve(ast).map_err(|e| TypeError {
+        span: e.span,
+        msg: e.msg,
+    })?;
+    let mut checker = TypeChecker {
+        vars: HashMap::new(),
+        fns: HashMap::new(),
+        structs: HashMap::new(),
+        enums: HashMap::new(),
+        generic_fns: HashMap::new(),
+        traits: HashMap::new(),
+        impls: Vec::new(),
+        globals: HashMap::new(),
+        instances: Vec::new(),
+        instantiating: Vec::new(),
+        type_args: HashMap::new(),
+        ret_type: Type::Void,
+        loops: Vec::new(),
+        diverging: false,
+        loop_vars: Vec::new(),
+        subst: Vec::new(),
+        span: Span::default(),
+    };
+
+    for (name, param_types, ret_type) in builtins() {
+        checker
+            .fns
+            .insert(name.to_string(), (param_types, ret_type));
     }
+    for item in ast.iter() {
+        checker.declare_item(item)?;
+    }
+    ast.into_iter()
+        .map(|item| checker.check_item(item))
+        .collect()
+}
 
-    return true;
+/// Whether `ty` is free of type variables that were never unified with a type.
+fn is_known(ty: &Type) -> bool {
+    match ty {
+        Type::Var(_) => false,
+        Type::Array(elem_type, _) => is_known(elem_type),
+        Type::Tuple(types) => types.iter().all(is_known),
+        Type::Fn(param_types, ret_type) => param_types.iter().all(is_known) && is_known(ret_type),
+        _ => true,
+    }
 }
 
-fn type_interp(ast: Vec<Expr>) -> ExprRep {
-    let mut res = ExprRep::Null;
-    for expr in ast.iter() {
-        res = type_expr(expr.clone());
-        match res {
-            _ => continue,
+/// Whether `expr` names a memory location that can be assigned to.
+fn is_place(expr: &Expr) -> bool {
+    match expr {
+        Expr::Var(name, _) => !name.is_empty(),
+        Expr::Index(array, _) | Expr::Field(array, _) | Expr::TupleField(array, _) => {
+            is_place(array)
         }
+        _ => false,
     }
-    return res;
 }
 
-fn type_expr(expr: Expr) -> ExprRep {
+/// The variable an assigned place is part of, `a` for `a.b[i]`.
+fn place_root(expr: &Expr) -> Option<(&str, Option<DeclId>)> {
     match expr {
-        Expr::Int(_) => ExprRep::Int(0),
-        Expr::Bool(_) => ExprRep::Bool(false),
-        Expr::Var(n) => read_var(&n),
+        Expr::Var(name, id) => Some((name, *id)),
+        Expr::Index(array, _) | Expr::Field(array, _) | Expr::TupleField(array, _) => {
+            place_root(array)
+        }
+        _ => None,
+    }
+}
 
-        Expr::BinExpr(l, op, r) => type_bin_expr(*l, op, *r),
-        Expr::VarExpr(var, op, expr) => type_var_expr(*var, op, *expr),
+/// Type arguments replacing `Self` in a trait or impl by the implementing type.
+fn self_type_args(impl_type: &Type) -> HashMap<String, Type> {
+    let mut type_args = HashMap::new();
+    type_args.insert("Self".to_string(), impl_type.clone());
+    type_args
+}
 
-        Expr::Let(var, var_type, expr) => type_let(*var, var_type, *expr),
+/// Type parameters with their trait bounds, parameters, return type and body of a
+/// generic function.
+type GenericFn = (
+    Vec<(String, Vec<String>)>,
+    Vec<(Expr, Type)>,
+    Type,
+    Vec<Expr>,
+);
 
-        Expr::If(cond, block) => type_if(*cond, block),
-        Expr::IfElse(cond, block1, block2) => type_if_else(*cond, block1, block2),
-        Expr::While(cond, block) => type_while(*cond, block),
+/// A `const` or `static` item.
+enum Global {
+    /// The type and initializer of a constant
+    Const(Type, Expr),
+    /// The type of a global variable, and whether it is `static mut`
+    Static(Type, bool),
+}
 
-        Expr::Fn(fn_var, params, ret_type, block) => type_fn(*fn_var, params, ret_type, block),
-        Expr::FnCall(fn_var, args) => type_fn_call(*fn_var, args),
-        Expr::Return(expr) => type_return(*expr),
-    }
+/// A loop around the statement being checked.
+enum Loop {
+    /// `while` or `for`, named by its keyword, its `break`s have no value
+    Stmt(&'static str),
+    /// `loop` with the type of the values of its `break`s, `None` until one is checked
+    Expr(Option<Type>),
 }
 
-fn type_fn(fn_var: Expr, params: Vec<(Expr, Type)>, ret_type: Type, block: Vec<Expr>) -> ExprRep {
-    match fn_var {
-        Expr::Var(a) => {
-            insert_fn(
-                ExprRep::Var(a.to_string()),
-                ExprRep::Fn(params, ret_type, block),
-            );
+struct TypeChecker {
+    /// Types of the local variables by their declarations
+    vars: HashMap<DeclId, Type>,
+    fns: HashMap<String, (Vec<Type>, Type)>,
+    structs: HashMap<String, Vec<(String, Type)>>,
+    enums: Enums,
+    /// Generic functions by name
+    generic_fns: HashMap<String, GenericFn>,
+    /// Names, parameter types and return types of the methods of each trait
+    traits: HashMap<String, Vec<(String, Vec<Type>, Type)>>,
+    /// Traits and the types they are implemented for
+    impls: Vec<(String, Type)>,
+    /// Constants and global variables by name
+    globals: HashMap<String, Global>,
+    /// Generic functions and type arguments whose bodies have been checked
+    instances: Vec<(String, Vec<Type>)>,
+    /// Generic functions whose instantiations are being checked, innermost last
+    instantiating: Vec<String>,
+    /// Types of the type parameters of the generic function being checked
+    type_args: HashMap<String, Type>,
+    ret_type: Type,
+    /// Loops around the statement being checked, innermost last, `break` and `continue`
+    /// need one
+    loops: Vec<Loop>,
+    /// Whether the statement just checked never completes, such as a `loop` without
+    /// `break`, the statements after it are unreachable
+    diverging: bool,
+    /// Variables of the enclosing `for` loops, they can not be assigned to
+    loop_vars: Vec<DeclId>,
+    /// What each `Type::Var` has been unified with so far
+    subst: Vec<Option<Type>>,
+    span: Span,
+}
+
+impl TypeChecker {
+    fn error<T>(&self, msg: String) -> Result<T, TypeError> {
+        Err(TypeError {
+            span: self.span,
+            msg,
+        })
+    }
+
+    fn expect(&mut self, expected: &Type, found: &Type) -> Result<(), TypeError> {
+        if !self.unify(expected, found) {
+            return self.error(format!(
+                "mismatched types: expected `{}`, found `{}`",
+                self.resolve(expected),
+                self.resolve(found)
+            ));
         }
-        _ => panic!("Fn stmt fail!"),
+        Ok(())
     }
-    return ExprRep::Null;
-}
 
-fn type_fn_call(fn_var: Expr, args: Vec<Expr>) -> ExprRep {
-    match fn_var {
-        Expr::Var(fn_var) => match read_fn(&fn_var) {
-            ExprRep::Fn(params, ret_type, block) => {
-                if params.len() != args.clone().len() {
-                    panic!("params != args")
-                }
-
-                for i in 0..params.len() {
-                    for x in params.clone() {
-                        match &x {
-                            (Expr::Var(v), t) => {
-                                let type_arg = type_expr(args[i].clone());
-                                match (t, type_arg.clone()) {
-                                    (Type::Int, ExprRep::Int(_)) => {
-                                        insert_var(ExprRep::Var(v.to_string()), type_arg)
-                                    }
-                                    (Type::Bool, ExprRep::Bool(_)) => {
-                                        insert_var(ExprRep::Var(v.to_string()), type_arg)
-                                    }
-                                    _ => panic!("Return type does not match!"),
-                                };
-                            }
-                            _ => panic!("Invalid param var!"),
-                        }
-                    }
+    fn fresh_var(&mut self) -> Type {
+        self.subst.push(None);
+        Type::Var(self.subst.len() - 1)
+    }
+
+    /// Replaces the type variables in `ty` that have been unified with a type.
+    fn resolve(&self, ty: &Type) -> Type {
+        match ty {
+            Type::Var(var) => match &self.subst[*var] {
+                Some(ty) => self.resolve(ty),
+                None => ty.clone(),
+            },
+            Type::Array(elem_type, len) => Type::Array(Box::new(self.resolve(elem_type)), *len),
+            Type::Tuple(types) => Type::Tuple(types.iter().map(|ty| self.resolve(ty)).collect()),
+            Type::Fn(param_types, ret_type) => Type::Fn(
+                param_types.iter().map(|ty| self.resolve(ty)).collect(),
+                Box::new(self.resolve(ret_type)),
+            ),
+            _ => ty.clone(),
+        }
+    }
+
+    fn occurs(&self, var: usize, ty: &Type) -> bool {
+        match self.resolve(ty) {
+            Type::Var(other) => other == var,
+            Type::Array(elem_type, _) => self.occurs(var, &elem_type),
+            Type::Tuple(types) => types.iter().any(|ty| self.occurs(var, ty)),
+            Type::Fn(param_types, ret_type) => {
+                param_types.iter().any(|ty| self.occurs(var, ty)) || self.occurs(var, &ret_type)
+            }
+            _ => false,
+        }
+    }
+
+    /// Makes the two types equal by binding type variables, false if they can not be.
+    fn unify(&mut self, a: &Type, b: &Type) -> bool {
+        match (self.resolve(a), self.resolve(b)) {
+            (Type::Var(a), Type::Var(b)) if a == b => true,
+            // `!` has no values, it fits wherever a value is expected
+            (Type::Never, _) | (_, Type::Never) => true,
+            (Type::Var(var), ty) | (ty, Type::Var(var)) => {
+                if self.occurs(var, &ty) {
+                    return false;
                 }
+                self.subst[var] = Some(ty);
+                true
+            }
+            (Type::Array(a, a_len), Type::Array(b, b_len)) => a_len == b_len && self.unify(&a, &b),
+            (Type::Tuple(a), Type::Tuple(b)) => {
+                a.len() == b.len() && a.iter().zip(b.iter()).all(|(a, b)| self.unify(a, b))
+            }
+            (Type::Fn(a, a_ret), Type::Fn(b, b_ret)) => {
+                a.len() == b.len()
+                    && a.iter().zip(b.iter()).all(|(a, b)| self.unify(a, b))
+                    && self.unify(&a_ret, &b_ret)
+            }
+            (a, b) => a == b,
+        }
+    }
+
+    fn expect_numeric(&self, found: &Type) -> Result<(), TypeError> {
+        if !found.is_numeric() {
+            return self.error(format!(
+                "mismatched types: expected a number, found `{}`",
+                found
+            ));
+        }
+        Ok(())
+    }
+
+    fn name(&self, var: &Expr) -> Result<String, TypeError> {
+        match var {
+            Expr::Var(name, _) if !name.is_empty() => Ok(name.to_string()),
+            _ => self.error(format!("expected a name, found {:?}", var)),
+        }
+    }
+
+    /// Type of the local variable declared by `id`, or of the global `name`.
+    fn read_var(&self, name: &str, id: Option<DeclId>) -> Result<Type, TypeError> {
+        if let Some(var_type) = id.and_then(|id| self.vars.get(&id)) {
+            return Ok(var_type.clone());
+        }
+        match self.globals.get(name) {
+            Some(Global::Const(ty, _)) | Some(Global::Static(ty, _)) => return Ok(ty.clone()),
+            None => {}
+        }
+        self.error(format!("cannot find value `{}` in this scope", name))
+    }
+
+    /// Type of a variable, or of a function used as a value.
+    fn type_var(&self, name: &str, id: Option<DeclId>) -> Result<Type, TypeError> {
+        if let Ok(var_type) = self.read_var(name, id) {
+            return Ok(self.resolve(&var_type));
+        }
+        match self.fns.get(name) {
+            Some(_) if is_builtin(name) => {
+                self.error(format!("built-in function `{}` can only be called", name))
+            }
+            Some(_) if self.generic_fns.contains_key(name) => {
+                self.error(format!("generic function `{}` can only be called", name))
+            }
+            Some((param_types, ret_type)) => {
+                Ok(Type::Fn(param_types.clone(), Box::new(ret_type.clone())))
+            }
+            None => self.read_var(name, id),
+        }
+    }
+
+    fn insert_var(&mut self, var: &Expr, var_type: Type) -> Result<(), TypeError> {
+        let name = self.name(var)?;
+        if var_type == Type::Void {
+            return self.error(format!("variable `{}` can not have type `()`", name));
+        }
+        self.check_type(&var_type)?;
+        match var {
+            Expr::Var(_, Some(id)) => self.vars.insert(*id, var_type),
+            _ => panic!("Unresolved variable {}!", name),
+        };
+        Ok(())
+    }
 
-                let res = type_interp(block);
-                match (ret_type, res.clone()) {
-                    (Type::Int, ExprRep::Int(_)) => res,
-                    (Type::Bool, ExprRep::Bool(_)) => res,
-                    _ => panic!("Return type does not match!"),
+    /// Declares the variables of a `let` or parameter, destructuring tuples.
+    fn insert_binding(&mut self, binding: &Expr, ty: Type) -> Result<(), TypeError> {
+        let mut names = Vec::new();
+        binding.binding_names(&mut names);
+        for (i, name) in names.iter().enumerate() {
+            if names[..i].contains(name) {
+                return self.error(format!(
+                    "identifier `{}` is bound more than once in the same pattern",
+                    name
+                ));
+            }
+        }
+        self.insert_destructured(binding, ty)
+    }
+
+    fn insert_destructured(&mut self, binding: &Expr, ty: Type) -> Result<(), TypeError> {
+        match (binding, ty) {
+            (Expr::Tuple(bindings), Type::Tuple(types)) => {
+                if bindings.len() != types.len() {
+                    return self.error(format!(
+                        "mismatched types: expected a tuple with {} elements, found one with {} elements",
+                        types.len(),
+                        bindings.len()
+                    ));
                 }
+                for (binding, ty) in bindings.iter().zip(types) {
+                    self.insert_destructured(binding, ty)?;
+                }
+                Ok(())
+            }
+            (Expr::Tuple(_), ty) => {
+                self.error(format!("mismatched types: expected `{}`, found tuple", ty))
             }
-            _ => panic!("Could not find fn_var in map!"),
-        },
-        _ => panic!("Invalid fn_var!"),
+            (var, ty) => self.insert_var(var, ty),
+        }
     }
-}
 
-fn type_if(cond: Expr, block: Vec<Expr>) -> ExprRep {
-    match type_expr(cond) {
-        ExprRep::Bool(c) => {
-            if c {
-                return type_interp(block);
+    fn array_type(&self, elem_type: Type, len: usize) -> Result<Type, TypeError> {
+        let array_type = Type::Array(Box::new(elem_type), len);
+        self.check_type(&array_type)?;
+        Ok(array_type)
+    }
+
+    /// Rejects types that can not be written in a program, like arrays of `()`.
+    fn check_type(&self, ty: &Type) -> Result<(), TypeError> {
+        match ty {
+            Type::Array(elem_type, _) if **elem_type == Type::Void => {
+                self.error("arrays can not hold `()` values".to_string())
+            }
+            Type::Array(elem_type, _) => self.check_type(elem_type),
+            Type::Tuple(types) if types.contains(&Type::Void) => {
+                self.error("tuples can not hold `()` values".to_string())
+            }
+            Type::Tuple(types) => types.iter().try_for_each(|ty| self.check_type(ty)),
+            Type::Fn(param_types, _) if param_types.contains(&Type::Void) => {
+                self.error("parameters can not have type `()`".to_string())
             }
-            return ExprRep::Null;
+            Type::Fn(param_types, ret_type) => {
+                param_types.iter().try_for_each(|ty| self.check_type(ty))?;
+                self.check_type(ret_type)
+            }
+            Type::Named(name) if !self.is_declared(name) && !self.type_args.contains_key(name) => {
+                self.error(format!("cannot find type `{}` in this scope", name))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn is_declared(&self, name: &str) -> bool {
+        self.structs.contains_key(name) || self.enums.contains_key(name)
+    }
+
+    /// Types of the fields of a struct, or of the fields of all variants of an enum.
+    fn member_types(&self, name: &str) -> Vec<Type> {
+        match (self.structs.get(name), self.enums.get(name)) {
+            (Some(fields), _) => fields.iter().map(|(_, ty)| ty.clone()).collect(),
+            (_, Some(variants)) => variants
+                .iter()
+                .flat_map(|(_, types)| types.clone())
+                .collect(),
+            _ => Vec::new(),
         }
-        _ => panic!("If stmt fail!"),
     }
-}
 
-fn type_if_else(cond: Expr, block1: Vec<Expr>, block2: Vec<Expr>) -> ExprRep {
-    match type_expr(cond) {
-        ExprRep::Bool(c) => {
-            if c {
-                return type_interp(block1);
-            } else {
-                return type_interp(block2);
+    /// Whether a value of `ty` contains a `name` struct or enum, `seen` guards against
+    /// cycles between other types.
+    fn contains_type(&self, ty: &Type, name: &str, seen: &mut Vec<String>) -> bool {
+        match ty {
+            Type::Array(elem_type, _) => self.contains_type(elem_type, name, seen),
+            Type::Tuple(types) => types.iter().any(|ty| self.contains_type(ty, name, seen)),
+            Type::Named(other) if other == name => true,
+            Type::Named(other) if !seen.contains(other) => {
+                seen.push(other.to_string());
+                self.member_types(other)
+                    .iter()
+                    .any(|member_type| self.contains_type(member_type, name, seen))
             }
+            _ => false,
+        }
+    }
+
+    /// Checks the type of a field of a struct or a variant of an enum.
+    fn check_member_type(&self, name: &str, ty: &Type) -> Result<(), TypeError> {
+        if *ty == Type::Void {
+            return self.error(format!("`{}` can not contain `()` values", name));
+        }
+        self.check_type(ty)?;
+        if self.contains_type(ty, name, &mut Vec::new()) {
+            return self.error(format!("recursive type `{}` has infinite size", name));
+        }
+        Ok(())
+    }
+
+    fn field_type(&self, struct_name: &str, field: &str) -> Result<Type, TypeError> {
+        let field_type = self
+            .structs
+            .get(struct_name)
+            .and_then(|fields| fields.iter().find(|(name, _)| name == field));
+        match field_type {
+            Some((_, field_type)) => Ok(field_type.clone()),
+            None => self.error(format!("no field `{}` on type `{}`", field, struct_name)),
         }
-        _ => panic!("IfElse stmt fail!"),
     }
-}
 
-fn type_while(cond: Expr, block: Vec<Expr>) -> ExprRep {
-    match type_expr(cond) {
-        ExprRep::Bool(c) => {
-            if c {
-                return type_interp(block);
+    /// Field types of `enum_name::variant`.
+    fn variant_types(&self, enum_name: &str, variant: &str) -> Result<Vec<Type>, TypeError> {
+        let variants = match self.enums.get(enum_name) {
+            Some(variants) => variants,
+            None => return self.error(format!("cannot find enum `{}` in this scope", enum_name)),
+        };
+        match variants.iter().find(|(name, _)| name == variant) {
+            Some((_, types)) => Ok(types.clone()),
+            None => self.error(format!(
+                "no variant named `{}` found for enum `{}`",
+                variant, enum_name
+            )),
+        }
+    }
+
+    /// Checks that `pattern` can match a value of `ty` and declares its bindings,
+    /// `bound` holds the names bound so far in the whole pattern.
+    fn check_pattern(
+        &mut self,
+        pattern: &Pattern,
+        ty: &Type,
+        bound: &mut Vec<String>,
+    ) -> Result<(), TypeError> {
+        match pattern {
+            Pattern::Wildcard => Ok(()),
+            Pattern::Binding(name, id) => {
+                if bound.contains(name) {
+                    return self.error(format!(
+                        "identifier `{}` is bound more than once in the same pattern",
+                        name
+                    ));
+                }
+                bound.push(name.to_string());
+                self.insert_var(&Expr::Var(name.to_string(), *id), ty.clone())
+            }
+            Pattern::Literal(expr) => {
+                let literal_type = self.type_expr(expr.clone())?;
+                self.expect(ty, &literal_type)
+            }
+            Pattern::Variant(enum_name, variant, fields) => {
+                let field_types = self.variant_types(enum_name, variant)?;
+                self.expect(ty, &Type::Named(enum_name.to_string()))?;
+                if fields.len() != field_types.len() {
+                    return self.error(format!(
+                        "this pattern has {} fields, but `{}::{}` has {} fields",
+                        fields.len(),
+                        enum_name,
+                        variant,
+                        field_types.len()
+                    ));
+                }
+                for (field, field_type) in fields.iter().zip(field_types.iter()) {
+                    self.check_pattern(field, field_type, bound)?;
+                }
+                Ok(())
             }
-            return ExprRep::Null;
         }
-        _ => panic!("While stmt fail!"),
     }
-}
 
-fn type_let(var: Expr, _var_type: Type, expr: Expr) -> ExprRep {
-    match (var, type_expr(expr)) {
-        (Expr::Var(v), ExprRep::Int(val)) => insert_var(ExprRep::Var(v), ExprRep::Int(val)),
-        (Expr::Var(v), ExprRep::Bool(val)) => insert_var(ExprRep::Var(v), ExprRep::Bool(val)),
-        _ => panic!("Invalid let expr!"),
+    /// Checks a match and returns it typed, with the type of the values of its arms.
+    fn check_match(
+        &mut self,
+        expr: Expr,
+        arms: Vec<(Pattern, Vec<Expr>)>,
+    ) -> Result<(Expr, Type), TypeError> {
+        let span = self.span;
+        let ty = self.type_expr(expr.clone())?;
+        let patterns: Vec<Pattern> = arms.iter().map(|(pattern, _)| pattern.clone()).collect();
+        let mut typed_arms = Vec::new();
+        let mut match_type: Option<Type> = None;
+        for (pattern, block) in arms {
+            self.check_pattern(&pattern, &ty, &mut Vec::new())?;
+            let (block, arm_type) = self.check_block(block)?;
+            match &match_type {
+                Some(match_type) if !self.unify(match_type, &arm_type) => {
+                    return self.error(format!(
+                        "`match` arms have incompatible types: expected `{}`, found `{}`",
+                        self.resolve(match_type),
+                        self.resolve(&arm_type)
+                    ))
+                }
+                Some(match_type) if self.resolve(match_type) != Type::Never => {}
+                _ => match_type = Some(arm_type),
+            }
+            typed_arms.push((pattern, block));
+        }
+
+        self.span = span;
+        match check_arms(&patterns, &ty, &self.enums) {
+            Ok(()) => Ok((
+                Expr::Match(Box::new(expr), typed_arms),
+                self.resolve(&match_type.unwrap_or(Type::Void)),
+            )),
+            Err(msg) => self.error(msg),
+        }
     }
-}
 
-fn type_return(expr: Expr) -> ExprRep {
-    return type_expr(expr);
-}
+    /// Records the signature of a top-level function so it can be called from anywhere.
+    fn declare_item(&mut self, item: &Expr) -> Result<(), TypeError> {
+        match item {
+            Expr::Spanned(span, item) => {
+                self.span = *span;
+                self.declare_item(item)
+            }
+            Expr::Fn(fn_var, generics, params, ret_type, block) => {
+                for (i, (generic, _)) in generics.iter().enumerate() {
+                    if generics[..i].iter().any(|(other, _)| other == generic) {
+                        return self.error(format!(
+                            "the name `{}` is already used for a generic parameter",
+                            generic
+                        ));
+                    }
+                }
+                if !generics.is_empty() {
+                    let generic_fn = (
+                        generics.clone(),
+                        params.clone(),
+                        ret_type.clone(),
+                        block.clone(),
+                    );
+                    self.generic_fns.insert(self.name(fn_var)?, generic_fn);
+                }
+                self.declare_fn(fn_var, params, ret_type)
+            }
+            Expr::ExternFn(fn_var, params, ret_type) => self.declare_fn(fn_var, params, ret_type),
+            Expr::Const(var, ty, value) => {
+                self.declare_global(var, Global::Const(ty.clone(), *value.clone()))
+            }
+            Expr::Static(var, ty, _, mutable) => {
+                self.declare_global(var, Global::Static(ty.clone(), *mutable))
+            }
+            Expr::Trait(trait_var, methods) => {
+                let name = self.name(trait_var)?;
+                if self.traits.contains_key(&name) || self.is_declared(&name) {
+                    return self.error(format!("the name `{}` is defined multiple times", name));
+                }
+                let mut signatures: Vec<(String, Vec<Type>, Type)> = Vec::new();
+                for (method_var, params, ret_type) in methods {
+                    let method = self.name(method_var)?;
+                    if signatures.iter().any(|(other, ..)| *other == method) {
+                        return self
+                            .error(format!("the name `{}` is defined multiple times", method));
+                    }
+                    match params.first() {
+                        Some((Expr::Var(param, _), _)) if param == "self" => {}
+                        _ => {
+                            return self.error(format!(
+                                "method `{}` must take `self` as its first parameter",
+                                method
+                            ))
+                        }
+                    }
+                    let param_types = params.iter().map(|param| param.1.clone()).collect();
+                    signatures.push((method, param_types, ret_type.clone()));
+                }
+                self.traits.insert(name, signatures);
+                Ok(())
+            }
+            // The methods are declared as functions named like `Circle::area`
+            Expr::Impl(trait_name, impl_type, methods) => {
+                let implementation = (trait_name.to_string(), impl_type.clone());
+                if self.impls.contains(&implementation) {
+                    return self.error(format!(
+                        "conflicting implementations of trait `{}` for type `{}`",
+                        trait_name, impl_type
+                    ));
+                }
+                self.impls.push(implementation);
+                let self_type = self_type_args(impl_type);
+                for method in methods {
+                    if let Expr::Fn(method_var, generics, params, ret_type, _) =
+                        method.clone().unspanned()
+                    {
+                        if !generics.is_empty() {
+                            return self.error("methods can not be generic".to_string());
+                        }
+                        let path = impl_type.method_path(&self.name(&method_var)?);
+                        let params: Vec<(Expr, Type)> = params
+                            .into_iter()
+                            .map(|(param, ty)| (param, ty.substitute(&self_type)))
+                            .collect();
+                        let ret_type = ret_type.substitute(&self_type);
+                        self.declare_fn(&Expr::Var(path, None), &params, &ret_type)?;
+                    }
+                }
+                Ok(())
+            }
+            Expr::Struct(struct_var, fields) => {
+                let name = self.name(struct_var)?;
+                if self.is_declared(&name) {
+                    return self.error(format!("the name `{}` is defined multiple times", name));
+                }
+                let mut field_types: Vec<(String, Type)> = Vec::new();
+                for (field, field_type) in fields {
+                    let field = self.name(field)?;
+                    if field_types.iter().any(|(other, _)| *other == field) {
+                        return self.error(format!("field `{}` is already declared", field));
+                    }
+                    field_types.push((field, field_type.clone()));
+                }
+                self.structs.insert(name, field_types);
+                Ok(())
+            }
+            Expr::Enum(enum_var, variants) => {
+                let name = self.name(enum_var)?;
+                if self.is_declared(&name) {
+                    return self.error(format!("the name `{}` is defined multiple times", name));
+                }
+                let mut variant_types: Vec<(String, Vec<Type>)> = Vec::new();
+                for (variant, types) in variants {
+                    let variant = self.name(variant)?;
+                    if variant_types.iter().any(|(other, _)| *other == variant) {
+                        return self
+                            .error(format!("the name `{}` is defined multiple times", variant));
+                    }
+                    variant_types.push((variant, types.clone()));
+                }
+                self.enums.insert(name, variant_types);
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
 
-fn type_bin_expr(l: Expr, op: Op, r: Expr) -> ExprRep {
-    match (type_expr(l), type_expr(r.clone())) {
-        (ExprRep::Int(left), ExprRep::Int(right)) => type_int_expr(left, op, right),
-        (ExprRep::Var(v), ExprRep::Int(right)) => match read_var(&v) {
-            ExprRep::Int(val) => type_int_expr(val, op, right),
-            _ => ExprRep::Int(right),
-        },
-        (ExprRep::Bool(left), ExprRep::Bool(right)) => type_bool_expr(left, op, right),
-        (ExprRep::Var(v), ExprRep::Bool(right)) => match read_var(&v) {
-            ExprRep::Bool(val) => type_bool_expr(val, op, right),
-            _ => ExprRep::Bool(right),
-        },
-        (ExprRep::Null, _) => type_expr(r),
-        _ => panic!("Invalid bin expr!"),
+    fn declare_fn(
+        &mut self,
+        fn_var: &Expr,
+        params: &[(Expr, Type)],
+        ret_type: &Type,
+    ) -> Result<(), TypeError> {
+        let name = self.name(fn_var)?;
+        if is_builtin(&name) {
+            return self.error(format!("`{}` is a built-in function", name));
+        }
+        if self.globals.contains_key(&name) {
+            return self.error(format!("the name `{}` is defined multiple times", name));
+        }
+        let param_types = params.iter().map(|param| param.1.clone()).collect();
+        self.fns.insert(name, (param_types, ret_type.clone()));
+        Ok(())
     }
-}
 
-/// Updates existing value in memory
-fn type_var_expr(var: Expr, op: Op, expr: Expr) -> ExprRep {
-    match op {
-        Op::AriOp(_) => var_ari_op(var, op, expr),
-        Op::AssOp(_) => var_ass_op(var, op, expr),
-        Op::LogOp(_) => var_log_op(var, op, expr),
-        Op::RelOp(_) => var_rel_op(var, op, expr),
+    fn declare_global(&mut self, var: &Expr, global: Global) -> Result<(), TypeError> {
+        let name = self.name(var)?;
+        if self.globals.contains_key(&name) || self.fns.contains_key(&name) {
+            return self.error(format!("the name `{}` is defined multiple times", name));
+        }
+        self.globals.insert(name, global);
+        Ok(())
     }
-}
 
-fn type_int_expr(l: i32, op: Op, r: i32) -> ExprRep {
-    match op {
-        Op::AriOp(AriOp::Add) => ExprRep::Int(l + r),
-        Op::AriOp(AriOp::Sub) => ExprRep::Int(l - r),
-        Op::AriOp(AriOp::Div) => ExprRep::Int(l / r),
-        Op::AriOp(AriOp::Mul) => ExprRep::Int(l * r),
-        Op::RelOp(RelOp::Eq) => ExprRep::Bool(l == r),
-        Op::RelOp(RelOp::Neq) => ExprRep::Bool(l != r),
-        Op::RelOp(RelOp::Leq) => ExprRep::Bool(l <= r),
-        Op::RelOp(RelOp::Geq) => ExprRep::Bool(l >= r),
-        Op::RelOp(RelOp::Les) => ExprRep::Bool(l < r),
-        Op::RelOp(RelOp::Gre) => ExprRep::Bool(l > r),
-        _ => panic!("Invalid Int expr!"),
+    fn check_item(&mut self, item: Expr) -> Result<Expr, TypeError> {
+        match item.clone() {
+            Expr::Spanned(span, item) => {
+                self.span = span;
+                Ok(Expr::Spanned(span, Box::new(self.check_item(*item)?)))
+            }
+            Expr::Fn(_, generics, params, ret_type, _) if !generics.is_empty() => {
+                // Only the signature, the body is checked for each instantiation
+                for (_, bounds) in generics.iter() {
+                    for bound in bounds {
+                        if !self.traits.contains_key(bound) {
+                            return self
+                                .error(format!("cannot find trait `{}` in this scope", bound));
+                        }
+                    }
+                }
+                let type_params: Vec<String> =
+                    generics.into_iter().map(|(generic, _)| generic).collect();
+                self.check_signature(&type_params, &params, &ret_type)?;
+                Ok(item)
+            }
+            Expr::Fn(fn_var, generics, params, ret_type, block) => {
+                let block = self.check_fn(params.clone(), ret_type.clone(), block)?;
+                Ok(Expr::Fn(fn_var, generics, params, ret_type, block))
+            }
+            Expr::ExternFn(..) => Ok(item),
+            Expr::Struct(struct_var, fields) => {
+                let name = self.name(&struct_var)?;
+                for (_, field_type) in fields {
+                    self.check_member_type(&name, &field_type)?;
+                }
+                Ok(item)
+            }
+            Expr::Enum(enum_var, variants) => {
+                let name = self.name(&enum_var)?;
+                for (_, types) in variants {
+                    for ty in types {
+                        self.check_member_type(&name, &ty)?;
+                    }
+                }
+                Ok(item)
+            }
+            Expr::Trait(_, methods) => {
+                for (_, params, ret_type) in methods {
+                    self.check_signature(&["Self".to_string()], &params, &ret_type)?;
+                }
+                Ok(item)
+            }
+            Expr::Impl(trait_name, impl_type, methods) => {
+                self.check_impl(trait_name, impl_type, methods)
+            }
+            Expr::Const(var, ty, value) => {
+                let value = self.check_global(&ty, *value)?;
+                Ok(Expr::Const(var, ty, Box::new(value)))
+            }
+            Expr::Static(var, ty, value, mutable) => {
+                let value = self.check_global(&ty, *value)?;
+                Ok(Expr::Static(var, ty, Box::new(value), mutable))
+            }
+            _ => self.error(
+                "expected `fn`, `extern fn`, `struct`, `enum`, `trait`, `impl`, `const` or `static` at the top level"
+                    .to_string(),
+            ),
+        }
     }
-}
-fn type_bool_expr(l: bool, op: Op, r: bool) -> ExprRep {
-    match op {
-        Op::LogOp(LogOp::And) => ExprRep::Bool(l && r),
-        Op::LogOp(LogOp::Or) => ExprRep::Bool(l || r),
-        Op::RelOp(RelOp::Eq) => ExprRep::Bool(l == r),
-        Op::RelOp(RelOp::Neq) => ExprRep::Bool(l != r),
-        Op::RelOp(RelOp::Leq) => ExprRep::Bool(l <= r),
-        Op::RelOp(RelOp::Geq) => ExprRep::Bool(l >= r),
-        Op::RelOp(RelOp::Les) => ExprRep::Bool(l < r),
-        Op::RelOp(RelOp::Gre) => ExprRep::Bool(l > r),
-        _ => panic!("Invalid Bool expr!"),
+
+    /// Checks the initializer of a `const` or `static` and evaluates it to a literal.
+    fn check_global(&mut self, ty: &Type, value: Expr) -> Result<Expr, TypeError> {
+        self.vars.clear();
+        self.check_type(ty)?;
+        let value_type = self.type_expr(value.clone())?;
+        self.expect(ty, &value_type)?;
+
+        let consts: HashMap<String, Expr> = self
+            .globals
+            .iter()
+            .filter_map(|(name, global)| match global {
+                Global::Const(_, value) => Some((name.to_string(), value.clone())),
+                Global::Static(..) => None,
+            })
+            .collect();
+        match const_eval(&value, &consts) {
+            Ok(value) => Ok(value),
+            Err(msg) => self.error(msg),
+        }
     }
-}
 
-fn var_ari_op(var: Expr, op: Op, expr: Expr) -> ExprRep {
-    match (var, op, expr) {
-        (Expr::Var(v), op, Expr::Var(expr)) => match (read_var(&v), read_var(&expr)) {
-            (ExprRep::Int(v1), ExprRep::Int(v2)) => type_int_expr(v1, op, v2),
-            _ => panic!("Var(Int) Var(Int) op fail!"),
-        },
-        (Expr::Var(v), op, Expr::Int(expr)) => match read_var(&v) {
-            ExprRep::Int(v1) => type_int_expr(v1, op, expr),
-            _ => panic!("Var(Int) Int op fail!"),
-        },
-        _ => panic!("Invalid Var Log op!"),
+    /// Checks the types in a signature, `type_params` are the types it is generic over.
+    fn check_signature(
+        &mut self,
+        type_params: &[String],
+        params: &[(Expr, Type)],
+        ret_type: &Type,
+    ) -> Result<(), TypeError> {
+        self.type_args = type_params
+            .iter()
+            .map(|name| (name.to_string(), Type::Named(name.to_string())))
+            .collect();
+        self.vars.clear();
+        for (param, param_type) in params {
+            self.insert_binding(param, param_type.clone())?;
+        }
+        self.check_type(ret_type)?;
+        self.type_args.clear();
+        Ok(())
     }
-}
 
-fn var_ass_op(var: Expr, op: Op, expr: Expr) -> ExprRep {
-    match (var.clone(), op, type_expr(expr)) {
-        (Expr::Var(v), Op::AssOp(AssOp::Eq), ExprRep::Int(val)) => {
-            insert_var(ExprRep::Var(v), ExprRep::Int(val))
-        }
-        (Expr::Var(v), Op::AssOp(AssOp::Eq), ExprRep::Bool(val)) => {
-            insert_var(ExprRep::Var(v), ExprRep::Bool(val))
-        }
-        (Expr::Var(v), Op::AssOp(AssOp::AddEq), ExprRep::Int(new_val)) => match type_expr(var) {
-            ExprRep::Int(old_val) => insert_var(ExprRep::Var(v), ExprRep::Int(old_val + new_val)),
-            _ => panic!("Var Add update fail!"),
-        },
-        (Expr::Var(v), Op::AssOp(AssOp::SubEq), ExprRep::Int(new_val)) => match type_expr(var) {
-            ExprRep::Int(old_val) => insert_var(ExprRep::Var(v), ExprRep::Int(old_val - new_val)),
-            _ => panic!("Var Sub update fail!"),
-        },
-        (Expr::Var(v), Op::AssOp(AssOp::DivEq), ExprRep::Int(new_val)) => match type_expr(var) {
-            ExprRep::Int(old_val) => insert_var(ExprRep::Var(v), ExprRep::Int(old_val / new_val)),
-            _ => panic!("Var Div update fail!"),
-        },
-        (Expr::Var(v), Op::AssOp(AssOp::MulEq), ExprRep::Int(new_val)) => match type_expr(var) {
-            ExprRep::Int(old_val) => insert_var(ExprRep::Var(v), ExprRep::Int(old_val * new_val)),
-            _ => panic!("Var Mul update fail!"),
-        },
-        _ => panic!("Var update fail!"),
+    /// Checks that an impl has exactly the methods of its trait, with the same types.
+    fn check_impl(
+        &mut self,
+        trait_name: String,
+        impl_type: Type,
+        methods: Vec<Expr>,
+    ) -> Result<Expr, TypeError> {
+        self.check_type(&impl_type)?;
+        let signatures = match self.traits.get(&trait_name) {
+            Some(signatures) => signatures.clone(),
+            None => return self.error(format!("cannot find trait `{}` in this scope", trait_name)),
+        };
+        let self_type = self_type_args(&impl_type);
+
+        let mut implemented: Vec<String> = Vec::new();
+        let mut typed_methods = Vec::new();
+        for method in methods {
+            let (span, method) = match method {
+                Expr::Spanned(span, method) => (span, *method),
+                method => (self.span, method),
+            };
+            self.span = span;
+            let (method_var, generics, params, ret_type, block) = match method {
+                Expr::Fn(method_var, generics, params, ret_type, block) => {
+                    (method_var, generics, params, ret_type, block)
+                }
+                _ => return self.error("expected `fn` in an impl".to_string()),
+            };
+
+            let name = self.name(&method_var)?;
+            if implemented.contains(&name) {
+                return self.error(format!("duplicate definitions with name `{}`", name));
+            }
+            let (_, trait_params, trait_ret) = match signatures.iter().find(|(m, ..)| *m == name) {
+                Some(method_signature) => method_signature,
+                None => {
+                    return self.error(format!(
+                        "method `{}` is not a member of trait `{}`",
+                        name, trait_name
+                    ))
+                }
+            };
+            let typed_params: Vec<(Expr, Type)> = params
+                .iter()
+                .map(|(param, ty)| (param.clone(), ty.substitute(&self_type)))
+                .collect();
+            let typed_ret = ret_type.substitute(&self_type);
+            let expected =
+                Type::Fn(trait_params.clone(), Box::new(trait_ret.clone())).substitute(&self_type);
+            let found = Type::Fn(
+                typed_params.iter().map(|(_, ty)| ty.clone()).collect(),
+                Box::new(typed_ret.clone()),
+            );
+            if expected != found {
+                return self.error(format!(
+                    "method `{}` has an incompatible type for trait: expected `{}`, found `{}`",
+                    name, expected, found
+                ));
+            }
+
+            self.type_args = self_type.clone();
+            let block = self.check_fn(typed_params, typed_ret, block)?;
+            self.type_args.clear();
+            let method = Expr::Fn(method_var, generics, params, ret_type, block);
+            typed_methods.push(Expr::Spanned(span, Box::new(method)));
+            implemented.push(name);
+        }
+
+        for (method, ..) in signatures.iter() {
+            if !implemented.contains(method) {
+                return self.error(format!(
+                    "not all trait items implemented, missing: `{}`",
+                    method
+                ));
+            }
+        }
+        Ok(Expr::Impl(trait_name, impl_type, typed_methods))
+    }
+
+    fn check_fn(
+        &mut self,
+        params: Vec<(Expr, Type)>,
+        ret_type: Type,
+        block: Vec<Expr>,
+    ) -> Result<Vec<Expr>, TypeError> {
+        self.vars.clear();
+        for (param, param_type) in params.iter() {
+            self.insert_binding(param, param_type.clone())?;
+        }
+        self.check_type(&ret_type)?;
+        self.ret_type = ret_type.clone();
+
+        let (block, body_type) = self.check_block(block)?;
+        if block.last().is_some_and(Expr::is_tail) {
+            self.expect(&ret_type, &body_type)?;
+        }
+        Ok(block)
     }
-}
 
-fn var_log_op(var: Expr, op: Op, expr: Expr) -> ExprRep {
-    match (type_expr(var), op, type_expr(expr)) {
-        (ExprRep::Bool(b1), op, ExprRep::Bool(b2)) => type_bool_expr(b1, op, b2),
-        _ => panic!("Invalid Var Log op!"),
+    /// Checks the statements of a block, its type is the type of its tail expression or
+    /// `()` without one.
+    fn check_block(&mut self, mut block: Vec<Expr>) -> Result<(Vec<Expr>, Type), TypeError> {
+        let tail = match block.last() {
+            Some(stmt) if stmt.is_tail() => block.pop(),
+            _ => None,
+        };
+        // Code after a statement that never completes is still checked, but left out of
+        // the typed program as it can never run
+        let mut typed_block = Vec::new();
+        let mut unreachable = false;
+        self.diverging = false;
+        for stmt in block {
+            if self.diverging && !unreachable {
+                self.warn_unreachable(&stmt, "statement");
+                unreachable = true;
+            }
+            let stmt = self.check_stmt(stmt)?;
+            if !unreachable {
+                typed_block.push(stmt);
+            }
+        }
+        let block_type = match tail {
+            Some(tail) => {
+                if self.diverging && !unreachable {
+                    self.warn_unreachable(&tail, "expression");
+                    unreachable = true;
+                }
+                let (tail, tail_type) = self.check_value(tail)?;
+                if unreachable {
+                    Type::Never
+                } else {
+                    typed_block.push(tail);
+                    self.resolve(&tail_type)
+                }
+            }
+            // A block that ends in `return`, `break` or `continue` never completes
+            None if self.diverging || unreachable => Type::Never,
+            None => Type::Void,
+        };
+        let span = self.span;
+        let typed_block = typed_block
+            .into_iter()
+            .map(|stmt| self.resolve_let(stmt))
+            .collect::<Result<_, _>>()?;
+        self.span = span;
+        self.diverging = false;
+        Ok((typed_block, block_type))
+    }
+
+    /// Fills in the type inferred for a variable of a block that just ended, the whole
+    /// block has been checked so nothing else can tell what it is.
+    fn resolve_let(&mut self, stmt: Expr) -> Result<Expr, TypeError> {
+        match stmt {
+            Expr::Spanned(span, stmt) => {
+                self.span = span;
+                Ok(Expr::Spanned(span, Box::new(self.resolve_let(*stmt)?)))
+            }
+            Expr::Let(var, Some(var_type), expr) => {
+                let var_type = self.known_type(&var, &var_type)?;
+                Ok(Expr::Let(var, Some(var_type), expr))
+            }
+            Expr::Declare(var, Some(var_type)) => {
+                let var_type = self.known_type(&var, &var_type)?;
+                Ok(Expr::Declare(var, Some(var_type)))
+            }
+            stmt => Ok(stmt),
+        }
+    }
+
+    /// The type inferred for the variables of `binding`, an error if nothing decided it.
+    fn known_type(&self, binding: &Expr, ty: &Type) -> Result<Type, TypeError> {
+        let ty = self.resolve(ty);
+        if !is_known(&ty) {
+            let mut names = Vec::new();
+            binding.binding_names(&mut names);
+            return self.error(format!(
+                "type annotations needed for `{}`",
+                names.join(", ")
+            ));
+        }
+        Ok(ty)
+    }
+
+    /// Warns about a statement that follows one that never completes.
+    fn warn_unreachable(&self, stmt: &Expr, kind: &str) {
+        let span = match stmt {
+            Expr::Spanned(span, _) => *span,
+            _ => self.span,
+        };
+        eprintln!("warning at {}: unreachable {}", span, kind);
+    }
+
+    /// Checks an expression that can contain blocks, which are returned with the types of
+    /// their `let`s filled in.
+    fn check_value(&mut self, expr: Expr) -> Result<(Expr, Type), TypeError> {
+        match expr {
+            Expr::Spanned(span, expr) => {
+                self.span = span;
+                let (expr, ty) = self.check_value(*expr)?;
+                Ok((Expr::Spanned(span, Box::new(expr)), ty))
+            }
+            Expr::Tail(expr) => {
+                let (expr, ty) = self.check_value(*expr)?;
+                Ok((Expr::Tail(Box::new(expr)), ty))
+            }
+            // The value of a `let`
+            Expr::BinExpr(l, Op::AssOp(AssOp::Eq), r) if *l == Expr::Var(String::new(), None) => {
+                let (r, ty) = self.check_value(*r)?;
+                Ok((Expr::BinExpr(l, Op::AssOp(AssOp::Eq), Box::new(r)), ty))
+            }
+            Expr::VarExpr(l, Op::AssOp(AssOp::Eq), r) if *l == Expr::Var(String::new(), None) => {
+                let (r, ty) = self.check_value(*r)?;
+                Ok((Expr::VarExpr(l, Op::AssOp(AssOp::Eq), Box::new(r)), ty))
+            }
+            Expr::IfElse(cond, block1, block2) => {
+                self.check_cond(*cond.clone())?;
+                let (block1, type1) = self.check_block(block1)?;
+                let (block2, type2) = self.check_block(block2)?;
+                if !self.unify(&type1, &type2) {
+                    return self.error(format!(
+                        "`if` and `else` have incompatible types: expected `{}`, found `{}`",
+                        self.resolve(&type1),
+                        self.resolve(&type2)
+                    ));
+                }
+                // A block that never completes does not decide the type
+                let ty = match self.resolve(&type1) {
+                    Type::Never => self.resolve(&type2),
+                    type1 => type1,
+                };
+                self.diverging = ty == Type::Never;
+                Ok((Expr::IfElse(cond, block1, block2), ty))
+            }
+            Expr::Match(expr, arms) => {
+                let (expr, ty) = self.check_match(*expr, arms)?;
+                self.diverging = ty == Type::Never;
+                Ok((expr, ty))
+            }
+            Expr::Block(block) => {
+                let (block, ty) = self.check_block(block)?;
+                self.diverging = ty == Type::Never;
+                Ok((Expr::Block(block), ty))
+            }
+            Expr::Loop(block) => {
+                self.loops.push(Loop::Expr(None));
+                let (block, block_type) = self.check_block(block)?;
+                self.expect(&Type::Void, &block_type)?;
+                // Without a `break` the loop never ends
+                let ty = match self.loops.pop() {
+                    Some(Loop::Expr(Some(break_type))) => self.resolve(&break_type),
+                    _ => Type::Never,
+                };
+                self.diverging = ty == Type::Never;
+                Ok((Expr::Loop(block), ty))
+            }
+            Expr::Return(_) | Expr::Break(_) | Expr::Continue => {
+                Ok((self.check_stmt(expr)?, Type::Never))
+            }
+            expr => {
+                let ty = self.type_expr(expr.clone())?;
+                Ok((expr, ty))
+            }
+        }
+    }
+
+    /// Checks a statement and returns it with the types of its `let`s filled in.
+    fn check_stmt(&mut self, stmt: Expr) -> Result<Expr, TypeError> {
+        match stmt.clone() {
+            Expr::Spanned(span, stmt) => {
+                self.span = span;
+                Ok(Expr::Spanned(span, Box::new(self.check_stmt(*stmt)?)))
+            }
+
+            Expr::Let(var, var_type, expr) => {
+                // Without an annotation the variable gets the type it is unified with
+                let var_type = match var_type {
+                    Some(var_type) => {
+                        let var_type = var_type.substitute(&self.type_args);
+                        self.check_type(&var_type)?;
+                        var_type
+                    }
+                    None => self.fresh_var(),
+                };
+                let (expr, expr_type) = self.check_value(*expr)?;
+                self.expect(&var_type, &expr_type)?;
+                let var_type = self.resolve(&var_type);
+                self.insert_binding(&var, var_type.clone())?;
+                Ok(Expr::Let(var, Some(var_type), Box::new(expr)))
+            }
+            Expr::Declare(var, var_type) => {
+                // Without an annotation the assignments and uses in the rest of the block
+                // decide the type, `check_block` fills it in at the end of the block
+                let var_type = match var_type {
+                    Some(var_type) => {
+                        let var_type = var_type.substitute(&self.type_args);
+                        self.check_type(&var_type)?;
+                        var_type
+                    }
+                    None => self.fresh_var(),
+                };
+                self.insert_binding(&var, var_type.clone())?;
+                Ok(Expr::Declare(var, Some(var_type)))
+            }
+
+            Expr::If(cond, block) => {
+                self.check_cond(*cond.clone())?;
+                let (block, block_type) = self.check_block(block)?;
+                if !self.unify(&Type::Void, &block_type) {
+                    return self.error(format!(
+                        "`if` may be missing an `else` clause: expected `()`, found `{}`",
+                        self.resolve(&block_type)
+                    ));
+                }
+                Ok(Expr::If(cond, block))
+            }
+            Expr::While(cond, block) => {
+                self.check_cond(*cond.clone())?;
+                self.loops.push(Loop::Stmt("while"));
+                let (block, block_type) = self.check_block(block)?;
+                self.loops.pop();
+                self.expect(&Type::Void, &block_type)?;
+                Ok(Expr::While(cond, block))
+            }
+            Expr::For(var, range, block) => self.check_for(*var, *range, block),
+            Expr::Break(_) | Expr::Continue if self.loops.is_empty() => {
+                let keyword = match stmt {
+                    Expr::Continue => "continue",
+                    _ => "break",
+                };
+                self.error(format!("`{}` outside of a loop", keyword))
+            }
+            Expr::Break(value) => {
+                let stmt = self.check_break(value)?;
+                self.diverging = true;
+                Ok(stmt)
+            }
+            Expr::Continue => {
+                self.diverging = true;
+                Ok(stmt)
+            }
+            // Only the tail of a block has a value, statements must not
+            Expr::IfElse(..) | Expr::Match(..) | Expr::Block(_) | Expr::Loop(_) => {
+                let (stmt, stmt_type) = self.check_value(stmt)?;
+                self.expect(&Type::Void, &stmt_type)?;
+                Ok(stmt)
+            }
+
+            Expr::Return(expr) => {
+                let (expr, expr_type) = self.check_value(*expr)?;
+                let ret_type = self.ret_type.clone();
+                self.expect(&ret_type, &expr_type)?;
+                self.diverging = true;
+                Ok(Expr::Return(Box::new(expr)))
+            }
+
+            Expr::Fn(..) | Expr::ExternFn(..) => {
+                self.error("functions can only be declared at the top level".to_string())
+            }
+            Expr::Struct(..) => {
+                self.error("structs can only be declared at the top level".to_string())
+            }
+            Expr::Enum(..) => self.error("enums can only be declared at the top level".to_string()),
+            Expr::Trait(..) => {
+                self.error("traits can only be declared at the top level".to_string())
+            }
+            Expr::Impl(..) => self.error("impls can only be declared at the top level".to_string()),
+            Expr::Const(..) | Expr::Static(..) => self.error(
+                "`const` and `static` items can only be declared at the top level".to_string(),
+            ),
+            Expr::Mod(..) | Expr::Use(..) | Expr::Pub(..) => self.error(
+                "modules, `use` and `pub` items can only be declared at the top level".to_string(),
+            ),
+
+            expr => {
+                self.type_expr(expr)?;
+                Ok(stmt)
+            }
+        }
     }
-}
 
-fn var_rel_op(var: Expr, op: Op, expr: Expr) -> ExprRep {
-    match (type_expr(var), op, type_expr(expr)) {
-        (ExprRep::Bool(b1), op, ExprRep::Bool(b2)) => type_bool_expr(b1, op, b2),
-        (ExprRep::Int(b1), op, ExprRep::Int(b2)) => type_int_expr(b1, op, b2),
-        _ => panic!("Invalid Var Log op!"),
+    /// Checks a `for` loop, its variable has the integer type of the bounds of the range.
+    fn check_for(&mut self, var: Expr, range: Expr, block: Vec<Expr>) -> Result<Expr, TypeError> {
+        let var_type = match &range {
+            Expr::Range(start, end, _) => {
+                let start_type = self.type_expr(*start.clone())?;
+                let end_type = self.type_expr(*end.clone())?;
+                self.expect(&start_type, &end_type)?;
+                self.resolve(&start_type)
+            }
+            _ => return self.error("`for` loops can only iterate over ranges".to_string()),
+        };
+        if !var_type.is_int() {
+            return self.error(format!(
+                "range bounds must be integers, found `{}`",
+                var_type
+            ));
+        }
+
+        self.insert_var(&var, var_type)?;
+        if let Expr::Var(_, Some(id)) = var {
+            self.loop_vars.push(id);
+        }
+        self.loops.push(Loop::Stmt("for"));
+        let (block, block_type) = self.check_block(block)?;
+        self.loops.pop();
+        self.loop_vars.pop();
+        self.expect(&Type::Void, &block_type)?;
+        Ok(Expr::For(Box::new(var), Box::new(range), block))
+    }
+
+    /// Checks a `break`, the values of the `break`s of a `loop` must have the same type.
+    fn check_break(&mut self, value: Option<Box<Expr>>) -> Result<Expr, TypeError> {
+        let (value, value_type) = match value {
+            Some(value) => {
+                let (value, value_type) = self.check_value(*value)?;
+                (Some(Box::new(value)), value_type)
+            }
+            None => (None, Type::Void),
+        };
+        let break_type = match self.loops.last_mut().unwrap() {
+            Loop::Expr(break_type) => break_type.get_or_insert(value_type.clone()).clone(),
+            Loop::Stmt(keyword) if value.is_some() => {
+                let msg = format!("`break` with value from a `{}` loop", keyword);
+                return self.error(msg);
+            }
+            Loop::Stmt(_) => Type::Void,
+        };
+        self.expect(&break_type, &value_type)?;
+        Ok(Expr::Break(value))
+    }
+
+    fn check_cond(&mut self, cond: Expr) -> Result<(), TypeError> {
+        let cond_type = self.type_expr(cond)?;
+        self.expect(&Type::Bool, &cond_type)
+    }
+
+    fn type_expr(&mut self, expr: Expr) -> Result<Type, TypeError> {
+        match expr {
+            Expr::Int(_) => Ok(Type::Int),
+            Expr::TypedInt(val, int_type) => {
+                if val as i128 > int_type.int_range().1 {
+                    return self.error(format!("literal out of range for `{}`", int_type));
+                }
+                Ok(int_type)
+            }
+
+            Expr::Array(elems) => {
+                let len = elems.len();
+                let mut elems = elems.into_iter();
+                let elem_type = match elems.next() {
+                    Some(elem) => self.type_expr(elem)?,
+                    None => {
+                        return self.error("can not infer the type of an empty array".to_string())
+                    }
+                };
+                for elem in elems {
+                    let found = self.type_expr(elem)?;
+                    self.expect(&elem_type, &found)?;
+                }
+                self.array_type(elem_type, len)
+            }
+            Expr::ArrayRepeat(elem, len) => {
+                let elem_type = self.type_expr(*elem)?;
+                self.array_type(elem_type, len)
+            }
+            Expr::Index(array, index) => {
+                let array_type = self.type_expr(*array)?;
+                let index_type = self.type_expr(*index)?;
+                if !index_type.is_int() {
+                    return self.error(format!(
+                        "arrays can only be indexed by integers, found `{}`",
+                        index_type
+                    ));
+                }
+                match array_type {
+                    Type::Array(elem_type, _) => Ok(*elem_type),
+                    _ => self.error(format!(
+                        "cannot index into a value of type `{}`",
+                        array_type
+                    )),
+                }
+            }
+
+            Expr::StructLit(name, fields) => {
+                let declared = match self.structs.get(&name) {
+                    Some(declared) => declared.clone(),
+                    None => {
+                        return self.error(format!("cannot find struct `{}` in this scope", name))
+                    }
+                };
+                for (i, (field, expr)) in fields.iter().enumerate() {
+                    if fields[..i].iter().any(|(other, _)| other == field) {
+                        return self.error(format!("field `{}` specified more than once", field));
+                    }
+                    let field_type = match declared.iter().find(|(other, _)| other == field) {
+                        Some((_, field_type)) => field_type,
+                        None => {
+                            return self
+                                .error(format!("struct `{}` has no field named `{}`", name, field))
+                        }
+                    };
+                    let found = self.type_expr(expr.clone())?;
+                    self.expect(field_type, &found)?;
+                }
+                for (field, _) in declared.iter() {
+                    if !fields.iter().any(|(other, _)| other == field) {
+                        return self.error(format!(
+                            "missing field `{}` in initializer of `{}`",
+                            field, name
+                        ));
+                    }
+                }
+                Ok(Type::Named(name))
+            }
+            Expr::Variant(enum_name, variant, args) => {
+                let field_types = self.variant_types(&enum_name, &variant)?;
+                if args.len() != field_types.len() {
+                    return self.error(format!(
+                        "this enum variant takes {} arguments but {} were supplied",
+                        field_types.len(),
+                        args.len()
+                    ));
+                }
+                for (arg, field_type) in args.into_iter().zip(field_types.iter()) {
+                    let arg_type = self.type_expr(arg)?;
+                    self.expect(field_type, &arg_type)?;
+                }
+                Ok(Type::Named(enum_name))
+            }
+            Expr::Tuple(elems) => {
+                let mut types = Vec::new();
+                for elem in elems {
+                    types.push(self.type_expr(elem)?);
+                }
+                let tuple_type = Type::Tuple(types);
+                self.check_type(&tuple_type)?;
+                Ok(tuple_type)
+            }
+            Expr::TupleField(expr, index) => match self.type_expr(*expr)? {
+                Type::Tuple(types) if index < types.len() => Ok(types[index].clone()),
+                ty => self.error(format!("no field `{}` on type `{}`", index, ty)),
+            },
+            Expr::Field(expr, field) => match self.type_expr(*expr)? {
+                Type::Named(name) => self.field_type(&name, &field),
+                ty => self.error(format!("no field `{}` on type `{}`", field, ty)),
+            },
+
+            Expr::Float(_) => Ok(Type::Float),
+            Expr::Bool(_) => Ok(Type::Bool),
+            Expr::Str(_) => Ok(Type::Str),
+            Expr::Var(name, id) => self.type_var(&name, id),
+
+            Expr::BinExpr(l, op, r) | Expr::VarExpr(l, op, r) => self.type_op(*l, op, *r),
+
+            Expr::FnCall(fn_var, args) => self.type_fn_call(*fn_var, args),
+            Expr::MethodCall(receiver, method, args) => {
+                self.type_method_call(*receiver, method, args)
+            }
+            Expr::Closure(params, body) => {
+                // The body sees the variables in scope, the parameters shadow them. It
+                // can not leave the loops around the closure.
+                let loops = std::mem::take(&mut self.loops);
+                let mut param_types = Vec::new();
+                for (param, param_type) in params {
+                    let param_type = param_type.substitute(&self.type_args);
+                    self.insert_binding(&param, param_type.clone())?;
+                    param_types.push(param_type);
+                }
+                let ret_type = self.type_expr(*body)?;
+                self.loops = loops;
+                self.diverging = false;
+                Ok(Type::Fn(param_types, Box::new(self.resolve(&ret_type))))
+            }
+
+            Expr::Cast(expr, to_type) => {
+                let from_type = self.type_expr(*expr)?;
+                let valid = match (&from_type, &to_type) {
+                    (Type::Bool, to_type) => to_type.is_int(),
+                    (from_type, to_type) => from_type.is_numeric() && to_type.is_numeric(),
+                };
+                if !valid {
+                    return self.error(format!(
+                        "non-primitive cast: `{}` as `{}`",
+                        from_type, to_type
+                    ));
+                }
+                Ok(to_type)
+            }
+
+            Expr::IfElse(..) | Expr::Match(..) | Expr::Block(_) | Expr::Loop(_) => {
+                Ok(self.check_value(expr)?.1)
+            }
+            Expr::Range(..) => self.error("ranges can only be used in `for` loops".to_string()),
+
+            Expr::Spanned(_, expr) => self.type_expr(*expr),
+
+            expr => self.error(format!("expected expression, found {:?}", expr)),
+        }
+    }
+
+    fn type_op(&mut self, l: Expr, op: Op, r: Expr) -> Result<Type, TypeError> {
+        match op {
+            // The value of a `let` is parsed as an assignment to the empty name
+            Op::AssOp(AssOp::Eq) if l == Expr::Var(String::new(), None) => self.type_expr(r),
+            Op::AssOp(op) => {
+                if !is_place(&l) {
+                    return self.error("invalid left-hand side of assignment".to_string());
+                }
+                if let Expr::Var(name, Some(id)) = &l {
+                    if self.loop_vars.contains(id) {
+                        return self.error(format!(
+                            "cannot assign twice to immutable variable `{}`",
+                            name
+                        ));
+                    }
+                }
+                let (root, id) = place_root(&l).unwrap();
+                if !matches!(id, Some(id) if self.vars.contains_key(&id)) {
+                    match self.globals.get(root) {
+                        Some(Global::Const(..)) => {
+                            return self.error("invalid left-hand side of assignment".to_string())
+                        }
+                        Some(Global::Static(_, false)) => {
+                            return self.error(format!(
+                                "cannot assign to immutable static item `{}`",
+                                root
+                            ))
+                        }
+                        _ => {}
+                    }
+                }
+                let var_type = self.type_expr(l)?;
+                let expr_type = self.type_expr(r)?;
+                match (op, &var_type) {
+                    (AssOp::Eq, _) | (AssOp::AddEq, Type::Str) => (),
+                    _ => self.expect_numeric(&var_type)?,
+                }
+                self.expect(&var_type, &expr_type)?;
+                Ok(Type::Void)
+            }
+            Op::AriOp(op) => {
+                let l_type = self.type_expr(l)?;
+                let r_type = self.type_expr(r)?;
+                match (op, &l_type) {
+                    // `+` also concatenates strings
+                    (AriOp::Add, Type::Str) => {
+                        self.expect(&Type::Str, &r_type)?;
+                        Ok(Type::Str)
+                    }
+                    // Integers of different types are never mixed implicitly
+                    _ => {
+                        self.expect_numeric(&l_type)?;
+                        self.expect(&l_type, &r_type)?;
+                        Ok(l_type)
+                    }
+                }
+            }
+            Op::LogOp(_) => {
+                let l_type = self.type_expr(l)?;
+                self.expect(&Type::Bool, &l_type)?;
+                let r_type = self.type_expr(r)?;
+                self.expect(&Type::Bool, &r_type)?;
+                Ok(Type::Bool)
+            }
+            Op::RelOp(_) => {
+                let l_type = self.type_expr(l)?;
+                let r_type = self.type_expr(r)?;
+                self.expect(&l_type, &r_type)?;
+                match (op, l_type) {
+                    (_, Type::Void) => self.error("`()` values can not be compared".to_string()),
+                    (Op::RelOp(RelOp::Eq), Type::Str) | (Op::RelOp(RelOp::Neq), Type::Str) => {
+                        Ok(Type::Bool)
+                    }
+                    (_, Type::Str) => {
+                        self.error("strings can only be compared for equality".to_string())
+                    }
+                    (_, Type::Array(..)) => self.error("arrays can not be compared".to_string()),
+                    (_, Type::Tuple(_)) => self.error("tuples can not be compared".to_string()),
+                    (_, Type::Fn(..)) => self.error("functions can not be compared".to_string()),
+                    (_, Type::Named(name)) => {
+                        self.error(format!("values of type `{}` can not be compared", name))
+                    }
+                    _ => Ok(Type::Bool),
+                }
+            }
+        }
+    }
+
+    fn type_fn_call(&mut self, fn_var: Expr, args: Vec<Expr>) -> Result<Type, TypeError> {
+        let name = self.name(&fn_var)?;
+        // `len` also takes arrays of any type
+        if let ("len", [arg]) = (name.as_str(), args.as_slice()) {
+            if let Type::Array(..) = self.type_expr(arg.clone())? {
+                return Ok(Type::Int);
+            }
+        }
+
+        // Variables holding functions or closures shadow the functions of the program
+        let id = match &fn_var {
+            Expr::Var(_, id) => *id,
+            _ => None,
+        };
+        let (param_types, ret_type, generics) = match self.read_var(&name, id) {
+            Ok(var_type) => match self.resolve(&var_type) {
+                Type::Fn(param_types, ret_type) => (param_types, *ret_type, Vec::new()),
+                ty => return self.error(format!("expected function, found `{}`", ty)),
+            },
+            Err(_) => {
+                let (param_types, ret_type) = match self.fns.get(&name) {
+                    Some(signature) => signature.clone(),
+                    None => return self.error(format!("cannot find function `{}`", name)),
+                };
+                let generics = match self.generic_fns.get(&name) {
+                    Some((generics, ..)) => generics.clone(),
+                    None => Vec::new(),
+                };
+                (param_types, ret_type, generics)
+            }
+        };
+        // Each type parameter is a type variable the arguments are unified with
+        let mut vars = HashMap::new();
+        for (generic, _) in generics.iter() {
+            let var = self.fresh_var();
+            vars.insert(generic.to_string(), var);
+        }
+        let param_types: Vec<Type> = param_types.iter().map(|ty| ty.substitute(&vars)).collect();
+        let ret_type = ret_type.substitute(&vars);
+
+        if param_types.len() != args.len() {
+            return self.error(format!(
+                "function `{}` takes {} arguments but {} were supplied",
+                name,
+                param_types.len(),
+                args.len()
+            ));
+        }
+        for (param_type, arg) in param_types.into_iter().zip(args) {
+            let arg_type = self.type_expr(arg)?;
+            self.expect(&param_type, &arg_type)?;
+        }
+
+        if !generics.is_empty() {
+            let mut type_args = Vec::new();
+            for (generic, bounds) in generics.iter() {
+                let type_arg = match self.resolve(&vars[generic]) {
+                    Type::Var(_) => {
+                        return self.error(format!(
+                            "cannot infer type for type parameter `{}` of function `{}`",
+                            generic, name
+                        ))
+                    }
+                    type_arg => type_arg,
+                };
+                for bound in bounds {
+                    if !self.impls.contains(&(bound.to_string(), type_arg.clone())) {
+                        return self.error(format!(
+                            "the trait bound `{}: {}` is not satisfied",
+                            type_arg, bound
+                        ));
+                    }
+                }
+                type_args.push(type_arg);
+            }
+            self.instantiate(&name, type_args)?;
+        }
+        Ok(self.resolve(&ret_type))
+    }
+
+    /// Resolves a method to the impl of the one trait of the receiver's type that has it.
+    fn type_method_call(
+        &mut self,
+        receiver: Expr,
+        method: String,
+        args: Vec<Expr>,
+    ) -> Result<Type, TypeError> {
+        let receiver_type = self.type_expr(receiver.clone())?;
+        let receiver_type = self.resolve(&receiver_type);
+        let traits = &self.traits;
+        let candidates = self
+            .impls
+            .iter()
+            .filter(|(trait_name, impl_type)| {
+                *impl_type == receiver_type
+                    && traits
+                        .get(trait_name)
+                        .is_some_and(|methods| methods.iter().any(|(m, ..)| *m == method))
+            })
+            .count();
+        match candidates {
+            0 => {
+                return self.error(format!(
+                    "no method named `{}` found for type `{}`",
+                    method, receiver_type
+                ))
+            }
+            1 => {}
+            _ => {
+                return self.error(format!(
+                    "multiple applicable items in scope for method `{}`",
+                    method
+                ))
+            }
+        }
+
+        let path = receiver_type.method_path(&method);
+        let param_count = match self.fns.get(&path) {
+            Some((param_types, _)) => param_types.len() - 1,
+            None => return self.error(format!("cannot find function `{}`", path)),
+        };
+        if param_count != args.len() {
+            return self.error(format!(
+                "method `{}` takes {} arguments but {} were supplied",
+                method,
+                param_count,
+                args.len()
+            ));
+        }
+        let mut call_args = vec![receiver];
+        call_args.extend(args);
+        self.type_fn_call(Expr::Var(path, None), call_args)
+    }
+
+    /// Checks the body of a generic function with the type parameters replaced by
+    /// `type_args`, once for each distinct list of type arguments.
+    fn instantiate(&mut self, name: &str, type_args: Vec<Type>) -> Result<(), TypeError> {
+        let instance = (name.to_string(), type_args);
+        if self.instances.contains(&instance) {
+            return Ok(());
+        }
+        let depth = self
+            .instantiating
+            .iter()
+            .filter(|other| *other == name)
+            .count();
+        if depth >= INSTANTIATION_LIMIT {
+            return self.error(format!(
+                "reached the recursion limit while instantiating `{}`",
+                name
+            ));
+        }
+
+        let (generics, params, ret_type, block) = self.generic_fns[name].clone();
+        let type_args: HashMap<String, Type> = generics
+            .into_iter()
+            .map(|(generic, _)| generic)
+            .zip(instance.1.iter().cloned())
+            .collect();
+        let params = params
+            .into_iter()
+            .map(|(param, param_type)| (param, param_type.substitute(&type_args)))
+            .collect();
+        let ret_type = ret_type.substitute(&type_args);
+        self.instances.push(instance);
+
+        let vars = std::mem::take(&mut self.vars);
+        let caller_type_args = std::mem::replace(&mut self.type_args, type_args);
+        let caller_ret_type = self.ret_type.clone();
+        let loops = std::mem::take(&mut self.loops);
+        let loop_vars = std::mem::take(&mut self.loop_vars);
+        let span = self.span;
+        self.instantiating.push(name.to_string());
+        self.check_fn(params, ret_type, block)?;
+        self.instantiating.pop();
+        self.vars = vars;
+        self.type_args = caller_type_args;
+        self.ret_type = caller_ret_type;
+        self.loops = loops;
+        self.loop_vars = loop_vars;
+        self.span = span;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod type_checker_tests {
+    use super::*;
+    use crate::parser::*;
+
+    fn check_str(input: &str) -> Result<(), TypeError> {
+        check(parser(input).unwrap().1).map(|_| ())
+    }
+
+    #[test]
+    fn test_check_fn() {
+        assert_eq!(
+            check_str(
+                "
+                fn add(a: i32, b: i32) -> i32 {
+                    let c: i32 = a + b;
+                    return c
+                }
+
+                fn main() -> bool {
+                    let x: bool = add(1, 2) > 2;
+                    return x && true
+                }
+            "
+            ),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn test_check_mismatch() {
+        assert_eq!(
+            check_str("fn main() -> i32 {\n    let a: i32 = true;\n    return a\n}"),
+            Err(TypeError {
+                span: Span {
+                    line: 2,
+                    column: 5,
+                    file: None,
+                },
+                msg: "mismatched types: expected `i32`, found `bool`".to_string(),
+            })
+        );
+        assert!(check_str("fn main() -> i32 { return 1 < 2 }").is_err());
+        assert!(check_str("fn main() -> bool { return 1 && true }").is_err());
+        assert!(check_str("fn main() -> i32 { let a: bool = true; a += 1; return 1 }").is_err());
+        assert!(check_str("fn main() -> i32 { if 1 { return 1 }; return 2 }").is_err());
+    }
+
+    #[test]
+    fn test_check_scopes() {
+        assert!(check_str("fn main() -> i32 { return a }").is_err());
+        assert!(check_str("fn main() -> i32 { if true { let a: i32 = 1; }; return a }").is_err());
+        assert!(
+            check_str("fn f(a: i32) -> i32 { return a } fn main() -> i32 { return a }").is_err()
+        );
+    }
+
+    #[test]
+    fn test_check_fn_call() {
+        assert!(check_str("fn main() -> i32 { return f(1) }").is_err());
+        assert!(
+            check_str("fn f(a: i32) -> i32 { return a } fn main() -> i32 { return f() }").is_err()
+        );
+        assert!(
+            check_str("fn f(a: i32) -> i32 { return a } fn main() -> i32 { return f(true) }")
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_check_builtins() {
+        let p = "
+            fn main() -> i32 {
+                let a: i32 = read_i32();
+                print_i32(a);
+                print_bool(a > 1);
+                println();
+                return a
+            }
+        ";
+        assert_eq!(check_str(p), Ok(()));
+
+        assert!(check_str("fn main() -> i32 { print_i32(true); return 1 }").is_err());
+        assert!(check_str("fn main() -> i32 { let a: i32 = println(); return a }").is_err());
+        assert_eq!(
+            check_str("fn println() -> i32 { return 1 }")
+                .unwrap_err()
+                .msg,
+            "`println` is a built-in function"
+        );
+    }
+
+    #[test]
+    fn test_check_str() {
+        let p = r#"
+            fn greet(name: str) -> str {
+                let s: str = "hello " + name;
+                s += "!";
+                return s
+            }
+
+            fn main() -> bool {
+                let s: str = greet("world");
+                print_str(s);
+                let n: i32 = len(s);
+                return s == "hello world!"
+            }
+        "#;
+        assert_eq!(check_str(p), Ok(()));
+
+        assert!(check_str(r#"fn main() -> i32 { let s: str = "a" + 1; return 1 }"#).is_err());
+        assert!(check_str(r#"fn main() -> i32 { let s: str = "a" - "b"; return 1 }"#).is_err());
+        assert!(check_str(r#"fn main() -> bool { return "a" < "b" }"#).is_err());
+        assert!(check_str(r#"fn main() -> i32 { let s: str = "a"; s -= "b"; return 1 }"#).is_err());
+        assert!(check_str(r#"fn main() -> str { return 1 }"#).is_err());
+    }
+
+    #[test]
+    fn test_check_int_types() {
+        let p = "
+            fn f(a: u8, b: i64) -> u64 {
+                let c: u8 = a * 2u8;
+                c -= 1u8;
+                return c as u64 + b as u64
+            }
+
+            fn main() -> i32 {
+                let x: u64 = f(255u8, 18446744073709551615u64 as i64);
+                let y: bool = x > 1u64;
+                return x as i32 + y as i32
+            }
+        ";
+        assert_eq!(check_str(p), Ok(()));
+
+        assert_eq!(
+            check_str("fn main() -> i32 { let a: u8 = 256u8; return 1 }")
+                .unwrap_err()
+                .msg,
+            "literal out of range for `u8`"
+        );
+        assert_eq!(
+            check_str("fn main() -> i32 { return 1 + 2u8 }")
+                .unwrap_err()
+                .msg,
+            "mismatched types: expected `i32`, found `u8`"
+        );
+        assert!(check_str("fn main() -> i32 { let a: i64 = 1; return 1 }").is_err());
+        assert!(check_str("fn main() -> bool { return 1u32 < 2 }").is_err());
+        assert!(check_str("fn main() -> bool { return 1 as bool }").is_err());
+        assert!(check_str(r#"fn main() -> i32 { return "1" as i32 }"#).is_err());
+        assert!(check_str("fn main() -> i32 { return true as i32 }").is_ok());
+    }
+
+    #[test]
+    fn test_check_float() {
+        let p = "
+            fn fall(t: f64) -> f64 {
+                let g: f64 = 9.81;
+                let d: f64 = g * t * t / 2.0;
+                d += 1e-3;
+                return d
+            }
+
+            fn main() -> i32 {
+                let d: f64 = fall(3 as f64);
+                let far: bool = d > 10.5;
+                return d as i32
+            }
+        ";
+        assert_eq!(check_str(p), Ok(()));
+
+        assert_eq!(
+            check_str("fn main() -> f64 { return 1.5 + 1 }")
+                .unwrap_err()
+                .msg,
+            "mismatched types: expected `f64`, found `i32`"
+        );
+        assert!(check_str("fn main() -> f64 { let a: f64 = 1; return a }").is_err());
+        assert!(check_str("fn main() -> f64 { return true as f64 }").is_err());
+        assert!(check_str("fn main() -> bool { return 1.5 < 2 }").is_err());
+        assert!(check_str("fn main() -> bool { return 1.5 && true }").is_err());
+    }
+
+    #[test]
+    fn test_check_array() {
+        let p = "
+            fn sum(a: [i32; 3]) -> i32 {
+                return a[0] + a[1] + a[2]
+            }
+
+            fn main() -> i32 {
+                let a: [i32; 3] = [1, 2, 3];
+                let grid: [[bool; 2]; 2] = [[false; 2]; 2];
+                a[1u8] = 5;
+                grid[0][1] = a[2] > 2;
+                return sum(a) + len(grid)
+            }
+        ";
+        assert_eq!(check_str(p), Ok(()));
+
+        assert_eq!(
+            check_str("fn main() -> i32 { let a: [i32; 2] = [1, 2, 3]; return 0 }")
+                .unwrap_err()
+                .msg,
+            "mismatched types: expected `[i32; 2]`, found `[i32; 3]`"
+        );
+        assert_eq!(
+            check_str("fn main() -> i32 { let a: i32 = 1; return a[0] }")
+                .unwrap_err()
+                .msg,
+            "cannot index into a value of type `i32`"
+        );
+        assert!(check_str("fn main() -> i32 { return [1, true][0] }").is_err());
+        assert!(check_str("fn main() -> i32 { return [1, 2][true] }").is_err());
+        assert!(check_str("fn main() -> bool { return [1] == [1] }").is_err());
+    }
+
+    #[test]
+    fn test_check_struct() {
+        let p = "
+            struct Point {
+                x: i32,
+                y: i32,
+            }
+
+            struct Line { from: Point, to: Point }
+
+            fn shift(p: Point, by: i32) -> Point {
+                return Point { y: p.y + by, x: p.x + by }
+            }
+
+            fn main() -> i32 {
+                let l: Line = Line { from: Point { x: 1, y: 2 }, to: shift(Point { x: 0, y: 0 }, 5) };
+                l.from.x += 10;
+                return l.to.y
+            }
+        ";
+        assert_eq!(check_str(p), Ok(()));
+
+        let check_main = |structs: &str, body: &str| {
+            check_str(&format!(
+                "{} fn main() -> i32 {{ {}; return 0 }}",
+                structs, body
+            ))
+            .unwrap_err()
+            .msg
+        };
+        let point = "struct Point { x: i32, y: i32 }";
+        assert_eq!(
+            check_main(point, "let p: Point = Point { x: 1 }"),
+            "missing field `y` in initializer of `Point`"
+        );
+        assert_eq!(
+            check_main(point, "let p: Point = Point { x: 1, y: 2, z: 3 }"),
+            "struct `Point` has no field named `z`"
+        );
+        assert_eq!(
+            check_main(point, "let p: Point = Point { x: 1, y: 2 }; p.z = 1"),
+            "no field `z` on type `Point`"
+        );
+        assert_eq!(
+            check_main(
+                "struct A { x: i32 } struct B { x: i32 }",
+                "let a: A = B { x: 1 }"
+            ),
+            "mismatched types: expected `A`, found `B`"
+        );
+        assert_eq!(
+            check_main("", "let a: Nope = 1"),
+            "cannot find type `Nope` in this scope"
+        );
+        assert_eq!(
+            check_main("struct A { b: B } struct B { a: [A; 2] }", ""),
+            "recursive type `A` has infinite size"
+        );
+        assert!(check_main(
+            point,
+            "let b: bool = Point { x: 1, y: 2 } == Point { x: 1, y: 2 }"
+        )
+        .contains("can not be compared"));
+    }
+
+    #[test]
+    fn test_check_tuple() {
+        let p = "
+            fn div_mod(a: i32, b: i32) -> (i32, i32) {
+                return (a / b, a % b)
+            }
+
+            fn add((a, b): (i32, i32), c: i32) -> i32 {
+                return a + b + c
+            }
+
+            fn main() -> i32 {
+                let (q, r): (i32, i32) = div_mod(7, 2);
+                let t: ((i32, i32), bool) = (div_mod(q, r), true);
+                t.0.1 += 1;
+                return add(t.0, q)
+            }
+        ";
+        assert_eq!(check_str(p), Ok(()));
+
+        let check_main = |body: &str| {
+            check_str(&format!("fn main() -> i32 {{ {}; return 0 }}", body))
+                .unwrap_err()
+                .msg
+        };
+        assert_eq!(
+            check_main("let t: (i32, bool) = (1, 2)"),
+            "mismatched types: expected `(i32, bool)`, found `(i32, i32)`"
+        );
+        assert_eq!(
+            check_main("let (a, b): (i32, i32, i32) = (1, 2, 3)"),
+            "mismatched types: expected a tuple with 3 elements, found one with 2 elements"
+        );
+        assert_eq!(
+            check_main("let (a, b): i32 = 1"),
+            "mismatched types: expected `i32`, found tuple"
+        );
+        assert_eq!(
+            check_main("let (a, a): (i32, i32) = (1, 2)"),
+            "identifier `a` is bound more than once in the same pattern"
+        );
+        assert_eq!(
+            check_main("let t: (i32, i32) = (1, 2); t.2 = 1"),
+            "no field `2` on type `(i32, i32)`"
+        );
+        assert_eq!(
+            check_main("let b: bool = (1, 2) == (1, 2)"),
+            "tuples can not be compared"
+        );
+    }
+
+    #[test]
+    fn test_check_match() {
+        let p = "
+            enum Shape { Circle(i32), Rect(i32, i32), Empty }
+
+            fn area(s: Shape) -> i32 {
+                match s {
+                    Shape::Circle(r) => { return r * r * 3 },
+                    Shape::Rect(w, 0) => { return 0 },
+                    Shape::Rect(w, h) => { return w * h },
+                    Shape::Empty => { return 0 },
+                };
+                return 0
+            }
+
+            fn main() -> i32 {
+                let b: bool = true;
+                match b {
+                    true => { print_i32(area(Shape::Rect(2, 3))); },
+                    false => { print_i32(area(Shape::Empty)); },
+                };
+                return 0
+            }
+        ";
+        assert_eq!(check_str(p), Ok(()));
+
+        let check_match = |arms: &str| {
+            check_str(&format!(
+                "enum Shape {{ Circle(i32), Rect(i32, i32), Empty }}
+                fn main() -> i32 {{ let s: Shape = Shape::Empty; match s {{ {} }}; return 0 }}",
+                arms
+            ))
+            .unwrap_err()
+            .msg
+        };
+        assert_eq!(
+            check_match("Shape::Circle(r) => { return r }, Shape::Empty => { return 0 }"),
+            "non-exhaustive patterns: `Shape::Rect(_, _)` not covered"
+        );
+        assert_eq!(
+            check_match("Shape::Rect(1, h) => { return h }, Shape::Circle(_) => { return 0 }, Shape::Empty => { return 0 }"),
+            "non-exhaustive patterns: `Shape::Rect(_, _)` not covered"
+        );
+        assert_eq!(
+            check_match("x => { return 0 }, Shape::Empty => { return 1 }"),
+            "unreachable pattern `Shape::Empty`"
+        );
+        assert_eq!(
+            check_match("Shape::Rect(a, a) => { return a }, _ => { return 0 }"),
+            "identifier `a` is bound more than once in the same pattern"
+        );
+        assert_eq!(
+            check_match("Shape::Circle(true) => { return 0 }, _ => { return 0 }"),
+            "mismatched types: expected `i32`, found `bool`"
+        );
+        assert_eq!(
+            check_match("Shape::Rect(w) => { return w }, _ => { return 0 }"),
+            "this pattern has 1 fields, but `Shape::Rect` has 2 fields"
+        );
+        assert_eq!(
+            check_match("Shape::Square(w) => { return w }, _ => { return 0 }"),
+            "no variant named `Square` found for enum `Shape`"
+        );
+        assert_eq!(
+            check_str("enum A { X, X } fn main() -> i32 { return 0 }")
+                .unwrap_err()
+                .msg,
+            "the name `X` is defined multiple times"
+        );
+        assert_eq!(
+            check_str("enum A { X(A) } fn main() -> i32 { return 0 }")
+                .unwrap_err()
+                .msg,
+            "recursive type `A` has infinite size"
+        );
+        assert_eq!(
+            check_str("enum A { X(i32) } fn main() -> i32 { let a: A = A::X(1, 2); return 0 }")
+                .unwrap_err()
+                .msg,
+            "this enum variant takes 1 arguments but 2 were supplied"
+        );
+        assert_eq!(
+            check_str("fn main() -> i32 { match 1 { 0 => { return 0 }, }; return 1 }")
+                .unwrap_err()
+                .msg,
+            "non-exhaustive patterns: `_` not covered"
+        );
+    }
+
+    #[test]
+    fn test_check_inferred_let() {
+        let p = "
+            fn div_mod(a: i32, b: i32) -> (i32, i32) {
+                return (a / b, a % b)
+            }
+
+            fn main() -> i64 {
+                let (q, r) = div_mod(7, 2);
+                let big = 3i64;
+                let arr = [q > r; 2];
+                let copy: [bool; 2] = arr;
+                if copy[0] {
+                    big += 1i64;
+                };
+                return big
+            }
+        ";
+        assert_eq!(check_str(p), Ok(()));
+
+        let typed = check(
+            parser("fn main() -> i32 { let t = (1, 2u8); return t.0 }")
+                .unwrap()
+                .1,
+        );
+        let lets: Vec<Expr> = match typed.unwrap()[0].clone().unspanned() {
+            Expr::Fn(_, _, _, _, block) => block.into_iter().map(Expr::unspanned).collect(),
+            item => panic!("expected a fn, found {:?}", item),
+        };
+        match &lets[0] {
+            Expr::Let(_, var_type, _) => {
+                assert_eq!(var_type, &Some(Type::Tuple(vec![Type::Int, Type::U8])))
+            }
+            stmt => panic!("expected a let, found {:?}", stmt),
+        }
+
+        let check_main = |body: &str| {
+            check_str(&format!("fn main() -> i32 {{ {}; return 0 }}", body))
+                .unwrap_err()
+                .msg
+        };
+        assert_eq!(
+            check_main("let a = 1; let b: bool = a"),
+            "mismatched types: expected `bool`, found `i32`"
+        );
+        assert_eq!(
+            check_main("let a: (i32, bool) = (1, 2u8)"),
+            "mismatched types: expected `(i32, bool)`, found `(i32, u8)`"
+        );
+        assert_eq!(
+            check_main("let a = 1; a = true"),
+            "mismatched types: expected `i32`, found `bool`"
+        );
+    }
+
+    #[test]
+    fn test_check_deferred_let() {
+        let typed = check(
+            parser(
+                "
+            fn main() -> i32 {
+                let big;
+                let copy = big;
+                if 3 > 2 {
+                    big = 7u8;
+                } else {
+                    big = 1u8;
+                };
+                copy = big;
+                return copy as i32
+            }
+        ",
+            )
+            .unwrap()
+            .1,
+        );
+        let stmts: Vec<Expr> = match typed.unwrap()[0].clone().unspanned() {
+            Expr::Fn(_, _, _, _, block) => block.into_iter().map(Expr::unspanned).collect(),
+            item => panic!("expected a fn, found {:?}", item),
+        };
+        // Neither type is known until the assignments in the `if`
+        match (&stmts[0], &stmts[1]) {
+            (Expr::Declare(_, big_type), Expr::Let(_, copy_type, _)) => {
+                assert_eq!(big_type, &Some(Type::U8));
+                assert_eq!(copy_type, &Some(Type::U8));
+            }
+            stmts => panic!("expected a declare and a let, found {:?}", stmts),
+        }
+
+        let check_main = |body: &str| {
+            check_str(&format!("fn main() -> i32 {{ {}; return 0 }}", body))
+                .unwrap_err()
+                .msg
+        };
+        assert_eq!(check_main("let a"), "type annotations needed for `a`");
+        assert_eq!(
+            check_main("let a; let b = a"),
+            "type annotations needed for `a`"
+        );
+        assert_eq!(
+            check_main("let a; a = 1; a = true"),
+            "mismatched types: expected `i32`, found `bool`"
+        );
+        assert_eq!(
+            check_main("let a: u8; a = 1"),
+            "mismatched types: expected `u8`, found `i32`"
+        );
+    }
+
+    #[test]
+    fn test_check_generic_fn() {
+        let p = "
+            fn max<T>(a: T, b: T) -> T {
+                if a > b {
+                    return a
+                };
+                return b
+            }
+
+            fn first<T, U>(pair: (T, U), _rest: [U; 2]) -> T {
+                let (x, _y): (T, U) = pair;
+                return x
+            }
+
+            fn main() -> f64 {
+                let i = max(1, 2);
+                let flag: bool = first((true, 1u8), [2u8, 3u8]);
+                return max(1.5, 2.5)
+            }
+        ";
+        assert_eq!(check_str(p), Ok(()));
+
+        let check_main = |body: &str| {
+            check_str(&format!(
+                "
+                fn max<T>(a: T, b: T) -> T {{
+                    if a > b {{
+                        return a
+                    }};
+                    return b
+                }}
+
+                fn make<T>(a: i32) -> T {{
+                    return a
+                }}
+
+                fn deeper<T>(a: T) -> i32 {{
+                    return deeper((a, a))
+                }}
+
+                fn widen<T>(a: T) -> i32 {{
+                    let b: i32 = 1 as T;
+                    return b
+                }}
+
+                fn main() -> i32 {{ {}; return 0 }}
+                ",
+                body
+            ))
+            .unwrap_err()
+            .msg
+        };
+        assert_eq!(
+            check_main("max(1, true)"),
+            "mismatched types: expected `i32`, found `bool`"
+        );
+        assert_eq!(
+            check_main("let b: bool = max(1, 2)"),
+            "mismatched types: expected `bool`, found `i32`"
+        );
+        assert_eq!(
+            check_main("max(\"a\", \"b\")"),
+            "strings can only be compared for equality"
+        );
+        assert_eq!(
+            check_main("let a: i32 = make(1)"),
+            "cannot infer type for type parameter `T` of function `make`"
+        );
+        assert_eq!(
+            check_main("deeper(1)"),
+            "reached the recursion limit while instantiating `deeper`"
+        );
+        assert_eq!(check_main("widen(1)"), "non-primitive cast: `i32` as `T`");
+        assert_eq!(
+            check_str("fn id<T, T>(a: T) -> T { return a } fn main() -> i32 { return 0 }")
+                .unwrap_err()
+                .msg,
+            "the name `T` is already used for a generic parameter"
+        );
+        assert_eq!(
+            check_str("fn id<T>(a: U) -> T { return a } fn main() -> i32 { return 0 }")
+                .unwrap_err()
+                .msg,
+            "cannot find type `U` in this scope"
+        );
+    }
+
+    #[test]
+    fn test_check_trait() {
+        let p = "
+            struct Circle {
+                r: i32,
+            }
+
+            trait Shape {
+                fn area(self) -> i32;
+                fn scaled(self, by: i32) -> Self;
+            }
+
+            impl Shape for Circle {
+                fn area(self) -> i32 {
+                    return 3 * self.r * self.r
+                }
+
+                fn scaled(self, by: i32) -> Self {
+                    return Circle { r: self.r * by }
+                }
+            }
+
+            impl Shape for i32 {
+                fn area(self) -> i32 {
+                    return self
+                }
+
+                fn scaled(self, by: i32) -> i32 {
+                    return self * by
+                }
+            }
+
+            fn total<T: Shape>(shape: T) -> i32 {
+                return shape.area()
+            }
+
+            fn main() -> i32 {
+                let c = Circle { r: 2 };
+                let big = c.scaled(2);
+                let a = big.area();
+                return total(c) + total(5)
+            }
+        ";
+        assert_eq!(check_str(p), Ok(()));
+
+        let check_prog = |items: &str, body: &str| {
+            check_str(&format!(
+                "
+                struct Circle {{
+                    r: i32,
+                }}
+
+                trait Shape {{
+                    fn area(self) -> i32;
+                }}
+
+                {}
+
+                fn total<T: Shape>(shape: T) -> i32 {{
+                    return shape.area()
+                }}
+
+                fn main() -> i32 {{ let c = Circle {{ r: 1 }}; {}; return 0 }}
+                ",
+                items, body
+            ))
+            .unwrap_err()
+            .msg
+        };
+        let circle_impl = "impl Shape for Circle { fn area(self) -> i32 { return self.r } }";
+        assert_eq!(
+            check_prog("impl Shape for Circle {}", "c"),
+            "not all trait items implemented, missing: `area`"
+        );
+        assert_eq!(
+            check_prog(
+                "impl Shape for Circle { fn area(self) -> bool { return true } }",
+                "c"
+            ),
+            "method `area` has an incompatible type for trait: expected `fn(Circle) -> i32`, found `fn(Circle) -> bool`"
+        );
+        assert_eq!(
+            check_prog(
+                "impl Shape for Circle { fn area(self) -> i32 { return 1 } fn perimeter(self) -> i32 { return 1 } }",
+                "c"
+            ),
+            "method `perimeter` is not a member of trait `Shape`"
+        );
+        assert_eq!(
+            check_prog(&format!("{} {}", circle_impl, circle_impl), "c"),
+            "conflicting implementations of trait `Shape` for type `Circle`"
+        );
+        assert_eq!(
+            check_prog(circle_impl, "total(1)"),
+            "the trait bound `i32: Shape` is not satisfied"
+        );
+        assert_eq!(
+            check_prog(circle_impl, "let a: i32 = 1.area()"),
+            "no method named `area` found for type `i32`"
+        );
+        assert_eq!(
+            check_prog(circle_impl, "let a: i32 = c.area(1)"),
+            "method `area` takes 0 arguments but 1 were supplied"
+        );
+        assert_eq!(
+            check_prog(circle_impl, "let a: bool = c.area()"),
+            "mismatched types: expected `bool`, found `i32`"
+        );
+        assert_eq!(
+            check_prog("impl Shape for Dot {}", "c"),
+            "cannot find type `Dot` in this scope"
+        );
+        assert_eq!(
+            check_prog("trait Sized { fn size() -> i32; }", "c"),
+            "method `size` must take `self` as its first parameter"
+        );
+    }
+
+    #[test]
+    fn test_check_block_value() {
+        let p = "
+            enum Shape {
+                Circle(i32),
+                Rect(i32, i32),
+            }
+
+            fn area(s: Shape) -> i32 {
+                match s {
+                    Shape::Circle(r) => r * r * 3,
+                    Shape::Rect(w, h) => w * h,
+                }
+            }
+
+            fn max(a: i32, b: i32) -> i32 {
+                if a > b { a } else { b }
+            }
+
+            fn main() -> i32 {
+                let big = max(3, 4) > 3;
+                let x: u8 = if big { 1u8 } else { 2u8 };
+                let y = {
+                    let t = 5;
+                    t * 2
+                };
+                let z = area(Shape::Rect(2, 3)) + { y };
+                if big {
+                    print_i32(z);
+                };
+                y
+            }
+        ";
+        assert_eq!(check_str(p), Ok(()));
+
+        let check_main = |body: &str| {
+            check_str(&format!(
+                "fn main() -> i32 {{ let c = true; let n = 1; {} }}",
+                body
+            ))
+            .unwrap_err()
+            .msg
+        };
+        assert_eq!(
+            check_main("c"),
+            "mismatched types: expected `i32`, found `bool`"
+        );
+        assert_eq!(
+            check_main("if c { 1 } else { false }"),
+            "`if` and `else` have incompatible types: expected `i32`, found `bool`"
+        );
+        assert_eq!(
+            check_main("match n { 1 => 1, _ => c }"),
+            "`match` arms have incompatible types: expected `i32`, found `bool`"
+        );
+        assert_eq!(
+            check_main("if c { 1 }; return 0"),
+            "`if` may be missing an `else` clause: expected `()`, found `i32`"
+        );
+        assert_eq!(
+            check_main("if c { 1 } else { 2 }; return 0"),
+            "mismatched types: expected `()`, found `i32`"
+        );
+        assert_eq!(
+            check_main("let m = { let inner = 1; inner }; inner"),
+            "cannot find value `inner` in this scope"
+        );
+        assert_eq!(
+            check_main("if c { print_i32(n) } else { print_i32(0) }"),
+            "mismatched types: expected `i32`, found `()`"
+        );
+    }
+
+    #[test]
+    fn test_check_for() {
+        let p = "
+            fn main() -> i32 {
+                let total = 0;
+                let limit: u8 = 10u8;
+                for i in 0u8..limit {
+                    if i == 3u8 {
+                        continue
+                    };
+                    let wide = i as i32;
+                    total += wide;
+                };
+                for j in 1..=5 {
+                    while j > 2 {
+                        break
+                    };
+                    total *= j;
+                };
+                return total
+            }
+        ";
+        assert_eq!(check_str(p), Ok(()));
+
+        let check_main = |body: &str| {
+            check_str(&format!(
+                "fn main() -> i32 {{ let c = true; let n = 1; {} return 0 }}",
+                body
+            ))
+            .unwrap_err()
+            .msg
+        };
+        assert_eq!(
+            check_main("for i in 0..c { print_i32(i); };"),
+            "mismatched types: expected `i32`, found `bool`"
+        );
+        assert_eq!(
+            check_main("for i in 0.5..1.5 { n += 1; };"),
+            "range bounds must be integers, found `f64`"
+        );
+        assert_eq!(
+            check_main("for i in 0..n { i += 1; };"),
+            "cannot assign twice to immutable variable `i`"
+        );
+        assert_eq!(
+            check_main("for i in 0..n { n += 1; }; i += 1;"),
+            "cannot find value `i` in this scope"
+        );
+        assert_eq!(check_main("if c { break };"), "`break` outside of a loop");
+        assert_eq!(
+            check_main("for i in 0..n { let f = |x: i32| { continue }; };"),
+            "`continue` outside of a loop"
+        );
+    }
+
+    #[test]
+    fn test_check_loop() {
+        let p = "
+            fn first_multiple(start: i32, divisor: i32) -> i32 {
+                let candidate = start;
+                loop {
+                    let remainder = candidate % divisor;
+                    if remainder == 0 {
+                        break candidate
+                    };
+                    candidate += 1;
+                }
+            }
+
+            fn spin() -> i32 {
+                loop {}
+            }
+
+            fn main() -> i32 {
+                let found = first_multiple(10, 7);
+                let small: u8 = loop {
+                    break 3u8
+                };
+                let picked = if found > 100 { loop {} } else { found + 1 };
+                loop {
+                    if picked > 0 {
+                        break
+                    };
+                };
+                return picked
+            }
+        ";
+        assert_eq!(check_str(p), Ok(()));
+
+        let check_main = |body: &str| {
+            check_str(&format!(
+                "fn main() -> i32 {{ let c = true; let n = 1; {} return 0 }}",
+                body
+            ))
+            .unwrap_err()
+            .msg
+        };
+        assert_eq!(
+            check_main("let x = loop { if c { break 1 }; break true; };"),
+            "mismatched types: expected `i32`, found `bool`"
+        );
+        assert_eq!(
+            check_main("let x: bool = loop { break n; };"),
+            "mismatched types: expected `bool`, found `i32`"
+        );
+        assert_eq!(
+            check_main("while n > 0 { break 1; };"),
+            "`break` with value from a `while` loop"
+        );
+        assert_eq!(
+            check_main("for i in 0..n { break i; };"),
+            "`break` with value from a `for` loop"
+        );
+        assert_eq!(
+            check_main("loop { n += 1; }; print_bool(n);"),
+            "mismatched types: expected `bool`, found `i32`"
+        );
+
+        // Unreachable code is only warned about and left out of the typed program
+        let typed = check(
+            parser("fn main() -> i32 { let n = 1; loop { n += 1; }; print_i32(n); 0 }")
+                .unwrap()
+                .1,
+        );
+        let stmts = match typed.unwrap()[0].clone().unspanned() {
+            Expr::Fn(_, _, _, _, block) => block.len(),
+            item => panic!("expected a fn, found {:?}", item),
+        };
+        assert_eq!(stmts, 2);
+        assert_eq!(
+            check_str("fn main() -> i32 { let x: i32 = loop { }; x }"),
+            Ok(())
+        );
+
+        // `return`, `break` and `continue` have type `!` where a value is expected
+        let p = "
+            fn main() -> i32 {
+                let c = true;
+                let x = if c { return 5 } else { 3 };
+                let y = loop {
+                    let z = match x { 0 => { break 1 }, _ => 2 };
+                    if z > 1 { continue };
+                };
+                x + y
+            }
+        ";
+        assert_eq!(check_str(p), Ok(()));
+    }
+
+    #[test]
+    fn test_check_const() {
+        let p = "
+            const AREA: usize = SIDE * SIDE;
+            const SIDE: usize = 3usize;
+            const TABLE: [i32; 3] = [1, 2, AREA as i32];
+            static GREETING: str = \"hi\";
+            static mut COUNT: i32 = TABLE[2] + 1;
+
+            fn main() -> i32 {
+                COUNT += 1;
+                print_str(GREETING);
+                let SIDE = 2;
+                SIDE = 4;
+                return COUNT + SIDE
+            }
+        ";
+        assert_eq!(check_str(p), Ok(()));
+        let typed = check(parser(p).unwrap().1).unwrap();
+        assert_eq!(
+            typed[0].clone().unspanned(),
+            Expr::Const(
+                Box::new(Expr::Var("AREA".to_string(), Some(builtins().len()))),
+                Type::Usize,
+                Box::new(Expr::TypedInt(9, Type::Usize))
+            )
+        );
+        assert_eq!(
+            typed[4].clone().unspanned(),
+            Expr::Static(
+                Box::new(Expr::Var("COUNT".to_string(), Some(builtins().len() + 4))),
+                Type::Int,
+                Box::new(Expr::Int(10)),
+                true
+            )
+        );
+
+        let check_prog = |items: &str| {
+            check_str(&format!(
+                "{} fn main() -> i32 {{ let n = 1; return 0 }}",
+                items
+            ))
+            .unwrap_err()
+            .msg
+        };
+        assert_eq!(
+            check_prog("const MAX: u8 = 200u8 + 100u8;"),
+            "evaluation of constant value failed: attempt to add with overflow"
+        );
+        assert_eq!(
+            check_prog("const N: i32 = true;"),
+            "mismatched types: expected `i32`, found `bool`"
+        );
+        assert_eq!(
+            check_prog("static S: i32 = 1; const N: i32 = S;"),
+            "attempt to use a non-constant value in a constant"
+        );
+        assert_eq!(
+            check_prog("fn one() -> i32 { return 1 } const N: i32 = one();"),
+            "calls in constants are limited to constant functions, tuple structs and tuple variants"
+        );
+        assert_eq!(
+            check_prog("const N: i32 = 1; static N: i32 = 2;"),
+            "the name `N` is defined multiple times"
+        );
+        assert_eq!(
+            check_prog("const N: i32 = 1; fn f() -> i32 { N = 2; return N }"),
+            "invalid left-hand side of assignment"
+        );
+        assert_eq!(
+            check_prog("static S: [i32; 1] = [1]; fn f() -> i32 { S[0] = 2; return 0 }"),
+            "cannot assign to immutable static item `S`"
+        );
+    }
+
+    #[test]
+    fn test_check_forward_refs() {
+        assert_eq!(
+            check_str(
+                "
+                fn main() -> i32 {
+                    let shape = Square { side: 2 };
+                    let area = shape.area();
+                    return double(area)
+                }
+
+                fn double(x: i32) -> i32 {
+                    return x * 2
+                }
+
+                impl Area for Square {
+                    fn area(self) -> i32 {
+                        return self.side * self.side
+                    }
+                }
+
+                trait Area {
+                    fn area(self) -> i32;
+                }
+
+                struct Square {
+                    side: i32
+                }
+                "
+            ),
+            Ok(())
+        );
+        let err = check_str(
+            "fn main() -> i32 { return double(true) } fn double(x: i32) -> i32 { return x * 2 }",
+        )
+        .unwrap_err();
+        assert_eq!(err.msg, "mismatched types: expected `i32`, found `bool`");
+    }
+
+    #[test]
+    fn test_check_closure() {
+        let p = "
+            fn apply(f: fn(i32) -> i32, x: i32) -> i32 {
+                return f(x)
+            }
+
+            fn double(x: i32) -> i32 {
+                return x * 2
+            }
+
+            fn adder(n: i32) -> fn(i32) -> i32 {
+                return |x: i32| x + n
+            }
+
+            fn twice<T>(f: fn(T) -> T, x: T) -> T {
+                let once = f(x);
+                return f(once)
+            }
+
+            fn main() -> i32 {
+                let n = 10;
+                let add = |x: i32| x + n;
+                let a = apply(add, 1);
+                let b = apply(double, 2);
+                let f: fn(i32) -> i32 = adder(5);
+                let c = f(3);
+                let d: f64 = twice(|s: f64| s * 2.0, 1.5);
+                let show = |flag: bool| print_bool(flag);
+                show(true);
+                let g = double;
+                return g(a)
+            }
+        ";
+        assert_eq!(check_str(p), Ok(()));
+
+        let check_main = |body: &str| {
+            check_str(&format!(
+                "
+                fn apply(f: fn(i32) -> i32, x: i32) -> i32 {{
+                    return f(x)
+                }}
+
+                fn id<T>(x: T) -> T {{
+                    return x
+                }}
+
+                fn main() -> i32 {{ let n = 1; let add = |x: i32| x + n; {}; return 0 }}
+                ",
+                body
+            ))
+            .unwrap_err()
+            .msg
+        };
+        assert_eq!(
+            check_main("apply(|x: bool| x, 1)"),
+            "mismatched types: expected `fn(i32) -> i32`, found `fn(bool) -> bool`"
+        );
+        assert_eq!(
+            check_main("let b: bool = add(1)"),
+            "mismatched types: expected `bool`, found `i32`"
+        );
+        assert_eq!(
+            check_main("add(1, 2)"),
+            "function `add` takes 1 arguments but 2 were supplied"
+        );
+        assert_eq!(check_main("n(1)"), "expected function, found `i32`");
+        assert_eq!(
+            check_main("let p = print_i32"),
+            "built-in function `print_i32` can only be called"
+        );
+        assert_eq!(
+            check_main("let i = id"),
+            "generic function `id` can only be called"
+        );
+        assert_eq!(
+            check_main("let same = add == add"),
+            "functions can not be compared"
+        );
+        assert_eq!(
+            check_main("let h = |x: i32| x + m"),
+            "cannot find value `m` in this scope"
+        );
+        assert_eq!(
+            check_main("let h = |x: i32| x; let y = x"),
+            "cannot find value `x` in this scope"
+        );
+    }
+
+    #[test]
+    fn test_check_extern_fn() {
+        let p = "
+            extern fn abs(x: i32) -> i32;
+
+            fn main() -> i32 {
+                return abs(1)
+            }
+        ";
+        assert_eq!(check_str(p), Ok(()));
+
+        let p = "
+            extern fn abs(x: i32) -> i32;
+
+            fn main() -> bool {
+                return abs(true)
+            }
+        ";
+        assert_eq!(
+            check_str(p).unwrap_err().msg,
+            "mismatched types: expected `i32`, found `bool`"
+        );
     }
 }